@@ -3,10 +3,15 @@ from pyhpo.pyhpo import _Ontology
 from pyhpo.pyhpo import Gene
 from pyhpo.pyhpo import Omim
 from pyhpo.pyhpo import Orpha
+from pyhpo.pyhpo import Mondo
 from pyhpo.pyhpo import HPOTerm
 from pyhpo.pyhpo import HPOSet
 from pyhpo.pyhpo import BasicHPOSet
 from pyhpo.pyhpo import HPOPhenoSet
+from pyhpo.pyhpo import Patient
+from pyhpo.pyhpo import Cohort
+from pyhpo.pyhpo import migrate_binary
+from pyhpo.pyhpo import register_similarity
 from pyhpo.pyhpo import __version__
 from pyhpo.pyhpo import __backend__
 
@@ -21,10 +26,15 @@ __all__ = (
     "Gene",
     "Omim",
     "Orpha",
+    "Mondo",
     "HPOTerm",
     "HPOSet",
     "BasicHPOSet",
     "HPOPhenoSet",
+    "Patient",
+    "Cohort",
+    "migrate_binary",
+    "register_similarity",
     "__version__",
     "__backend__",
     "annotations",
@@ -1,16 +1,25 @@
-from typing import Any, Collection, Dict, Iterable, Iterator, List, Set, Tuple
+from os import PathLike
+from typing import IO, Any, Callable, Collection, Dict, Iterable, Iterator, List, Set, Tuple
 
 from . import annotations as annotations
 from .annotations import Gene as Gene
 from .annotations import Omim as Omim
 from .annotations import Orpha as Orpha
+from .annotations import Mondo as Mondo
 from .types import InformationContent
+from .types import SimilarityMatrix
+from .types import SparseMatrix
 
 from . import helper as helper
 
 __version__: str
 __backend__: str
 
+Source = str | PathLike[str] | bytes | bytearray | IO[str] | IO[bytes]
+
+def migrate_binary(old: Source, new: str | PathLike[str], compress: bool | None = None) -> int: ...
+def register_similarity(name: str, similarity: str | Callable[[HPOTerm, HPOTerm], float]) -> None: ...
+
 
 class HPOTerm:
     id: str
@@ -22,42 +31,83 @@ class HPOTerm:
     genes: Set[Gene]
     omim_diseases: Set[Omim]
     orpha_diseases: Set[Orpha]
+    direct_genes: Set[Gene]
+    direct_omim_diseases: Set[Omim]
+    direct_orpha_diseases: Set[Orpha]
+    gene_count: int
+    omim_count: int
+    orpha_count: int
     categories: List[HPOTerm]
     def parent_of(self, other: HPOTerm) ->  bool: ...
     def child_of(self, other: HPOTerm) -> bool: ...
     def parent_ids(self) -> List[int]: ...
     def common_ancestors(self, other: HPOTerm) -> Set[HPOTerm]: ...
+    def sorted_parents(self) -> List[HPOTerm]: ...
+    def sorted_children(self) -> List[HPOTerm]: ...
+    def sorted_genes(self) -> List[Gene]: ...
+    def sorted_omim_diseases(self) -> List[Omim]: ...
+    def sorted_orpha_diseases(self) -> List[Orpha]: ...
     def count_parents(self) -> int: ...
     def shortest_path_to_root(self) -> int: ...
+    def path_to_root(self) -> List[HPOTerm]: ...
     def shortest_path_to_parent(self, other: HPOTerm) -> Tuple[float, List[HPOTerm]]: ...
-    def path_to_other(self, other: HPOTerm) -> Tuple[int, List[HPOTerm], int, int]: ...
-    def similarity_score(self, other: HPOTerm, kind: str = "omim", method: str = "graphic") -> float: ...
-    def similarity_scores(self, other: List[HPOTerm], kind: str = "omim", method: str = "graphic") -> List[float]: ...
+    def paths_to_ancestor(self, other: HPOTerm) -> List[List[HPOTerm]]: ...
+    def path_to_other(self, other: HPOTerm, replace_obsolete: bool = False) -> Tuple[int, List[HPOTerm], int, int]: ...
+    def similarity_score(self, other: HPOTerm, kind: str = "omim", method: str = "graphic", cache: SimilarityMatrix | None = None) -> float: ...
+    def set_custom_ic(self, value: float) -> None: ...
+    def set_ic(self, kind: str, value: float) -> None: ...
+    def similarity_scores(self, other: List[HPOTerm], kind: str = "omim", method: str = "graphic", nan_policy: str = "propagate", nan_value: float = 0.0) -> List[float]: ...
+    def similarity_all(self, kind: str = "omim", method: str = "graphic", top_n: int | None = None) -> List[Tuple[int, float]]: ...
+    def xrefs(self, system: str | None = None) -> List[str]: ...
     def toJSON(self, verbose: bool = False) -> Dict[str, Any]: ...
+    def to_json_str(self, verbose: bool = False) -> str: ...
+    def to_fhir_coding(self) -> Dict[str, str]: ...
+    def to_fhir_codeable_concept(self) -> Dict[str, Any]: ...
     def __str__(self) -> str: ...
     def __int__(self) -> int: ...
     def __hash__(self) -> int: ...
 
 
 class HPOSet:
-    def __init__(self, terms: List[int | HPOTerm]): ...
+    def __init__(self, terms: List[int | HPOTerm] | Collection[int]): ...
     def add(self, term: int | HPOTerm): ...
     def child_nodes(self) -> HPOSet: ...
-    def remove_modifier(self) -> HPOSet: ...
+    def remove_modifier(self, remove: List[str] | None = None, keep: List[str] | None = None) -> HPOSet: ...
+    def filter_category(self, category: HPOTerm | int) -> HPOSet: ...
+    def map_to_level(self, level: int) -> HPOSet: ...
+    def map_to_slim(self, slim_terms: Iterable[HPOTerm | int], keep_unmapped: bool = False) -> HPOSet: ...
     def replace_obsolete(self) -> HPOSet: ...
     def all_genes(self) -> Set[Gene]: ...
     def omim_diseases(self) -> Set[Omim]: ...
     def orpha_diseases(self) -> Set[Orpha]: ...
     def information_content(self) -> Dict[str, Any]: ...
-    def similarity(self, other: HPOSet, kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg") -> float: ...
-    def similarity_scores(self, other: List[HPOSet], kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg") -> List[float]: ...
+    def terms_by_ic(self, kind: str = "omim", descending: bool = True) -> List[HPOTerm]: ...
+    def representative_terms(self, n: int, kind: str = "omim") -> List[HPOTerm]: ...
+    def redundancy(self, kind: str = "omim", method: str = "graphic") -> float: ...
+    def diversity(self, kind: str = "omim", method: str = "graphic") -> float: ...
+    def common_ancestors(self, include_self: bool = False) -> Set[HPOTerm]: ...
+    def most_informative_common_ancestor(self, kind: str = "omim", include_self: bool = False) -> HPOTerm | None: ...
+    def categories(self, counts: bool = False) -> Dict[HPOTerm, List[HPOTerm]] | Dict[HPOTerm, int]: ...
+    def subgraph(self) -> Tuple[List[HPOTerm], List[Tuple[int, int]]]: ...
+    def similarity(self, other: HPOSet, kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg", on_empty: str = "zero", explain: bool = False, dtype: str = "float32") -> float | Dict[str, Any]: ...
+    def similarity_scores(self, other: List[HPOSet], kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg", on_empty: str = "zero", nan_policy: str = "propagate", nan_value: float = 0.0, dtype: str = "float32") -> List[float]: ...
+    def similarity_to_term(self, term: HPOTerm, kind: str = "omim", method: str = "graphic", combine: str = "max", on_empty: str = "zero") -> float: ...
     def toJSON(self, verbose: bool = False) -> Dict[str, Any]: ...
-    def serialize(self) -> str: ...
-    def terms(self) -> Iterator[HPOTerm]: ...
+    def to_json_str(self, verbose: bool = False) -> str: ...
+    def to_fhir(self) -> List[Dict[str, Any]]: ...
+    def serialize(self, include_version: bool = False) -> str: ...
+    def serialize_compact(self) -> str: ...
+    def terms(self, as_list: bool = False) -> Iterator[HPOTerm] | List[HPOTerm]: ...
     @classmethod
     def from_queries(cls, queries: List[int | str]) -> HPOSet: ...
     @classmethod
-    def from_serialized(cls, pickle: str) -> HPOSet: ...
+    def from_series(cls, series: Iterable[int | str | HPOTerm | None], skip_invalid: bool = False) -> HPOSet: ...
+    @classmethod
+    def from_fhir(cls, fragments: Iterable[Dict[str, Any]], skip_invalid: bool = False) -> HPOSet: ...
+    @classmethod
+    def from_serialized(cls, pickle: str, on_version_mismatch: str = "warn") -> HPOSet: ...
+    @classmethod
+    def from_compact(cls, data: str) -> HPOSet: ...
     @classmethod
     def from_gene(cls, gene: Gene) -> HPOSet: ...
     @classmethod
@@ -69,6 +119,7 @@ class HPOSet:
     def __len__(self) -> int: ...
     def __str__(self) -> str: ...
     def __iter__(self) -> Iterator[HPOTerm]: ...
+    def __getitem__(self, index: int | slice) -> HPOTerm | HPOSet: ...
     def __contains__(self, term: HPOTerm) -> bool: ...
 
 
@@ -76,21 +127,42 @@ class BasicHPOSet:
     def __init__(self, terms: List[int | HPOTerm]): ...
     def add(self, term: int | HPOTerm): ...
     def child_nodes(self) -> HPOSet: ...
-    def remove_modifier(self) -> HPOSet: ...
+    def remove_modifier(self, remove: List[str] | None = None, keep: List[str] | None = None) -> HPOSet: ...
+    def filter_category(self, category: HPOTerm | int) -> HPOSet: ...
+    def map_to_level(self, level: int) -> HPOSet: ...
+    def map_to_slim(self, slim_terms: Iterable[HPOTerm | int], keep_unmapped: bool = False) -> HPOSet: ...
     def replace_obsolete(self) -> HPOSet: ...
     def all_genes(self) -> Set[Gene]: ...
     def omim_diseases(self) -> Set[Omim]: ...
     def orpha_diseases(self) -> Set[Orpha]: ...
     def information_content(self) -> Dict[str, Any]: ...
-    def similarity(self, other: HPOSet, kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg") -> float: ...
-    def similarity_scores(self, other: List[HPOSet], kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg") -> List[float]: ...
+    def terms_by_ic(self, kind: str = "omim", descending: bool = True) -> List[HPOTerm]: ...
+    def representative_terms(self, n: int, kind: str = "omim") -> List[HPOTerm]: ...
+    def redundancy(self, kind: str = "omim", method: str = "graphic") -> float: ...
+    def diversity(self, kind: str = "omim", method: str = "graphic") -> float: ...
+    def common_ancestors(self, include_self: bool = False) -> Set[HPOTerm]: ...
+    def most_informative_common_ancestor(self, kind: str = "omim", include_self: bool = False) -> HPOTerm | None: ...
+    def categories(self, counts: bool = False) -> Dict[HPOTerm, List[HPOTerm]] | Dict[HPOTerm, int]: ...
+    def subgraph(self) -> Tuple[List[HPOTerm], List[Tuple[int, int]]]: ...
+    def similarity(self, other: HPOSet, kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg", on_empty: str = "zero", explain: bool = False, dtype: str = "float32") -> float | Dict[str, Any]: ...
+    def similarity_scores(self, other: List[HPOSet], kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg", on_empty: str = "zero", nan_policy: str = "propagate", nan_value: float = 0.0, dtype: str = "float32") -> List[float]: ...
+    def similarity_to_term(self, term: HPOTerm, kind: str = "omim", method: str = "graphic", combine: str = "max", on_empty: str = "zero") -> float: ...
     def toJSON(self, verbose: bool = False) -> Dict[str, Any]: ...
-    def serialize(self) -> str: ...
-    def terms(self) -> Iterator[HPOTerm]: ...
+    def to_json_str(self, verbose: bool = False) -> str: ...
+    def to_fhir(self) -> List[Dict[str, Any]]: ...
+    def serialize(self, include_version: bool = False) -> str: ...
+    def serialize_compact(self) -> str: ...
+    def terms(self, as_list: bool = False) -> Iterator[HPOTerm] | List[HPOTerm]: ...
     @classmethod
     def from_queries(cls, queries: List[int | str]) -> HPOSet: ...
     @classmethod
-    def from_serialized(cls, pickle: str) -> HPOSet: ...
+    def from_series(cls, series: Iterable[int | str | HPOTerm | None], skip_invalid: bool = False) -> HPOSet: ...
+    @classmethod
+    def from_fhir(cls, fragments: Iterable[Dict[str, Any]], skip_invalid: bool = False) -> HPOSet: ...
+    @classmethod
+    def from_serialized(cls, pickle: str, on_version_mismatch: str = "warn") -> HPOSet: ...
+    @classmethod
+    def from_compact(cls, data: str) -> HPOSet: ...
     @classmethod
     def from_gene(cls, gene: Gene) -> HPOSet: ...
     @classmethod
@@ -102,6 +174,7 @@ class BasicHPOSet:
     def __len__(self) -> int: ...
     def __str__(self) -> str: ...
     def __iter__(self) -> Iterator[HPOTerm]: ...
+    def __getitem__(self, index: int | slice) -> HPOTerm | HPOSet: ...
     def __contains__(self, term: HPOTerm) -> bool: ...
 
 
@@ -109,21 +182,42 @@ class HPOPhenoSet:
     def __init__(self, terms: List[int | HPOTerm]): ...
     def add(self, term: int | HPOTerm): ...
     def child_nodes(self) -> HPOSet: ...
-    def remove_modifier(self) -> HPOSet: ...
+    def remove_modifier(self, remove: List[str] | None = None, keep: List[str] | None = None) -> HPOSet: ...
+    def filter_category(self, category: HPOTerm | int) -> HPOSet: ...
+    def map_to_level(self, level: int) -> HPOSet: ...
+    def map_to_slim(self, slim_terms: Iterable[HPOTerm | int], keep_unmapped: bool = False) -> HPOSet: ...
     def replace_obsolete(self) -> HPOSet: ...
     def all_genes(self) -> Set[Gene]: ...
     def omim_diseases(self) -> Set[Omim]: ...
     def orpha_diseases(self) -> Set[Orpha]: ...
     def information_content(self) -> Dict[str, Any]: ...
-    def similarity(self, other: HPOSet, kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg") -> float: ...
-    def similarity_scores(self, other: List[HPOSet], kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg") -> List[float]: ...
+    def terms_by_ic(self, kind: str = "omim", descending: bool = True) -> List[HPOTerm]: ...
+    def representative_terms(self, n: int, kind: str = "omim") -> List[HPOTerm]: ...
+    def redundancy(self, kind: str = "omim", method: str = "graphic") -> float: ...
+    def diversity(self, kind: str = "omim", method: str = "graphic") -> float: ...
+    def common_ancestors(self, include_self: bool = False) -> Set[HPOTerm]: ...
+    def most_informative_common_ancestor(self, kind: str = "omim", include_self: bool = False) -> HPOTerm | None: ...
+    def categories(self, counts: bool = False) -> Dict[HPOTerm, List[HPOTerm]] | Dict[HPOTerm, int]: ...
+    def subgraph(self) -> Tuple[List[HPOTerm], List[Tuple[int, int]]]: ...
+    def similarity(self, other: HPOSet, kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg", on_empty: str = "zero", explain: bool = False, dtype: str = "float32") -> float | Dict[str, Any]: ...
+    def similarity_scores(self, other: List[HPOSet], kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg", on_empty: str = "zero", nan_policy: str = "propagate", nan_value: float = 0.0, dtype: str = "float32") -> List[float]: ...
+    def similarity_to_term(self, term: HPOTerm, kind: str = "omim", method: str = "graphic", combine: str = "max", on_empty: str = "zero") -> float: ...
     def toJSON(self, verbose: bool = False) -> Dict[str, Any]: ...
-    def serialize(self) -> str: ...
-    def terms(self) -> Iterator[HPOTerm]: ...
+    def to_json_str(self, verbose: bool = False) -> str: ...
+    def to_fhir(self) -> List[Dict[str, Any]]: ...
+    def serialize(self, include_version: bool = False) -> str: ...
+    def serialize_compact(self) -> str: ...
+    def terms(self, as_list: bool = False) -> Iterator[HPOTerm] | List[HPOTerm]: ...
     @classmethod
     def from_queries(cls, queries: List[int | str]) -> HPOSet: ...
     @classmethod
-    def from_serialized(cls, pickle: str) -> HPOSet: ...
+    def from_series(cls, series: Iterable[int | str | HPOTerm | None], skip_invalid: bool = False) -> HPOSet: ...
+    @classmethod
+    def from_fhir(cls, fragments: Iterable[Dict[str, Any]], skip_invalid: bool = False) -> HPOSet: ...
+    @classmethod
+    def from_serialized(cls, pickle: str, on_version_mismatch: str = "warn") -> HPOSet: ...
+    @classmethod
+    def from_compact(cls, data: str) -> HPOSet: ...
     @classmethod
     def from_gene(cls, gene: Gene) -> HPOSet: ...
     @classmethod
@@ -135,9 +229,40 @@ class HPOPhenoSet:
     def __len__(self) -> int: ...
     def __str__(self) -> str: ...
     def __iter__(self) -> Iterator[HPOTerm]: ...
+    def __getitem__(self, index: int | slice) -> HPOTerm | HPOSet: ...
     def __contains__(self, term: HPOTerm) -> bool: ...
 
 
+class Patient:
+    def __init__(self, id: str, hpo_set: HPOSet, metadata: Dict[str, Any] | None = None): ...
+    @property
+    def id(self) -> str: ...
+    @property
+    def hpo_set(self) -> HPOSet: ...
+    @hpo_set.setter
+    def hpo_set(self, hpo_set: HPOSet) -> None: ...
+    @property
+    def metadata(self) -> Dict[str, Any]: ...
+    def similarity(self, other: Patient, kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg", on_empty: str = "zero") -> float: ...
+    def rank_diseases(self, source: str = "omim", kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg", limit: int | None = 10, output: str = "list") -> List[Tuple[Omim | Orpha, float]] | Any: ...
+    def rank_genes(self, kind: str = "gene", method: str = "graphic", combine: str = "funSimAvg", limit: int | None = 10, output: str = "list") -> List[Tuple[Gene, float]] | Any: ...
+    def __len__(self) -> int: ...
+
+
+class Cohort:
+    def __init__(self, members: Iterable[Patient | Tuple[str, HPOSet]]): ...
+    @property
+    def ids(self) -> List[str]: ...
+    def similarity_matrix(self, kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg", on_empty: str = "zero") -> List[List[float]]: ...
+    def similarity_to_file(self, path: str | PathLike, format: str | None = None, kind: str = "omim", method: str = "graphic", combine: str = "funSimAvg", on_empty: str = "zero", progress: Callable[[int, int], None] | None = None, chunk_size: int = 1000000) -> int: ...
+    def term_prevalence(self, inherited: bool = False) -> Dict[HPOTerm, int]: ...
+    def enrichment(self, other: Cohort, inherited: bool = True, sort_by: str = "pvalue", output: str = "list") -> List[Dict[str, Any]] | Any: ...
+    def to_dataframe(self) -> Any: ...
+    def __len__(self) -> int: ...
+    def __getitem__(self, key: int | str) -> Patient: ...
+    def __iter__(self) -> Iterator[Patient]: ...
+
+
 class Ontology:
     def __init__(self, data_folder: str = "", from_obo_file: bool = True): ...
     # We're documenting the Ontology as if it were a static method,
@@ -145,25 +270,74 @@ class Ontology:
     genes: Collection[Gene]
     omim_diseases: Collection[Omim]
     orpha_diseases: Collection[Orpha]
+    root: HPOTerm
+    phenotypic_abnormality: HPOTerm
+    mode_of_inheritance: HPOTerm
+    clinical_modifier: HPOTerm
+    frequency: HPOTerm
+    past_medical_history: HPOTerm
+    branches: Dict[str, HPOTerm]
+    @staticmethod
+    def filter_genes(sorted_by: str | None = None, having_term: HPOTerm | int | None = None, min_terms: int = 0) -> List[Gene]: ...
+    @staticmethod
+    def filter_omim_diseases(sorted_by: str | None = None, having_term: HPOTerm | int | None = None, min_terms: int = 0) -> List[Omim]: ...
+    @staticmethod
+    def filter_orpha_diseases(sorted_by: str | None = None, having_term: HPOTerm | int | None = None, min_terms: int = 0) -> List[Orpha]: ...
     @staticmethod
     def get_hpo_object(query: str | int) -> HPOTerm: ...
     @staticmethod
     def match(query: str) -> HPOTerm: ...
     @staticmethod
-    def path(query1: str | int, query2: str | int) -> Tuple[int, List[HPOTerm], int, int]: ...
+    def path(query1: str | int, query2: str | int, replace_obsolete: bool = False) -> Tuple[int, List[HPOTerm], int, int]: ...
+    @staticmethod
+    def common_ancestors(terms: List[HPOTerm | int], include_self: bool = False) -> Set[HPOTerm]: ...
+    @staticmethod
+    def most_informative_common_ancestor(terms: List[HPOTerm | int], kind: str = "omim", include_self: bool = False) -> HPOTerm | None: ...
+    @staticmethod
+    def search(query: str, fields: List[str] | None = None) -> Iterator[HPOTerm]: ...
+    @staticmethod
+    def term_by_xref(xref: str) -> List[HPOTerm]: ...
+    @staticmethod
+    def hpo(id: int | str) -> HPOTerm: ...
+    @staticmethod
+    def hpos(ids: List[int | str]) -> List[HPOTerm]: ...
+    @staticmethod
+    def hpo_from_fhir(fragment: Dict[str, Any]) -> HPOTerm: ...
+    @staticmethod
+    def leaves(term: HPOTerm | int | None = None) -> Iterator[HPOTerm]: ...
+    @staticmethod
+    def ancestor_matrix(include_self: bool = False) -> SparseMatrix: ...
     @staticmethod
-    def search(query: str) -> Iterator[HPOTerm]: ...
+    def adjacency_matrix() -> SparseMatrix: ...
     @staticmethod
-    def hpo(id: int) -> HPOTerm: ...
+    def category_counts() -> List[Dict[str, Any]]: ...
     @staticmethod
     def version() -> str: ...
     @staticmethod
-    def __call__(data_folder: str = "", from_obo_file: bool = True, transitive: bool = False): ...
+    def annotation_metadata() -> Dict[str, Dict[str, Any]]: ...
+    @staticmethod
+    def load_mondo(source: Source) -> int: ...
+    @staticmethod
+    def save(path: str | PathLike[str], compress: bool | None = None) -> None: ...
+    @staticmethod
+    def to_obo(path: str | PathLike[str]) -> None: ...
+    @staticmethod
+    def to_table(output: str = "polars") -> Any: ...
+    @staticmethod
+    def to_owl(path: str | PathLike[str], syntax: str | None = None) -> None: ...
+    @staticmethod
+    def memory_usage() -> Dict[str, int]: ...
+    @staticmethod
+    def build_kwargs() -> Dict[str, Any]: ...
+    @staticmethod
+    def __call__(data_folder: Source | None = None, from_obo_file: bool = True, transitive: bool = False, obo: Source | None = None, hpoa: Source | None = None, gene_file: Source | None = None, progress: Callable[[str, int], None] | None = None, annotations: bool = True, min_frequency: float | None = None, evidence_codes: List[str] | None = None, gene_annotation_mode: str | None = None): ...
     @staticmethod
     def __len__() -> int: ...
     @staticmethod
     def __repr__() -> int: ...
     @staticmethod
-    def __getitem__(id_: int) -> HPOTerm: ...
+    def __getitem__(query: int | str) -> HPOTerm: ...
+    @staticmethod
+    def __contains__(query: HPOTerm | int | str) -> bool: ...
     @staticmethod
     def __iter__() -> Iterable[HPOTerm]: ...
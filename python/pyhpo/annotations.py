@@ -2,5 +2,6 @@
 from pyhpo.pyhpo import Gene
 from pyhpo.pyhpo import Omim
 from pyhpo.pyhpo import Orpha
+from pyhpo.pyhpo import Mondo
 
-__all__ = ("Gene", "Omim", "Orpha")
+__all__ = ("Gene", "Omim", "Orpha", "Mondo")
@@ -1,12 +1,14 @@
-from typing import Any, Dict, Set
-from pyhpo.pyhpo import HPOSet
+from typing import Any, Dict, List, Optional, Set
+from pyhpo.pyhpo import HPOSet, HPOTerm
 
 class Gene:
     id: int
     name: str
     hpo: Set[int]
+    direct_hpo: Set[int]
     def hpo_set(self) -> HPOSet: ...
     def toJSON(self, verbose: bool = False) -> Dict[str, Any]: ...
+    def to_json_str(self, verbose: bool = False) -> str: ...
     @classmethod
     def get(cls, query: int|str) -> 'Gene': ...
     def __str__(self) -> str: ...
@@ -19,7 +21,14 @@ class Omim:
     name: str
     hpo: Set[int]
     def hpo_set(self) -> HPOSet: ...
+    def specificity(self, term: HPOTerm) -> float: ...
+    def frequency(self, term: HPOTerm) -> Optional[float]: ...
+    def annotations(self) -> List[Dict[str, Any]]: ...
+    def hpo_with_modifiers(self) -> Dict[HPOTerm, List[HPOTerm]]: ...
     def toJSON(self, verbose: bool = False) -> Dict[str, Any]: ...
+    def to_json_str(self, verbose: bool = False) -> str: ...
+    @property
+    def mondo(self) -> List[Mondo]: ...
     @classmethod
     def get(cls, query: int|str) -> 'Omim': ...
     def __str__(self) -> str: ...
@@ -31,9 +40,29 @@ class Orpha:
     name: str
     hpo: Set[int]
     def hpo_set(self) -> HPOSet: ...
+    def specificity(self, term: HPOTerm) -> float: ...
+    def frequency(self, term: HPOTerm) -> Optional[float]: ...
+    def annotations(self) -> List[Dict[str, Any]]: ...
+    def hpo_with_modifiers(self) -> Dict[HPOTerm, List[HPOTerm]]: ...
     def toJSON(self, verbose: bool = False) -> Dict[str, Any]: ...
+    def to_json_str(self, verbose: bool = False) -> str: ...
+    @property
+    def mondo(self) -> List[Mondo]: ...
     @classmethod
     def get(cls, query: int|str) -> 'Orpha': ...
     def __str__(self) -> str: ...
     def __int__(self) -> int: ...
     def __hash__(self) -> int: ...
+
+class Mondo:
+    id: int
+    name: str
+    omim_diseases: List[Omim]
+    orpha_diseases: List[Orpha]
+    hpo: Set[int]
+    def hpo_set(self) -> HPOSet: ...
+    @classmethod
+    def get(cls, query: int|str) -> 'Mondo': ...
+    def __str__(self) -> str: ...
+    def __int__(self) -> int: ...
+    def __hash__(self) -> int: ...
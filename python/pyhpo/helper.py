@@ -1,15 +1,41 @@
 from pyhpo.pyhpo import batch_similarity
 from pyhpo.pyhpo import batch_set_similarity
+from pyhpo.pyhpo import batch_set_similarity_chunks
 from pyhpo.pyhpo import batch_gene_enrichment
 from pyhpo.pyhpo import batch_disease_enrichment
 from pyhpo.pyhpo import batch_omim_disease_enrichment
 from pyhpo.pyhpo import batch_orpha_disease_enrichment
+from pyhpo.pyhpo import similarity_matrix
+from pyhpo.pyhpo import ancestor_embedding
+from pyhpo.pyhpo import term_distance_matrix
+from pyhpo.pyhpo import random_sets
+from pyhpo.pyhpo import similarity_significance
+from pyhpo.pyhpo import resolve
+from pyhpo.pyhpo import init_worker
+from pyhpo.pyhpo import load_phenopackets
+from pyhpo.pyhpo import similarity_cache
+from pyhpo.pyhpo import SimilarityCache
+from pyhpo.pyhpo import SetIndex
+from pyhpo.pyhpo import MinHashIndex
 
 __all__ = (
     "batch_similarity",
     "batch_set_similarity",
+    "batch_set_similarity_chunks",
     "batch_gene_enrichment",
     "batch_disease_enrichment",
     "batch_omim_disease_enrichment",
     "batch_orpha_disease_enrichment",
+    "similarity_matrix",
+    "ancestor_embedding",
+    "term_distance_matrix",
+    "random_sets",
+    "similarity_significance",
+    "resolve",
+    "init_worker",
+    "load_phenopackets",
+    "similarity_cache",
+    "SimilarityCache",
+    "SetIndex",
+    "MinHashIndex",
 )
@@ -1,23 +1,140 @@
 
 
-from typing import Any, Dict, List, Tuple
+from os import PathLike
+from typing import IO, Any, Dict, Iterable, Iterator, List, Tuple
 from pyhpo.pyhpo import HPOSet
+from pyhpo import Cohort
 
 from pyhpo.pyhpo import HPOTerm
+from pyhpo.types import ScoreBuffer, SimilarityMatrix, SparseMatrix
 
 
 def batch_similarity(
     comparisons: List[Tuple[HPOTerm, HPOTerm]],
     kind:str,
-    method: str
-) -> List[float]: ...
+    method: str,
+    nan_policy: str = "propagate",
+    nan_value: float = 0.0,
+    errors: str = "raise",
+    output: str = "list",
+    workers: int | None = None
+) -> List[float] | ScoreBuffer | Any | Tuple[List[float] | ScoreBuffer | Any, List[Tuple[int, str]]]: ...
 def batch_set_similarity(
     comparisons: List[Tuple[HPOSet, HPOSet]],
     kind:str,
     method: str,
-    combine: str
-) -> List[float]: ...
-def batch_gene_enrichment(hposets: List[HPOSet]) -> List[List[Dict[str, Any]]]: ...
+    combine: str,
+    on_empty: str = "zero",
+    nan_policy: str = "propagate",
+    nan_value: float = 0.0,
+    errors: str = "raise",
+    methods: List[str] | None = None,
+    output_path: str | PathLike | None = None,
+    format: str | None = None,
+    output: str = "list",
+    workers: int | None = None,
+    dtype: str = "float32"
+) -> int | List[float] | ScoreBuffer | Dict[str, List[float] | ScoreBuffer] | Any | Tuple[int | List[float] | ScoreBuffer | Dict[str, List[float] | ScoreBuffer] | Any, List[Tuple[int, str]]]: ...
+def batch_set_similarity_chunks(
+    comparisons: Iterable[Tuple[HPOSet, HPOSet]],
+    kind: str = "omim",
+    method: str = "graphic",
+    combine: str = "funSimAvg",
+    on_empty: str = "zero",
+    nan_policy: str = "propagate",
+    nan_value: float = 0.0,
+    errors: str = "raise",
+    chunk_size: int | None = None,
+    max_memory: int = 100_000_000,
+    output: str = "numpy",
+    workers: int | None = None
+) -> Iterator[Any]: ...
+def batch_gene_enrichment(hposets: List[HPOSet], sort_by: str = "pvalue", output_path: str | PathLike | None = None, format: str | None = None, workers: int | None = None) -> int | List[List[Dict[str, Any]]]: ...
 def batch_disease_enrichment(hposets: List[HPOSet]) -> List[List[Dict[str, Any]]]: ...
-def batch_omim_disease_enrichment(hposets: List[HPOSet]) -> List[List[Dict[str, Any]]]: ...
-def batch_orpha_disease_enrichment(hposets: List[HPOSet]) -> List[List[Dict[str, Any]]]: ...
\ No newline at end of file
+def batch_omim_disease_enrichment(hposets: List[HPOSet], sort_by: str = "pvalue", output_path: str | PathLike | None = None, format: str | None = None, workers: int | None = None) -> int | List[List[Dict[str, Any]]]: ...
+def batch_orpha_disease_enrichment(hposets: List[HPOSet], sort_by: str = "pvalue", output_path: str | PathLike | None = None, format: str | None = None, workers: int | None = None) -> int | List[List[Dict[str, Any]]]: ...
+def similarity_matrix(
+    terms: List[HPOTerm] | None = None,
+    kind: str = "omim",
+    method: str = "graphic"
+) -> SimilarityMatrix: ...
+def ancestor_embedding(
+    items: List[HPOTerm | HPOSet],
+    include_self: bool = True
+) -> SparseMatrix: ...
+def term_distance_matrix(
+    terms: List[HPOTerm | int],
+    output: str = "numpy"
+) -> Any: ...
+def random_sets(
+    n_sets: int,
+    size: int | None = None,
+    match_ic_distribution_of: HPOSet | None = None,
+    kind: str = "omim",
+    bins: int = 20,
+    seed: int | None = None
+) -> List[HPOSet]: ...
+def similarity_significance(
+    set1: HPOSet,
+    set2: HPOSet,
+    n_permutations: int = 1000,
+    kind: str = "omim",
+    method: str = "graphic",
+    combine: str = "funSimAvg",
+    bins: int = 20,
+    seed: int | None = None
+) -> Dict[str, Any]: ...
+def resolve(queries: List[int | str]) -> Tuple[List[HPOTerm | None], List[Tuple[int, str]]]: ...
+def init_worker(kwargs: Dict[str, Any] | None = None) -> None: ...
+def load_phenopackets(
+    source: str | PathLike[str] | bytes | IO[bytes] | IO[str],
+    skip_invalid: bool = False
+) -> List[Tuple[str, HPOSet]]: ...
+
+
+class SimilarityCache:
+    def enable(self, max_size: int | None = None) -> None: ...
+    def disable(self) -> None: ...
+    @property
+    def enabled(self) -> bool: ...
+    def clear(self) -> None: ...
+    def stats(self) -> Dict[str, Any]: ...
+
+
+similarity_cache: SimilarityCache
+
+
+class SetIndex:
+    def __init__(
+        self,
+        sets: List[HPOSet] | Cohort,
+        keys: List[Any] | None = None,
+        kind: str = "omim",
+        method: str = "graphic",
+        combine: str = "funSimAvg"
+    ): ...
+    @classmethod
+    def from_diseases(
+        cls,
+        source: str = "omim",
+        kind: str = "omim",
+        method: str = "graphic",
+        combine: str = "funSimAvg"
+    ) -> SetIndex: ...
+    def nearest(self, query: HPOSet, k: int | None = 10) -> List[Tuple[Any, float]]: ...
+    def __len__(self) -> int: ...
+
+
+class MinHashIndex:
+    def __init__(
+        self,
+        sets: List[HPOSet] | Cohort,
+        num_perm: int = 128,
+        bands: int = 32,
+        seed: int = 0
+    ): ...
+    @property
+    def threshold(self) -> float: ...
+    def candidates(self, query: HPOSet) -> List[int]: ...
+    def candidate_pairs(self, output: str = "numpy") -> Any: ...
+    def __len__(self) -> int: ...
@@ -0,0 +1,213 @@
+"""
+pandas extension type for HPO terms
+
+Importing this module registers the ``hpo_term`` dtype and the ``.hpo``
+Series accessor. It requires ``pandas`` to be installed.
+
+.. code-block:: python
+
+    import pandas as pd
+    from pyhpo import Ontology
+    import pyhpo.pandas
+
+    Ontology()
+    df = pd.DataFrame({"term": pd.array([118, "HP:0002650", None], dtype="hpo_term")})
+    df["term"].hpo.name
+    # >> 0    Phenotypic abnormality
+    # >> 1                 Scoliosis
+    # >> 2                      <NA>
+"""
+
+import numbers
+
+import numpy as np
+import pandas as pd
+from pandas.api.extensions import (
+    ExtensionArray,
+    ExtensionDtype,
+    register_extension_dtype,
+    register_series_accessor,
+)
+
+from pyhpo.pyhpo import HPOTerm
+from pyhpo.types import TermArray
+
+
+def _is_missing(value):
+    return value is None or value is pd.NA or (
+        isinstance(value, float) and np.isnan(value)
+    )
+
+
+@register_extension_dtype
+class HPOTermDtype(ExtensionDtype):
+    """
+    The pandas dtype of :class:`HPOTermArray`, available as ``hpo_term``
+    """
+
+    name = "hpo_term"
+    type = HPOTerm
+    kind = "O"
+    na_value = None
+
+    @classmethod
+    def construct_array_type(cls):
+        return HPOTermArray
+
+
+class HPOTermArray(ExtensionArray):
+    """
+    A pandas extension array of ``HPOTerm``
+
+    The terms are stored as integer IDs in a :class:`pyhpo.types.TermArray`,
+    so that lookups of term attributes are calculated for the whole array
+    at once instead of for every row.
+    """
+
+    def __init__(self, values, copy=False):
+        if isinstance(values, HPOTermArray):
+            values = values._data
+        if isinstance(values, TermArray):
+            self._data = values.copy() if copy else values
+        else:
+            self._data = TermArray(
+                [None if _is_missing(value) else value for value in values]
+            )
+
+    @classmethod
+    def _from_sequence(cls, scalars, *, dtype=None, copy=False):
+        return cls(scalars, copy=copy)
+
+    @classmethod
+    def _from_factorized(cls, values, original):
+        return cls(
+            TermArray.from_ids([None if value < 0 else int(value) for value in values])
+        )
+
+    @classmethod
+    def _concat_same_type(cls, to_concat):
+        return cls(TermArray.concat([array._data for array in to_concat]))
+
+    @property
+    def dtype(self):
+        return HPOTermDtype()
+
+    @property
+    def nbytes(self):
+        return self._data.nbytes
+
+    def __len__(self):
+        return len(self._data)
+
+    def __getitem__(self, item):
+        if isinstance(item, numbers.Integral):
+            return self._data[int(item)]
+        item = pd.api.indexers.check_array_indexer(self, item)
+        if isinstance(item, slice):
+            indices = range(len(self))[item]
+        elif item.dtype == bool:
+            indices = np.flatnonzero(item)
+        else:
+            indices = item
+        return type(self)(self._data.take([int(idx) for idx in indices]))
+
+    def __eq__(self, other):
+        if isinstance(other, (pd.Series, pd.Index, pd.DataFrame)):
+            return NotImplemented
+        ids = self._int_ids()
+        if isinstance(other, HPOTermArray):
+            other_ids = other._int_ids()
+        elif isinstance(other, (HPOTerm, str, numbers.Integral)):
+            other_ids = HPOTermArray([other])._int_ids()[0]
+        else:
+            other_ids = HPOTermArray(other)._int_ids()
+        return (ids == other_ids) & (ids >= 0)
+
+    def __array__(self, dtype=None, copy=None):
+        return np.array(list(self), dtype=object if dtype is None else dtype)
+
+    def isna(self):
+        return np.array(self._data.isna(), dtype=bool)
+
+    def take(self, indices, allow_fill=False, fill_value=None):
+        if _is_missing(fill_value):
+            fill_value = None
+        return type(self)(
+            self._data.take(
+                [int(idx) for idx in indices],
+                allow_fill=allow_fill,
+                fill_value=fill_value,
+            )
+        )
+
+    def copy(self):
+        return type(self)(self._data.copy())
+
+    def _int_ids(self):
+        return np.array(
+            [-1 if value is None else value for value in self._data.ids()],
+            dtype=np.int64,
+        )
+
+    def _values_for_factorize(self):
+        return self._int_ids(), -1
+
+    def _values_for_argsort(self):
+        return self._int_ids()
+
+
+@register_series_accessor("hpo")
+class HPOAccessor:
+    """
+    Vectorized term attributes of a Series with ``hpo_term`` dtype
+
+    .. code-block:: python
+
+        series.hpo.name
+        series.hpo.depth
+        series.hpo.information_content("gene")
+    """
+
+    def __init__(self, series):
+        if not isinstance(series.dtype, HPOTermDtype):
+            raise AttributeError("Can only use .hpo accessor with hpo_term values")
+        self._series = series
+        self._data = series.array._data
+
+    def _series_of(self, values, dtype=None):
+        return pd.Series(values, index=self._series.index, name=self._series.name, dtype=dtype)
+
+    @property
+    def id(self):
+        """The HPO-ID of every term, e.g. ``HP:0000118``"""
+        return self._series_of(self._data.hpo_ids(), dtype="string")
+
+    @property
+    def int_id(self):
+        """The integer representation of every term"""
+        return self._series_of(self._data.ids(), dtype="Int64")
+
+    @property
+    def name(self):
+        """The name of every term"""
+        return self._series_of(self._data.names(), dtype="string")
+
+    @property
+    def depth(self):
+        """The length of the shortest path of every term to the root term"""
+        return self._series_of(self._data.depths(), dtype="Int64")
+
+    def information_content(self, kind="omim"):
+        """
+        The information content of every term
+
+        Parameters
+        ----------
+        kind: str, default: ``omim``
+            Which kind of information content to return,
+            ``omim``, ``orpha``, ``gene`` or ``custom``
+        """
+        return self._series_of(self._data.information_content(kind), dtype="float32")
+
+
+__all__ = ("HPOTermDtype", "HPOTermArray", "HPOAccessor")
@@ -1,5 +1,8 @@
 from pyhpo.pyhpo import EnrichmentModel
 from pyhpo.pyhpo import linkage
+from pyhpo.pyhpo import hypergeom
+from pyhpo.pyhpo import Dendrogram
+from pyhpo.pyhpo import SimilarityKernel
 
 class HPOEnrichment:
     """
@@ -15,5 +18,8 @@ class HPOEnrichment:
 __all__ = (
     "EnrichmentModel",
     "linkage",
+    "hypergeom",
+    "Dendrogram",
+    "SimilarityKernel",
     "HPOEnrichment",
 )
@@ -1,13 +1,17 @@
-from typing import Any, List, Tuple, TypedDict
-from pyhpo import HPOSet, HPOTerm
+from typing import Any, Iterator, List, Tuple, TypedDict
+from pyhpo import Cohort, HPOSet, HPOTerm
 from pyhpo.annotations import Gene, Omim
 
 
 class EnrichmentOutput(TypedDict):
     enrichment: float
+    log10_pvalue: float
     fold: float
     count: int
     item: Gene | Omim
+    rank: int
+    qvalue: float
+    log10_qvalue: float
 
 class HpoEnrichmentOutput(TypedDict):
     hpo: HPOTerm
@@ -15,12 +19,16 @@ class HpoEnrichmentOutput(TypedDict):
     enrichment: float
 
 class EnrichmentModel:
-    def __init__(self, category: str): ...
+    background: str
+    universe_size: int
+    def __init__(self, category: str, background: str = "ontology"): ...
     def enrichment(
         self,
         method: str,
-        hposet: HPOSet
-    ) -> List[EnrichmentOutput]: ...
+        hposet: HPOSet,
+        sort_by: str = "pvalue",
+        output: str = "list"
+    ) -> List[EnrichmentOutput] | Any: ...
 
 
 class HPOEnrichment:
@@ -32,10 +40,49 @@ class HPOEnrichment:
     ) -> List[HpoEnrichmentOutput]: ...
 
 
+class Dendrogram:
+    @property
+    def n_observations(self) -> int: ...
+    @property
+    def clusters(self) -> List[Tuple[int, int, float, int]]: ...
+    @property
+    def distances(self) -> List[float]: ...
+    def members(self, cluster: int) -> List[int]: ...
+    def fcluster(self, threshold: float | None = None, k: int | None = None) -> List[int]: ...
+    def to_numpy(self) -> Any: ...
+    def __array__(self, dtype: Any = None, copy: bool | None = None) -> Any: ...
+    def __len__(self) -> int: ...
+    def __getitem__(self, idx: int) -> Tuple[int, int, float, int]: ...
+    def __iter__(self) -> Iterator[Tuple[int, int, float, int]]: ...
+
+
+class SimilarityKernel:
+    def __init__(
+        self,
+        sets: List[HPOSet] | Cohort | None = None,
+        kind: str = "omim",
+        method: str = "graphic",
+        combine: str = "funSimAvg",
+        on_empty: str = "zero",
+        output: str = "numpy"
+    ): ...
+    @property
+    def n_sets(self) -> int: ...
+    def __call__(self, X: Any, Y: Any = None) -> Any: ...
+
+
 def linkage(
     sets: List[HPOSet],
     method: str,
     kind: str,
     similarity_method: str,
-    combine: str
-) -> List[Tuple[int, int, float, int]]: ...
+    combine: str,
+    on_empty: str = "zero",
+    nan_policy: str = "propagate",
+    nan_value: float = 0.0,
+    deterministic: bool = False,
+    dtype: str = "float32"
+) -> Dendrogram: ...
+
+
+def hypergeom(k: int, n: int, K: int, N: int) -> float: ...
@@ -1,4 +1,4 @@
-from pyhpo.pyhpo import InformationContent
+from pyhpo.pyhpo import InformationContent, ScoreBuffer, SimilarityMatrix, SparseMatrix, TermArray
 
 
-__all__ = ("InformationContent", )
+__all__ = ("InformationContent", "ScoreBuffer", "SimilarityMatrix", "SparseMatrix", "TermArray")
@@ -1,5 +1,75 @@
+from typing import Any, List, Tuple
+from pyhpo.pyhpo import HPOTerm
+
+
 class InformationContent:
     def gene(self) -> float: ...
     def omim(self) -> float: ...
     def orpha(self) -> float: ...
+    def custom(self) -> float: ...
     def __getitem__(self, key: str) -> float: ...
+
+
+class SparseMatrix:
+    @property
+    def row(self) -> List[int]: ...
+    @property
+    def col(self) -> List[int]: ...
+    @property
+    def data(self) -> List[float]: ...
+    @property
+    def index(self) -> List[int]: ...
+    @property
+    def columns(self) -> List[int]: ...
+    @property
+    def shape(self) -> Tuple[int, int]: ...
+    @property
+    def nnz(self) -> int: ...
+    def to_scipy(self, format: str = "coo") -> Any: ...
+    def to_csr(self) -> Tuple[List[float], List[int], List[int]]: ...
+
+
+class SimilarityMatrix:
+    @property
+    def kind(self) -> str: ...
+    @property
+    def method(self) -> str: ...
+    @property
+    def ids(self) -> List[int]: ...
+    def get(self, a: HPOTerm, b: HPOTerm) -> float: ...
+    def save(self, path: str) -> None: ...
+    @staticmethod
+    def load(path: str) -> SimilarityMatrix: ...
+    def __len__(self) -> int: ...
+
+
+class ScoreBuffer:
+    @property
+    def shape(self) -> List[int]: ...
+    @property
+    def dtype(self) -> str: ...
+    @property
+    def nbytes(self) -> int: ...
+    def tolist(self) -> List[float] | List[List[float]]: ...
+    def __buffer__(self, flags: int) -> memoryview: ...
+    def __len__(self) -> int: ...
+
+
+class TermArray:
+    def __init__(self, terms: List[HPOTerm | int | str | None]) -> None: ...
+    @classmethod
+    def from_ids(cls, ids: List[int | None]) -> TermArray: ...
+    @classmethod
+    def concat(cls, arrays: List[TermArray]) -> TermArray: ...
+    def __len__(self) -> int: ...
+    def __getitem__(self, idx: int) -> HPOTerm | None: ...
+    def take(self, indices: List[int], allow_fill: bool = False, fill_value: HPOTerm | int | str | None = None) -> TermArray: ...
+    def copy(self) -> TermArray: ...
+    @property
+    def nbytes(self) -> int: ...
+    def ids(self) -> List[int | None]: ...
+    def isna(self) -> List[bool]: ...
+    def names(self) -> List[str | None]: ...
+    def hpo_ids(self) -> List[str | None]: ...
+    def depths(self) -> List[int | None]: ...
+    def information_content(self, kind: str = "omim") -> List[float]: ...
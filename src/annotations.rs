@@ -1,18 +1,82 @@
 use hpo::annotations::Disease;
+use once_cell::sync::OnceCell;
 use std::collections::HashSet;
 use std::hash::Hash;
 
 use pyo3::class::basic::CompareOp;
-use pyo3::exceptions::{PyKeyError, PyTypeError};
+use pyo3::exceptions::{PyKeyError, PyRuntimeError, PyTypeError, PyValueError};
 use pyo3::types::PyDict;
 use pyo3::{prelude::*, types::PyType};
+use serde::Serialize;
 
 use hpo::annotations::{AnnotationId, OrphaDiseaseId};
 use hpo::annotations::{GeneId, OmimDiseaseId};
 
-use crate::{get_ontology, set::PyHpoSet, PyQuery};
+/// The terms that are directly linked to genes
+///
+/// Not set if the Ontology was built from custom binary data,
+/// which can contain direct or transitive annotations
+pub(crate) static DIRECT_GENE_TERMS: OnceCell<DirectGeneTerms> = OnceCell::new();
 
-#[pyclass(name = "Gene")]
+use crate::hpoa::{disease_annotations, modifiers_by_term, term_frequency, HpoaRecord};
+use crate::loader::DirectGeneTerms;
+use crate::mondo::{self, PyMondoDisease};
+use crate::term::PyHpoTerm;
+use crate::{get_ontology, pyterm_from_id, set::PyHpoSet, term_from_id, to_json_string, PyQuery};
+
+/// Returns the integer ID of an annotation from an int or string query
+///
+/// String queries can contain any of the provided `prefixes`,
+/// e.g. `OMIM:183849`, or only digits, e.g. `183849`
+///
+/// # Errors
+///
+/// - PyValueError: query cannot be converted to an integer ID
+fn annotation_id_from_query(query: PyQuery, prefixes: &[&str]) -> PyResult<u32> {
+    match query {
+        PyQuery::Id(id) => Ok(id),
+        PyQuery::Str(query) => {
+            let digits = prefixes
+                .iter()
+                .find_map(|prefix| query.strip_prefix(prefix))
+                .unwrap_or(&query);
+            digits
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| PyValueError::new_err(format!("Invalid id: {}", query)))
+        }
+    }
+}
+
+/// The number of all Omim and Orpha diseases of the Ontology
+static DISEASE_COUNTS: OnceCell<(usize, usize)> = OnceCell::new();
+
+/// Returns the number of all Omim and Orpha diseases
+///
+/// The diseases are counted once, on first use
+///
+/// # Errors
+///
+/// - PyNameError: Ontology not yet constructed
+fn disease_counts() -> PyResult<(usize, usize)> {
+    let ont = get_ontology()?;
+    Ok(*DISEASE_COUNTS.get_or_init(|| (ont.omim_diseases().count(), ont.orpha_diseases().count())))
+}
+
+/// JSON representation of genes and diseases
+///
+/// The structure is identical to the dict from `toJSON`
+#[derive(Serialize)]
+struct AnnotationJson<'a> {
+    name: &'a str,
+    id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    symbol: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hpo: Option<Vec<u32>>,
+}
+
+#[pyclass(name = "Gene", module = "pyhpo")]
 pub(crate) struct PyGene {
     id: GeneId,
     name: String,
@@ -109,6 +173,56 @@ impl PyGene {
             }))
     }
 
+    /// A set of the directly associated HPO terms
+    ///
+    /// Only the terms that are explicitly linked to the gene in
+    /// ``genes_to_phenotype.txt``, without the terms of ``phenotype_to_genes.txt``.
+    /// These terms match the annotations on the JAX website.
+    /// Same as :attr:`pyhpo.Gene.hpo`, unless the Ontology was built with
+    /// ``gene_annotation_mode="union"``.
+    ///
+    /// Returns
+    /// -------
+    /// Set[int]
+    ///     A set of integers, representing the HPO-IDs
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// RuntimeError
+    ///     The Ontology was built with ``gene_annotation_mode="transitive"``
+    ///     or from custom binary data and does not contain direct annotations
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Gene
+    ///     Ontology("/path/to/data", gene_annotation_mode="union")
+    ///     gene = Gene.get("WT1")
+    ///     len(gene.direct_hpo) <= len(gene.hpo)
+    ///     # >> True
+    ///
+    #[getter(direct_hpo)]
+    pub fn direct_hpo(&self) -> PyResult<HashSet<u32>> {
+        match DIRECT_GENE_TERMS.get() {
+            Some(DirectGeneTerms::Annotated) => self.hpo(),
+            Some(DirectGeneTerms::Recorded(direct)) => Ok(direct
+                .get(&self.id)
+                .map(|terms| terms.iter().map(|id| id.as_u32()).collect())
+                .unwrap_or_default()),
+            Some(DirectGeneTerms::Unavailable) => Err(PyRuntimeError::new_err(
+                "Direct gene annotations are not available with gene_annotation_mode `transitive`",
+            )),
+            None => Err(PyRuntimeError::new_err(
+                "Direct gene annotations are only available for the builtin Ontology \
+                or if the Ontology is built from the JAX source files",
+            )),
+        }
+    }
+
     /// Returns a ``HPOSet`` of all associated ``HPOTerm``
     ///
     /// Returns
@@ -213,8 +327,8 @@ impl PyGene {
     #[pyo3(signature = (verbose = false))]
     #[pyo3(text_signature = "($self, verbose)")]
     #[allow(non_snake_case)]
-    pub fn toJSON<'a>(&'a self, py: Python<'a>, verbose: bool) -> PyResult<Bound<'_, PyDict>> {
-        let dict = PyDict::new_bound(py);
+    pub fn toJSON<'a>(&'a self, py: Python<'a>, verbose: bool) -> PyResult<Bound<'a, PyDict>> {
+        let dict = PyDict::new(py);
         dict.set_item("name", self.name())?;
         dict.set_item("id", self.id())?;
         dict.set_item("symbol", self.name())?;
@@ -226,6 +340,54 @@ impl PyGene {
         Ok(dict)
     }
 
+    /// Returns a JSON string representation of the Gene
+    ///
+    /// The JSON is generated directly in Rust and has the same
+    /// structure as ``toJSON``.
+    ///
+    /// Parameters
+    /// ----------
+    /// verbose: bool, default: ``False``
+    ///     Indicates if all associated ``HPOTerm`` should be included in the output
+    ///
+    /// Returns
+    /// -------
+    /// str
+    ///     JSON representation of the gene
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Gene, Ontology
+    ///     Ontology()
+    ///     Gene.get("BRCA2").to_json_str()
+    ///     # >> '{"name":"BRCA2","id":675,"symbol":"BRCA2"}'
+    ///
+    #[pyo3(signature = (verbose = false))]
+    #[pyo3(text_signature = "($self, verbose)")]
+    fn to_json_str(&self, verbose: bool) -> PyResult<String> {
+        let hpo = if verbose {
+            let mut hpos: Vec<u32> = self.hpo()?.into_iter().collect();
+            hpos.sort_unstable();
+            Some(hpos)
+        } else {
+            None
+        };
+        to_json_string(&AnnotationJson {
+            name: self.name(),
+            id: self.id(),
+            symbol: Some(self.name()),
+            hpo,
+        })
+    }
+
     fn __str__(&self) -> String {
         format!("{} | {}", self.id(), self.name())
     }
@@ -242,6 +404,12 @@ impl PyGene {
         self.__int__()
     }
 
+    /// The gene is pickled by its ID and retrieved
+    /// from the Ontology when it is unpickled
+    fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, (u32,))> {
+        Ok((py.get_type::<Self>().getattr("get")?, (self.id(),)))
+    }
+
     /// Raises
     /// ------
     /// TypeError
@@ -290,13 +458,24 @@ impl From<&hpo::annotations::Gene> for PyGene {
     }
 }
 
-#[pyclass(name = "Omim")]
+#[pyclass(name = "Omim", module = "pyhpo")]
 pub(crate) struct PyOmimDisease {
     id: OmimDiseaseId,
     name: String,
 }
 
 impl PyOmimDisease {
+    /// Returns the details of all annotations from the JAX source files
+    ///
+    /// # Errors
+    ///
+    /// - PyNameError: Ontology not yet constructed
+    /// - PyRuntimeError: The Ontology was not built from the JAX source files
+    fn records(&self) -> PyResult<&'static [HpoaRecord]> {
+        get_ontology()?;
+        Ok(disease_annotations()?.omim(self.id.as_u32()))
+    }
+
     pub fn new(id: OmimDiseaseId, name: String) -> Self {
         Self { id, name }
     }
@@ -406,12 +585,242 @@ impl PyOmimDisease {
         PyHpoSet::try_from(self)
     }
 
+    /// Returns the specificity of an ``HPOTerm`` for the Omim disease
+    ///
+    /// The specificity quantifies how specific a term is for the disease,
+    /// based on how many other Omim diseases share the term (Phenomizer-style).
+    /// It is calculated as ``-ln(n / N)``, with ``n`` being the number of
+    /// diseases that are associated with the term (or any of its children)
+    /// and ``N`` the number of all Omim diseases. Terms that are not associated
+    /// with the disease have a specificity of ``0.0``.
+    ///
+    /// Parameters
+    /// ----------
+    /// term: :class:`pyhpo.HPOTerm`
+    ///     The ``HPOTerm`` to calculate the specificity of
+    ///
+    /// Returns
+    /// -------
+    /// float
+    ///     The specificity of the term for the disease
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Omim
+    ///     Ontology()
+    ///     disease = Omim.get(620402)
+    ///     for term in disease.hpo_set():
+    ///         print(term.name, disease.specificity(term))
+    ///
+    #[pyo3(text_signature = "($self, term)")]
+    fn specificity(&self, term: &PyHpoTerm) -> PyResult<f32> {
+        let total = disease_counts()?.0;
+        let term = term_from_id(term.hpo_term_id().as_u32())?;
+        let diseases = term.omim_disease_ids();
+        if !diseases.contains(&self.id) {
+            return Ok(0.0);
+        }
+        Ok((total as f32 / diseases.len() as f32).ln())
+    }
+
+    /// Returns the annotated frequency of the ``HPOTerm`` in the disease
+    ///
+    /// The frequency is the fraction of patients with the disease that
+    /// present the phenotype. Frequency classes are converted to the lower
+    /// bound of their range, e.g. *Frequent* (``HP:0040282``, 30-79%) to
+    /// ``0.3``. If the term is annotated multiple times, the mean of all
+    /// annotated frequencies is returned.
+    ///
+    /// .. note::
+    ///
+    ///     Frequencies are only available if the Ontology was built
+    ///     from the JAX source files.
+    ///
+    /// Parameters
+    /// ----------
+    /// term: :class:`pyhpo.HPOTerm`
+    ///     The ``HPOTerm`` to get the frequency of
+    ///
+    /// Returns
+    /// -------
+    /// float or None
+    ///     The frequency of the term in the disease. ``None`` if the term
+    ///     is not directly annotated to the disease or has no frequency
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// RuntimeError
+    ///     The Ontology was not built from the JAX source files
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Omim
+    ///     Ontology("/path/to/jax/files/")
+    ///     disease = Omim.get(620402)
+    ///     for term in disease.hpo_set():
+    ///         print(term.name, disease.frequency(term))
+    ///
+    #[pyo3(text_signature = "($self, term)")]
+    fn frequency(&self, term: &PyHpoTerm) -> PyResult<Option<f32>> {
+        Ok(term_frequency(self.records()?, term.hpo_term_id().as_u32()))
+    }
+
+    /// Returns the equivalent MONDO diseases
+    ///
+    /// .. note::
+    ///
+    ///     MONDO diseases are only available after loading the MONDO
+    ///     cross-reference table via :func:`pyhpo.Ontology.load_mondo`,
+    ///     otherwise an empty list is returned.
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.Mondo`]
+    ///     All MONDO diseases with an exact match to the Omim disease
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Omim
+    ///     Ontology()
+    ///     Ontology.load_mondo("/path/to/mondo.sssom.tsv")
+    ///     Omim.get(143100).mondo
+    ///     # >> [<MondoDisease (MONDO:0007739)>]
+    ///
+    #[getter(mondo)]
+    fn mondo(&self) -> Vec<PyMondoDisease> {
+        mondo::from_omim(self.id.as_u32())
+    }
+
+    /// Returns the details of all annotations of the Omim disease
+    ///
+    /// Each annotation links the disease to one ``HPOTerm`` and contains
+    /// the evidence code of the association:
+    ///
+    /// * **IEA** - Inferred from electronic annotation
+    /// * **PCS** - Published clinical study
+    /// * **TAS** - Traceable author statement
+    ///
+    /// and the IDs of the clinical modifier terms, e.g. the severity
+    /// or laterality of the phenotype.
+    ///
+    /// A term can be annotated multiple times, e.g. based on different
+    /// publications.
+    ///
+    /// .. note::
+    ///
+    ///     Annotation details are only available if the Ontology was built
+    ///     from the JAX source files.
+    ///
+    /// Returns
+    /// -------
+    /// list[dict]
+    ///     One dict with the keys ``hpo`` (int), ``evidence`` (str),
+    ///     ``frequency`` (str or None) and ``modifiers`` (list[int])
+    ///     per annotation
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// RuntimeError
+    ///     The Ontology was not built from the JAX source files
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Omim
+    ///     Ontology("/path/to/jax/files/")
+    ///     Omim.get(620402).annotations()
+    ///     # >> [
+    ///     # >>     {'hpo': 7, 'evidence': 'TAS', 'frequency': None, 'modifiers': []},
+    ///     # >>     {'hpo': 1250, 'evidence': 'PCS', 'frequency': '3/5', 'modifiers': [12828]},
+    ///     # >>     ...
+    ///     # >> ]
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn annotations<'a>(&self, py: Python<'a>) -> PyResult<Vec<Bound<'a, PyDict>>> {
+        self.records()?
+            .iter()
+            .map(|record| record.to_dict(py))
+            .collect()
+    }
+
+    /// Returns all associated ``HPOTerm`` with their clinical modifiers
+    ///
+    /// Clinical modifiers are subterms of ``HP:0012823`` and describe
+    /// e.g. the severity (``Mild``, ``Severe``), laterality or trigger
+    /// of the phenotype. Modifiers of multiple annotations of the same
+    /// term are merged.
+    ///
+    /// .. note::
+    ///
+    ///     Modifiers are only available if the Ontology was built
+    ///     from the JAX source files.
+    ///
+    /// Returns
+    /// -------
+    /// dict[:class:`pyhpo.HPOTerm`, list[:class:`pyhpo.HPOTerm`]]
+    ///     The modifier terms of each associated term. Terms without
+    ///     modifiers have an empty list.
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// RuntimeError
+    ///     The Ontology was not built from the JAX source files
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Omim
+    ///     Ontology("/path/to/jax/files/")
+    ///     for term, modifiers in Omim.get(620402).hpo_with_modifiers().items():
+    ///         print(term.name, [m.name for m in modifiers])
+    ///     # >> Seizure ['Severe']
+    ///     # >> ...
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn hpo_with_modifiers<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+        let dict = PyDict::new(py);
+        for (term, modifiers) in modifiers_by_term(self.records()?) {
+            let modifiers = modifiers
+                .into_iter()
+                .map(pyterm_from_id)
+                .collect::<PyResult<Vec<PyHpoTerm>>>()?;
+            dict.set_item(pyterm_from_id(term)?, modifiers)?;
+        }
+        Ok(dict)
+    }
+
     /// Returns the Omim disease based on the Omim-ID
     ///
     /// Parameters
     /// ----------
-    /// query: int
-    ///     An Omim ID
+    /// query: str or int
+    ///
+    ///     * **str** Omim-ID (e.g.: ``OMIM:183849`` or ``183849``)
+    ///     * **int** Omim-ID (e.g.: ``183849``)
     ///
     /// Returns
     /// -------
@@ -424,6 +833,9 @@ impl PyOmimDisease {
     ///     Ontology not yet constructed
     /// KeyError
     ///     No disease found for the query
+    /// ValueError
+    ///     The provided Omim-ID cannot be converted to the correct
+    ///     integer representation
     ///
     /// Examples
     /// --------
@@ -435,9 +847,13 @@ impl PyOmimDisease {
     ///     Omim.get(183849)
     ///     # >> <OmimDisease (183849)>
     ///
+    ///     Omim.get("OMIM:183849")
+    ///     # >> <OmimDisease (183849)>
+    ///
     #[classmethod]
-    fn get(_cls: &Bound<'_, PyType>, id: u32) -> PyResult<PyOmimDisease> {
+    fn get(_cls: &Bound<'_, PyType>, query: PyQuery) -> PyResult<PyOmimDisease> {
         let ont = get_ontology()?;
+        let id = annotation_id_from_query(query, &["OMIM:"])?;
         ont.omim_disease(&id.into())
             .ok_or(PyKeyError::new_err("'No disease found for query'"))
             .map(|d| PyOmimDisease::new(*d.id(), d.name().into()))
@@ -473,8 +889,8 @@ impl PyOmimDisease {
     #[pyo3(signature = (verbose = false))]
     #[pyo3(text_signature = "($self, verbose)")]
     #[allow(non_snake_case)]
-    pub fn toJSON<'a>(&'a self, py: Python<'a>, verbose: bool) -> PyResult<Bound<'_, PyDict>> {
-        let dict = PyDict::new_bound(py);
+    pub fn toJSON<'a>(&'a self, py: Python<'a>, verbose: bool) -> PyResult<Bound<'a, PyDict>> {
+        let dict = PyDict::new(py);
         dict.set_item("name", self.name())?;
         dict.set_item("id", self.id())?;
 
@@ -486,6 +902,54 @@ impl PyOmimDisease {
         Ok(dict)
     }
 
+    /// Returns a JSON string representation of the Omim disease
+    ///
+    /// The JSON is generated directly in Rust and has the same
+    /// structure as ``toJSON``.
+    ///
+    /// Parameters
+    /// ----------
+    /// verbose: bool, default: ``False``
+    ///     Indicates if all associated ``HPOTerm`` should be included in the output
+    ///
+    /// Returns
+    /// -------
+    /// str
+    ///     JSON representation of the Omim disease
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Omim
+    ///     Ontology()
+    ///     Omim.get(183849).to_json_str()
+    ///     # >> '{"name":"Spondyloepimetaphyseal dysplasia with hypotrichosis","id":183849}'
+    ///
+    #[pyo3(signature = (verbose = false))]
+    #[pyo3(text_signature = "($self, verbose)")]
+    fn to_json_str(&self, verbose: bool) -> PyResult<String> {
+        let hpo = if verbose {
+            let mut hpos: Vec<u32> = self.hpo()?.into_iter().collect();
+            hpos.sort_unstable();
+            Some(hpos)
+        } else {
+            None
+        };
+        to_json_string(&AnnotationJson {
+            name: self.name(),
+            id: self.id(),
+            symbol: None,
+            hpo,
+        })
+    }
+
     fn __str__(&self) -> String {
         format!("{} | {}", self.id(), self.name())
     }
@@ -502,6 +966,12 @@ impl PyOmimDisease {
         self.__int__()
     }
 
+    /// The disease is pickled by its ID and retrieved
+    /// from the Ontology when it is unpickled
+    fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, (u32,))> {
+        Ok((py.get_type::<Self>().getattr("get")?, (self.id(),)))
+    }
+
     /// Raises
     /// ------
     /// TypeError
@@ -549,13 +1019,24 @@ impl From<&hpo::annotations::OmimDisease> for PyOmimDisease {
     }
 }
 
-#[pyclass(name = "Orpha")]
+#[pyclass(name = "Orpha", module = "pyhpo")]
 pub(crate) struct PyOrphaDisease {
     id: OrphaDiseaseId,
     name: String,
 }
 
 impl PyOrphaDisease {
+    /// Returns the details of all annotations from the JAX source files
+    ///
+    /// # Errors
+    ///
+    /// - PyNameError: Ontology not yet constructed
+    /// - PyRuntimeError: The Ontology was not built from the JAX source files
+    fn records(&self) -> PyResult<&'static [HpoaRecord]> {
+        get_ontology()?;
+        Ok(disease_annotations()?.orpha(self.id.as_u32()))
+    }
+
     pub fn new(id: OrphaDiseaseId, name: String) -> Self {
         Self { id, name }
     }
@@ -667,12 +1148,242 @@ impl PyOrphaDisease {
         PyHpoSet::try_from(self)
     }
 
+    /// Returns the specificity of an ``HPOTerm`` for the Orpha disease
+    ///
+    /// The specificity quantifies how specific a term is for the disease,
+    /// based on how many other Orpha diseases share the term (Phenomizer-style).
+    /// It is calculated as ``-ln(n / N)``, with ``n`` being the number of
+    /// diseases that are associated with the term (or any of its children)
+    /// and ``N`` the number of all Orpha diseases. Terms that are not associated
+    /// with the disease have a specificity of ``0.0``.
+    ///
+    /// Parameters
+    /// ----------
+    /// term: :class:`pyhpo.HPOTerm`
+    ///     The ``HPOTerm`` to calculate the specificity of
+    ///
+    /// Returns
+    /// -------
+    /// float
+    ///     The specificity of the term for the disease
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Orpha
+    ///     Ontology()
+    ///     disease = Orpha.get(110)
+    ///     for term in disease.hpo_set():
+    ///         print(term.name, disease.specificity(term))
+    ///
+    #[pyo3(text_signature = "($self, term)")]
+    fn specificity(&self, term: &PyHpoTerm) -> PyResult<f32> {
+        let total = disease_counts()?.1;
+        let term = term_from_id(term.hpo_term_id().as_u32())?;
+        let diseases = term.orpha_disease_ids();
+        if !diseases.contains(&self.id) {
+            return Ok(0.0);
+        }
+        Ok((total as f32 / diseases.len() as f32).ln())
+    }
+
+    /// Returns the annotated frequency of the ``HPOTerm`` in the disease
+    ///
+    /// The frequency is the fraction of patients with the disease that
+    /// present the phenotype. Frequency classes are converted to the lower
+    /// bound of their range, e.g. *Frequent* (``HP:0040282``, 30-79%) to
+    /// ``0.3``. If the term is annotated multiple times, the mean of all
+    /// annotated frequencies is returned.
+    ///
+    /// .. note::
+    ///
+    ///     Frequencies are only available if the Ontology was built
+    ///     from the JAX source files.
+    ///
+    /// Parameters
+    /// ----------
+    /// term: :class:`pyhpo.HPOTerm`
+    ///     The ``HPOTerm`` to get the frequency of
+    ///
+    /// Returns
+    /// -------
+    /// float or None
+    ///     The frequency of the term in the disease. ``None`` if the term
+    ///     is not directly annotated to the disease or has no frequency
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// RuntimeError
+    ///     The Ontology was not built from the JAX source files
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Orpha
+    ///     Ontology("/path/to/jax/files/")
+    ///     disease = Orpha.get(110)
+    ///     for term in disease.hpo_set():
+    ///         print(term.name, disease.frequency(term))
+    ///
+    #[pyo3(text_signature = "($self, term)")]
+    fn frequency(&self, term: &PyHpoTerm) -> PyResult<Option<f32>> {
+        Ok(term_frequency(self.records()?, term.hpo_term_id().as_u32()))
+    }
+
+    /// Returns the equivalent MONDO diseases
+    ///
+    /// .. note::
+    ///
+    ///     MONDO diseases are only available after loading the MONDO
+    ///     cross-reference table via :func:`pyhpo.Ontology.load_mondo`,
+    ///     otherwise an empty list is returned.
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.Mondo`]
+    ///     All MONDO diseases with an exact match to the Orpha disease
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Orpha
+    ///     Ontology()
+    ///     Ontology.load_mondo("/path/to/mondo.sssom.tsv")
+    ///     Orpha.get(399).mondo
+    ///     # >> [<MondoDisease (MONDO:0007739)>]
+    ///
+    #[getter(mondo)]
+    fn mondo(&self) -> Vec<PyMondoDisease> {
+        mondo::from_orpha(self.id.as_u32())
+    }
+
+    /// Returns the details of all annotations of the Orpha disease
+    ///
+    /// Each annotation links the disease to one ``HPOTerm`` and contains
+    /// the evidence code of the association:
+    ///
+    /// * **IEA** - Inferred from electronic annotation
+    /// * **PCS** - Published clinical study
+    /// * **TAS** - Traceable author statement
+    ///
+    /// and the IDs of the clinical modifier terms, e.g. the severity
+    /// or laterality of the phenotype.
+    ///
+    /// A term can be annotated multiple times, e.g. based on different
+    /// publications.
+    ///
+    /// .. note::
+    ///
+    ///     Annotation details are only available if the Ontology was built
+    ///     from the JAX source files.
+    ///
+    /// Returns
+    /// -------
+    /// list[dict]
+    ///     One dict with the keys ``hpo`` (int), ``evidence`` (str),
+    ///     ``frequency`` (str or None) and ``modifiers`` (list[int])
+    ///     per annotation
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// RuntimeError
+    ///     The Ontology was not built from the JAX source files
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Orpha
+    ///     Ontology("/path/to/jax/files/")
+    ///     Orpha.get(110).annotations()
+    ///     # >> [
+    ///     # >>     {'hpo': 7, 'evidence': 'TAS', 'frequency': None, 'modifiers': []},
+    ///     # >>     {'hpo': 1250, 'evidence': 'PCS', 'frequency': '3/5', 'modifiers': [12828]},
+    ///     # >>     ...
+    ///     # >> ]
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn annotations<'a>(&self, py: Python<'a>) -> PyResult<Vec<Bound<'a, PyDict>>> {
+        self.records()?
+            .iter()
+            .map(|record| record.to_dict(py))
+            .collect()
+    }
+
+    /// Returns all associated ``HPOTerm`` with their clinical modifiers
+    ///
+    /// Clinical modifiers are subterms of ``HP:0012823`` and describe
+    /// e.g. the severity (``Mild``, ``Severe``), laterality or trigger
+    /// of the phenotype. Modifiers of multiple annotations of the same
+    /// term are merged.
+    ///
+    /// .. note::
+    ///
+    ///     Modifiers are only available if the Ontology was built
+    ///     from the JAX source files.
+    ///
+    /// Returns
+    /// -------
+    /// dict[:class:`pyhpo.HPOTerm`, list[:class:`pyhpo.HPOTerm`]]
+    ///     The modifier terms of each associated term. Terms without
+    ///     modifiers have an empty list.
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// RuntimeError
+    ///     The Ontology was not built from the JAX source files
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Orpha
+    ///     Ontology("/path/to/jax/files/")
+    ///     for term, modifiers in Orpha.get(110).hpo_with_modifiers().items():
+    ///         print(term.name, [m.name for m in modifiers])
+    ///     # >> Seizure ['Severe']
+    ///     # >> ...
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn hpo_with_modifiers<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+        let dict = PyDict::new(py);
+        for (term, modifiers) in modifiers_by_term(self.records()?) {
+            let modifiers = modifiers
+                .into_iter()
+                .map(pyterm_from_id)
+                .collect::<PyResult<Vec<PyHpoTerm>>>()?;
+            dict.set_item(pyterm_from_id(term)?, modifiers)?;
+        }
+        Ok(dict)
+    }
+
     /// Returns the Orpha disease based on the Orpha-ID
     ///
     /// Parameters
     /// ----------
-    /// query: int
-    ///     An Orpha ID
+    /// query: str or int
+    ///
+    ///     * **str** Orpha-ID (e.g.: ``ORPHA:110``, ``Orphanet:110`` or ``110``)
+    ///     * **int** Orpha-ID (e.g.: ``110``)
     ///
     /// Returns
     /// -------
@@ -685,6 +1396,9 @@ impl PyOrphaDisease {
     ///     Ontology not yet constructed
     /// KeyError
     ///     No disease found for the query
+    /// ValueError
+    ///     The provided Orpha-ID cannot be converted to the correct
+    ///     integer representation
     ///
     /// Examples
     /// --------
@@ -693,12 +1407,19 @@ impl PyOrphaDisease {
     ///
     ///     from pyhpo import Ontology, Orpha
     ///     Ontology()
-    ///     Orpha.get(183849)
-    ///     # >> <OrphaDisease (183849)>
+    ///     Orpha.get(110)
+    ///     # >> <OrphaDisease (110)>
+    ///
+    ///     Orpha.get("ORPHA:110")
+    ///     # >> <OrphaDisease (110)>
+    ///
+    ///     Orpha.get("Orphanet:110")
+    ///     # >> <OrphaDisease (110)>
     ///
     #[classmethod]
-    fn get(_cls: &Bound<'_, PyType>, id: u32) -> PyResult<PyOrphaDisease> {
+    fn get(_cls: &Bound<'_, PyType>, query: PyQuery) -> PyResult<PyOrphaDisease> {
         let ont = get_ontology()?;
+        let id = annotation_id_from_query(query, &["ORPHA:", "Orphanet:"])?;
         ont.orpha_disease(&id.into())
             .ok_or(PyKeyError::new_err("'No disease found for query'"))
             .map(|d| PyOrphaDisease::new(*d.id(), d.name().into()))
@@ -734,8 +1455,8 @@ impl PyOrphaDisease {
     #[pyo3(signature = (verbose = false))]
     #[pyo3(text_signature = "($self, verbose)")]
     #[allow(non_snake_case)]
-    pub fn toJSON<'a>(&'a self, py: Python<'a>, verbose: bool) -> PyResult<Bound<'_, PyDict>> {
-        let dict = PyDict::new_bound(py);
+    pub fn toJSON<'a>(&'a self, py: Python<'a>, verbose: bool) -> PyResult<Bound<'a, PyDict>> {
+        let dict = PyDict::new(py);
         dict.set_item("name", self.name())?;
         dict.set_item("id", self.id())?;
 
@@ -747,6 +1468,54 @@ impl PyOrphaDisease {
         Ok(dict)
     }
 
+    /// Returns a JSON string representation of the Orpha disease
+    ///
+    /// The JSON is generated directly in Rust and has the same
+    /// structure as ``toJSON``.
+    ///
+    /// Parameters
+    /// ----------
+    /// verbose: bool, default: ``False``
+    ///     Indicates if all associated ``HPOTerm`` should be included in the output
+    ///
+    /// Returns
+    /// -------
+    /// str
+    ///     JSON representation of the Orpha disease
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Orpha
+    ///     Ontology()
+    ///     Orpha.get(110).to_json_str()
+    ///     # >> '{"name":"Bardet-Biedl syndrome","id":110}'
+    ///
+    #[pyo3(signature = (verbose = false))]
+    #[pyo3(text_signature = "($self, verbose)")]
+    fn to_json_str(&self, verbose: bool) -> PyResult<String> {
+        let hpo = if verbose {
+            let mut hpos: Vec<u32> = self.hpo()?.into_iter().collect();
+            hpos.sort_unstable();
+            Some(hpos)
+        } else {
+            None
+        };
+        to_json_string(&AnnotationJson {
+            name: self.name(),
+            id: self.id(),
+            symbol: None,
+            hpo,
+        })
+    }
+
     fn __str__(&self) -> String {
         format!("{} | {}", self.id(), self.name())
     }
@@ -763,6 +1532,12 @@ impl PyOrphaDisease {
         self.__int__()
     }
 
+    /// The disease is pickled by its ID and retrieved
+    /// from the Ontology when it is unpickled
+    fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, (u32,))> {
+        Ok((py.get_type::<Self>().getattr("get")?, (self.id(),)))
+    }
+
     /// Raises
     /// ------
     /// TypeError
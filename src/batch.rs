@@ -0,0 +1,311 @@
+//! Error handling of the parallel batch helpers
+//!
+//! By default, the batch helpers fail completely if a single comparison
+//! is malformed. The [`ErrorPolicy`] allows to complete all valid
+//! comparisons instead and to report the failed ones individually.
+//!
+//! The [`NanPolicy`] validates the calculated scores, so that `NaN`
+//! scores do not silently end up in downstream analyses.
+use std::path::PathBuf;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::IntoPyObjectExt;
+
+use crate::buffer::{ArrayOutput, PyScoreBuffer};
+use crate::output::{Format, TableWriter, Value};
+use crate::similarity::Score;
+
+/// The number of rows that are written to a file at once
+const ROWS_PER_BLOCK: usize = 100_000;
+
+/// How to handle comparisons that cannot be calculated
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ErrorPolicy {
+    /// Raise the error of the first failed comparison
+    Raise,
+    /// Use `NaN` as the score of failed comparisons
+    Nan,
+    /// Use `NaN` as the score and return the errors of all failed comparisons
+    Collect,
+}
+
+impl TryFrom<&str> for ErrorPolicy {
+    type Error = PyErr;
+    fn try_from(value: &str) -> PyResult<Self> {
+        match value {
+            "raise" => Ok(Self::Raise),
+            "nan" => Ok(Self::Nan),
+            "collect" => Ok(Self::Collect),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid errors policy {value}. Use `raise`, `nan` or `collect`"
+            ))),
+        }
+    }
+}
+
+/// How to handle similarity scores that are `NaN`
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum NanPolicy {
+    /// Return `NaN` scores as they are
+    Propagate,
+    /// Raise a `ValueError`
+    Raise,
+    /// Omit `NaN` scores from the result
+    Skip,
+    /// Replace `NaN` scores with the given value
+    Substitute(f32),
+}
+
+impl NanPolicy {
+    /// Creates the policy from its name and the value used by `substitute`
+    ///
+    /// # Errors
+    ///
+    /// - PyValueError: Invalid policy name
+    pub fn new(policy: &str, nan_value: f32) -> PyResult<Self> {
+        match policy {
+            "propagate" => Ok(Self::Propagate),
+            "raise" => Ok(Self::Raise),
+            "skip" => Ok(Self::Skip),
+            "substitute" => Ok(Self::Substitute(nan_value)),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid nan_policy {policy}. Use `propagate`, `raise`, `skip` or `substitute`"
+            ))),
+        }
+    }
+
+    /// Returns the score to use for the comparison at `idx`, `None` if it is skipped
+    ///
+    /// # Errors
+    ///
+    /// - PyValueError: The score is `NaN` and the policy is `Raise`
+    pub fn check<T: Score>(self, idx: usize, score: T) -> PyResult<Option<T>> {
+        if !score.into().is_nan() {
+            return Ok(Some(score));
+        }
+        match self {
+            Self::Propagate => Ok(Some(score)),
+            Self::Raise => Err(PyValueError::new_err(format!(
+                "The similarity score of comparison {idx} is NaN"
+            ))),
+            Self::Skip => Ok(None),
+            Self::Substitute(value) => Ok(Some(value.into())),
+        }
+    }
+
+    /// Applies the policy to all scores
+    ///
+    /// # Errors
+    ///
+    /// - PyValueError: A score is `NaN` and the policy is `Raise`
+    pub fn apply<T: Score>(self, scores: Vec<T>) -> PyResult<Vec<T>> {
+        if self == Self::Propagate {
+            return Ok(scores);
+        }
+        let mut checked = Vec::with_capacity(scores.len());
+        for (idx, score) in scores.into_iter().enumerate() {
+            checked.extend(self.check(idx, score)?);
+        }
+        Ok(checked)
+    }
+}
+
+/// The index and error message of a failed comparison
+pub(crate) type BatchError = (usize, String);
+
+/// A single comparison of a batch, or the reason why it is invalid
+pub(crate) type Comparison<T> = PyResult<(T, T)>;
+
+/// Extracts every comparison of the batch individually
+///
+/// # Errors
+///
+/// - The error of the first malformed comparison, if `policy` is `Raise`
+pub(crate) fn extract_comparisons<'py, T>(
+    comparisons: Vec<Bound<'py, PyAny>>,
+    policy: ErrorPolicy,
+) -> PyResult<Vec<Comparison<T>>>
+where
+    T: FromPyObject<'py>,
+{
+    comparisons
+        .iter()
+        .map(|comparison| match comparison.extract::<(T, T)>() {
+            Err(err) if policy == ErrorPolicy::Raise => Err(err),
+            pair => Ok(pair),
+        })
+        .collect()
+}
+
+/// Converts the scores of a batch into the Python return value
+///
+/// Failed comparisons are returned as `NaN`. With the `Collect` policy, the
+/// scores are returned together with a list of `(index, error)` tuples.
+/// The `nan_policy` is only applied to the scores of successful comparisons.
+/// The scores are returned as type of `output`.
+///
+/// # Errors
+///
+/// - The error of the first failed comparison, if `policy` is `Raise`
+/// - PyValueError: A score is `NaN` and `nan_policy` is `Raise`
+/// - ImportError: `numpy` is not installed and `output` is `Numpy`
+pub(crate) fn scores_to_py<T: Score>(
+    py: Python<'_>,
+    scores: Vec<PyResult<T>>,
+    policy: ErrorPolicy,
+    nan_policy: NanPolicy,
+    output: ArrayOutput,
+) -> PyResult<PyObject> {
+    let (checked, errors) = check_scores(scores, policy, nan_policy, 0)?;
+    let checked = output.convert(py, PyScoreBuffer::vector(T::buffer_data(checked)))?;
+    match policy {
+        ErrorPolicy::Collect => (checked, errors).into_py_any(py),
+        ErrorPolicy::Raise | ErrorPolicy::Nan => checked.into_py_any(py),
+    }
+}
+
+/// Applies the `policy` and `nan_policy` to the scores of a batch
+///
+/// Returns the checked scores and the `(index, error)` of all failed comparisons.
+/// The indices start at `offset`, for batches that are part of a larger input.
+///
+/// # Errors
+///
+/// - The error of the first failed comparison, if `policy` is `Raise`
+/// - PyValueError: A score is `NaN` and `nan_policy` is `Raise`
+pub(crate) fn check_scores<T: Score>(
+    scores: Vec<PyResult<T>>,
+    policy: ErrorPolicy,
+    nan_policy: NanPolicy,
+    offset: usize,
+) -> PyResult<(Vec<T>, Vec<BatchError>)> {
+    let mut errors: Vec<BatchError> = Vec::new();
+    let mut checked: Vec<T> = Vec::with_capacity(scores.len());
+    for (idx, score) in (offset..).zip(scores) {
+        match score {
+            Ok(score) => checked.extend(nan_policy.check(idx, score)?),
+            Err(err) if policy == ErrorPolicy::Raise => return Err(err),
+            Err(err) => {
+                errors.push((idx, err.to_string()));
+                checked.push(f32::NAN.into());
+            }
+        }
+    }
+    Ok((checked, errors))
+}
+
+/// Converts the scores of a batch with several methods into the Python return value
+///
+/// The scores are returned as a dict with the scores of every method.
+/// Otherwise identical to [`scores_to_py`].
+///
+/// # Errors
+///
+/// - The error of the first failed comparison, if `policy` is `Raise`
+/// - PyValueError: A score is `NaN` and `nan_policy` is `Raise`
+/// - ImportError: `numpy` is not installed and `output` is `Numpy`
+pub(crate) fn multi_scores_to_py<T: Score>(
+    py: Python<'_>,
+    methods: &[String],
+    scores: Vec<PyResult<Vec<T>>>,
+    policy: ErrorPolicy,
+    nan_policy: NanPolicy,
+    output: ArrayOutput,
+) -> PyResult<PyObject> {
+    let mut errors: Vec<(usize, String)> = Vec::new();
+    let mut columns: Vec<Vec<T>> = vec![Vec::with_capacity(scores.len()); methods.len()];
+    for (idx, row) in scores.into_iter().enumerate() {
+        match row {
+            Ok(row) => {
+                for (column, score) in columns.iter_mut().zip(row) {
+                    column.extend(nan_policy.check(idx, score)?);
+                }
+            }
+            Err(err) if policy == ErrorPolicy::Raise => return Err(err),
+            Err(err) => {
+                errors.push((idx, err.to_string()));
+                for column in &mut columns {
+                    column.push(f32::NAN.into());
+                }
+            }
+        }
+    }
+    let dict = PyDict::new(py);
+    for (method, column) in methods.iter().zip(columns) {
+        let column = PyScoreBuffer::vector(T::buffer_data(column));
+        dict.set_item(method, output.convert(py, column)?)?;
+    }
+    match policy {
+        ErrorPolicy::Collect => (dict, errors).into_py_any(py),
+        ErrorPolicy::Raise | ErrorPolicy::Nan => dict.into_py_any(py),
+    }
+}
+
+/// Writes the scores of a batch to a file instead of returning them
+///
+/// Every row contains the index of the comparison and its score for every
+/// one of `methods`. Failed comparisons are written with `NaN` scores.
+/// With the `Skip` policy, rows with a `NaN` score are omitted.
+///
+/// Returns the number of written rows. With the `Collect` policy, it is
+/// returned together with a list of `(index, error)` tuples.
+///
+/// # Errors
+///
+/// - The error of the first failed comparison, if `policy` is `Raise`
+/// - PyValueError: A score is `NaN` and `nan_policy` is `Raise`
+/// - PyOSError: The file cannot be written
+/// - ImportError: `pyarrow` is not installed (Parquet only)
+pub(crate) fn scores_to_file<T: Score>(
+    py: Python<'_>,
+    path: PathBuf,
+    format: Format,
+    methods: &[String],
+    scores: Vec<PyResult<Vec<T>>>,
+    policy: ErrorPolicy,
+    nan_policy: NanPolicy,
+) -> PyResult<PyObject> {
+    let columns = std::iter::once("index").chain(methods.iter().map(String::as_str));
+    let mut writer = TableWriter::new(py, path, format, columns)?;
+    let mut errors: Vec<BatchError> = Vec::new();
+    let mut written = 0;
+    let mut rows: Vec<Vec<Value>> = Vec::with_capacity(ROWS_PER_BLOCK);
+    for (idx, row) in scores.into_iter().enumerate() {
+        let row = match row {
+            Ok(row) => {
+                let checked = row
+                    .into_iter()
+                    .map(|score| nan_policy.check(idx, score))
+                    .collect::<PyResult<Option<Vec<T>>>>()?;
+                match checked {
+                    Some(checked) => checked,
+                    None => continue,
+                }
+            }
+            Err(err) if policy == ErrorPolicy::Raise => return Err(err),
+            Err(err) => {
+                errors.push((idx, err.to_string()));
+                vec![f32::NAN.into(); methods.len()]
+            }
+        };
+        rows.push(
+            std::iter::once(Value::Int(idx as u64))
+                .chain(row.into_iter().map(|score| Value::Float(score.into())))
+                .collect(),
+        );
+        if rows.len() == ROWS_PER_BLOCK {
+            writer.write(py, &rows)?;
+            written += rows.len();
+            rows.clear();
+        }
+    }
+    writer.write(py, &rows)?;
+    written += rows.len();
+    writer.finish(py)?;
+    match policy {
+        ErrorPolicy::Collect => (written, errors).into_py_any(py),
+        ErrorPolicy::Raise | ErrorPolicy::Nan => written.into_py_any(py),
+    }
+}
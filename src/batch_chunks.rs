@@ -0,0 +1,225 @@
+//! Memory-bounded batch similarity calculation
+//!
+//! Instead of collecting all comparisons and scores at once, the
+//! comparisons are consumed lazily from a Python iterable and the
+//! scores are returned chunk by chunk.
+use std::mem::size_of;
+
+use rayon::prelude::*;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyIterator;
+use pyo3::IntoPyObjectExt;
+
+use hpo::similarity::{GroupSimilarity, StandardCombiner};
+
+use crate::batch::{check_scores, extract_comparisons, ErrorPolicy, NanPolicy};
+use crate::buffer::{ArrayOutput, PyScoreBuffer};
+use crate::get_ontology;
+use crate::set::{EmptySetPolicy, PyHpoSet};
+use crate::similarity::PySimilarity;
+use crate::similarity_cache::{self, CachedSimilarity};
+use crate::worker;
+
+/// Estimated memory of a single comparison while its chunk is calculated:
+/// the comparison item, both extracted sets, the score and its output value
+const BYTES_PER_COMPARISON: usize =
+    size_of::<PyObject>() + 2 * size_of::<PyHpoSet>() + size_of::<PyResult<f32>>() + 32;
+
+/// Iterator over the similarity scores of chunks of comparisons
+///
+/// Returned by :func:`pyhpo.helper.batch_set_similarity_chunks`
+#[pyclass(name = "SetSimilarityChunks")]
+pub(crate) struct PySetSimilarityChunks {
+    comparisons: Py<PyIterator>,
+    similarity: GroupSimilarity<CachedSimilarity<PySimilarity>, StandardCombiner>,
+    on_empty: EmptySetPolicy,
+    nan_policy: NanPolicy,
+    policy: ErrorPolicy,
+    chunk_size: usize,
+    output: ArrayOutput,
+    workers: Option<usize>,
+    /// Index of the first comparison of the next chunk
+    offset: usize,
+}
+
+#[pymethods]
+impl PySetSimilarityChunks {
+    /// The number of comparisons per chunk
+    #[getter(chunk_size)]
+    fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let mut comparisons = self.comparisons.bind(py).clone();
+        let chunk = comparisons
+            .by_ref()
+            .take(self.chunk_size)
+            .collect::<PyResult<Vec<Bound<'_, PyAny>>>>()?;
+        if chunk.is_empty() {
+            return Ok(None);
+        }
+
+        let ont = get_ontology()?;
+        let comparisons = extract_comparisons::<PyHpoSet>(chunk, self.policy)?;
+        let scores: Vec<PyResult<f32>> = worker::install_with(py, self.workers, || {
+            comparisons
+                .into_par_iter()
+                .map(|comp| {
+                    let (a, b) = comp?;
+                    let (set_a, set_b) = (a.set(ont), b.set(ont));
+                    self.on_empty
+                        .score(set_a.is_empty() || set_b.is_empty(), || {
+                            self.similarity.calculate(&set_a, &set_b)
+                        })
+                })
+                .collect()
+        })?;
+
+        let count = scores.len();
+        let (scores, errors) = check_scores(scores, self.policy, self.nan_policy, self.offset)?;
+        self.offset += count;
+
+        let block = self.output.convert(py, PyScoreBuffer::vector(scores))?;
+        Ok(Some(match self.policy {
+            ErrorPolicy::Collect => (block, errors).into_py_any(py)?,
+            ErrorPolicy::Raise | ErrorPolicy::Nan => block,
+        }))
+    }
+}
+
+/// Calculate similarity between ``HPOSet`` in memory-bounded chunks
+///
+/// This method runs parallelized on all avaible CPU
+///
+/// Works like :func:`pyhpo.helper.batch_set_similarity`, but the comparisons
+/// are consumed lazily from any iterable and the scores are returned
+/// chunk by chunk. Only a single chunk of comparisons and scores is held
+/// in memory at any time, which allows to calculate billions of comparisons.
+///
+/// Parameters
+/// ----------
+/// comparisons: Iterable[tuple[:class:`pyhpo.HPOSet`, :class:`pyhpo.HPOSet`]]
+///     An iterable of ``HPOSet`` tuples, e.g. a generator. The two
+///     ``HPOSet`` within one tuple will be compared to each other.
+/// kind: str, default: ``omim``
+///     Which kind of information content to use for similarity calculation
+/// method: str, default ``graphic``
+///     The method to use to calculate the similarity
+/// combine: str, default ``funSimAvg``
+///     The method to combine similarity measures
+/// on_empty: str, default ``zero``
+///     The similarity of comparisons with an empty ``HPOSet``
+/// nan_policy: str, default ``propagate``
+///     How to handle similarity scores that are ``NaN``
+/// nan_value: float, default ``0.0``
+///     The score that replaces ``NaN`` if ``nan_policy`` is ``substitute``
+/// errors: str, default ``raise``
+///     How to handle comparisons that cannot be calculated. With ``collect``,
+///     every chunk is a tuple of the scores and the errors of the chunk. The
+///     indices of the errors refer to the position within all comparisons.
+/// chunk_size: int, optional
+///     The number of comparisons per chunk. Defaults to the number of
+///     comparisons that fit into ``max_memory``
+/// max_memory: int, default ``100_000_000``
+///     The approximate memory budget in bytes for each chunk.
+///     Ignored if ``chunk_size`` is provided.
+/// output: str, default ``numpy``
+///     The type of each chunk, ``numpy`` for a ``float32`` numpy array,
+///     ``list`` for a list of floats or ``buffer`` for a
+///     :class:`pyhpo.types.ScoreBuffer`
+/// workers: int, optional
+///     The number of threads to calculate each chunk. Defaults to all
+///     threads of the shared thread pool
+///
+/// See :func:`pyhpo.helper.batch_set_similarity` for all options of
+/// ``kind``, ``method``, ``combine``, ``on_empty``, ``nan_policy`` and ``errors``.
+///
+/// Returns
+/// -------
+/// Iterator[numpy.ndarray | list[float] | :class:`pyhpo.types.ScoreBuffer`]
+///     The similarity scores of each chunk of comparisons, in the same order
+///     as ``comparisons``
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+/// KeyError
+///     Invalid ``kind`` provided
+/// RuntimeError
+///     Invalid ``method`` or ``combine``
+/// ValueError
+///     Invalid ``errors``, ``on_empty``, ``nan_policy`` or ``output``,
+///     a ``chunk_size`` or ``workers`` of ``0`` or a ``NaN`` score with
+///     ``nan_policy="raise"``
+/// ImportError
+///     ``numpy`` is not installed and ``output`` is ``numpy``
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     import itertools
+///     from pyhpo import Ontology, helper
+///
+///     Ontology()
+///
+///     gene_sets = [g.hpo_set() for g in Ontology.genes]
+///     comparisons = itertools.combinations(gene_sets, 2)
+///
+///     for scores in helper.batch_set_similarity_chunks(comparisons, max_memory=50_000_000):
+///         # e.g. write the scores to disk
+///         scores.tofile(output_file)
+///
+#[pyfunction]
+#[pyo3(signature = (comparisons, kind = "omim", method = "graphic", combine = "funSimAvg", on_empty = "zero", nan_policy = "propagate", nan_value = 0.0, errors = "raise", chunk_size = None, max_memory = 100_000_000, output = "numpy", workers = None))]
+#[pyo3(
+    text_signature = "(comparisons, kind, method, combine, on_empty, nan_policy, nan_value, errors, chunk_size, max_memory, output, workers)"
+)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn batch_set_similarity_chunks(
+    comparisons: &Bound<'_, PyAny>,
+    kind: &str,
+    method: &str,
+    combine: &str,
+    on_empty: &str,
+    nan_policy: &str,
+    nan_value: f32,
+    errors: &str,
+    chunk_size: Option<usize>,
+    max_memory: usize,
+    output: &str,
+    workers: Option<usize>,
+) -> PyResult<PySetSimilarityChunks> {
+    let similarity = similarity_cache::builtin(method, kind)?;
+    let combiner = StandardCombiner::try_from(combine)
+        .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+    let output = ArrayOutput::try_from(output)?;
+    let chunk_size = chunk_size.unwrap_or_else(|| (max_memory / BYTES_PER_COMPARISON).max(1));
+    if chunk_size == 0 {
+        return Err(PyValueError::new_err("chunk_size must be at least 1"));
+    }
+    if workers == Some(0) {
+        return Err(PyValueError::new_err("workers must be at least 1"));
+    }
+
+    Ok(PySetSimilarityChunks {
+        comparisons: comparisons.try_iter()?.unbind(),
+        similarity: GroupSimilarity::new(combiner, similarity),
+        on_empty: EmptySetPolicy::try_from(on_empty)?,
+        nan_policy: NanPolicy::new(nan_policy, nan_value)?,
+        policy: ErrorPolicy::try_from(errors)?,
+        chunk_size,
+        output,
+        workers,
+        offset: 0,
+    })
+}
@@ -0,0 +1,318 @@
+//! Validation and conversion of the binary Ontology format
+//!
+//! The binary format is defined by the `hpo` crate. Since version 2, it
+//! starts with the magic bytes `HPO`, followed by a single byte with the
+//! format version. Version 1 has no header at all.
+//!
+//! The `hpo` crate assumes that the data is valid and panics on malformed
+//! input. This module checks the overall layout first to raise proper
+//! Python exceptions instead.
+//!
+//! Binary data can optionally be compressed with zstd. Compressed data is
+//! detected by its magic bytes and decompressed transparently.
+use std::borrow::Cow;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+
+use hpo::Ontology;
+
+use crate::loader::Source;
+
+/// The magic bytes at the start of versioned binary data
+const MAGIC: &[u8; 3] = b"HPO";
+
+/// The binary format version that is written by `hpo3`
+pub(crate) const CURRENT_VERSION: u8 = 3;
+
+/// The magic bytes at the start of a zstd frame
+const ZSTD_MAGIC: &[u8; 4] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The maximum size of decompressed data
+///
+/// Protects against small zstd streams that expand to huge outputs.
+/// The complete Ontology is only a few MB in size.
+const MAX_DECOMPRESSED_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The zstd compression level used for compressed dumps
+const ZSTD_LEVEL: i32 = 9;
+
+/// The file extension that indicates zstd-compressed data
+const ZSTD_EXTENSION: &str = "zst";
+
+/// Returns the format version of the binary data
+///
+/// Data without the `HPO` header is in version 1
+pub(crate) fn binary_version(bytes: &[u8]) -> u8 {
+    match bytes.split_first_chunk::<3>() {
+        Some((magic, [version, ..])) if magic == MAGIC => *version,
+        _ => 1,
+    }
+}
+
+/// Decompresses zstd-compressed data, uncompressed data is returned as is
+///
+/// # Errors
+///
+/// - PyValueError: The data is not a valid zstd stream or the
+///   decompressed data is larger than [`MAX_DECOMPRESSED_SIZE`]
+fn decompress(bytes: &[u8]) -> PyResult<Cow<'_, [u8]>> {
+    if !bytes.starts_with(ZSTD_MAGIC) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    let decoder = zstd::Decoder::new(bytes)
+        .map_err(|err| invalid(&format!("unable to decompress zstd data: {err}")))?;
+    let mut data = Vec::new();
+    decoder
+        .take(MAX_DECOMPRESSED_SIZE + 1)
+        .read_to_end(&mut data)
+        .map_err(|err| invalid(&format!("unable to decompress zstd data: {err}")))?;
+    if data.len() as u64 > MAX_DECOMPRESSED_SIZE {
+        return Err(invalid(&format!(
+            "decompressed data exceeds {} MB",
+            MAX_DECOMPRESSED_SIZE / 1024 / 1024
+        )));
+    }
+    Ok(Cow::Owned(data))
+}
+
+/// Returns whether data written to `path` should be compressed
+///
+/// If not specified explicitly, files with a `.zst` extension are compressed
+pub(crate) fn should_compress(path: &Path, compress: Option<bool>) -> bool {
+    compress.unwrap_or_else(|| path.extension().is_some_and(|ext| ext == ZSTD_EXTENSION))
+}
+
+fn invalid(detail: &str) -> PyErr {
+    PyValueError::new_err(format!("Invalid binary ontology data: {detail}"))
+}
+
+/// Cursor to walk through the length-prefixed sections of the binary data
+struct Sections<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Sections<'a> {
+    fn u32(&self, pos: usize) -> PyResult<usize> {
+        self.bytes
+            .get(pos..pos + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| invalid("unexpected end of data"))
+    }
+
+    /// Returns the next section and advances the cursor
+    fn next_section(&mut self, name: &str) -> PyResult<&'a [u8]> {
+        let len = self
+            .u32(self.pos)
+            .map_err(|_| invalid(&format!("missing {name} section")))?;
+        let start = self.pos + 4;
+        let section = self
+            .bytes
+            .get(start..start + len)
+            .ok_or_else(|| invalid(&format!("truncated {name} section")))?;
+        self.pos = start + len;
+        Ok(section)
+    }
+}
+
+/// Checks that all records of a section fit into the section
+///
+/// `record_len` returns the length of the record starting at the given position
+fn check_records<F>(section: &[u8], name: &str, record_len: F) -> PyResult<()>
+where
+    F: Fn(&Sections, usize) -> PyResult<usize>,
+{
+    let cursor = Sections {
+        bytes: section,
+        pos: 0,
+    };
+    let mut pos = 0;
+    while pos < section.len() {
+        let len = record_len(&cursor, pos)?;
+        if len == 0 || pos + len > section.len() {
+            return Err(invalid(&format!("corrupted record in {name} section")));
+        }
+        pos += len;
+    }
+    Ok(())
+}
+
+/// Returns the format version and the length of the header
+///
+/// # Errors
+///
+/// - PyValueError: Unsupported format version or truncated header
+fn header(bytes: &[u8]) -> PyResult<(u8, usize)> {
+    let version = binary_version(bytes);
+    let header_len = match (bytes.starts_with(MAGIC), version) {
+        (false, _) => 0,
+        (true, 2 | 3) => 8,
+        (true, v) if v > CURRENT_VERSION => {
+            return Err(PyValueError::new_err(format!(
+                "The binary ontology uses format version {v}, but this version of hpo3 \
+                only supports versions 1 to {CURRENT_VERSION}. Please update hpo3."
+            )))
+        }
+        (true, v) => {
+            return Err(PyValueError::new_err(format!(
+                "Unsupported binary ontology format version {v}"
+            )))
+        }
+    };
+    if bytes.len() < header_len {
+        return Err(invalid("truncated header"));
+    }
+    Ok((version, header_len))
+}
+
+/// Validates the overall layout of binary Ontology data
+///
+/// # Errors
+///
+/// - PyValueError: Unsupported format version or malformed data
+pub(crate) fn validate(bytes: &[u8]) -> PyResult<u8> {
+    let (version, header_len) = header(bytes)?;
+
+    let mut sections = Sections {
+        bytes,
+        pos: header_len,
+    };
+    let min_term_len = if version == 1 { 9 } else { 14 };
+    check_records(sections.next_section("terms")?, "terms", |c, pos| {
+        let len = c.u32(pos)?;
+        Ok(if len < min_term_len { 0 } else { len })
+    })?;
+    check_records(sections.next_section("parents")?, "parents", |c, pos| {
+        Ok(8 + 4 * c.u32(pos)?)
+    })?;
+    let mut annotations = vec!["genes", "omim"];
+    if version > 2 {
+        annotations.push("orpha");
+    }
+    for name in annotations {
+        check_records(sections.next_section(name)?, name, |c, pos| c.u32(pos))?;
+    }
+    if sections.pos != bytes.len() {
+        return Err(invalid("unexpected trailing data"));
+    }
+    Ok(version)
+}
+
+/// Removes all gene and disease annotations from validated binary data
+///
+/// The annotation sections are kept, but emptied.
+fn without_annotations(bytes: &[u8]) -> PyResult<Vec<u8>> {
+    let (version, header_len) = header(bytes)?;
+    let mut sections = Sections {
+        bytes,
+        pos: header_len,
+    };
+    sections.next_section("terms")?;
+    sections.next_section("parents")?;
+    let annotation_sections = if version > 2 { 3 } else { 2 };
+    let mut stripped = Vec::with_capacity(sections.pos + 4 * annotation_sections);
+    stripped.extend_from_slice(&bytes[..sections.pos]);
+    stripped.resize(sections.pos + 4 * annotation_sections, 0);
+    Ok(stripped)
+}
+
+/// Validates the (optionally compressed) binary data and builds the Ontology
+///
+/// If `annotations` is `false`, only terms and their hierarchy are loaded.
+///
+/// Returns the Ontology and the format version of the data
+///
+/// # Errors
+///
+/// - PyValueError: Unsupported format version or malformed data
+pub(crate) fn load(bytes: &[u8], annotations: bool) -> PyResult<(Ontology, u8)> {
+    let mut bytes = decompress(bytes)?;
+    let version = validate(&bytes)?;
+    if !annotations {
+        bytes = Cow::Owned(without_annotations(&bytes)?);
+    }
+    let ont = Ontology::from_bytes(&bytes).map_err(|err| invalid(&err.to_string()))?;
+    Ok((ont, version))
+}
+
+/// Writes the Ontology in the current binary format
+///
+/// # Errors
+///
+/// - PyOSError: The file cannot be written
+pub(crate) fn save(ont: &Ontology, path: &Path, compress: bool) -> PyResult<()> {
+    let write_error = |err: std::io::Error| {
+        PyOSError::new_err(format!("Unable to write {}: {err}", path.display()))
+    };
+    let bytes = ont.as_bytes();
+    let bytes = if compress {
+        zstd::encode_all(&bytes[..], ZSTD_LEVEL).map_err(write_error)?
+    } else {
+        bytes
+    };
+    fs::write(path, bytes).map_err(write_error)
+}
+
+/// Converts a binary ontology file into the current binary format
+///
+/// Older versions of ``hpo3`` or ``hpo`` might have created binary
+/// ontology files in an older format. They can still be loaded,
+/// but converting them ensures compatibility with future versions.
+///
+/// This function does not require the Ontology to be loaded and
+/// does not modify the loaded Ontology.
+///
+/// Parameters
+/// ----------
+/// old: str, bytes or file-like
+///     Path to, or content of, the existing binary ontology.
+///     zstd-compressed data is decompressed automatically
+/// new: str
+///     Path of the converted binary ontology
+/// compress: bool, optional
+///     Whether to compress the converted ontology with zstd.
+///     Defaults to compressing if ``new`` has a ``.zst`` extension
+///
+/// Returns
+/// -------
+/// int
+///     The format version of the ``old`` binary ontology
+///
+/// Raises
+/// ------
+/// FileNotFoundError
+///     ``old`` does not exist
+/// ValueError
+///     ``old`` is not a valid binary ontology or uses an unsupported version
+/// OSError
+///     ``new`` cannot be written
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     import pyhpo
+///
+///     pyhpo.migrate_binary("old_ontology.hpo", "ontology.hpo")
+///     # >> 2
+///
+///     pyhpo.Ontology("ontology.hpo", from_obo_file=False)
+///
+#[pyfunction]
+#[pyo3(signature = (old, new, compress = None))]
+#[pyo3(text_signature = "(old, new, compress)")]
+pub(crate) fn migrate_binary(
+    py: Python<'_>,
+    old: Source,
+    new: PathBuf,
+    compress: Option<bool>,
+) -> PyResult<u8> {
+    let (ont, version) = load(&old.read(py)?, true)?;
+    save(&ont, &new, should_compress(&new, compress))?;
+    Ok(version)
+}
@@ -0,0 +1,296 @@
+//! Zero-copy export of numeric results
+//!
+//! Large results, e.g. the scores of a batch or a Gram matrix, are
+//! returned as [`PyScoreBuffer`]. It implements the Python buffer
+//! protocol, so that `numpy`, `torch` or `memoryview` wrap the memory
+//! that was allocated by Rust, instead of copying every single value.
+use std::ffi::CStr;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+use pyo3::exceptions::{PyBufferError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+use pyo3::{ffi, IntoPyObjectExt};
+
+/// The values of a [`PyScoreBuffer`]
+pub(crate) enum BufferData {
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+impl From<Vec<f32>> for BufferData {
+    fn from(values: Vec<f32>) -> Self {
+        Self::F32(values)
+    }
+}
+
+impl From<Vec<f64>> for BufferData {
+    fn from(values: Vec<f64>) -> Self {
+        Self::F64(values)
+    }
+}
+
+impl BufferData {
+    fn len(&self) -> usize {
+        match self {
+            Self::F32(values) => values.len(),
+            Self::F64(values) => values.len(),
+        }
+    }
+
+    fn itemsize(&self) -> usize {
+        match self {
+            Self::F32(_) => size_of::<f32>(),
+            Self::F64(_) => size_of::<f64>(),
+        }
+    }
+
+    /// The `struct` format character of the values
+    fn format(&self) -> &'static CStr {
+        match self {
+            Self::F32(_) => c"f",
+            Self::F64(_) => c"d",
+        }
+    }
+
+    /// The values in `start..end` as Python floats
+    fn slice<'py>(&self, py: Python<'py>, start: usize, end: usize) -> PyResult<Bound<'py, PyAny>> {
+        match self {
+            Self::F32(values) => values[start..end].into_bound_py_any(py),
+            Self::F64(values) => values[start..end].into_bound_py_any(py),
+        }
+    }
+}
+
+/// How array results are returned
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArrayOutput {
+    /// A writable `numpy.ndarray`, copied from the buffer at once
+    Numpy,
+    /// Python lists
+    List,
+    /// A [`PyScoreBuffer`]
+    Buffer,
+}
+
+impl TryFrom<&str> for ArrayOutput {
+    type Error = PyErr;
+    fn try_from(value: &str) -> PyResult<Self> {
+        match value {
+            "numpy" => Ok(Self::Numpy),
+            "list" => Ok(Self::List),
+            "buffer" => Ok(Self::Buffer),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid output {value}. Use `numpy`, `list` or `buffer`"
+            ))),
+        }
+    }
+}
+
+impl ArrayOutput {
+    /// Returns the `buffer` as Python object of this output type
+    ///
+    /// # Errors
+    ///
+    /// - ImportError: `numpy` is not installed
+    pub fn convert(self, py: Python<'_>, buffer: PyScoreBuffer) -> PyResult<PyObject> {
+        match self {
+            Self::Numpy => {
+                let buffer = Bound::new(py, buffer)?;
+                Ok(py
+                    .import("numpy")?
+                    .getattr("array")?
+                    .call1((buffer,))?
+                    .unbind())
+            }
+            Self::List => Ok(buffer.tolist(py)?.unbind()),
+            Self::Buffer => buffer.into_py_any(py),
+        }
+    }
+}
+
+/// A read-only array of floats
+///
+/// Returned by batch calculations with ``output="buffer"``. The
+/// scores remain in the memory that was allocated during the
+/// calculation. ``numpy.asarray``, ``torch.frombuffer`` or ``memoryview``
+/// wrap the buffer without copying it, which is important for results
+/// of several gigabytes. The buffer is C-contiguous and contains
+/// ``float32`` or ``float64`` values.
+///
+/// The buffer cannot be modified, the wrapping arrays are read-only as
+/// well. Use ``numpy.array(buffer)`` for a writable copy.
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     import numpy as np
+///     from pyhpo import Ontology, helper
+///
+///     Ontology()
+///
+///     sets = [g.hpo_set() for g in Ontology.genes]
+///     buffer = helper.batch_set_similarity(
+///         [(sets[0], other) for other in sets],
+///         output="buffer"
+///     )
+///     scores = np.asarray(buffer)
+///     # >> array([1.  , 0.21, ...], dtype=float32)
+///
+#[pyclass(name = "ScoreBuffer")]
+pub(crate) struct PyScoreBuffer {
+    data: BufferData,
+    shape: Vec<isize>,
+    strides: Vec<isize>,
+}
+
+impl PyScoreBuffer {
+    /// Creates a C-contiguous buffer of `data` with the given `shape`
+    ///
+    /// # Panics
+    ///
+    /// The number of values of `shape` and `data` must be identical
+    pub fn new(data: impl Into<BufferData>, shape: &[usize]) -> Self {
+        let data = data.into();
+        assert_eq!(
+            shape.iter().product::<usize>(),
+            data.len(),
+            "the shape must match the data"
+        );
+        let mut strides = vec![data.itemsize(); shape.len()];
+        for dim in (0..shape.len().saturating_sub(1)).rev() {
+            strides[dim] = strides[dim + 1] * shape[dim + 1];
+        }
+        Self {
+            data,
+            shape: shape.iter().map(|dim| *dim as isize).collect(),
+            strides: strides.into_iter().map(|stride| stride as isize).collect(),
+        }
+    }
+
+    /// Creates a one-dimensional buffer of `data`
+    pub fn vector(data: impl Into<BufferData>) -> Self {
+        let data = data.into();
+        let len = data.len();
+        Self::new(data, &[len])
+    }
+}
+
+#[pymethods]
+impl PyScoreBuffer {
+    /// The length of every dimension
+    #[getter(shape)]
+    fn shape(&self) -> Vec<isize> {
+        self.shape.clone()
+    }
+
+    /// The type of the values, ``float32`` or ``float64``
+    #[getter(dtype)]
+    fn dtype(&self) -> &'static str {
+        match self.data {
+            BufferData::F32(_) => "float32",
+            BufferData::F64(_) => "float64",
+        }
+    }
+
+    /// The size of all values in bytes
+    #[getter(nbytes)]
+    fn nbytes(&self) -> usize {
+        self.data.len() * self.data.itemsize()
+    }
+
+    /// Returns the values as (nested) list of floats
+    ///
+    /// Returns
+    /// -------
+    /// list[float] or list[list[float]]
+    ///     The values, with one level of nesting per dimension
+    ///
+    fn tolist<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.nested(py, 0, 0, self.data.len())
+    }
+
+    /// The buffer is exported read-only, so that the values can be
+    /// shared by all threads. They are never moved or resized and stay
+    /// valid until the last export is released, because every export
+    /// references the buffer.
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("ScoreBuffer is read-only"));
+        }
+        let buffer = slf.borrow();
+        let itemsize = buffer.data.itemsize();
+        (*view).buf = match &buffer.data {
+            BufferData::F32(values) => values.as_ptr() as *mut c_void,
+            BufferData::F64(values) => values.as_ptr() as *mut c_void,
+        };
+        (*view).len = (buffer.data.len() * itemsize) as isize;
+        (*view).readonly = 1;
+        (*view).itemsize = itemsize as isize;
+        (*view).format = if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
+            buffer.data.format().as_ptr().cast_mut()
+        } else {
+            ptr::null_mut()
+        };
+        (*view).ndim = buffer.shape.len() as c_int;
+        (*view).shape = if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
+            buffer.shape.as_ptr().cast_mut()
+        } else {
+            ptr::null_mut()
+        };
+        (*view).strides = if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
+            buffer.strides.as_ptr().cast_mut()
+        } else {
+            ptr::null_mut()
+        };
+        (*view).suboffsets = ptr::null_mut();
+        (*view).internal = ptr::null_mut();
+        (*view).obj = slf.clone().into_any().into_ptr();
+        Ok(())
+    }
+
+    fn __len__(&self) -> usize {
+        self.shape.first().map_or(0, |len| *len as usize)
+    }
+
+    fn __repr__(&self) -> String {
+        let shape: Vec<String> = self.shape.iter().map(|dim| dim.to_string()).collect();
+        format!(
+            "<ScoreBuffer (shape={}, dtype={})>",
+            shape.join("x"),
+            self.dtype()
+        )
+    }
+}
+
+impl PyScoreBuffer {
+    /// Returns the values `start..end` of dimension `dim` as nested lists
+    fn nested<'py>(
+        &self,
+        py: Python<'py>,
+        dim: usize,
+        start: usize,
+        end: usize,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        if dim + 1 >= self.shape.len() {
+            return self.data.slice(py, start, end);
+        }
+        let step = (end - start) / self.shape[dim].max(1) as usize;
+        let list = PyList::empty(py);
+        for row in 0..self.shape[dim] as usize {
+            let row_start = start + row * step;
+            list.append(self.nested(py, dim + 1, row_start, row_start + step)?)?;
+        }
+        Ok(list.into_any())
+    }
+}
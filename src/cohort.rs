@@ -0,0 +1,584 @@
+use std::collections::HashMap;
+use std::f64::consts::LN_10;
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+
+use pyo3::exceptions::{PyIndexError, PyKeyError, PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple, PyType};
+use pyo3::IntoPyObjectExt;
+
+use hpo::annotations::AnnotationId;
+use hpo::similarity::{GroupSimilarity, StandardCombiner};
+use hpo::stats::Enrichment;
+use hpo::term::HpoGroup;
+use hpo::{HpoSet, HpoTermId, Ontology};
+
+use crate::enrichment::{
+    enrichment_dicts, sort_enrichment, LogEnrichment, SortKey, ENRICHMENT_KEYS,
+};
+use crate::get_ontology;
+use crate::hypergeom::{hypergeom_sf, ln_hypergeom_sf};
+use crate::output::{record_columns, Format, TableOutput, TableWriter, Value};
+use crate::patient::PyPatient;
+use crate::pyterm_from_id;
+use crate::set::{EmptySetPolicy, PyHpoSet};
+use crate::similarity_cache;
+use crate::worker;
+
+/// The default number of member pairs that are calculated at once
+/// in [`PyCohort::similarity_to_file`]
+const PAIRS_PER_BLOCK: usize = 1_000_000;
+
+/// A cohort of many patients
+///
+/// The cohort provides parallel operations on the ``HPOSet`` of all
+/// its members, e.g. pairwise similarities, the prevalence of terms
+/// or the enrichment of terms compared to another cohort.
+///
+/// Parameters
+/// ----------
+/// members: list[:class:`pyhpo.Patient` | tuple[str, :class:`pyhpo.HPOSet`]]
+///     The members of the cohort, as ``Patient`` or ``(id, HPOSet)``,
+///     e.g. from :func:`pyhpo.helper.load_phenopackets`
+///
+/// Raises
+/// ------
+/// TypeError
+///     A member is neither a ``Patient`` nor an ``(id, HPOSet)`` tuple
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, Cohort, helper
+///
+///     Ontology()
+///     cohort = Cohort(helper.load_phenopackets("cohort.json"))
+///     matrix = cohort.similarity_matrix()
+///     prevalence = cohort.term_prevalence()
+///
+#[pyclass(name = "Cohort", module = "pyhpo")]
+pub(crate) struct PyCohort {
+    ids: Vec<String>,
+    sets: Vec<PyHpoSet>,
+    metadata: Vec<Py<PyDict>>,
+}
+
+#[pymethods]
+impl PyCohort {
+    #[new]
+    fn new(py: Python<'_>, members: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let mut cohort = Self {
+            ids: Vec::new(),
+            sets: Vec::new(),
+            metadata: Vec::new(),
+        };
+        for member in members.try_iter()? {
+            let member = member?;
+            let (id, hpo_set, metadata) = if let Ok(patient) = member.downcast::<PyPatient>() {
+                patient.borrow().parts(py)
+            } else if let Ok((id, hpo_set)) = member.extract::<(String, PyHpoSet)>() {
+                (id, hpo_set, PyDict::new(py).unbind())
+            } else {
+                return Err(PyTypeError::new_err(
+                    "Cohort members must be a Patient or an (id, HPOSet) tuple",
+                ));
+            };
+            cohort.ids.push(id);
+            cohort.sets.push(hpo_set);
+            cohort.metadata.push(metadata);
+        }
+        Ok(cohort)
+    }
+
+    /// The IDs of all members
+    ///
+    /// Returns
+    /// -------
+    /// list[str]
+    ///     The IDs of all members, in the order of the cohort
+    #[getter(ids)]
+    pub(crate) fn ids(&self) -> Vec<String> {
+        self.ids.clone()
+    }
+
+    /// Calculate the pairwise similarity of all members
+    ///
+    /// This method runs parallelized on all avaible CPU
+    ///
+    /// Parameters
+    /// ----------
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use for similarity calculation
+    /// method: str, default ``graphic``
+    ///     The method to use to calculate the similarity.
+    ///     See :func:`pyhpo.HPOSet.similarity` for available options
+    /// combine: str, default ``funSimAvg``
+    ///     The method to combine individual term similarity
+    ///     to HPOSet similarities.
+    /// on_empty: str, default ``zero``
+    ///     The similarity if one of the members has no phenotypes
+    ///
+    /// Returns
+    /// -------
+    /// list[list[float]]
+    ///     The symmetric similarity matrix, rows and columns are in
+    ///     the order of :attr:`pyhpo.Cohort.ids`
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method`` or ``combine``
+    /// ValueError
+    ///     Invalid ``on_empty`` or a member without phenotypes
+    ///     with ``on_empty="raise"``
+    ///
+    #[pyo3(signature = (kind = "omim", method = "graphic", combine = "funSimAvg", on_empty = "zero"))]
+    #[pyo3(text_signature = "($self, kind, method, combine, on_empty)")]
+    fn similarity_matrix(
+        &self,
+        py: Python<'_>,
+        kind: &str,
+        method: &str,
+        combine: &str,
+        on_empty: &str,
+    ) -> PyResult<Vec<Vec<f32>>> {
+        let ont = get_ontology()?;
+        let similarity = similarity_cache::builtin(method, kind)?;
+        let combiner = StandardCombiner::try_from(combine)
+            .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+        let on_empty = EmptySetPolicy::try_from(on_empty)?;
+        let g_sim = GroupSimilarity::new(combiner, similarity);
+
+        let sets: Vec<HpoSet> = self.sets.iter().map(|set| set.set(ont)).collect();
+        let pairs: Vec<(usize, usize)> = (0..sets.len())
+            .flat_map(|a| (a..sets.len()).map(move |b| (a, b)))
+            .collect();
+        let scores = worker::install(py, || {
+            pairs
+                .par_iter()
+                .map(|&(a, b)| {
+                    on_empty.score(sets[a].is_empty() || sets[b].is_empty(), || {
+                        g_sim.calculate(&sets[a], &sets[b])
+                    })
+                })
+                .collect::<PyResult<Vec<f32>>>()
+        })?;
+
+        let mut matrix = vec![vec![0.0; sets.len()]; sets.len()];
+        for ((a, b), score) in pairs.into_iter().zip(scores) {
+            matrix[a][b] = score;
+            matrix[b][a] = score;
+        }
+        Ok(matrix)
+    }
+
+    /// Calculate the pairwise similarity of all members and write it to a file
+    ///
+    /// Use this method for large cohorts, whose similarity matrix does not
+    /// fit into memory. The similarities are calculated in parallel, in
+    /// blocks of ``chunk_size`` pairs, and every block is written to the
+    /// file before the next one is calculated.
+    ///
+    /// Only the upper triangle of the matrix is written, i.e. every pair of
+    /// members once, in the order ``(0, 1), (0, 2), ... (1, 2), ...``
+    ///
+    /// - ``csv``: The columns ``id_a``, ``id_b`` and ``similarity``
+    /// - ``parquet``: Same columns as ``csv``. Requires ``pyarrow``
+    /// - ``npy``: The similarity scores as one-dimensional ``float32`` array,
+    ///   i.e. the condensed matrix of ``scipy.spatial.distance.squareform``
+    ///
+    /// Parameters
+    /// ----------
+    /// path: str
+    ///     Path of the output file
+    /// format: str, optional
+    ///     ``csv``, ``npy`` or ``parquet``. Defaults to the extension of
+    ///     ``path``, using ``csv`` for unknown extensions
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use for similarity calculation
+    /// method: str, default ``graphic``
+    ///     The method to use to calculate the similarity.
+    ///     See :func:`pyhpo.HPOSet.similarity` for available options
+    /// combine: str, default ``funSimAvg``
+    ///     The method to combine individual term similarity
+    ///     to HPOSet similarities.
+    /// on_empty: str, default ``zero``
+    ///     The similarity if one of the members has no phenotypes
+    /// progress: Callable[[int, int], None], optional
+    ///     Called after every block with the number of written pairs
+    ///     and the total number of pairs
+    /// chunk_size: int, default: ``1000000``
+    ///     The number of pairs that are calculated at once
+    ///
+    /// Returns
+    /// -------
+    /// int
+    ///     The number of written pairs
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method`` or ``combine``
+    /// ValueError
+    ///     Invalid ``format``, ``on_empty`` or ``chunk_size``, or a member
+    ///     without phenotypes with ``on_empty="raise"``
+    /// OSError
+    ///     The file cannot be written
+    /// ImportError
+    ///     ``pyarrow`` is not installed for ``parquet`` output
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     import numpy as np
+    ///     from scipy.spatial.distance import squareform
+    ///
+    ///     cohort.similarity_to_file(
+    ///         "similarity.npy",
+    ///         progress=lambda done, total: print(f"{done / total:.1%}")
+    ///     )
+    ///
+    ///     # for cohorts that fit into memory
+    ///     distances = squareform(1 - np.load("similarity.npy"), checks=False)
+    ///
+    #[pyo3(signature = (path, format = None, kind = "omim", method = "graphic", combine = "funSimAvg", on_empty = "zero", progress = None, chunk_size = PAIRS_PER_BLOCK))]
+    #[pyo3(
+        text_signature = "($self, path, format, kind, method, combine, on_empty, progress, chunk_size)"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn similarity_to_file(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        format: Option<&str>,
+        kind: &str,
+        method: &str,
+        combine: &str,
+        on_empty: &str,
+        progress: Option<PyObject>,
+        chunk_size: usize,
+    ) -> PyResult<usize> {
+        let ont = get_ontology()?;
+        let format = Format::new(&path, format)?;
+        let similarity = similarity_cache::builtin(method, kind)?;
+        let combiner = StandardCombiner::try_from(combine)
+            .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+        let on_empty = EmptySetPolicy::try_from(on_empty)?;
+        if chunk_size == 0 {
+            return Err(PyValueError::new_err("chunk_size must be at least 1"));
+        }
+        let g_sim = GroupSimilarity::new(combiner, similarity);
+
+        let sets: Vec<HpoSet> = self.sets.iter().map(|set| set.set(ont)).collect();
+        let total = sets.len() * sets.len().saturating_sub(1) / 2;
+        let mut writer = TableWriter::new(py, path, format, ["id_a", "id_b", "similarity"])?;
+        let mut written = 0;
+        let mut row = 0;
+        while row + 1 < sets.len() {
+            // Blocks contain complete rows of the upper triangle
+            let mut pairs: Vec<(usize, usize)> = Vec::new();
+            while row < sets.len() && pairs.len() < chunk_size {
+                pairs.extend((row + 1..sets.len()).map(|b| (row, b)));
+                row += 1;
+            }
+            let scores = worker::install(py, || {
+                pairs
+                    .par_iter()
+                    .map(|&(a, b)| {
+                        on_empty.score(sets[a].is_empty() || sets[b].is_empty(), || {
+                            g_sim.calculate(&sets[a], &sets[b])
+                        })
+                    })
+                    .collect::<PyResult<Vec<f32>>>()
+            })?;
+            let rows: Vec<Vec<Value>> = pairs
+                .iter()
+                .zip(scores)
+                .map(|(&(a, b), score)| {
+                    vec![
+                        Value::Str(self.ids[a].as_str().into()),
+                        Value::Str(self.ids[b].as_str().into()),
+                        Value::Float(f64::from(score)),
+                    ]
+                })
+                .collect();
+            writer.write(py, &rows)?;
+            written += rows.len();
+            if let Some(callback) = &progress {
+                callback.call1(py, (written, total))?;
+            }
+            py.check_signals()?;
+        }
+        writer.finish(py)?;
+        Ok(written)
+    }
+
+    /// Count the members that have each term
+    ///
+    /// Parameters
+    /// ----------
+    /// inherited: bool, default: ``False``
+    ///     Also count the ancestors of each term, i.e. a member with
+    ///     ``Scoliosis`` is also counted for ``Abnormality of the vertebral column``
+    ///
+    /// Returns
+    /// -------
+    /// dict[:class:`pyhpo.HPOTerm`, int]
+    ///     The number of members with each term
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    #[pyo3(signature = (inherited = false))]
+    #[pyo3(text_signature = "($self, inherited)")]
+    fn term_prevalence<'py>(
+        &self,
+        py: Python<'py>,
+        inherited: bool,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let ont = get_ontology()?;
+        let counts = term_counts(py, ont, &self.sets, inherited);
+        let mut counts: Vec<(HpoTermId, u64)> = counts.into_iter().collect();
+        counts.sort_by_key(|(id, count)| (std::cmp::Reverse(*count), *id));
+        let dict = PyDict::new(py);
+        for (id, count) in counts {
+            dict.set_item(pyterm_from_id(id.as_u32())?, count)?;
+        }
+        Ok(dict)
+    }
+
+    /// Calculate the enrichment of terms compared to another cohort
+    ///
+    /// The members of both cohorts are pooled and the hypergeometric
+    /// test determines how likely it is to draw as many members with a
+    /// term as there are in this cohort.
+    ///
+    /// Parameters
+    /// ----------
+    /// other: :class:`pyhpo.Cohort`
+    ///     The cohort to compare to, e.g. a control cohort
+    /// inherited: bool, default: ``True``
+    ///     Also count the ancestors of each term,
+    ///     see :func:`pyhpo.Cohort.term_prevalence`
+    /// sort_by: str, default ``pvalue``
+    ///     The order of the results, ``pvalue``, ``fold`` or ``count``.
+    ///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details
+    /// output: str, default ``list``
+    ///     The type of the results, ``list``, ``polars`` or ``arrow``.
+    ///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details.
+    ///     Tables contain an additional ``other_count`` column
+    ///
+    /// Returns
+    /// -------
+    /// list[dict] or polars.DataFrame or pyarrow.RecordBatch
+    ///     The enrichment of every term that is present in this cohort,
+    ///     with the same keys as :func:`pyhpo.stats.EnrichmentModel.enrichment`.
+    ///     ``item`` is the :class:`pyhpo.HPOTerm`, ``count`` the number
+    ///     of members with the term and ``other_count`` the number of
+    ///     members of ``other`` with the term.
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// ValueError
+    ///     Invalid ``sort_by`` or ``output``
+    /// ImportError
+    ///     ``polars`` or ``pyarrow`` is not installed for the ``output``
+    ///
+    #[pyo3(signature = (other, inherited = true, sort_by = "pvalue", output = "list"))]
+    #[pyo3(text_signature = "($self, other, inherited, sort_by, output)")]
+    fn enrichment(
+        &self,
+        py: Python<'_>,
+        other: &PyCohort,
+        inherited: bool,
+        sort_by: &str,
+        output: &str,
+    ) -> PyResult<PyObject> {
+        let ont = get_ontology()?;
+        let sort_by = SortKey::try_from(sort_by)?;
+        let output = TableOutput::try_from(output)?;
+        let counts = term_counts(py, ont, &self.sets, inherited);
+        let other_counts = term_counts(py, ont, &other.sets, inherited);
+
+        let draws = self.sets.len() as u64;
+        let population = draws + other.sets.len() as u64;
+        let mut enrichment: Vec<LogEnrichment<HpoTermId>> = counts
+            .iter()
+            .map(|(id, &count)| {
+                let successes = count + other_counts.get(id).copied().unwrap_or_default();
+                let pvalue = hypergeom_sf(population, successes, draws, count);
+                let ln_pvalue = ln_hypergeom_sf(population, successes, draws, count);
+                let fold = (count as f64 / draws as f64) / (successes as f64 / population as f64);
+                (
+                    Enrichment::annotation(*id, pvalue, count, fold),
+                    ln_pvalue / LN_10,
+                )
+            })
+            .collect();
+        sort_enrichment(&mut enrichment);
+
+        let records = enrichment_dicts(py, &enrichment, sort_by, |py, result| {
+            let dict = PyDict::new(py);
+            dict.set_item("enrichment", result.pvalue())?;
+            dict.set_item("fold", result.enrichment())?;
+            dict.set_item("count", result.count())?;
+            dict.set_item(
+                "other_count",
+                other_counts.get(result.id()).copied().unwrap_or_default(),
+            )?;
+            dict.set_item("item", pyterm_from_id(result.id().as_u32())?)?;
+            Ok(dict)
+        })?;
+        if output == TableOutput::List {
+            return records.into_py_any(py);
+        }
+        let mut keys = ENRICHMENT_KEYS.to_vec();
+        keys.push("other_count");
+        output.table(py, &record_columns(py, &records, &keys)?)
+    }
+
+    /// Returns the members as ``pandas.DataFrame``
+    ///
+    /// Requires ``pandas`` to be installed.
+    ///
+    /// Returns
+    /// -------
+    /// pandas.DataFrame
+    ///     One row per member with the columns ``id``, ``hpo_set``
+    ///     and ``n_terms`` and one column per key of the metadata
+    ///
+    /// Raises
+    /// ------
+    /// ImportError
+    ///     ``pandas`` is not installed
+    ///
+    fn to_dataframe<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let rows = PyList::empty(py);
+        for (idx, id) in self.ids.iter().enumerate() {
+            let row = PyDict::new(py);
+            row.set_item("id", id)?;
+            row.set_item("hpo_set", self.sets[idx].clone())?;
+            row.set_item("n_terms", self.sets[idx].__len__())?;
+            row.update(self.metadata[idx].bind(py).as_mapping())?;
+            rows.append(row)?;
+        }
+        PyModule::import(py, "pandas")?
+            .getattr("DataFrame")?
+            .call1((rows,))
+    }
+
+    /// The cohort is pickled as the list of its patients
+    fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>)> {
+        let patients = self.patients(py)?;
+        Ok((py.get_type::<Self>(), PyTuple::new(py, [patients])?))
+    }
+
+    fn __len__(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<PyPatient> {
+        let idx = if let Ok(id) = key.extract::<&str>() {
+            self.ids
+                .iter()
+                .position(|member| member == id)
+                .ok_or_else(|| PyKeyError::new_err(format!("No member with ID {id}")))?
+        } else {
+            let idx: isize = key.extract()?;
+            let len = self.ids.len() as isize;
+            let idx = if idx < 0 { idx + len } else { idx };
+            if !(0..len).contains(&idx) {
+                return Err(PyIndexError::new_err("Cohort index out of range"));
+            }
+            idx as usize
+        };
+        Ok(self.patient(py, idx))
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        Ok(self.patients(py)?.try_iter()?.into_any())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Cohort with {} members>", self.ids.len())
+    }
+}
+
+impl PyCohort {
+    /// Returns the `HPOSet` of all members
+    pub(crate) fn sets(&self) -> &[PyHpoSet] {
+        &self.sets
+    }
+
+    /// Returns the member at `idx` as `Patient`
+    fn patient(&self, py: Python<'_>, idx: usize) -> PyPatient {
+        PyPatient::from_parts(
+            self.ids[idx].clone(),
+            self.sets[idx].clone(),
+            self.metadata[idx].clone_ref(py),
+        )
+    }
+
+    /// Returns all members as list of `Patient`
+    fn patients<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, (0..self.ids.len()).map(|idx| self.patient(py, idx)))
+    }
+}
+
+/// Returns the number of `sets` that contain each term
+///
+/// If `inherited` is set, the ancestors of each term are counted as well
+fn term_counts(
+    py: Python<'_>,
+    ont: &Ontology,
+    sets: &[PyHpoSet],
+    inherited: bool,
+) -> HashMap<HpoTermId, u64> {
+    worker::install(py, || {
+        sets.par_iter()
+            .map(|set| {
+                let set = set.set(ont);
+                let mut ids = HpoGroup::new();
+                for term in &set {
+                    ids.insert(term.id());
+                    if inherited {
+                        ids = &ids | term.all_parent_ids();
+                    }
+                }
+                ids
+            })
+            .fold(HashMap::new, |mut counts, ids| {
+                for id in &ids {
+                    *counts.entry(id).or_insert(0) += 1;
+                }
+                counts
+            })
+            .reduce(HashMap::new, |mut counts, other| {
+                for (id, count) in other {
+                    *counts.entry(id).or_insert(0) += count;
+                }
+                counts
+            })
+    })
+}
@@ -0,0 +1,91 @@
+//! Compact serialization of term IDs
+//!
+//! The term IDs are handled as a bitset, compressed by encoding the
+//! gaps between consecutive set bits as variable length integers
+//! (LEB128). The resulting bytes are encoded as URL-safe base64
+//! without padding, so they can be stored in text columns.
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes the sorted, deduplicated `ids`
+pub(crate) fn encode(ids: &[u32]) -> String {
+    let mut bytes = Vec::with_capacity(ids.len() * 2);
+    let mut previous = 0;
+    for id in ids {
+        let mut gap = id - previous;
+        previous = *id;
+        while gap >= 0x80 {
+            bytes.push((gap & 0x7f) as u8 | 0x80);
+            gap >>= 7;
+        }
+        bytes.push(gap as u8);
+    }
+    to_base64(&bytes)
+}
+
+/// Decodes the term IDs of a compact string
+///
+/// # Errors
+///
+/// - PyValueError: `data` is not a valid compact string
+pub(crate) fn decode(data: &str) -> PyResult<Vec<u32>> {
+    let invalid = || PyValueError::new_err(format!("Invalid compact HPOSet: {data}"));
+    let bytes = from_base64(data).ok_or_else(invalid)?;
+
+    let mut ids = Vec::new();
+    let mut previous: u32 = 0;
+    let mut gap: u32 = 0;
+    let mut shift = 0;
+    for byte in &bytes {
+        let value = u32::from(byte & 0x7f);
+        if shift > 28 || (shift == 28 && value > 0x0f) {
+            return Err(invalid());
+        }
+        gap |= value << shift;
+        if byte & 0x80 == 0 {
+            previous = previous.checked_add(gap).ok_or_else(invalid)?;
+            ids.push(previous);
+            gap = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+        }
+    }
+    if shift != 0 {
+        return Err(invalid());
+    }
+    Ok(ids)
+}
+
+fn to_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let block = chunk.iter().enumerate().fold(0u32, |block, (idx, byte)| {
+            block | u32::from(*byte) << (16 - idx * 8)
+        });
+        for idx in 0..=chunk.len() {
+            encoded.push(ALPHABET[(block >> (18 - idx * 6) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+fn from_base64(data: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    for chunk in data.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut block = 0u32;
+        for (idx, c) in chunk.iter().enumerate() {
+            let value = ALPHABET.iter().position(|a| a == c)? as u32;
+            block |= value << (18 - idx * 6);
+        }
+        for idx in 0..chunk.len() - 1 {
+            bytes.push((block >> (16 - idx * 8)) as u8);
+        }
+    }
+    Some(bytes)
+}
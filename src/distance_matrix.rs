@@ -0,0 +1,134 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use rayon::prelude::*;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+
+use hpo::annotations::AnnotationId;
+use hpo::Ontology;
+
+use crate::term_from_term_or_id;
+use crate::worker;
+use crate::{get_ontology, TermOrId};
+
+/// Returns the undirected graph distance from `source` to every term of `targets`
+///
+/// Runs a breadth-first search over parent and child relations
+/// that stops as soon as all targets are reached.
+fn distances_from(ont: &Ontology, source: u32, targets: &HashMap<u32, Vec<usize>>) -> Vec<usize> {
+    let mut distances = vec![usize::MAX; targets.values().map(Vec::len).sum()];
+    let mut remaining = targets.len();
+    let mut visited = HashSet::from([source]);
+    let mut queue = VecDeque::from([(source, 0)]);
+    while let Some((id, distance)) = queue.pop_front() {
+        if let Some(indices) = targets.get(&id) {
+            for idx in indices {
+                distances[*idx] = distance;
+            }
+            remaining -= 1;
+            if remaining == 0 {
+                break;
+            }
+        }
+        let term = ont.hpo(id).expect("term must be part of the Ontology");
+        for next in term.parent_ids().iter().chain(term.children_ids().iter()) {
+            if visited.insert(next.as_u32()) {
+                queue.push_back((next.as_u32(), distance + 1));
+            }
+        }
+    }
+    distances
+}
+
+/// Calculates the graph distance between all pairs of terms
+///
+/// The distance is the number of edges of the shortest path between
+/// two terms, following both parent and child relations, e.g. ``2``
+/// between two siblings. All distances are calculated in parallel.
+///
+/// Parameters
+/// ----------
+/// terms: list[:class:`pyhpo.HPOTerm` or int]
+///     The terms or their integer IDs
+/// output: str, default ``numpy``
+///     The type of the matrix, ``numpy`` for an ``int64`` numpy array
+///     or ``list`` for a list of lists
+///
+/// Returns
+/// -------
+/// numpy.ndarray or list[list[int]]
+///     The distance of every term (rows) to every term (columns),
+///     in the order of ``terms``
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+/// KeyError
+///     No HPO term is found for a provided ID
+/// ValueError
+///     Invalid ``output``
+/// ImportError
+///     ``numpy`` is not installed and ``output`` is ``numpy``
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, helper
+///     Ontology()
+///
+///     helper.term_distance_matrix([1, 118, 2650])
+///     # >> array([[0, 1, 7],
+///     # >>        [1, 0, 6],
+///     # >>        [7, 6, 0]])
+///
+#[pyfunction]
+#[pyo3(signature = (terms, output = "numpy"))]
+#[pyo3(text_signature = "(terms, output)")]
+pub(crate) fn term_distance_matrix(
+    py: Python<'_>,
+    terms: Vec<TermOrId>,
+    output: &str,
+) -> PyResult<PyObject> {
+    let numpy = match output {
+        "numpy" => true,
+        "list" => false,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "Invalid output {output}. Use `numpy` or `list`"
+            )))
+        }
+    };
+    let ont = get_ontology()?;
+    let ids = terms
+        .into_iter()
+        .map(|term| term_from_term_or_id(term).map(|term| term.id().as_u32()))
+        .collect::<PyResult<Vec<u32>>>()?;
+
+    let mut targets: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (idx, id) in ids.iter().enumerate() {
+        targets.entry(*id).or_default().push(idx);
+    }
+    let matrix: Vec<Vec<usize>> = worker::install(py, || {
+        ids.par_iter()
+            .map(|id| distances_from(ont, *id, &targets))
+            .collect()
+    });
+
+    if !numpy {
+        return matrix.into_py_any(py);
+    }
+    let numpy = py.import("numpy")?;
+    let array = numpy
+        .getattr("array")?
+        .call1((matrix, numpy.getattr("int64")?))?;
+    if array.getattr("ndim")?.extract::<usize>()? == 1 {
+        // `numpy` cannot infer the shape of an empty matrix
+        return Ok(array.call_method1("reshape", ((0, 0),))?.unbind());
+    }
+    Ok(array.unbind())
+}
@@ -1,14 +1,57 @@
-use hpo::annotations::{Disease, OrphaDiseaseId};
+use std::collections::HashMap;
+use std::f64::consts::LN_10;
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+
+use hpo::annotations::{AnnotationId, Disease, OrphaDiseaseId};
 use hpo::annotations::{GeneId, OmimDiseaseId};
-use pyo3::exceptions::PyNotImplementedError;
+use pyo3::exceptions::{PyNotImplementedError, PyValueError};
 use pyo3::types::PyDict;
-use pyo3::{exceptions::PyKeyError, prelude::*};
+use pyo3::{exceptions::PyKeyError, prelude::*, IntoPyObjectExt};
 
 use hpo::stats::hypergeom::{gene_enrichment, omim_disease_enrichment, orpha_disease_enrichment};
+use hpo::stats::Enrichment;
+use hpo::{HpoSet, HpoTerm, Ontology};
 
 use crate::annotations::{PyGene, PyOmimDisease, PyOrphaDisease};
 use crate::get_ontology;
+use crate::hypergeom::{
+    binomial_sf, chi_square_sf, ln_binomial_sf, ln_chi_square_sf, ln_hypergeom_sf,
+};
+use crate::output::{record_columns, Format, TableOutput, TableWriter, Value};
 use crate::set::PyHpoSet;
+use crate::worker;
+
+/// The columns of enrichment results that are written to a file
+const ENRICHMENT_COLUMNS: [&str; 10] = [
+    "set",
+    "rank",
+    "id",
+    "name",
+    "enrichment",
+    "log10_pvalue",
+    "qvalue",
+    "log10_qvalue",
+    "fold",
+    "count",
+];
+
+/// The keys of enrichment results that are returned as table columns,
+/// in addition to the `id` and `name` of the item
+pub(crate) const ENRICHMENT_KEYS: [&str; 7] = [
+    "rank",
+    "enrichment",
+    "log10_pvalue",
+    "qvalue",
+    "log10_qvalue",
+    "fold",
+    "count",
+];
+
+/// The number of `HPOSet`s whose enrichment is calculated at once
+/// in [`enrichment_to_file`]
+const SETS_PER_BLOCK: usize = 1000;
 
 #[derive(Clone)]
 enum EnrichmentType {
@@ -17,6 +60,28 @@ enum EnrichmentType {
     Orpha,
 }
 
+/// Alternatives to the hypergeometric test of the `hpo` crate
+#[derive(Clone, Copy)]
+enum Test {
+    Binomial,
+    ChiSquare,
+}
+
+/// An enrichment result and its p-value on the log10 scale
+///
+/// The p-values of strong enrichments underflow to `0.0`, the log10
+/// p-value keeps them comparable.
+pub(crate) type LogEnrichment<T> = (Enrichment<T>, f64);
+
+/// The HPO terms that form the universe of the enrichment
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Background {
+    /// All terms of the Ontology
+    Ontology,
+    /// Only terms with at least one annotation of the category
+    Annotated,
+}
+
 /// Calculate the hypergeometric enrichment of genes
 /// or diseases in a set of HPO terms
 ///
@@ -24,11 +89,22 @@ enum EnrichmentType {
 /// ----------
 /// category: str
 ///     Specify ``gene``, ``omim`` or ``orpha`` to determine which enrichments to calculate
+/// background: str, default ``ontology``
+///     The universe of the hypergeometric test
+///
+///     * **ontology** - All terms of the Ontology
+///     * **annotated** - Only terms that are associated with at least one
+///       gene or disease of ``category``. Terms of the ``HPOSet`` without
+///       any association are ignored as well.
+///
+///     The number of terms in the universe is available as ``universe_size``
 ///
 /// Raises
 /// ------
 /// KeyError
 ///     Invalid category, only ``gene``, ``omim`` or ``orpha`` are possible
+/// ValueError
+///     Invalid background, only ``ontology`` or ``annotated`` are possible
 ///
 /// Examples
 /// --------
@@ -48,6 +124,7 @@ enum EnrichmentType {
 #[derive(Clone)]
 pub(crate) struct PyEnrichmentModel {
     kind: EnrichmentType,
+    background: Background,
 }
 
 #[pymethods]
@@ -59,11 +136,22 @@ impl PyEnrichmentModel {
     /// ----------
     /// category: str
     ///     Specify ``gene``, ``omim`` or ``orpha`` to determine which enrichments to calculate
+    /// background: str, default ``ontology``
+    ///     The universe of the hypergeometric test
+    ///
+    ///     * **ontology** - All terms of the Ontology
+    ///     * **annotated** - Only terms that are associated with at least one
+    ///       gene or disease of ``category``. Terms of the ``HPOSet`` without
+    ///       any association are ignored as well.
+    ///
+    ///     The number of terms in the universe is available as ``universe_size``
     ///
     /// Raises
     /// ------
     /// KeyError
     ///     Invalid category, only ``gene``, ``omim`` or ``orpha`` are possible
+    /// ValueError
+    ///     Invalid background, only ``ontology`` or ``annotated`` are possible
     ///
     /// Examples
     /// --------
@@ -80,14 +168,46 @@ impl PyEnrichmentModel {
     ///     # the enrichment of Omim Diseases within an HPOSet
     ///
     #[new]
-    fn new(category: &str) -> PyResult<Self> {
+    #[pyo3(signature = (category, background = "ontology"))]
+    pub(crate) fn new(category: &str, background: &str) -> PyResult<Self> {
         let kind = match category {
             "gene" => EnrichmentType::Gene,
             "omim" => EnrichmentType::Omim,
             "orpha" => EnrichmentType::Orpha,
             _ => return Err(PyKeyError::new_err("kind")),
         };
-        Ok(PyEnrichmentModel { kind })
+        let background = match background {
+            "ontology" => Background::Ontology,
+            "annotated" => Background::Annotated,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid background {background}. Use `ontology` or `annotated`"
+                )))
+            }
+        };
+        Ok(PyEnrichmentModel { kind, background })
+    }
+
+    /// The universe of the hypergeometric test, ``ontology`` or ``annotated``
+    #[getter(background)]
+    fn background(&self) -> &str {
+        match self.background {
+            Background::Ontology => "ontology",
+            Background::Annotated => "annotated",
+        }
+    }
+
+    /// The number of HPO terms in the universe of the hypergeometric test
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    #[getter(universe_size)]
+    fn universe_size(&self) -> PyResult<usize> {
+        let ont = get_ontology()?;
+        Ok(ont.iter().filter(|term| self.in_universe(term)).count())
     }
 
     /// Calculate the enrichment for all genes or diseeases in the `HPOSet`
@@ -95,31 +215,86 @@ impl PyEnrichmentModel {
     /// Parameters
     /// ----------
     /// method: `str`
-    ///     Currently, only `hypergeom` is implemented
+    ///     The statistical test
+    ///
+    ///     Available options:
+    ///
+    ///     * **hypergeom** - Hypergeometric test, i.e. drawing the terms of the
+    ///       ``HPOSet`` from the background without replacement
+    ///     * **binomial** - Binomial test, i.e. drawing with replacement, with
+    ///       the fraction of background terms of the gene or disease as
+    ///       success probability
+    ///     * **chisquare** - One-sided Pearson's chi-square test of the 2x2
+    ///       contingency table, without continuity correction. An approximation
+    ///       that is only reliable for large counts
+    ///
     /// hposet: :class:`pyhpo.HPOSet`
     ///     The set of HPOTerms to use as sampleset for calculation of
-    ///     enrichment. The full ontology is used as background set.
+    ///     enrichment. The ``background`` of the model is used as background set.
+    /// sort_by: `str`, default ``pvalue``
+    ///     The order of the results
+    ///
+    ///     Available options:
+    ///
+    ///     * **pvalue** - Ascending ``log10_pvalue``, so that p-values that
+    ///       underflow to ``0.0`` are still ranked. Ties are sorted
+    ///       by descending ``fold`` and then by ID, so the order is
+    ///       identical between runs
+    ///     * **fold** - Descending ``fold`` enrichment
+    ///     * **count** - Descending ``count``
+    ///
+    ///     Ties of ``fold`` and ``count`` are sorted by p-value
+    ///
+    /// output: `str`, default ``list``
+    ///     The type of the results
+    ///
+    ///     Available options:
+    ///
+    ///     * **list** - A list of dicts
+    ///     * **polars** - A ``polars.DataFrame``
+    ///     * **arrow** - A ``pyarrow.RecordBatch``
+    ///
+    ///     Tables contain the integer ``id`` and the ``name`` of the ``item``
+    ///     instead of the ``item`` itself, followed by ``rank``,
+    ///     ``enrichment``, ``log10_pvalue``, ``qvalue``, ``log10_qvalue``, ``fold`` and ``count``
     ///
     /// Returns
     /// -------
-    /// list[dict]
-    ///     a list with dict that contain data about the enrichment, with the keys:
+    /// list[dict] or polars.DataFrame or pyarrow.RecordBatch
+    ///     a list with dict that contain data about the enrichment, sorted by
+    ///     ``sort_by``. Each dict has the keys:
     ///
     ///     * **enrichment** : `float`
-    ///         The hypergeometric enrichment score
+    ///         The p-value of the enrichment, according to ``method``
+    ///     * **log10_pvalue** : `float`
+    ///         The base 10 logarithm of the p-value. It is calculated
+    ///         separately, so it remains finite even if ``enrichment``
+    ///         underflows to ``0.0`` below ``1e-308``
     ///     * **fold** : `float`
     ///         The fold enrichment
     ///     * **count** : `int`
     ///         Number of occurrences
     ///     * **item** : `Gene` :class:`pyhpo.Gene`, :class:`pyhpo.Omim` or :class:`pyhpo.Orpha`
     ///         The actual enriched gene or disease
+    ///     * **rank** : `int`
+    ///         The 1-based rank of the result by p-value, independent of ``sort_by``
+    ///     * **qvalue** : `float`
+    ///         The Benjamini-Hochberg FDR-adjusted p-value
+    ///     * **log10_qvalue** : `float`
+    ///         The base 10 logarithm of the q-value. Like ``log10_pvalue``,
+    ///         it remains finite even if ``qvalue`` underflows to ``0.0``
     ///
     /// Raises
     /// ------
     /// NameError
     ///     Ontology not yet constructed
     /// NotImplementedError
-    ///     invalid ``method`` provided, only ``hypergeom`` is implemented
+    ///     invalid ``method`` provided, only ``hypergeom``, ``binomial``
+    ///     and ``chisquare`` are implemented
+    /// ValueError
+    ///     invalid ``sort_by`` or ``output``
+    /// ImportError
+    ///     ``polars`` or ``pyarrow`` is not installed for the ``output``
     ///
     /// Examples
     /// --------
@@ -141,56 +316,346 @@ impl PyEnrichmentModel {
     ///
     ///     # >> {
     ///     # >>     "enrichment": 7.708086517543451e-223,
+    ///     # >>     "log10_pvalue": -222.11305...,
     ///     # >>     "fold": 27.44879391414045,
     ///     # >>     "count": 164,
-    ///     # >>     "item": <OmimDisease (608013)>
+    ///     # >>     "item": <OmimDisease (608013)>,
+    ///     # >>     "rank": 1,
+    ///     # >>     "qvalue": 6.237303593876...e-219,
+    ///     # >>     "log10_qvalue": -218.20498...
     ///     # >> }
     ///
     ///
-    #[pyo3(text_signature = "($self, method, hposet)")]
-    fn enrichment<'a>(
+    #[pyo3(signature = (method, hposet, sort_by = "pvalue", output = "list"))]
+    #[pyo3(text_signature = "($self, method, hposet, sort_by, output)")]
+    fn enrichment(
+        &self,
+        py: Python<'_>,
+        method: &str,
+        hposet: &PyHpoSet,
+        sort_by: &str,
+        output: &str,
+    ) -> PyResult<PyObject> {
+        let output = TableOutput::try_from(output)?;
+        let records = self.records(py, method, hposet, sort_by)?;
+        if output == TableOutput::List {
+            return records.into_py_any(py);
+        }
+        output.table(py, &record_columns(py, &records, &ENRICHMENT_KEYS)?)
+    }
+}
+
+impl PyEnrichmentModel {
+    /// Returns the enrichment results as dicts, sorted by `sort_by`
+    ///
+    /// # Errors
+    ///
+    /// - PyNameError: Ontology not yet constructed
+    /// - PyNotImplementedError: Invalid `method`
+    /// - PyValueError: Invalid `sort_by`
+    fn records<'a>(
         &self,
         py: Python<'a>,
         method: &str,
         hposet: &PyHpoSet,
+        sort_by: &str,
     ) -> PyResult<Vec<Bound<'a, PyDict>>> {
         let ont = get_ontology()?;
+        let sort_by = SortKey::try_from(sort_by)?;
         let set = hposet.set(ont);
+        let universe = self.universe(ont);
 
-        if method != "hypergeom" {
-            // we currently only implement hypergeometric enrichment.
-            // Once we support more methods, we should refactor this method
-            // accordingly.
-            return Err(PyNotImplementedError::new_err(
-                "Enrichment method not implemented",
-            ));
+        let test = match method {
+            "hypergeom" => None,
+            "binomial" => Some(Test::Binomial),
+            "chisquare" => Some(Test::ChiSquare),
+            _ => {
+                return Err(PyNotImplementedError::new_err(
+                    "Enrichment method not implemented",
+                ))
+            }
         };
+        if let Some(test) = test {
+            return match self.kind {
+                EnrichmentType::Gene => {
+                    let mut enr = universe.test_enrichment::<GeneId>(&set, test);
+                    sort_enrichment(&mut enr);
+                    enrichment_dicts(py, &enr, sort_by, gene_enrichment_dict)
+                }
+                EnrichmentType::Omim => {
+                    let mut enr = universe.test_enrichment::<OmimDiseaseId>(&set, test);
+                    sort_enrichment(&mut enr);
+                    enrichment_dicts(py, &enr, sort_by, omim_disease_enrichment_dict)
+                }
+                EnrichmentType::Orpha => {
+                    let mut enr = universe.test_enrichment::<OrphaDiseaseId>(&set, test);
+                    sort_enrichment(&mut enr);
+                    enrichment_dicts(py, &enr, sort_by, orpha_disease_enrichment_dict)
+                }
+            };
+        }
 
+        let background = ont.iter().filter(|term| self.in_universe(term));
+        let terms = set.iter().filter(|term| self.in_universe(term));
         let res = match self.kind {
             EnrichmentType::Gene => {
-                let mut enr = gene_enrichment(ont, &set);
-                enr.sort_by(|a, b| a.pvalue().partial_cmp(&b.pvalue()).unwrap());
-                enr.iter()
-                    .map(|enrichment| gene_enrichment_dict(py, enrichment))
-                    .collect::<PyResult<Vec<Bound<'a, PyDict>>>>()
+                let mut enr = universe.hypergeom(&set, gene_enrichment(background, terms));
+                sort_enrichment(&mut enr);
+                enrichment_dicts(py, &enr, sort_by, gene_enrichment_dict)
             }
             EnrichmentType::Omim => {
-                let mut enr = omim_disease_enrichment(ont, &set);
-                enr.sort_by(|a, b| a.pvalue().partial_cmp(&b.pvalue()).unwrap());
-                enr.iter()
-                    .map(|enrichment| omim_disease_enrichment_dict(py, enrichment))
-                    .collect::<PyResult<Vec<Bound<'a, PyDict>>>>()
+                let mut enr = universe.hypergeom(&set, omim_disease_enrichment(background, terms));
+                sort_enrichment(&mut enr);
+                enrichment_dicts(py, &enr, sort_by, omim_disease_enrichment_dict)
             }
             EnrichmentType::Orpha => {
-                let mut enr = orpha_disease_enrichment(ont, &set);
-                enr.sort_by(|a, b| a.pvalue().partial_cmp(&b.pvalue()).unwrap());
-                enr.iter()
-                    .map(|enrichment| orpha_disease_enrichment_dict(py, enrichment))
-                    .collect::<PyResult<Vec<Bound<'a, PyDict>>>>()
+                let mut enr = universe.hypergeom(&set, orpha_disease_enrichment(background, terms));
+                sort_enrichment(&mut enr);
+                enrichment_dicts(py, &enr, sort_by, orpha_disease_enrichment_dict)
             }
         };
         res
     }
+
+    /// Returns the number of terms and the number of terms per gene or
+    /// disease of all `terms` in the universe of the model
+    fn term_counts<'a>(
+        &self,
+        terms: impl Iterator<Item = HpoTerm<'a>>,
+    ) -> (u64, HashMap<u32, u64>) {
+        let mut size = 0;
+        let mut counts: HashMap<u32, u64> = HashMap::new();
+        for term in terms.filter(|term| self.in_universe(term)) {
+            size += 1;
+            let ids: Vec<u32> = match self.kind {
+                EnrichmentType::Gene => term.gene_ids().iter().map(|id| id.as_u32()).collect(),
+                EnrichmentType::Omim => term
+                    .omim_disease_ids()
+                    .iter()
+                    .map(|id| id.as_u32())
+                    .collect(),
+                EnrichmentType::Orpha => term
+                    .orpha_disease_ids()
+                    .iter()
+                    .map(|id| id.as_u32())
+                    .collect(),
+            };
+            for id in ids {
+                *counts.entry(id).or_default() += 1;
+            }
+        }
+        (size, counts)
+    }
+
+    /// Returns the terms of the universe of the model
+    pub(crate) fn universe(&self, ont: &Ontology) -> Universe<'_> {
+        let (population, successes) = self.term_counts(ont.iter());
+        Universe {
+            model: self,
+            population,
+            successes,
+        }
+    }
+
+    /// Returns `true` if `term` is part of the universe of the model
+    fn in_universe(&self, term: &HpoTerm) -> bool {
+        match (self.background, &self.kind) {
+            (Background::Ontology, _) => true,
+            (Background::Annotated, EnrichmentType::Gene) => !term.gene_ids().is_empty(),
+            (Background::Annotated, EnrichmentType::Omim) => !term.omim_disease_ids().is_empty(),
+            (Background::Annotated, EnrichmentType::Orpha) => !term.orpha_disease_ids().is_empty(),
+        }
+    }
+}
+
+/// The terms of the universe of an [`PyEnrichmentModel`]
+///
+/// The number of terms of the universe and of every gene or disease in it
+/// are identical to the hypergeometric enrichment of the `hpo` crate, so
+/// that its p-values can be recalculated on the log10 scale.
+pub(crate) struct Universe<'a> {
+    model: &'a PyEnrichmentModel,
+    population: u64,
+    successes: HashMap<u32, u64>,
+}
+
+impl Universe<'_> {
+    /// Returns the number of terms of `set` in the universe and the number
+    /// of these terms of every gene or disease
+    fn draws(&self, set: &HpoSet) -> (u64, HashMap<u32, u64>) {
+        self.model.term_counts(set.iter())
+    }
+
+    /// Returns the number of terms of the gene or disease `id` in the universe
+    fn successes(&self, id: u32, observed: u64) -> u64 {
+        self.successes.get(&id).copied().unwrap_or(observed)
+    }
+
+    /// Adds the log10 p-value to every result of the hypergeometric
+    /// `enrichment` of `set` by the `hpo` crate
+    pub(crate) fn hypergeom<T: AnnotationId>(
+        &self,
+        set: &HpoSet,
+        enrichment: Vec<Enrichment<T>>,
+    ) -> Vec<LogEnrichment<T>> {
+        let (draws, _) = self.draws(set);
+        enrichment
+            .into_iter()
+            .map(|result| {
+                let observed = result.count();
+                let successes = self.successes(result.id().as_u32(), observed);
+                let ln_pvalue = ln_hypergeom_sf(self.population, successes, draws, observed);
+                (result, ln_pvalue / LN_10)
+            })
+            .collect()
+    }
+
+    /// Calculates the enrichment of all genes or diseases in `set`
+    /// with the binomial or chi-square `test`
+    ///
+    /// The counts are identical to the hypergeometric enrichment of the
+    /// `hpo` crate, only the p-value differs.
+    fn test_enrichment<T: AnnotationId>(&self, set: &HpoSet, test: Test) -> Vec<LogEnrichment<T>> {
+        let population = self.population;
+        let (draws, sample) = self.draws(set);
+        sample
+            .into_iter()
+            .map(|(id, observed)| {
+                let successes = self.successes(id, observed);
+                let (pvalue, ln_pvalue) = match test {
+                    Test::Binomial => {
+                        let probability = successes as f64 / population as f64;
+                        (
+                            binomial_sf(draws, probability, observed),
+                            ln_binomial_sf(draws, probability, observed),
+                        )
+                    }
+                    Test::ChiSquare => (
+                        chi_square_sf(population, successes, draws, observed),
+                        ln_chi_square_sf(population, successes, draws, observed),
+                    ),
+                };
+                let fold =
+                    (observed as f64 / draws as f64) / (successes as f64 / population as f64);
+                (
+                    Enrichment::annotation(T::from(id), pvalue, observed, fold),
+                    ln_pvalue / LN_10,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Sorts enrichment results deterministically
+///
+/// Results are sorted by ascending log10 p-value, which also orders the
+/// p-values that underflow to `0.0`. Ties are sorted by descending
+/// fold enrichment and finally by ascending ID, so that results are
+/// identical between runs.
+pub(crate) fn sort_enrichment<T: AnnotationId>(enrichment: &mut [LogEnrichment<T>]) {
+    enrichment.sort_by(|(a, log_a), (b, log_b)| {
+        log_a
+            .total_cmp(log_b)
+            .then_with(|| b.enrichment().total_cmp(&a.enrichment()))
+            .then_with(|| a.id().as_u32().cmp(&b.id().as_u32()))
+    });
+}
+
+/// Returns the Benjamini-Hochberg FDR-adjusted q-values of sorted enrichment
+/// results on the log10 scale
+///
+/// The q-values are calculated from the log10 p-values, so that they
+/// do not underflow to `0.0` either.
+///
+/// `enrichment` must be sorted with [`sort_enrichment`]
+fn log10_qvalues<T: AnnotationId>(enrichment: &[LogEnrichment<T>]) -> Vec<f64> {
+    let log10_tests = (enrichment.len() as f64).log10();
+    let mut qvalues = vec![0.0; enrichment.len()];
+    let mut min = 0.0f64;
+    for (idx, (_, log10_pvalue)) in enrichment.iter().enumerate().rev() {
+        min = min.min(log10_pvalue + log10_tests - ((idx + 1) as f64).log10());
+        qvalues[idx] = min;
+    }
+    qvalues
+}
+
+/// The order of enrichment results
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortKey {
+    /// Ascending p-value
+    Pvalue,
+    /// Descending fold enrichment
+    Fold,
+    /// Descending count
+    Count,
+}
+
+impl TryFrom<&str> for SortKey {
+    type Error = PyErr;
+    fn try_from(value: &str) -> PyResult<Self> {
+        match value {
+            "pvalue" => Ok(Self::Pvalue),
+            "fold" => Ok(Self::Fold),
+            "count" => Ok(Self::Count),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid sort_by {value}. Use `pvalue`, `fold` or `count`"
+            ))),
+        }
+    }
+}
+
+/// Returns the indices of `enrichment` in the order of `sort_by`
+///
+/// `enrichment` must be sorted with [`sort_enrichment`], ties keep
+/// their order by p-value.
+fn order<T: AnnotationId>(enrichment: &[LogEnrichment<T>], sort_by: SortKey) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..enrichment.len()).collect();
+    match sort_by {
+        SortKey::Pvalue => {}
+        SortKey::Fold => order.sort_by(|&a, &b| {
+            enrichment[b]
+                .0
+                .enrichment()
+                .total_cmp(&enrichment[a].0.enrichment())
+        }),
+        SortKey::Count => order.sort_by_key(|&idx| std::cmp::Reverse(enrichment[idx].0.count())),
+    }
+    order
+}
+
+/// Returns the enrichment results as Python dicts, including their rank,
+/// log10 p-value and q-value
+///
+/// `enrichment` must be sorted with [`sort_enrichment`]. The rank is the
+/// 1-based position in that order. The dicts are returned in the order of
+/// `sort_by`, ties keep their order by p-value.
+///
+/// # Errors
+///
+/// - PyNameError: Ontology not yet constructed
+pub(crate) fn enrichment_dicts<'a, T, F>(
+    py: Python<'a>,
+    enrichment: &[LogEnrichment<T>],
+    sort_by: SortKey,
+    to_dict: F,
+) -> PyResult<Vec<Bound<'a, PyDict>>>
+where
+    T: AnnotationId,
+    F: Fn(Python<'a>, &Enrichment<T>) -> PyResult<Bound<'a, PyDict>>,
+{
+    let log10_qvalues = log10_qvalues(enrichment);
+    order(enrichment, sort_by)
+        .into_iter()
+        .map(|idx| {
+            let (result, log10_pvalue) = &enrichment[idx];
+            let dict = to_dict(py, result)?;
+            dict.set_item("log10_pvalue", log10_pvalue)?;
+            dict.set_item("rank", idx + 1)?;
+            dict.set_item("qvalue", 10f64.powf(log10_qvalues[idx]))?;
+            dict.set_item("log10_qvalue", log10_qvalues[idx])?;
+            Ok(dict)
+        })
+        .collect()
 }
 
 /// Returns the disease enrichment data as a Python dict
@@ -209,11 +674,11 @@ where
         .omim_disease(&OmimDiseaseId::from(enrichment.id().as_u32()))
         .map(|d| PyOmimDisease::new(*d.id(), d.name().into()))
         .unwrap();
-    let dict = PyDict::new_bound(py);
+    let dict = PyDict::new(py);
     dict.set_item("enrichment", enrichment.pvalue())?;
     dict.set_item("fold", enrichment.enrichment())?;
     dict.set_item("count", enrichment.count())?;
-    dict.set_item("item", disease.into_py(py))?;
+    dict.set_item("item", disease)?;
     Ok(dict)
 }
 
@@ -233,11 +698,11 @@ where
         .orpha_disease(&OrphaDiseaseId::from(enrichment.id().as_u32()))
         .map(|d| PyOrphaDisease::new(*d.id(), d.name().into()))
         .unwrap();
-    let dict = PyDict::new_bound(py);
+    let dict = PyDict::new(py);
     dict.set_item("enrichment", enrichment.pvalue())?;
     dict.set_item("fold", enrichment.enrichment())?;
     dict.set_item("count", enrichment.count())?;
-    dict.set_item("item", disease.into_py(py))?;
+    dict.set_item("item", disease)?;
     Ok(dict)
 }
 
@@ -257,10 +722,81 @@ where
         .gene(&GeneId::from(enrichment.id().as_u32()))
         .map(|g| PyGene::new(*g.id(), g.name().into()))
         .unwrap();
-    let dict = PyDict::new_bound(py);
+    let dict = PyDict::new(py);
     dict.set_item("enrichment", enrichment.pvalue())?;
     dict.set_item("fold", enrichment.enrichment())?;
     dict.set_item("count", enrichment.count())?;
-    dict.set_item("item", gene.into_py(py))?;
+    dict.set_item("item", gene)?;
     Ok(dict)
 }
+
+/// Calculates the enrichment of every `HPOSet` and writes it to a file
+///
+/// The enrichment is calculated in parallel, in blocks of
+/// [`SETS_PER_BLOCK`] sets, and every block is written before the next one
+/// is calculated. Every row contains the index of the set and one enrichment
+/// result, in the order of `sort_by`. `name` returns the name of an item.
+/// The blocks are calculated with `workers` threads, see [`worker::install_with`].
+///
+/// Returns the number of written rows
+///
+/// # Errors
+///
+/// - PyNameError: Ontology not yet constructed
+/// - PyValueError: `workers` is `0`
+/// - PyOSError: The file cannot be written
+/// - ImportError: `pyarrow` is not installed (Parquet only)
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn enrichment_to_file<T, E, N>(
+    py: Python<'_>,
+    hposets: &[PyHpoSet],
+    path: PathBuf,
+    format: Format,
+    sort_by: SortKey,
+    workers: Option<usize>,
+    enrichment: E,
+    name: N,
+) -> PyResult<usize>
+where
+    T: AnnotationId + std::fmt::Display + Send,
+    E: Fn(&Ontology, &HpoSet) -> Vec<LogEnrichment<T>> + Sync,
+    N: Fn(&T) -> String,
+{
+    let ont = get_ontology()?;
+    let mut writer = TableWriter::new(py, path, format, ENRICHMENT_COLUMNS)?;
+    let mut written = 0;
+    for (block, sets) in hposets.chunks(SETS_PER_BLOCK).enumerate() {
+        let enrichments = worker::install_with(py, workers, || {
+            sets.par_iter()
+                .map(|pyset| {
+                    let mut result = enrichment(ont, &pyset.set(ont));
+                    sort_enrichment(&mut result);
+                    result
+                })
+                .collect::<Vec<Vec<LogEnrichment<T>>>>()
+        })?;
+        let mut rows = Vec::new();
+        for (idx, set) in enrichments.iter().enumerate() {
+            let log10_qvalues = log10_qvalues(set);
+            for item in order(set, sort_by) {
+                let (result, log10_pvalue) = &set[item];
+                rows.push(vec![
+                    Value::Int((block * SETS_PER_BLOCK + idx) as u64),
+                    Value::Int(item as u64 + 1),
+                    Value::Str(result.id().to_string().into()),
+                    Value::Str(name(result.id()).into()),
+                    Value::Float(result.pvalue()),
+                    Value::Float(*log10_pvalue),
+                    Value::Float(10f64.powf(log10_qvalues[item])),
+                    Value::Float(log10_qvalues[item]),
+                    Value::Float(result.enrichment()),
+                    Value::Int(result.count()),
+                ]);
+            }
+        }
+        writer.write(py, &rows)?;
+        written += rows.len();
+    }
+    writer.finish(py)?;
+    Ok(written)
+}
@@ -0,0 +1,99 @@
+//! Conversion between HPO terms and FHIR `Coding` and `CodeableConcept`
+//!
+//! Both are represented as Python dicts with the same structure as their
+//! FHIR JSON representation, so they can be embedded into FHIR resources
+//! with `json.dumps` directly.
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use hpo::annotations::AnnotationId;
+use hpo::{HpoTerm, HpoTermId};
+
+use crate::term_from_id;
+
+/// The FHIR code system of the HPO
+pub(crate) const SYSTEM: &str = "http://purl.obolibrary.org/obo/hp.owl";
+
+/// Returns the FHIR `Coding` of `term`
+pub(crate) fn coding<'py>(py: Python<'py>, term: &HpoTerm) -> PyResult<Bound<'py, PyDict>> {
+    let coding = PyDict::new(py);
+    coding.set_item("system", SYSTEM)?;
+    coding.set_item("code", term.id().to_string())?;
+    coding.set_item("display", term.name())?;
+    Ok(coding)
+}
+
+/// Returns the FHIR `CodeableConcept` of `term`
+pub(crate) fn codeable_concept<'py>(
+    py: Python<'py>,
+    term: &HpoTerm,
+) -> PyResult<Bound<'py, PyDict>> {
+    let concept = PyDict::new(py);
+    concept.set_item("coding", PyList::new(py, [coding(py, term)?])?)?;
+    concept.set_item("text", term.name())?;
+    Ok(concept)
+}
+
+/// Returns the HPO term ID of a `Coding` with the HPO system
+///
+/// Returns `None` if the coding is from a different system
+///
+/// # Errors
+///
+/// - PyTypeError: The coding is not a dict
+/// - PyValueError: The code is not a valid HPO term ID
+fn coding_id(coding: &Bound<'_, PyAny>) -> PyResult<Option<HpoTermId>> {
+    let coding = coding
+        .downcast::<PyDict>()
+        .map_err(|_| PyTypeError::new_err("FHIR Coding must be a dict"))?;
+    let system: Option<String> = match coding.get_item("system")? {
+        Some(system) => system.extract()?,
+        None => None,
+    };
+    if system.as_deref() != Some(SYSTEM) {
+        return Ok(None);
+    }
+    let code: String = coding
+        .get_item("code")?
+        .ok_or_else(|| PyValueError::new_err("FHIR Coding does not contain a code"))?
+        .extract()?;
+    HpoTermId::try_from(code.as_str())
+        .map(Some)
+        .map_err(|_| PyValueError::new_err(format!("Invalid id: {code}")))
+}
+
+/// Returns the HPO term of a FHIR `Coding` or `CodeableConcept`
+///
+/// The first coding with the HPO system is used for `CodeableConcept`
+///
+/// # Errors
+///
+/// - PyNameError: Ontology not yet constructed
+/// - PyTypeError: The fragment is not a dict
+/// - PyValueError: The fragment does not contain a valid HPO coding
+/// - PyKeyError: No term with that ID present in Ontology
+pub(crate) fn term_from_fhir(fragment: &Bound<'_, PyAny>) -> PyResult<HpoTerm<'static>> {
+    let dict = fragment
+        .downcast::<PyDict>()
+        .map_err(|_| PyTypeError::new_err("FHIR Coding or CodeableConcept must be a dict"))?;
+    let id = match dict.get_item("coding")? {
+        Some(codings) => {
+            let mut id = None;
+            for coding in codings.try_iter()? {
+                id = coding_id(&coding?)?;
+                if id.is_some() {
+                    break;
+                }
+            }
+            id
+        }
+        None => coding_id(fragment)?,
+    };
+    let id = id.ok_or_else(|| {
+        PyValueError::new_err(format!(
+            "FHIR fragment does not contain a coding of {SYSTEM}"
+        ))
+    })?;
+    term_from_id(id.as_u32())
+}
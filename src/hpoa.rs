@@ -0,0 +1,203 @@
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+
+use once_cell::sync::OnceCell;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use hpo::Ontology;
+
+/// Details of the disease annotations, only set when the Ontology
+/// is built from the JAX source files
+pub(crate) static DISEASE_ANNOTATIONS: OnceCell<DiseaseAnnotations> = OnceCell::new();
+
+/// Returns the details of all disease annotations
+///
+/// # Errors
+///
+/// - PyRuntimeError: The Ontology was not built from the JAX source files
+pub(crate) fn disease_annotations() -> PyResult<&'static DiseaseAnnotations> {
+    DISEASE_ANNOTATIONS.get().ok_or_else(|| {
+        PyRuntimeError::new_err(
+            "Disease annotation details are only available if the Ontology is built \
+            from the JAX source files, e.g. `Ontology(\"/path/to/jax/files/\")`",
+        )
+    })
+}
+
+/// A single disease-term annotation of `phenotype.hpoa`
+pub(crate) struct HpoaRecord {
+    term: u32,
+    evidence: String,
+    frequency: String,
+    modifiers: Vec<u32>,
+}
+
+impl HpoaRecord {
+    /// Creates a record from all columns of a `phenotype.hpoa` line
+    ///
+    /// Modifier terms that are not present in the Ontology are ignored
+    pub fn new(term: u32, cols: &[&str], ont: &Ontology) -> Self {
+        let column = |idx: usize| cols.get(idx).copied().unwrap_or_default().trim();
+        Self {
+            term,
+            evidence: column(EVIDENCE_COLUMN).to_string(),
+            frequency: column(FREQUENCY_COLUMN).to_string(),
+            modifiers: column(MODIFIER_COLUMN)
+                .split(';')
+                .filter_map(|id| id.trim().strip_prefix("HP:")?.parse::<u32>().ok())
+                .filter(|id| ont.hpo(*id).is_some())
+                .collect(),
+        }
+    }
+
+    pub fn term(&self) -> u32 {
+        self.term
+    }
+
+    /// The frequency of the phenotype in the disease as fraction
+    ///
+    /// `None` if the annotation does not specify a frequency
+    pub fn frequency(&self) -> Option<f32> {
+        parse_frequency(&self.frequency)
+    }
+
+    /// The clinical modifier terms of the annotation, e.g. `HP:0012828` (Severe)
+    pub fn modifiers(&self) -> &[u32] {
+        &self.modifiers
+    }
+
+    pub fn to_dict<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("hpo", self.term)?;
+        dict.set_item("evidence", &self.evidence)?;
+        dict.set_item(
+            "frequency",
+            (!self.frequency.is_empty()).then_some(&self.frequency),
+        )?;
+        dict.set_item("modifiers", &self.modifiers)?;
+        Ok(dict)
+    }
+}
+
+/// Frequencies of the HPO frequency terms (subterms of `HP:0040279`)
+///
+/// Each frequency term covers a range, the lower bound is used
+const FREQUENCY_TERMS: [(&str, f32); 6] = [
+    ("HP:0040280", 1.0),  // Obligate
+    ("HP:0040281", 0.8),  // Very frequent
+    ("HP:0040282", 0.3),  // Frequent
+    ("HP:0040283", 0.05), // Occasional
+    ("HP:0040284", 0.01), // Very rare
+    ("HP:0040285", 0.0),  // Excluded
+];
+
+/// Column index of the frequency in `phenotype.hpoa`
+pub(crate) const FREQUENCY_COLUMN: usize = 7;
+
+/// Parses the frequency column of `phenotype.hpoa`
+///
+/// The frequency is either an HPO frequency term (`HP:0040283`),
+/// a ratio (`3/5`) or a percentage (`45%`). Returns `None` if the
+/// frequency is missing or cannot be parsed.
+pub(crate) fn parse_frequency(value: &str) -> Option<f32> {
+    let value = value.trim();
+    if let Some((_, frequency)) = FREQUENCY_TERMS.iter().find(|(id, _)| *id == value) {
+        Some(*frequency)
+    } else if let Some((numerator, denominator)) = value.split_once('/') {
+        let numerator: f32 = numerator.trim().parse().ok()?;
+        let denominator: f32 = denominator.trim().parse().ok()?;
+        (denominator > 0.0).then(|| numerator / denominator)
+    } else {
+        value
+            .strip_suffix('%')
+            .and_then(|percent| percent.trim().parse::<f32>().ok())
+            .map(|percent| percent / 100.0)
+    }
+}
+
+/// Column index of the evidence code in `phenotype.hpoa`
+pub(crate) const EVIDENCE_COLUMN: usize = 5;
+
+/// Column index of the semicolon-separated modifier terms in `phenotype.hpoa`
+const MODIFIER_COLUMN: usize = 9;
+
+/// The evidence codes used in `phenotype.hpoa`
+///
+/// - `IEA`: Inferred from electronic annotation
+/// - `PCS`: Published clinical study
+/// - `TAS`: Traceable author statement
+pub(crate) const EVIDENCE_CODES: [&str; 3] = ["IEA", "PCS", "TAS"];
+
+/// All loaded annotations of Omim and Orpha diseases, by disease ID
+#[derive(Default)]
+pub(crate) struct DiseaseAnnotations {
+    omim: HashMap<u32, Vec<HpoaRecord>>,
+    orpha: HashMap<u32, Vec<HpoaRecord>>,
+}
+
+impl DiseaseAnnotations {
+    pub fn add_omim(&mut self, disease: u32, record: HpoaRecord) {
+        self.omim.entry(disease).or_default().push(record);
+    }
+
+    pub fn add_orpha(&mut self, disease: u32, record: HpoaRecord) {
+        self.orpha.entry(disease).or_default().push(record);
+    }
+
+    pub fn omim(&self, disease: u32) -> &[HpoaRecord] {
+        self.omim.get(&disease).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn orpha(&self, disease: u32) -> &[HpoaRecord] {
+        self.orpha.get(&disease).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the approximate memory usage of all records in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.omim
+            .values()
+            .chain(self.orpha.values())
+            .flatten()
+            .map(|record| {
+                size_of::<HpoaRecord>()
+                    + record.evidence.len()
+                    + record.frequency.len()
+                    + record.modifiers.len() * size_of::<u32>()
+            })
+            .sum::<usize>()
+            + (self.omim.capacity() + self.orpha.capacity())
+                * (size_of::<(u32, Vec<HpoaRecord>)>() + 1)
+    }
+}
+
+/// Returns the mean annotated frequency of `term`
+///
+/// `None` if the term is not annotated or none of its annotations
+/// specifies a frequency
+pub(crate) fn term_frequency(records: &[HpoaRecord], term: u32) -> Option<f32> {
+    let frequencies: Vec<f32> = records
+        .iter()
+        .filter(|record| record.term() == term)
+        .filter_map(HpoaRecord::frequency)
+        .collect();
+    (!frequencies.is_empty()).then(|| frequencies.iter().sum::<f32>() / frequencies.len() as f32)
+}
+
+/// Returns the modifier terms of all annotated terms, merged across
+/// multiple annotations of the same term
+pub(crate) fn modifiers_by_term(records: &[HpoaRecord]) -> BTreeMap<u32, Vec<u32>> {
+    let mut terms: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    for record in records {
+        terms
+            .entry(record.term())
+            .or_default()
+            .extend_from_slice(record.modifiers());
+    }
+    for modifiers in terms.values_mut() {
+        modifiers.sort_unstable();
+        modifiers.dedup();
+    }
+    terms
+}
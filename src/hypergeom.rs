@@ -0,0 +1,320 @@
+//! Hypergeometric distribution for enrichment tests of arbitrary counts
+//!
+//! The `hpo` crate only exposes the enrichment of genes and diseases,
+//! this module provides the underlying test for other counts, e.g. of
+//! terms in a cohort. The test is also available in Python as
+//! `pyhpo.stats.hypergeom`.
+//!
+//! The binomial and chi-square tests are alternatives to the hypergeometric
+//! test for the enrichment of genes and diseases.
+//!
+//! Strong enrichments have p-values far below the smallest `f64`, so all
+//! tests are also available as natural logarithm of the p-value.
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Auxiliary variable of the Lanczos approximation of [`ln_gamma`]
+const GAMMA_R: f64 = 10.900_511;
+
+/// Polynomial coefficients of the Lanczos approximation of [`ln_gamma`]
+#[allow(clippy::excessive_precision)]
+const GAMMA_DK: [f64; 11] = [
+    2.485_740_891_387_535_655_46e-5,
+    1.051_423_785_817_219_742_10,
+    -3.456_870_972_220_162_354_69,
+    4.512_277_094_668_948_237_00,
+    -2.982_852_253_235_766_557_21,
+    1.056_397_115_771_267_130_77,
+    -1.954_287_731_916_458_695_83e-1,
+    1.709_705_434_044_412_243_07e-2,
+    -5.719_261_174_043_057_812_83e-4,
+    4.633_994_733_599_056_367_08e-6,
+    -2.719_949_084_886_077_039_10e-9,
+];
+
+/// `ln(2 * sqrt(e / pi))`
+#[allow(clippy::excessive_precision)]
+const LN_2_SQRT_E_OVER_PI: f64 =
+    0.620_782_237_635_245_222_345_518_445_781_647_212_251_852_727_902_597_8;
+
+/// The largest factorial that fits into a `f64`, larger
+/// factorials are derived from [`ln_gamma`]
+const MAX_FACTORIAL: usize = 170;
+
+/// All factorials from `0!` to `170!`
+const FACTORIALS: [f64; MAX_FACTORIAL + 1] = {
+    let mut factorials = [1.0; MAX_FACTORIAL + 1];
+    let mut n = 1;
+    while n <= MAX_FACTORIAL {
+        factorials[n] = factorials[n - 1] * n as f64;
+        n += 1;
+    }
+    factorials
+};
+
+/// Returns `ln(gamma(x))` for `x >= 0.5`
+///
+/// Lanczos approximation with an accuracy of 16 digits, identical
+/// to the hypergeometric enrichment of the `hpo` crate (derived from `statrs`)
+fn ln_gamma(x: f64) -> f64 {
+    let s = GAMMA_DK
+        .iter()
+        .enumerate()
+        .skip(1)
+        .fold(GAMMA_DK[0], |s, (idx, dk)| s + dk / (x + idx as f64 - 1.0));
+    s.ln() + LN_2_SQRT_E_OVER_PI + (x - 0.5) * ((x - 0.5 + GAMMA_R) / std::f64::consts::E).ln()
+}
+
+/// Returns `ln(n!)`
+fn ln_factorial(n: u64) -> f64 {
+    usize::try_from(n)
+        .ok()
+        .and_then(|n| FACTORIALS.get(n))
+        .map_or_else(|| ln_gamma(n as f64 + 1.0), |factorial| factorial.ln())
+}
+
+/// Returns `ln(n choose k)`, `-inf` if `k > n`
+fn ln_binomial(n: u64, k: u64) -> f64 {
+    if k > n {
+        f64::NEG_INFINITY
+    } else {
+        ln_factorial(n) - ln_factorial(k) - ln_factorial(n - k)
+    }
+}
+
+/// Returns the natural logarithms of the probabilities to observe
+/// exactly `observed..=max` successes of a hypergeometric distribution
+///
+/// Returns the p-value as `Err` instead, if at least `observed` successes
+/// are certain (`1.0`) or impossible (`0.0`).
+fn hypergeom_ln_pmfs(
+    population: u64,
+    successes: u64,
+    draws: u64,
+    observed: u64,
+) -> Result<impl Iterator<Item = f64>, f64> {
+    let min = (draws + successes).saturating_sub(population);
+    let max = successes.min(draws);
+    if observed <= min {
+        return Err(1.0);
+    }
+    if observed > max {
+        return Err(0.0);
+    }
+    let total = ln_binomial(population, draws);
+    Ok((observed..=max).map(move |k| {
+        ln_binomial(successes, k) + ln_binomial(population - successes, draws - k) - total
+    }))
+}
+
+/// Returns the probability to observe at least `observed` successes
+///
+/// i.e. the survival function `P(X >= observed)` of a hypergeometric
+/// distribution with `draws` from a `population` that contains
+/// `successes` items. The result is identical to the hypergeometric
+/// enrichment of the `hpo` crate.
+///
+/// # Panics
+///
+/// `population` must not be smaller than `successes` or `draws`
+pub(crate) fn hypergeom_sf(population: u64, successes: u64, draws: u64, observed: u64) -> f64 {
+    match hypergeom_ln_pmfs(population, successes, draws, observed) {
+        Ok(ln_pmfs) => ln_pmfs.fold(0.0, |sum, ln_pmf| sum + ln_pmf.exp()),
+        Err(pvalue) => pvalue,
+    }
+}
+
+/// Returns the natural logarithm of [`hypergeom_sf`]
+///
+/// # Panics
+///
+/// Identical to [`hypergeom_sf`]
+pub(crate) fn ln_hypergeom_sf(population: u64, successes: u64, draws: u64, observed: u64) -> f64 {
+    match hypergeom_ln_pmfs(population, successes, draws, observed) {
+        Ok(ln_pmfs) => ln_sum_exp(ln_pmfs),
+        Err(pvalue) => pvalue.ln(),
+    }
+}
+
+/// Returns the natural logarithms of the probabilities to observe
+/// exactly `observed..=draws` successes of a binomial distribution
+///
+/// Returns the p-value as `Err` instead, if at least `observed` successes
+/// are certain (`1.0`) or impossible (`0.0`).
+fn binomial_ln_pmfs(
+    draws: u64,
+    probability: f64,
+    observed: u64,
+) -> Result<impl Iterator<Item = f64>, f64> {
+    if observed == 0 || probability >= 1.0 {
+        return Err(1.0);
+    }
+    if observed > draws || probability <= 0.0 {
+        return Err(0.0);
+    }
+    Ok((observed..=draws).map(move |k| {
+        ln_binomial(draws, k)
+            + k as f64 * probability.ln()
+            + (draws - k) as f64 * (-probability).ln_1p()
+    }))
+}
+
+/// Returns the probability to observe at least `observed` successes
+///
+/// i.e. the survival function `P(X >= observed)` of a binomial
+/// distribution with `draws` trials and a success `probability`.
+pub(crate) fn binomial_sf(draws: u64, probability: f64, observed: u64) -> f64 {
+    match binomial_ln_pmfs(draws, probability, observed) {
+        Ok(ln_pmfs) => ln_pmfs.map(f64::exp).sum::<f64>().min(1.0),
+        Err(pvalue) => pvalue,
+    }
+}
+
+/// Returns the natural logarithm of [`binomial_sf`]
+pub(crate) fn ln_binomial_sf(draws: u64, probability: f64, observed: u64) -> f64 {
+    match binomial_ln_pmfs(draws, probability, observed) {
+        Ok(ln_pmfs) => ln_sum_exp(ln_pmfs),
+        Err(pvalue) => pvalue.ln(),
+    }
+}
+
+/// Returns `ln(sum(exp(x)))` of all `values`, capped at `0.0`
+///
+/// The largest value is factored out, so that the sum does not
+/// underflow to `0.0` for very small probabilities.
+fn ln_sum_exp(values: impl Iterator<Item = f64>) -> f64 {
+    let values: Vec<f64> = values.collect();
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    let sum: f64 = values.iter().map(|value| (value - max).exp()).sum();
+    (max + sum.ln()).min(0.0)
+}
+
+/// Returns the one-sided p-value of Pearson's chi-square test
+///
+/// The 2x2 contingency table contains the `draws` from a `population`
+/// that contains `successes` items, of which `observed` were drawn.
+/// The p-value is the probability of a chi-square statistic at least as
+/// large as the observed one, in the direction of an over-representation
+/// of successes in the draws. No continuity correction is applied.
+pub(crate) fn chi_square_sf(population: u64, successes: u64, draws: u64, observed: u64) -> f64 {
+    chi_square_z(population, successes, draws, observed)
+        .map_or(1.0, |z| 0.5 * erfc(z / std::f64::consts::SQRT_2))
+}
+
+/// Returns the natural logarithm of [`chi_square_sf`]
+pub(crate) fn ln_chi_square_sf(population: u64, successes: u64, draws: u64, observed: u64) -> f64 {
+    chi_square_z(population, successes, draws, observed).map_or(0.0, |z| {
+        ln_erfc(z / std::f64::consts::SQRT_2) - std::f64::consts::LN_2
+    })
+}
+
+/// Returns the signed square root of the chi-square statistic of the 2x2
+/// contingency table, `None` if the table has an empty row or column
+///
+/// With one degree of freedom, it is standard normal distributed.
+fn chi_square_z(population: u64, successes: u64, draws: u64, observed: u64) -> Option<f64> {
+    let population = population as f64;
+    let successes = successes as f64;
+    let draws = draws as f64;
+    let observed = observed as f64;
+    let denominator = successes * (population - successes) * draws * (population - draws);
+    if denominator <= 0.0 {
+        return None;
+    }
+    Some((observed * population - successes * draws) * population.sqrt() / denominator.sqrt())
+}
+
+/// Returns the complementary error function of `x`
+///
+/// Chebyshev approximation with a fractional error below `1.2e-7`
+/// (Numerical Recipes, `erfcc`)
+fn erfc(x: f64) -> f64 {
+    let (t, exponent) = erfc_terms(x.abs());
+    let value = t * exponent.exp();
+    if x >= 0.0 {
+        value
+    } else {
+        2.0 - value
+    }
+}
+
+/// Returns the natural logarithm of [`erfc`]
+fn ln_erfc(x: f64) -> f64 {
+    if x < 0.0 {
+        return erfc(x).ln();
+    }
+    let (t, exponent) = erfc_terms(x);
+    t.ln() + exponent
+}
+
+/// Returns the factor `t` and the exponent of the approximation of
+/// `erfc(z)` for `z >= 0`
+fn erfc_terms(z: f64) -> (f64, f64) {
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let exponent = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    (t, exponent)
+}
+
+/// Calculate the p-value of a hypergeometric test
+///
+/// Returns the probability to observe at least ``k`` successes when
+/// drawing ``n`` items without replacement from a population of ``N``
+/// items, of which ``K`` are successes. This is the same test that is used
+/// by :class:`pyhpo.stats.EnrichmentModel` and equivalent to
+/// ``scipy.stats.hypergeom.sf(k - 1, N, K, n)``.
+///
+/// Parameters
+/// ----------
+/// k: int
+///     The number of observed successes
+/// n: int
+///     The number of draws
+/// K: int
+///     The number of successes in the population
+/// N: int
+///     The size of the population
+///
+/// Returns
+/// -------
+/// float
+///     The p-value ``P(X >= k)``
+///
+/// Raises
+/// ------
+/// ValueError
+///     ``K`` or ``n`` are larger than ``N``
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo.stats import hypergeom
+///
+///     # 4 of 10 patients have a phenotype that is present
+///     # in 20 of 1000 individuals
+///     hypergeom(4, 10, 20, 1000)
+///     # >> 2.2730228468737426e-05
+///
+#[pyfunction]
+#[pyo3(signature = (k, n, K, N))]
+#[pyo3(text_signature = "(k, n, K, N)")]
+#[allow(non_snake_case)]
+pub(crate) fn hypergeom(k: u64, n: u64, K: u64, N: u64) -> PyResult<f64> {
+    if K > N || n > N {
+        return Err(PyValueError::new_err(format!(
+            "Invalid hypergeometric parameters: K ({K}) and n ({n}) must not be larger than N ({N})"
+        )));
+    }
+    Ok(hypergeom_sf(N, K, n, k))
+}
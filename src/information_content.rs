@@ -1,22 +1,102 @@
-use pyo3::exceptions::PyKeyError;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+use hpo::annotations::AnnotationId;
+use once_cell::sync::Lazy;
+use pyo3::exceptions::{PyKeyError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::PyErr;
 use pyo3::PyResult;
 
+/// The name of the default custom information content
+pub(crate) const CUSTOM_KIND: &str = "custom";
+
+/// User-provided information content of terms, e.g. based on a cohort,
+/// by the name of the kind
+///
+/// Terms without a custom information content have an IC of `0.0`
+static CUSTOM_IC: Lazy<RwLock<HashMap<String, HashMap<u32, f32>>>> = Lazy::new(Default::default);
+
+/// Returns `true` if `kind` is the name of a custom information content
+///
+/// The default kind `custom` always exists, other kinds once an
+/// information content was set for them
+pub(crate) fn is_custom_kind(kind: &str) -> bool {
+    kind == CUSTOM_KIND
+        || CUSTOM_IC
+            .read()
+            .expect("custom IC lock must not be poisoned")
+            .contains_key(kind)
+}
+
+/// Sets the custom information content `kind` of the term
+///
+/// # Errors
+///
+/// - PyValueError: `kind` is a builtin kind
+pub(crate) fn set_custom_ic(kind: &str, id: u32, value: f32) -> PyResult<()> {
+    if PyInformationContentKind::try_from(kind).is_ok() {
+        return Err(PyValueError::new_err(format!(
+            "The builtin information content {kind} cannot be set"
+        )));
+    }
+    CUSTOM_IC
+        .write()
+        .expect("custom IC lock must not be poisoned")
+        .entry(kind.to_string())
+        .or_default()
+        .insert(id, value);
+    Ok(())
+}
+
+/// Returns a copy of the custom information content `kind` of all terms
+///
+/// Use this for batch calculations to prevent locking for every lookup
+pub(crate) fn custom_ic_table(kind: &str) -> HashMap<u32, f32> {
+    CUSTOM_IC
+        .read()
+        .expect("custom IC lock must not be poisoned")
+        .get(kind)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Returns the number of allocated entries of all custom information contents
+pub(crate) fn custom_ic_capacity() -> usize {
+    CUSTOM_IC
+        .read()
+        .expect("custom IC lock must not be poisoned")
+        .values()
+        .map(HashMap::capacity)
+        .sum()
+}
+
 /// Holds the information content for an ``HPOTerm``
 #[pyclass(name = "InformationContent")]
 pub struct PyInformationContent {
     omim: f32,
     orpha: f32,
     gene: f32,
+    /// All custom information contents, by kind
+    custom: BTreeMap<String, f32>,
 }
 
-impl From<&hpo::term::InformationContent> for PyInformationContent {
-    fn from(value: &hpo::term::InformationContent) -> Self {
+impl From<&hpo::HpoTerm<'_>> for PyInformationContent {
+    fn from(term: &hpo::HpoTerm<'_>) -> Self {
+        let value = term.information_content();
+        let id = term.id().as_u32();
+        let mut custom: BTreeMap<String, f32> = CUSTOM_IC
+            .read()
+            .expect("custom IC lock must not be poisoned")
+            .iter()
+            .map(|(kind, ic)| (kind.clone(), ic.get(&id).copied().unwrap_or_default()))
+            .collect();
+        custom.entry(CUSTOM_KIND.to_string()).or_default();
         Self {
             omim: value.omim_disease(),
             orpha: value.orpha_disease(),
             gene: value.gene(),
+            custom,
         }
     }
 }
@@ -40,12 +120,25 @@ impl PyInformationContent {
         self.orpha
     }
 
+    /// Returns the custom information content
+    ///
+    /// See :func:`pyhpo.HPOTerm.set_custom_ic`. Other custom kinds are
+    /// available by their name, e.g. ``term.information_content["peds"]``
+    #[getter(custom)]
+    pub fn custom(&self) -> f32 {
+        self.custom[CUSTOM_KIND]
+    }
+
     fn __getitem__(&self, key: &str) -> PyResult<f32> {
         match key {
             "omim" => Ok(self.omim()),
             "orpha" => Ok(self.orpha()),
             "gene" => Ok(self.gene()),
-            _ => Err(PyKeyError::new_err(format!("Unknown key {}", key))),
+            _ => self
+                .custom
+                .get(key)
+                .copied()
+                .ok_or_else(|| PyKeyError::new_err(format!("Unknown key {}", key))),
         }
     }
 
@@ -59,7 +152,7 @@ impl PyInformationContent {
     }
 }
 
-#[pyclass(name = "InformationContentKind")]
+#[pyclass(name = "InformationContentKind", eq, eq_int)]
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum PyInformationContentKind {
     Omim,
@@ -84,6 +177,17 @@ impl TryFrom<&str> for PyInformationContentKind {
     }
 }
 
+impl PyInformationContentKind {
+    /// Returns the name of the kind, as used in the Python API
+    pub fn name(self) -> &'static str {
+        match self {
+            PyInformationContentKind::Omim => "omim",
+            PyInformationContentKind::Orpha => "orpha",
+            PyInformationContentKind::Gene => "gene",
+        }
+    }
+}
+
 impl From<PyInformationContentKind> for hpo::term::InformationContentKind {
     fn from(value: PyInformationContentKind) -> Self {
         match value {
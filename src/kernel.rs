@@ -0,0 +1,299 @@
+use rayon::prelude::*;
+
+use pyo3::exceptions::{PyIndexError, PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyString, PyTuple, PyType};
+
+use hpo::similarity::{GroupSimilarity, StandardCombiner};
+use hpo::HpoSet;
+
+use crate::buffer::{ArrayOutput, PyScoreBuffer};
+use crate::cohort::PyCohort;
+use crate::get_ontology;
+use crate::set::{EmptySetPolicy, PyHpoSet};
+use crate::similarity::PySimilarity;
+use crate::similarity_cache::{self, CachedSimilarity};
+use crate::worker;
+
+/// A similarity kernel for ``scikit-learn``
+///
+/// Calling the kernel with two arrays of ``HPOSet`` returns their Gram
+/// matrix, i.e. the similarity of every pair of sets, calculated in
+/// parallel. The kernel can be used as ``kernel`` of
+/// ``sklearn.svm.SVC`` or as ``affinity`` of ``SpectralClustering``
+/// (via ``affinity="precomputed"``).
+///
+/// ``scikit-learn`` only accepts numerical input data. Register the
+/// ``HPOSet`` of all samples with the kernel and use their indices as input
+/// data instead, e.g. ``numpy.arange(len(sets)).reshape(-1, 1)``.
+///
+/// Parameters
+/// ----------
+/// sets: list[:class:`pyhpo.HPOSet`] or :class:`pyhpo.Cohort`, optional
+///     The registered sets, that are referenced by their index
+/// kind: str, default: ``omim``
+///     Which kind of information content to use for similarity calculation
+/// method: str, default ``graphic``
+///     The method to use to calculate the similarity.
+///     See :func:`pyhpo.HPOSet.similarity` for available options
+/// combine: str, default ``funSimAvg``
+///     The method to combine individual term similarity
+///     to HPOSet similarities.
+/// on_empty: str, default ``zero``
+///     The similarity if one of the sets is empty
+/// output: str, default ``numpy``
+///     The type of the Gram matrix, ``numpy`` for a ``float64`` numpy array,
+///     ``list`` for a list of lists or ``buffer`` for a two-dimensional
+///     :class:`pyhpo.types.ScoreBuffer`
+///
+/// Raises
+/// ------
+/// KeyError
+///     Invalid ``kind``
+/// RuntimeError
+///     Invalid ``method`` or ``combine``
+/// ValueError
+///     Invalid ``on_empty`` or ``output``
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     import numpy as np
+///     from sklearn.svm import SVC
+///     from pyhpo import Ontology
+///     from pyhpo.stats import SimilarityKernel
+///
+///     Ontology()
+///
+///     sets = [disease.hpo_set() for disease in list(Ontology.omim_diseases)[:200]]
+///     labels = [len(s) > 10 for s in sets]
+///
+///     kernel = SimilarityKernel(sets)
+///     X = np.arange(len(sets)).reshape(-1, 1)
+///     model = SVC(kernel=kernel).fit(X, labels)
+///
+///     # serialized HPOSets can be used without registering them
+///     kernel(["HP:0000118+HP:0002650", "HP:0001166"])
+///
+#[pyclass(name = "SimilarityKernel", module = "pyhpo.stats")]
+pub(crate) struct PyKernel {
+    sets: Vec<PyHpoSet>,
+    kind: String,
+    method: String,
+    combine: String,
+    on_empty: String,
+    output: String,
+    similarity: GroupSimilarity<CachedSimilarity<PySimilarity>, StandardCombiner>,
+    empty_policy: EmptySetPolicy,
+    array_output: ArrayOutput,
+}
+
+#[pymethods]
+impl PyKernel {
+    #[new]
+    #[pyo3(signature = (sets = None, kind = "omim", method = "graphic", combine = "funSimAvg", on_empty = "zero", output = "numpy"))]
+    #[pyo3(text_signature = "(sets, kind, method, combine, on_empty, output)")]
+    fn new(
+        sets: Option<&Bound<'_, PyAny>>,
+        kind: &str,
+        method: &str,
+        combine: &str,
+        on_empty: &str,
+        output: &str,
+    ) -> PyResult<Self> {
+        let sets = match sets {
+            Some(sets) => match sets.downcast::<PyCohort>() {
+                Ok(cohort) => cohort.borrow().sets().to_vec(),
+                Err(_) => sets.extract()?,
+            },
+            None => Vec::new(),
+        };
+        let similarity = similarity_cache::builtin(method, kind)?;
+        let combiner = StandardCombiner::try_from(combine)
+            .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+        let array_output = ArrayOutput::try_from(output)?;
+        Ok(Self {
+            sets,
+            kind: kind.to_string(),
+            method: method.to_string(),
+            combine: combine.to_string(),
+            on_empty: on_empty.to_string(),
+            output: output.to_string(),
+            similarity: GroupSimilarity::new(combiner, similarity),
+            empty_policy: EmptySetPolicy::try_from(on_empty)?,
+            array_output,
+        })
+    }
+
+    /// The number of registered sets
+    #[getter(n_sets)]
+    fn n_sets(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Returns the Gram matrix of ``X`` and ``Y``
+    ///
+    /// Parameters
+    /// ----------
+    /// X: array-like
+    ///     The samples, each as index of a registered set (also as
+    ///     single-column row of a 2D array), as ``HPOSet`` or as serialized
+    ///     ``HPOSet``
+    /// Y: array-like, optional
+    ///     The samples to compare ``X`` to, same format as ``X``.
+    ///     Defaults to ``X``
+    ///
+    /// Returns
+    /// -------
+    /// numpy.ndarray or list[list[float]] or :class:`pyhpo.types.ScoreBuffer`
+    ///     The similarity of every sample of ``X`` (rows) to every sample
+    ///     of ``Y`` (columns)
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// TypeError
+    ///     A sample is neither an index, nor an ``HPOSet``
+    /// IndexError
+    ///     A sample index is not registered
+    /// ValueError
+    ///     A sample index is not an integer, or an empty set
+    ///     with ``on_empty="raise"``
+    /// ImportError
+    ///     ``numpy`` is not installed and ``output`` is ``numpy``
+    ///
+    #[pyo3(signature = (X, Y = None))]
+    #[allow(non_snake_case)]
+    fn __call__(
+        &self,
+        py: Python<'_>,
+        X: &Bound<'_, PyAny>,
+        Y: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        let ont = get_ontology()?;
+        let rows = self.samples(X)?;
+        let columns = match Y {
+            Some(y) => Some(self.samples(y)?),
+            None => None,
+        };
+        let rows: Vec<HpoSet> = rows.iter().map(|set| set.set(ont)).collect();
+        let columns: Option<Vec<HpoSet>> = columns
+            .as_ref()
+            .map(|sets| sets.iter().map(|set| set.set(ont)).collect());
+
+        // Without `Y`, the matrix is symmetric and only the upper
+        // triangle is calculated
+        let symmetric = columns.is_none();
+        let columns = columns.as_ref().unwrap_or(&rows);
+        let pairs: Vec<(usize, usize)> = (0..rows.len())
+            .flat_map(|a| {
+                let start = if symmetric { a } else { 0 };
+                (start..columns.len()).map(move |b| (a, b))
+            })
+            .collect();
+        let scores = worker::install(py, || {
+            pairs
+                .par_iter()
+                .map(|&(a, b)| {
+                    self.empty_policy
+                        .score(rows[a].is_empty() || columns[b].is_empty(), || {
+                            self.similarity.calculate(&rows[a], &columns[b])
+                        })
+                })
+                .collect::<PyResult<Vec<f32>>>()
+        })?;
+
+        let width = columns.len();
+        let mut matrix = vec![0.0f64; rows.len() * width];
+        for ((a, b), score) in pairs.into_iter().zip(scores) {
+            matrix[a * width + b] = f64::from(score);
+            if symmetric {
+                matrix[b * width + a] = f64::from(score);
+            }
+        }
+        self.array_output
+            .convert(py, PyScoreBuffer::new(matrix, &[rows.len(), width]))
+    }
+
+    fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>)> {
+        let args = (
+            self.sets.clone(),
+            &self.kind,
+            &self.method,
+            &self.combine,
+            &self.on_empty,
+            &self.output,
+        );
+        Ok((py.get_type::<Self>(), args.into_pyobject(py)?))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SimilarityKernel(kind='{}', method='{}', combine='{}', n_sets={})",
+            self.kind,
+            self.method,
+            self.combine,
+            self.sets.len()
+        )
+    }
+}
+
+impl PyKernel {
+    /// Returns the `HPOSet` of every sample of `samples`
+    ///
+    /// # Errors
+    ///
+    /// - PyTypeError: Invalid sample
+    /// - PyIndexError: The sample index is not registered
+    /// - PyValueError: The sample index is not an integer
+    fn samples(&self, samples: &Bound<'_, PyAny>) -> PyResult<Vec<PyHpoSet>> {
+        samples
+            .try_iter()?
+            .map(|sample| self.sample(&sample?))
+            .collect()
+    }
+
+    /// Returns the `HPOSet` of a single sample
+    fn sample(&self, sample: &Bound<'_, PyAny>) -> PyResult<PyHpoSet> {
+        if let Ok(set) = sample.extract::<PyHpoSet>() {
+            return Ok(set);
+        }
+        if sample.is_instance_of::<PyString>() {
+            return sample
+                .py()
+                .get_type::<PyHpoSet>()
+                .call_method1("from_serialized", (sample,))?
+                .extract();
+        }
+        let index = match sample.extract::<f64>() {
+            Ok(index) => index,
+            // A row of a 2D array with a single column
+            Err(_) => match sample.try_iter().and_then(|mut row| {
+                row.next()
+                    .ok_or_else(|| PyValueError::new_err("Empty sample"))?
+            }) {
+                Ok(value) => value.extract::<f64>()?,
+                Err(_) => {
+                    return Err(PyTypeError::new_err(
+                        "Samples must be an index, an HPOSet or a serialized HPOSet",
+                    ))
+                }
+            },
+        };
+        if index.fract() != 0.0 || index < 0.0 {
+            return Err(PyValueError::new_err(format!(
+                "Invalid sample index {index}"
+            )));
+        }
+        self.sets
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| PyIndexError::new_err(format!("No registered set with index {index}")))
+    }
+}
@@ -1,57 +1,163 @@
 use annotations::PyOrphaDisease;
 use once_cell::sync::OnceCell;
+use std::path::PathBuf;
 
 use rayon::prelude::*;
 
 use pyo3::exceptions::{PyKeyError, PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
+use pyo3::IntoPyObjectExt;
 
-use hpo::annotations::{AnnotationId, GeneId, OmimDiseaseId, OrphaDiseaseId};
-use hpo::similarity::{GroupSimilarity, Similarity, StandardCombiner};
+use hpo::annotations::{AnnotationId, Disease, GeneId, OmimDiseaseId, OrphaDiseaseId};
+use hpo::similarity::{Similarity, StandardCombiner};
 use hpo::stats::hypergeom::{gene_enrichment, omim_disease_enrichment, orpha_disease_enrichment};
 use hpo::term::HpoTermId;
-use hpo::{HpoResult, HpoTerm, Ontology as ActualOntology};
+use hpo::{HpoTerm, Ontology as ActualOntology};
 
 mod annotations;
+mod batch;
+mod batch_chunks;
+mod binary;
+mod buffer;
+mod cohort;
+mod compact;
+mod distance_matrix;
 mod enrichment;
+mod fhir;
+mod hpoa;
+mod hypergeom;
 mod information_content;
+mod kernel;
 mod linkage;
+mod loader;
+mod lsh;
+mod matrix;
+mod memory;
+mod metadata;
+mod mondo;
+mod obo;
 mod ontology;
+mod output;
+mod owl;
+mod patient;
+mod phenopacket;
+mod random;
+mod resolve;
 mod set;
+mod set_index;
+mod similarity;
+mod similarity_cache;
+mod similarity_matrix;
 mod term;
+mod term_array;
+mod term_details;
+mod worker;
 
+use crate::annotations::DIRECT_GENE_TERMS;
 use crate::annotations::{PyGene, PyOmimDisease};
-use crate::enrichment::PyEnrichmentModel;
-use crate::information_content::{PyInformationContent, PyInformationContentKind};
+use crate::batch::{
+    extract_comparisons, multi_scores_to_py, scores_to_file, scores_to_py, ErrorPolicy, NanPolicy,
+};
+use crate::buffer::ArrayOutput;
+use crate::enrichment::{
+    enrichment_dicts, enrichment_to_file, sort_enrichment, LogEnrichment, PyEnrichmentModel,
+    SortKey,
+};
+use crate::hpoa::DISEASE_ANNOTATIONS;
+use crate::information_content::PyInformationContent;
+use crate::loader::{DirectGeneTerms, GeneSource, JaxContents, JaxSources};
+use crate::matrix::PySparseMatrix;
+use crate::metadata::{FileMetadata, ANNOTATION_METADATA};
 use crate::ontology::PyOntology;
-use crate::set::PyHpoSet;
+use crate::output::Format;
+use crate::set::{EmptySetPolicy, PyHpoSet};
+use crate::similarity::{Dtype, MultiSimilarity, Score};
+use crate::similarity_matrix::PySimilarityMatrix;
 use crate::term::PyHpoTerm;
+use crate::term_details::TERM_DETAILS;
 
 static ONTOLOGY: OnceCell<ActualOntology> = OnceCell::new();
 
-/// Builds the ontology from a binary HPO dump
-fn from_binary(path: &str) -> usize {
-    let ont = ActualOntology::from_binary(path).unwrap();
-    ONTOLOGY.set(ont).unwrap();
-    ONTOLOGY.get().unwrap().len()
+/// Stores the Ontology and returns the number of its terms
+///
+/// Several threads can build the Ontology at the same time,
+/// only the first built Ontology is kept and used by all threads.
+fn set_ontology(ont: ActualOntology) -> usize {
+    let _ = ONTOLOGY.set(ont);
+    ONTOLOGY.get().expect("Ontology must be present").len()
+}
+
+/// Builds the ontology from the binary HPO data
+///
+/// If `annotations` is `false`, genes and diseases are not loaded
+///
+/// # Errors
+///
+/// - PyValueError: Unsupported format version or malformed data
+fn from_binary_bytes(bytes: &[u8], annotations: bool) -> PyResult<usize> {
+    let (ont, _) = binary::load(bytes, annotations)?;
+    Ok(set_ontology(ont))
 }
 
-fn from_builtin() -> usize {
+/// Builds the ontology from the builtin binary data
+///
+/// The builtin data only contains the direct gene annotations
+/// of `genes_to_phenotype.txt`
+fn from_builtin(annotations: bool) -> usize {
     let bytes = include_bytes!("../data/ontology.hpo");
-    let ont = ActualOntology::from_bytes(&bytes[..]).expect("Unable to build Ontology");
-    ONTOLOGY.set(ont).unwrap();
-    ONTOLOGY.get().unwrap().len()
+    let ont = if annotations {
+        ActualOntology::from_bytes(&bytes[..]).expect("Unable to build Ontology")
+    } else {
+        binary::load(&bytes[..], false)
+            .expect("Unable to build Ontology")
+            .0
+    };
+    if ONTOLOGY.set(ont).is_ok() {
+        let _ = DIRECT_GENE_TERMS.set(DirectGeneTerms::Annotated);
+    }
+    ONTOLOGY.get().expect("Ontology must be present").len()
 }
 
 /// Builds the ontology from the JAX download files
-fn from_obo(path: &str, transitive: bool) -> HpoResult<usize> {
-    let ont = if transitive {
-        ActualOntology::from_standard_transitive(path)?
-    } else {
-        ActualOntology::from_standard(path)?
+///
+/// `progress` is an optional Python callable that is called after each
+/// build stage with the name of the stage and the number of processed items
+fn from_obo(py: Python<'_>, sources: &JaxSources, progress: Option<&PyObject>) -> PyResult<usize> {
+    let report = |stage: &str, count: usize| -> PyResult<()> {
+        if let Some(callback) = progress {
+            callback.call1(py, (stage, count))?;
+        }
+        Ok(())
     };
-    ONTOLOGY.set(ont).unwrap();
+    let contents = JaxContents::read(py, sources)?;
+    report("read", contents.size())?;
+    let build = loader::build(&contents, report)?;
+    if ONTOLOGY.set(build.ontology).is_err() {
+        // Another thread built the Ontology first, its data is kept
+        return Ok(ONTOLOGY.get().expect("Ontology must be present").len());
+    }
+    let _ = DISEASE_ANNOTATIONS.set(build.disease_annotations);
+    let _ = TERM_DETAILS.set(build.term_details);
+    let _ = DIRECT_GENE_TERMS.set(build.direct_gene_terms);
+    if !sources.annotations {
+        return Ok(ONTOLOGY.get().unwrap().len());
+    }
+
+    let mut metadata = vec![FileMetadata::from_disease_file(
+        sources.hpoa.filename(py, loader::DISEASE_FILENAME),
+        &contents.hpoa,
+        &contents.filter,
+    )];
+    for (source, (genes, _)) in sources.genes.iter().zip(&contents.genes) {
+        let default_filename = GeneSource::default_filename(source.transitive);
+        metadata.push(FileMetadata::from_gene_file(
+            source.source.filename(py, default_filename),
+            genes,
+        ));
+    }
+    let _ = ANNOTATION_METADATA.set(metadata);
+
     Ok(ONTOLOGY.get().unwrap().len())
 }
 
@@ -70,6 +176,16 @@ fn get_ontology() -> PyResult<&'static ActualOntology> {
     })
 }
 
+/// Serializes `value` into a JSON string
+///
+/// # Errors
+///
+/// - PyRuntimeError: value cannot be serialized
+fn to_json_string<T: serde::Serialize>(value: &T) -> PyResult<String> {
+    serde_json::to_string(value)
+        .map_err(|err| PyRuntimeError::new_err(format!("Unable to serialize to JSON: {err}")))
+}
+
 /// Returns a [`PyHpoTerm`] from a `u32` ID
 ///
 /// # Errors
@@ -95,6 +211,25 @@ fn term_from_id(id: u32) -> PyResult<hpo::HpoTerm<'static>> {
     }
 }
 
+/// Returns the `u32` representation of an HPO ID from a `str` or `u32` query
+///
+/// String queries must be HPO-IDs (e.g. `HP:0000118`) or only digits
+///
+/// # Errors
+///
+/// - PyValueError: query cannot be converted to HpoTermId
+fn id_from_query(query: PyQuery) -> PyResult<u32> {
+    match query {
+        PyQuery::Id(id) => Ok(id),
+        PyQuery::Str(query) => query
+            .strip_prefix("HP:")
+            .unwrap_or(&query)
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| PyValueError::new_err(format!("Invalid id: {}", query))),
+    }
+}
+
 /// Returns an [`HpoTerm`] from a `str` or `u32` query
 ///
 /// # Errors
@@ -131,6 +266,19 @@ fn term_from_query(query: PyQuery) -> PyResult<HpoTerm<'static>> {
     Err(PyRuntimeError::new_err("Unknown HPO term"))
 }
 
+/// Returns an [`HpoTerm`] from an `HPOTerm` or `u32` ID
+///
+/// # Errors
+///
+/// - PyKeyError: No term with that ID present in Ontology
+/// - PyNameError: Ontology not yet constructed
+fn term_from_term_or_id(term: TermOrId) -> PyResult<HpoTerm<'static>> {
+    match term {
+        TermOrId::Term(term) => term_from_id(term.hpo_term_id().as_u32()),
+        TermOrId::Id(id) => term_from_id(id),
+    }
+}
+
 #[derive(FromPyObject)]
 pub enum PyQuery {
     Id(u32),
@@ -147,25 +295,54 @@ pub enum TermOrId {
 ///
 /// This library aims to be a drop-in replacement for
 /// `pyhpo <https://pypi.org/project/pyhpo/>`_
-#[pymodule]
+#[pymodule(gil_used = false)]
 fn pyhpo(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     let ont = PyOntology::blank();
     m.add_class::<PyGene>()?;
     m.add_class::<PyOmimDisease>()?;
     m.add_class::<PyOrphaDisease>()?;
+    m.add_class::<mondo::PyMondoDisease>()?;
     m.add_class::<PyHpoSet>()?;
     m.add_class::<PyHpoTerm>()?;
     m.add_class::<PyEnrichmentModel>()?;
     m.add_class::<PyInformationContent>()?;
+    m.add_class::<PySparseMatrix>()?;
+    m.add_class::<PySimilarityMatrix>()?;
+    m.add_class::<term_array::PyTermArray>()?;
+    m.add_class::<buffer::PyScoreBuffer>()?;
     m.add_class::<PyOntology>()?;
+    m.add_class::<patient::PyPatient>()?;
+    m.add_class::<cohort::PyCohort>()?;
+    m.add_class::<linkage::PyDendrogram>()?;
+    m.add_class::<kernel::PyKernel>()?;
+    m.add_class::<set_index::PySetIndex>()?;
+    m.add_class::<lsh::PyMinHashIndex>()?;
     m.add_function(wrap_pyfunction!(linkage::linkage, m)?)?;
+    m.add_function(wrap_pyfunction!(hypergeom::hypergeom, m)?)?;
+    m.add_function(wrap_pyfunction!(binary::migrate_binary, m)?)?;
+    m.add_function(wrap_pyfunction!(similarity::register_similarity, m)?)?;
     m.add("Ontology", ont)?;
     m.add("BasicHPOSet", set::BasicPyHpoSet)?;
     m.add("HPOPhenoSet", set::PhenoSet)?;
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
     m.add("__backend__", env!("CARGO_PKG_NAME"))?;
     m.add_function(wrap_pyfunction!(batch_similarity, m)?)?;
+    m.add_function(wrap_pyfunction!(similarity_matrix::similarity_matrix, m)?)?;
+    m.add_function(wrap_pyfunction!(matrix::ancestor_embedding, m)?)?;
+    m.add_function(wrap_pyfunction!(distance_matrix::term_distance_matrix, m)?)?;
+    m.add_function(wrap_pyfunction!(random::random_sets, m)?)?;
+    m.add_function(wrap_pyfunction!(random::similarity_significance, m)?)?;
+    m.add_function(wrap_pyfunction!(resolve::resolve, m)?)?;
+    m.add_function(wrap_pyfunction!(worker::init_worker, m)?)?;
+    m.add_function(wrap_pyfunction!(phenopacket::load_phenopackets, m)?)?;
+    m.add_class::<similarity_cache::PySimilarityCache>()?;
+    m.add("similarity_cache", similarity_cache::PySimilarityCache)?;
     m.add_function(wrap_pyfunction!(batch_set_similarity, m)?)?;
+    m.add_class::<batch_chunks::PySetSimilarityChunks>()?;
+    m.add_function(wrap_pyfunction!(
+        batch_chunks::batch_set_similarity_chunks,
+        m
+    )?)?;
     m.add_function(wrap_pyfunction!(batch_gene_enrichment, m)?)?;
     m.add_function(wrap_pyfunction!(batch_disease_enrichment, m)?)?;
     m.add_function(wrap_pyfunction!(batch_omim_disease_enrichment, m)?)?;
@@ -209,10 +386,92 @@ fn pyhpo(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
 ///       Deng Y, et. al., PLoS One, (2015)
 ///     * **dist** - Distance between terms
 ///
+/// on_empty: str, default ``zero``
+///     The similarity of comparisons with an empty ``HPOSet``
+///
+///     Available options:
+///
+///     * **zero** - The similarity is ``0.0``
+///     * **nan** - The similarity is ``NaN``
+///     * **raise** - The comparison fails, see ``errors``
+///
+/// nan_policy: str, default ``propagate``
+///     How to handle similarity scores that are ``NaN``. Failed comparisons
+///     are not affected, see ``errors``
+///
+///     Available options:
+///
+///     * **propagate** - Return ``NaN`` scores
+///     * **raise** - Raise a ``ValueError``
+///     * **skip** - Omit ``NaN`` scores from the result. The result
+///       does not align with the input any longer
+///     * **substitute** - Replace ``NaN`` scores with ``nan_value``
+///
+/// nan_value: float, default ``0.0``
+///     The score that replaces ``NaN`` if ``nan_policy`` is ``substitute``
+///
+/// errors: str, default ``raise``
+///     How to handle comparisons that cannot be calculated, e.g. because
+///     they are not a tuple of two ``HPOSet``
+///
+///     Available options:
+///
+///     * **raise** - Raise the error of the first failed comparison
+///     * **nan** - Return ``NaN`` as the score of failed comparisons
+///     * **collect** - Return ``NaN`` as the score of failed comparisons and
+///       additionally return a list of ``(index, error message)`` tuples
+///       of all failed comparisons
+///
+/// methods: list[str], optional
+///     Calculate the similarity with several methods at once. The ancestors
+///     of each term pair are traversed only once for all methods that are
+///     based on the information content, which is considerably faster
+///     than calling this function once per method. If provided, ``method``
+///     is ignored and a dict with the scores of every method is returned.
+///     With ``nan_policy="skip"``, the lists of different methods might
+///     not align any longer.
+///
+/// output_path: str, optional
+///     Write the scores to this file instead of returning them. Every row
+///     contains the ``index`` of the comparison and its ``similarity``, or
+///     one column per method if ``methods`` are provided. With
+///     ``nan_policy="skip"``, rows with a ``NaN`` score are omitted.
+///
+/// format: str, optional
+///     The format of ``output_path``, ``csv``, ``npy`` or ``parquet``.
+///     Defaults to the extension of ``output_path``, using ``csv`` for
+///     unknown extensions. ``npy`` files only contain the scores, as
+///     ``float32`` array. ``parquet`` requires ``pyarrow``
+///
+/// output: str, default ``list``
+///     The type of the returned scores, ignored if ``output_path``
+///     is provided
+///
+///     Available options:
+///
+///     * **list** - A list of floats
+///     * **numpy** - A numpy array of ``dtype``
+///     * **buffer** - A :class:`pyhpo.types.ScoreBuffer`, that ``numpy``
+///       or ``torch`` can wrap without copying the scores
+///
+/// dtype: str, default ``float32``
+///     The precision to combine the term similarities with, ``float32``
+///     or ``float64``, see :func:`pyhpo.HPOSet.similarity`. Also the type of
+///     ``numpy`` and ``buffer`` outputs. ``npy`` files always contain ``float32``
+///
+/// workers: int, optional
+///     The number of threads of this call. Defaults to all threads of
+///     the shared thread pool. Limiting one call does not affect the
+///     threads of other calls that run at the same time
+///
 /// Returns
 /// -------
-/// list[float]
-///     The similarity scores of each comparison
+/// list[float] or numpy.ndarray or :class:`pyhpo.types.ScoreBuffer`
+///     The similarity scores of each comparison. If ``methods`` are provided,
+///     a dict with the scores for every method.
+///     If ``output_path`` is provided, the number of written rows.
+///     If ``errors`` is ``collect``,
+///     a tuple of the scores and the list of errors is returned instead
 ///
 /// Raises
 /// ------
@@ -222,6 +481,14 @@ fn pyhpo(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
 ///     Invalid ``kind`` provided
 /// RuntimeError
 ///     Invalid ``method`` or ``combine``
+/// ValueError
+///     Invalid ``errors``, ``on_empty`` or ``nan_policy`` policy, invalid
+///     ``format``, ``output`` or ``dtype``, ``workers`` of ``0`` or a ``NaN`` score
+///     with ``nan_policy="raise"``
+/// OSError
+///     ``output_path`` cannot be written
+/// ImportError
+///     ``numpy`` is not installed and ``output`` is ``numpy``
 ///
 /// Examples
 /// --------
@@ -237,33 +504,167 @@ fn pyhpo(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
 ///     gene_set_combinations = [(a[0], a[1]) for a in itertools.combinations(gene_sets,2)]
 ///     similarities = helper.batch_set_similarity(gene_set_combinations[0:100], kind="omim", method="graphic", combine = "funSimAvg")
 ///
+///     # Complete all valid comparisons and report the failed ones
+///     similarities, errors = helper.batch_set_similarity(
+///         [(gene_sets[0], gene_sets[1]), (gene_sets[0], None)],
+///         errors="collect"
+///     )
+///     # >> errors: [(1, "TypeError: ...")]
+///
+///     # Calculate several methods in a single pass
+///     similarities = helper.batch_set_similarity(
+///         gene_set_combinations[0:100],
+///         methods=["resnik", "lin", "graphic"]
+///     )
+///     similarities["lin"][0:2]
+///     # >> [0.5386, 0.2468]
+///
+///     # Write the scores to a file instead
+///     helper.batch_set_similarity(
+///         gene_set_combinations,
+///         output_path="similarities.csv"
+///     )
+///     # >> 18550
+///
 #[pyfunction]
-#[pyo3(signature = (comparisons, kind = "omim", method = "graphic", combine = "funSimAvg"))]
-#[pyo3(text_signature = "(comparisons, kind, method, combine)")]
+#[pyo3(signature = (comparisons, kind = "omim", method = "graphic", combine = "funSimAvg", on_empty = "zero", nan_policy = "propagate", nan_value = 0.0, errors = "raise", methods = None, output_path = None, format = None, output = "list", workers = None, dtype = "float32"))]
+#[pyo3(
+    text_signature = "(comparisons, kind, method, combine, on_empty, nan_policy, nan_value, errors, methods, output_path, format, output, workers, dtype)"
+)]
+#[allow(clippy::too_many_arguments)]
 fn batch_set_similarity(
-    comparisons: Vec<(PyHpoSet, PyHpoSet)>,
+    py: Python<'_>,
+    comparisons: Vec<Bound<'_, PyAny>>,
+    kind: &str,
+    method: &str,
+    combine: &str,
+    on_empty: &str,
+    nan_policy: &str,
+    nan_value: f32,
+    errors: &str,
+    methods: Option<Vec<String>>,
+    output_path: Option<PathBuf>,
+    format: Option<&str>,
+    output: &str,
+    workers: Option<usize>,
+    dtype: &str,
+) -> PyResult<PyObject> {
+    match Dtype::try_from(dtype)? {
+        Dtype::Float32 => set_similarities::<f32>(
+            py,
+            comparisons,
+            kind,
+            method,
+            combine,
+            on_empty,
+            nan_policy,
+            nan_value,
+            errors,
+            methods,
+            output_path,
+            format,
+            output,
+            workers,
+        ),
+        Dtype::Float64 => set_similarities::<f64>(
+            py,
+            comparisons,
+            kind,
+            method,
+            combine,
+            on_empty,
+            nan_policy,
+            nan_value,
+            errors,
+            methods,
+            output_path,
+            format,
+            output,
+            workers,
+        ),
+    }
+}
+
+/// Calculates [`batch_set_similarity`] with scores of type `T`
+#[allow(clippy::too_many_arguments)]
+fn set_similarities<T: Score>(
+    py: Python<'_>,
+    comparisons: Vec<Bound<'_, PyAny>>,
     kind: &str,
     method: &str,
     combine: &str,
-) -> PyResult<Vec<f32>> {
+    on_empty: &str,
+    nan_policy: &str,
+    nan_value: f32,
+    errors: &str,
+    methods: Option<Vec<String>>,
+    output_path: Option<PathBuf>,
+    format: Option<&str>,
+    output: &str,
+    workers: Option<usize>,
+) -> PyResult<PyObject> {
     let ont = get_ontology()?;
+    let policy = ErrorPolicy::try_from(errors)?;
+    let nan_policy = NanPolicy::new(nan_policy, nan_value)?;
+    let array_output = ArrayOutput::try_from(output)?;
+    let output = output_path
+        .map(|path| Format::new(&path, format).map(|format| (path, format)))
+        .transpose()?;
+    let comparisons = extract_comparisons::<PyHpoSet>(comparisons, policy)?;
 
-    let kind = PyInformationContentKind::try_from(kind)?;
-    let similarity = hpo::similarity::Builtins::new(method, kind.into())
-        .map_err(|_| PyRuntimeError::new_err("Unknown method to calculate similarity"))?;
+    if let Some(methods) = methods {
+        let similarity = MultiSimilarity::new(&methods, kind)?;
+        let combiner = StandardCombiner::try_from(combine)
+            .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+        let on_empty = EmptySetPolicy::try_from(on_empty)?;
+
+        let scores: Vec<PyResult<Vec<T>>> = worker::install_with(py, workers, || {
+            comparisons
+                .into_par_iter()
+                .map(|comp| {
+                    let (a, b) = comp?;
+                    let (set_a, set_b) = (a.set(ont), b.set(ont));
+                    if set_a.is_empty() || set_b.is_empty() {
+                        let score = on_empty.score(true, || T::from(0.0))?;
+                        return Ok(vec![score; similarity.len()]);
+                    }
+                    Ok(similarity.calculate_sets(&combiner, &set_a, &set_b))
+                })
+                .collect()
+        })?;
+        if let Some((path, format)) = output {
+            return scores_to_file(py, path, format, &methods, scores, policy, nan_policy);
+        }
+        return multi_scores_to_py(py, &methods, scores, policy, nan_policy, array_output);
+    }
+
+    let similarity = similarity_cache::builtin(method, kind)?;
     let combiner = StandardCombiner::try_from(combine)
         .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
 
-    let g_sim = GroupSimilarity::new(combiner, similarity);
+    let on_empty = EmptySetPolicy::try_from(on_empty)?;
 
-    Ok(comparisons
-        .par_iter()
-        .map(|comp| {
-            let set_a = comp.0.set(ont);
-            let set_b = comp.1.set(ont);
-            g_sim.calculate(&set_a, &set_b)
-        })
-        .collect())
+    let scores: Vec<PyResult<T>> = worker::install_with(py, workers, || {
+        comparisons
+            .into_par_iter()
+            .map(|comp| {
+                let (a, b) = comp?;
+                let (set_a, set_b) = (a.set(ont), b.set(ont));
+                on_empty.score(set_a.is_empty() || set_b.is_empty(), || {
+                    T::set_similarity(&similarity, &combiner, &set_a, &set_b)
+                })
+            })
+            .collect()
+    })?;
+    if let Some((path, format)) = output {
+        let scores = scores
+            .into_iter()
+            .map(|score| score.map(|score| vec![score]))
+            .collect();
+        let methods = ["similarity".to_string()];
+        return scores_to_file(py, path, format, &methods, scores, policy, nan_policy);
+    }
+    scores_to_py(py, scores, policy, nan_policy, array_output)
 }
 
 /// Calculate similarity between ``HPOTerm`` in batches
@@ -303,10 +704,47 @@ fn batch_set_similarity(
 ///       Deng Y, et. al., PLoS One, (2015)
 ///     * **dist** - Distance between terms
 ///
+/// nan_policy: str, default ``propagate``
+///     How to handle similarity scores that are ``NaN``. Failed comparisons
+///     are not affected, see ``errors``
+///
+///     Available options:
+///
+///     * **propagate** - Return ``NaN`` scores
+///     * **raise** - Raise a ``ValueError``
+///     * **skip** - Omit ``NaN`` scores from the result. The result
+///       does not align with the input any longer
+///     * **substitute** - Replace ``NaN`` scores with ``nan_value``
+///
+/// nan_value: float, default ``0.0``
+///     The score that replaces ``NaN`` if ``nan_policy`` is ``substitute``
+///
+/// errors: str, default ``raise``
+///     How to handle comparisons that cannot be calculated, e.g. because
+///     they are not a tuple of two ``HPOTerm``
+///
+///     Available options:
+///
+///     * **raise** - Raise the error of the first failed comparison
+///     * **nan** - Return ``NaN`` as the score of failed comparisons
+///     * **collect** - Return ``NaN`` as the score of failed comparisons and
+///       additionally return a list of ``(index, error message)`` tuples
+///       of all failed comparisons
+///
+/// output: str, default ``list``
+///     The type of the returned scores, ``list``, ``numpy`` for a ``float32``
+///     numpy array or ``buffer`` for a :class:`pyhpo.types.ScoreBuffer`
+///
+/// workers: int, optional
+///     The number of threads of this call. Defaults to all threads of
+///     the shared thread pool. Limiting one call does not affect the
+///     threads of other calls that run at the same time
+///
 /// Returns
 /// -------
-/// list[float]
-///     The similarity scores of each comparison
+/// list[float] or numpy.ndarray or :class:`pyhpo.types.ScoreBuffer`
+///     The similarity scores of each comparison. If ``errors`` is ``collect``,
+///     a tuple of the scores and the list of errors is returned instead
 ///
 /// Raises
 /// ------
@@ -314,6 +752,11 @@ fn batch_set_similarity(
 ///     Invalid ``kind`` provided
 /// RuntimeError
 ///     Invalid ``method``
+/// ValueError
+///     Invalid ``errors`` or ``nan_policy`` policy, invalid ``output``,
+///     ``workers`` of ``0`` or a ``NaN`` score with ``nan_policy="raise"``
+/// ImportError
+///     ``numpy`` is not installed and ``output`` is ``numpy``
 ///
 /// Examples
 /// --------
@@ -329,26 +772,48 @@ fn batch_set_similarity(
 ///     term_combinations = [(a[0], a[1]) for a in itertools.combinations(terms,2)]
 ///     similarities = helper.batch_similarity(term_combinations[0:10000], kind="omim", method="graphic")
 ///
+///     # Use NaN for malformed comparisons instead of failing
+///     similarities = helper.batch_similarity(
+///         [(terms[0], terms[1]), (terms[0],)],
+///         errors="nan"
+///     )
+///     # >> [0.1234, nan]
+///
 #[pyfunction]
-#[pyo3(signature = (comparisons, kind = "omim", method = "graphic"))]
-#[pyo3(text_signature = "(comparisons, kind, method)")]
+#[pyo3(signature = (comparisons, kind = "omim", method = "graphic", nan_policy = "propagate", nan_value = 0.0, errors = "raise", output = "list", workers = None))]
+#[pyo3(
+    text_signature = "(comparisons, kind, method, nan_policy, nan_value, errors, output, workers)"
+)]
+#[allow(clippy::too_many_arguments)]
 fn batch_similarity(
-    comparisons: Vec<(PyHpoTerm, PyHpoTerm)>,
+    py: Python<'_>,
+    comparisons: Vec<Bound<'_, PyAny>>,
     kind: &str,
     method: &str,
-) -> PyResult<Vec<f32>> {
-    let kind = PyInformationContentKind::try_from(kind)?;
-    let similarity = hpo::similarity::Builtins::new(method, kind.into())
-        .map_err(|_| PyRuntimeError::new_err("Unknown method to calculate similarity"))?;
-
-    Ok(comparisons
-        .par_iter()
-        .map(|comp| {
-            let t1: hpo::HpoTerm = (&comp.0).into();
-            let t2: hpo::HpoTerm = (&comp.1).into();
-            similarity.calculate(&t1, &t2)
-        })
-        .collect())
+    nan_policy: &str,
+    nan_value: f32,
+    errors: &str,
+    output: &str,
+    workers: Option<usize>,
+) -> PyResult<PyObject> {
+    let similarity = similarity_cache::builtin(method, kind)?;
+    let policy = ErrorPolicy::try_from(errors)?;
+    let nan_policy = NanPolicy::new(nan_policy, nan_value)?;
+    let output = ArrayOutput::try_from(output)?;
+    let comparisons = extract_comparisons::<PyHpoTerm>(comparisons, policy)?;
+
+    let scores = worker::install_with(py, workers, || {
+        comparisons
+            .into_par_iter()
+            .map(|comp| {
+                let (a, b) = comp?;
+                let t1: hpo::HpoTerm = (&a).into();
+                let t2: hpo::HpoTerm = (&b).into();
+                Ok(similarity.calculate(&t1, &t2))
+            })
+            .collect()
+    })?;
+    scores_to_py(py, scores, policy, nan_policy, output)
 }
 
 /// Calculate enriched genes in a list of ``HPOSet``
@@ -365,16 +830,41 @@ fn batch_similarity(
 ///     A list of HPOSets. The enrichment of all genes is calculated separately
 ///     for each HPOset in the list
 ///
+/// sort_by: str, default ``pvalue``
+///     The order of the results of each set, ``pvalue``, ``fold`` or ``count``.
+///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details
+///
+/// output_path: str, optional
+///     Write the results to this file instead of returning them. Every row
+///     contains one result of one set, with the columns ``set`` (the index
+///     of the ``HPOSet``), ``rank``, ``id``, ``name``, ``enrichment``,
+///     ``log10_pvalue``, ``qvalue``, ``log10_qvalue``, ``fold`` and ``count``
+///
+/// format: str, optional
+///     The format of ``output_path``, ``csv`` or ``parquet``. Defaults to
+///     the extension of ``output_path``, using ``csv`` for unknown
+///     extensions. ``parquet`` requires ``pyarrow``
+///
+/// workers: int, optional
+///     The number of threads of this call. Defaults to all threads of
+///     the shared thread pool. Limiting one call does not affect the
+///     threads of other calls that run at the same time
+///
 /// Returns
 /// -------
 /// list[dict]
 ///     The enrichment result for every gene.
-///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details
+///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details.
+///     If ``output_path`` is provided, the number of written rows
 ///
 /// Raises
 /// ------
 /// NameError
 ///     Ontology not yet constructed
+/// ValueError
+///     Invalid ``sort_by`` or ``format`` or ``workers`` of ``0``
+/// OSError
+///     ``output_path`` cannot be written
 ///
 /// Examples
 /// --------
@@ -403,28 +893,55 @@ fn batch_similarity(
 ///     # >>> The top enriched genes for Oculopharyngodistal myopathy 4 are: RILPL1, (1.4351489331895004e-49), LRP12, (2.168165858699749e-30), GIPC1, (3.180801819975307e-27), NOTCH2NLC, (1.0700847991253517e-23), VCP, (2.8742020666947536e-20)
 ///
 #[pyfunction]
+#[pyo3(signature = (hposets, sort_by = "pvalue", output_path = None, format = None, workers = None))]
+#[pyo3(text_signature = "(hposets, sort_by, output_path, format, workers)")]
 fn batch_gene_enrichment(
     py: Python,
     hposets: Vec<PyHpoSet>,
-) -> PyResult<Vec<Vec<Bound<'_, PyDict>>>> {
+    sort_by: &str,
+    output_path: Option<PathBuf>,
+    format: Option<&str>,
+    workers: Option<usize>,
+) -> PyResult<PyObject> {
     let ont = get_ontology()?;
-    let enrichments = hposets
-        .par_iter()
-        .map(|pyset| {
-            let mut enrichment = gene_enrichment(ont, &pyset.set(ont));
-            enrichment.sort_by(|a, b| a.pvalue().partial_cmp(&b.pvalue()).unwrap());
-            enrichment
-        })
-        .collect::<Vec<Vec<hpo::stats::Enrichment<GeneId>>>>();
+    let sort_by = SortKey::try_from(sort_by)?;
+    let model = PyEnrichmentModel::new("gene", "ontology")?;
+    let universe = model.universe(ont);
+    if let Some(path) = output_path {
+        let format = Format::new(&path, format)?;
+        return enrichment_to_file(
+            py,
+            &hposets,
+            path,
+            format,
+            sort_by,
+            workers,
+            |ont, set| universe.hypergeom(set, gene_enrichment(ont, set)),
+            |id| {
+                ont.gene(id)
+                    .map(|gene| gene.name().to_string())
+                    .unwrap_or_default()
+            },
+        )?
+        .into_py_any(py);
+    }
+    let enrichments = worker::install_with(py, workers, || {
+        hposets
+            .par_iter()
+            .map(|pyset| {
+                let set = pyset.set(ont);
+                let mut enrichment = universe.hypergeom(&set, gene_enrichment(ont, &set));
+                sort_enrichment(&mut enrichment);
+                enrichment
+            })
+            .collect::<Vec<Vec<LogEnrichment<GeneId>>>>()
+    })?;
 
     enrichments
         .iter()
-        .map(|set| {
-            set.iter()
-                .map(|enrichment| crate::enrichment::gene_enrichment_dict(py, enrichment))
-                .collect::<PyResult<Vec<Bound<'_, PyDict>>>>()
-        })
-        .collect::<PyResult<Vec<Vec<Bound<'_, PyDict>>>>>()
+        .map(|set| enrichment_dicts(py, set, sort_by, crate::enrichment::gene_enrichment_dict))
+        .collect::<PyResult<Vec<Vec<Bound<'_, PyDict>>>>>()?
+        .into_py_any(py)
 }
 
 /// Deprecated since 1.3.0
@@ -432,11 +949,8 @@ fn batch_gene_enrichment(
 /// Use :func:`pyhpo.helper.batch_omim_disease_enrichment` or
 /// :func:`pyhpo.helper.batch_orpha_disease_enrichment` instead
 #[pyfunction]
-fn batch_disease_enrichment(
-    py: Python,
-    hposets: Vec<PyHpoSet>,
-) -> PyResult<Vec<Vec<Bound<'_, PyDict>>>> {
-    batch_omim_disease_enrichment(py, hposets)
+fn batch_disease_enrichment(py: Python<'_>, hposets: Vec<PyHpoSet>) -> PyResult<PyObject> {
+    batch_omim_disease_enrichment(py, hposets, "pvalue", None, None, None)
 }
 
 /// Calculate enriched Omim diseases in a list of ``HPOSet``
@@ -453,16 +967,41 @@ fn batch_disease_enrichment(
 ///     A list of HPOSets. The enrichment of all diseases is calculated separately
 ///     for each HPOset in the list
 ///
+/// sort_by: str, default ``pvalue``
+///     The order of the results of each set, ``pvalue``, ``fold`` or ``count``.
+///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details
+///
+/// output_path: str, optional
+///     Write the results to this file instead of returning them. Every row
+///     contains one result of one set, with the columns ``set`` (the index
+///     of the ``HPOSet``), ``rank``, ``id``, ``name``, ``enrichment``,
+///     ``log10_pvalue``, ``qvalue``, ``log10_qvalue``, ``fold`` and ``count``
+///
+/// format: str, optional
+///     The format of ``output_path``, ``csv`` or ``parquet``. Defaults to
+///     the extension of ``output_path``, using ``csv`` for unknown
+///     extensions. ``parquet`` requires ``pyarrow``
+///
+/// workers: int, optional
+///     The number of threads of this call. Defaults to all threads of
+///     the shared thread pool. Limiting one call does not affect the
+///     threads of other calls that run at the same time
+///
 /// Returns
 /// -------
 /// list[dict]
 ///     The enrichment result for every disease.
-///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details
+///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details.
+///     If ``output_path`` is provided, the number of written rows
 ///
 /// Raises
 /// ------
 /// NameError
 ///     Ontology not yet constructed
+/// ValueError
+///     Invalid ``sort_by`` or ``format`` or ``workers`` of ``0``
+/// OSError
+///     ``output_path`` cannot be written
 ///
 /// Examples
 /// --------
@@ -490,28 +1029,62 @@ fn batch_disease_enrichment(
 ///     # >>> The top enriched diseases for TYMS are: Dyskeratosis congenita, X-linked, (5.008058437787544e-192), Dyskeratosis congenita, digenic, (2.703378203105612e-184), Dyskeratosis congenita, autosomal dominant 2, (1.3109083102058795e-150), Bloom syndrome, (3.965926308699221e-141), Dyskeratosis congenita, autosomal dominant 3, (1.123439117889186e-131)
 ///
 #[pyfunction]
+#[pyo3(signature = (hposets, sort_by = "pvalue", output_path = None, format = None, workers = None))]
+#[pyo3(text_signature = "(hposets, sort_by, output_path, format, workers)")]
 fn batch_omim_disease_enrichment(
     py: Python,
     hposets: Vec<PyHpoSet>,
-) -> PyResult<Vec<Vec<Bound<'_, PyDict>>>> {
+    sort_by: &str,
+    output_path: Option<PathBuf>,
+    format: Option<&str>,
+    workers: Option<usize>,
+) -> PyResult<PyObject> {
     let ont = get_ontology()?;
-    let enrichments = hposets
-        .par_iter()
-        .map(|pyset| {
-            let mut enrichment = omim_disease_enrichment(ont, &pyset.set(ont));
-            enrichment.sort_by(|a, b| a.pvalue().partial_cmp(&b.pvalue()).unwrap());
-            enrichment
-        })
-        .collect::<Vec<Vec<hpo::stats::Enrichment<OmimDiseaseId>>>>();
+    let sort_by = SortKey::try_from(sort_by)?;
+    let model = PyEnrichmentModel::new("omim", "ontology")?;
+    let universe = model.universe(ont);
+    if let Some(path) = output_path {
+        let format = Format::new(&path, format)?;
+        return enrichment_to_file(
+            py,
+            &hposets,
+            path,
+            format,
+            sort_by,
+            workers,
+            |ont, set| universe.hypergeom(set, omim_disease_enrichment(ont, set)),
+            |id| {
+                ont.omim_disease(id)
+                    .map(|disease| disease.name().to_string())
+                    .unwrap_or_default()
+            },
+        )?
+        .into_py_any(py);
+    }
+    let enrichments = worker::install_with(py, workers, || {
+        hposets
+            .par_iter()
+            .map(|pyset| {
+                let set = pyset.set(ont);
+                let mut enrichment = universe.hypergeom(&set, omim_disease_enrichment(ont, &set));
+                sort_enrichment(&mut enrichment);
+                enrichment
+            })
+            .collect::<Vec<Vec<LogEnrichment<OmimDiseaseId>>>>()
+    })?;
 
     enrichments
         .iter()
         .map(|set| {
-            set.iter()
-                .map(|enrichment| crate::enrichment::omim_disease_enrichment_dict(py, enrichment))
-                .collect::<PyResult<Vec<Bound<'_, PyDict>>>>()
+            enrichment_dicts(
+                py,
+                set,
+                sort_by,
+                crate::enrichment::omim_disease_enrichment_dict,
+            )
         })
-        .collect::<PyResult<Vec<Vec<Bound<'_, PyDict>>>>>()
+        .collect::<PyResult<Vec<Vec<Bound<'_, PyDict>>>>>()?
+        .into_py_any(py)
 }
 
 /// Calculate enriched Orpha diseases in a list of ``HPOSet``
@@ -528,16 +1101,41 @@ fn batch_omim_disease_enrichment(
 ///     A list of HPOSets. The enrichment of all diseases is calculated separately
 ///     for each HPOset in the list
 ///
+/// sort_by: str, default ``pvalue``
+///     The order of the results of each set, ``pvalue``, ``fold`` or ``count``.
+///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details
+///
+/// output_path: str, optional
+///     Write the results to this file instead of returning them. Every row
+///     contains one result of one set, with the columns ``set`` (the index
+///     of the ``HPOSet``), ``rank``, ``id``, ``name``, ``enrichment``,
+///     ``log10_pvalue``, ``qvalue``, ``log10_qvalue``, ``fold`` and ``count``
+///
+/// format: str, optional
+///     The format of ``output_path``, ``csv`` or ``parquet``. Defaults to
+///     the extension of ``output_path``, using ``csv`` for unknown
+///     extensions. ``parquet`` requires ``pyarrow``
+///
+/// workers: int, optional
+///     The number of threads of this call. Defaults to all threads of
+///     the shared thread pool. Limiting one call does not affect the
+///     threads of other calls that run at the same time
+///
 /// Returns
 /// -------
 /// list[dict]
 ///     The enrichment result for every disease.
-///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details
+///     See :func:`pyhpo.stats.EnrichmentModel.enrichment` for details.
+///     If ``output_path`` is provided, the number of written rows
 ///
 /// Raises
 /// ------
 /// NameError
 ///     Ontology not yet constructed
+/// ValueError
+///     Invalid ``sort_by`` or ``format`` or ``workers`` of ``0``
+/// OSError
+///     ``output_path`` cannot be written
 ///
 /// Examples
 /// --------
@@ -565,26 +1163,60 @@ fn batch_omim_disease_enrichment(
 ///     # >>> The top enriched diseases for TYMS are: Dyskeratosis congenita, X-linked, (5.008058437787544e-192), Dyskeratosis congenita, digenic, (2.703378203105612e-184), Dyskeratosis congenita, autosomal dominant 2, (1.3109083102058795e-150), Bloom syndrome, (3.965926308699221e-141), Dyskeratosis congenita, autosomal dominant 3, (1.123439117889186e-131)
 ///
 #[pyfunction]
+#[pyo3(signature = (hposets, sort_by = "pvalue", output_path = None, format = None, workers = None))]
+#[pyo3(text_signature = "(hposets, sort_by, output_path, format, workers)")]
 fn batch_orpha_disease_enrichment(
     py: Python,
     hposets: Vec<PyHpoSet>,
-) -> PyResult<Vec<Vec<Bound<'_, PyDict>>>> {
+    sort_by: &str,
+    output_path: Option<PathBuf>,
+    format: Option<&str>,
+    workers: Option<usize>,
+) -> PyResult<PyObject> {
     let ont = get_ontology()?;
-    let enrichments = hposets
-        .par_iter()
-        .map(|pyset| {
-            let mut enrichment = orpha_disease_enrichment(ont, &pyset.set(ont));
-            enrichment.sort_by(|a, b| a.pvalue().partial_cmp(&b.pvalue()).unwrap());
-            enrichment
-        })
-        .collect::<Vec<Vec<hpo::stats::Enrichment<OrphaDiseaseId>>>>();
+    let sort_by = SortKey::try_from(sort_by)?;
+    let model = PyEnrichmentModel::new("orpha", "ontology")?;
+    let universe = model.universe(ont);
+    if let Some(path) = output_path {
+        let format = Format::new(&path, format)?;
+        return enrichment_to_file(
+            py,
+            &hposets,
+            path,
+            format,
+            sort_by,
+            workers,
+            |ont, set| universe.hypergeom(set, orpha_disease_enrichment(ont, set)),
+            |id| {
+                ont.orpha_disease(id)
+                    .map(|disease| disease.name().to_string())
+                    .unwrap_or_default()
+            },
+        )?
+        .into_py_any(py);
+    }
+    let enrichments = worker::install_with(py, workers, || {
+        hposets
+            .par_iter()
+            .map(|pyset| {
+                let set = pyset.set(ont);
+                let mut enrichment = universe.hypergeom(&set, orpha_disease_enrichment(ont, &set));
+                sort_enrichment(&mut enrichment);
+                enrichment
+            })
+            .collect::<Vec<Vec<LogEnrichment<OrphaDiseaseId>>>>()
+    })?;
 
     enrichments
         .iter()
         .map(|set| {
-            set.iter()
-                .map(|enrichment| crate::enrichment::orpha_disease_enrichment_dict(py, enrichment))
-                .collect::<PyResult<Vec<Bound<'_, PyDict>>>>()
+            enrichment_dicts(
+                py,
+                set,
+                sort_by,
+                crate::enrichment::orpha_disease_enrichment_dict,
+            )
         })
-        .collect::<PyResult<Vec<Vec<Bound<'_, PyDict>>>>>()
+        .collect::<PyResult<Vec<Vec<Bound<'_, PyDict>>>>>()?
+        .into_py_any(py)
 }
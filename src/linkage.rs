@@ -1,12 +1,21 @@
-use pyo3::{exceptions::PyRuntimeError, prelude::*};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use pyo3::exceptions::{PyIndexError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
 use rayon::prelude::*;
 
-use hpo::similarity::{GroupSimilarity, StandardCombiner};
+use hpo::similarity::{Similarity, StandardCombiner};
 use hpo::stats::Linkage;
 use hpo::utils::Combinations;
 use hpo::HpoSet;
 
-use crate::{get_ontology, information_content::PyInformationContentKind, set::PyHpoSet};
+use crate::batch::NanPolicy;
+use crate::get_ontology;
+use crate::set::{EmptySetPolicy, PyHpoSet};
+use crate::similarity::{Dtype, PySimilarity, Score};
+use crate::similarity_cache;
+use crate::worker;
 
 /// Crate a linkage matrix from a list of ``HpoSet``\s to use in dendograms
 /// or other hierarchical cluster analyses
@@ -39,6 +48,10 @@ use crate::{get_ontology, information_content::PyInformationContentKind, set::Py
 ///     * **omim**
 ///     * **orpha**
 ///     * **gene**
+///     * **custom** - The custom information content of the terms,
+///       see :func:`pyhpo.HPOTerm.set_custom_ic`, or the name of any other
+///       custom information content, see :func:`pyhpo.HPOTerm.set_ic`.
+///       Only supported by the information content based ``similarity_method``\s
 ///
 /// similarity_method: `str`, default `graphic`
 ///     The method to use to calculate the similarity between HPOSets.
@@ -67,6 +80,52 @@ use crate::{get_ontology, information_content::PyInformationContentKind, set::Py
 ///     * **funSimMax** - Schlicker A, BMC Bioinformatics, (2006)
 ///     * **BMA** - Deng Y, et. al., PLoS One, (2015)
 ///
+/// on_empty: str, default ``zero``
+///     The similarity of comparisons with an empty ``HPOSet``
+///
+///     Available options:
+///
+///     * **zero** - The similarity is ``0.0``, i.e. the distance is ``1.0``
+///     * **nan** - The similarity and distance are ``NaN``
+///     * **raise** - Raise a ``ValueError`` if any of the ``sets`` is empty
+///
+/// nan_policy: str, default ``propagate``
+///     How to handle similarity scores that are ``NaN``. ``NaN`` distances
+///     make the clustering unreliable.
+///
+///     Available options:
+///
+///     * **propagate** - Use ``NaN`` as distance
+///     * **raise** - Raise a ``ValueError``
+///     * **substitute** - Use ``nan_value`` as similarity score instead
+///
+///     ``skip`` is not supported, because every pair of sets requires a distance
+///
+/// nan_value: float, default ``0.0``
+///     The similarity score that replaces ``NaN`` if ``nan_policy`` is ``substitute``
+///
+/// deterministic: bool, default ``False``
+///     Merge clusters in a fixed order. By default, one of several pairs of
+///     clusters with the same distance is merged first at random, so the
+///     clusters and their distances can differ between runs. With
+///     ``deterministic=True``, the pair with the lowest indices is merged
+///     first, which is slightly slower. All similarity scores are always
+///     calculated in the same order, regardless of the number of threads.
+///     The flag has no effect with ``dtype="float64"``, which is always
+///     deterministic
+///
+/// dtype: str, default ``float32``
+///     The precision of the similarity scores and distances, ``float32``
+///     or ``float64``. The term similarities of two sets are combined and
+///     the distances of merged clusters are calculated with this precision,
+///     see :func:`pyhpo.HPOSet.similarity`. ``float64`` always merges clusters
+///     in a fixed order, like ``deterministic=True``
+///
+/// Returns
+/// -------
+/// :class:`pyhpo.stats.Dendrogram`
+///     The clusters, in the same format as a ``scipy`` linkage matrix
+///
 /// Raises
 /// ------
 /// NameError
@@ -75,6 +134,9 @@ use crate::{get_ontology, information_content::PyInformationContentKind, set::Py
 ///     Invalid ``kind``
 /// RuntimeError
 ///     Invalid ``method`` or ``similarity_method`` or ``combine``
+/// ValueError
+///     Invalid ``on_empty``, ``nan_policy`` or ``dtype``, an empty set with
+///     ``on_empty="raise"`` or a ``NaN`` score with ``nan_policy="raise"``
 ///
 /// Examples
 /// --------
@@ -96,56 +158,519 @@ use crate::{get_ontology, information_content::PyInformationContentKind, set::Py
 ///     # Cluster the diseases using default settings
 ///     lnk = pyhpo.stats.linkage(disease_sets)
 ///
+///     # The HPOSets of the last formed cluster
+///     lnk.members(len(lnk) - 1)
+///
 ///     # For plotting, you can use `scipy`
 ///     import scipy
 ///
 ///     scipy.cluster.hierarchy.dendrogram(lnk)
 ///
 #[pyfunction]
-#[pyo3(signature = (sets, method = "single", kind = "omim", similarity_method = "graphic", combine = "funSimAvg"))]
-#[pyo3(text_signature = "(sets, method, kind, similarity_method, combine)")]
+#[pyo3(signature = (sets, method = "single", kind = "omim", similarity_method = "graphic", combine = "funSimAvg", on_empty = "zero", nan_policy = "propagate", nan_value = 0.0, deterministic = false, dtype = "float32"))]
+#[pyo3(
+    text_signature = "(sets, method, kind, similarity_method, combine, on_empty, nan_policy, nan_value, deterministic, dtype)"
+)]
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn linkage(
+    py: Python<'_>,
     sets: Vec<PyHpoSet>,
     method: &str,
     kind: &str,
     similarity_method: &str,
     combine: &str,
-) -> PyResult<Vec<(usize, usize, f32, usize)>> {
-    let kind = PyInformationContentKind::try_from(kind)?;
-
-    let similarity = hpo::similarity::Builtins::new(similarity_method, kind.into())
-        .map_err(|_| PyRuntimeError::new_err("Unknown method to calculate similarity"))?;
+    on_empty: &str,
+    nan_policy: &str,
+    nan_value: f32,
+    deterministic: bool,
+    dtype: &str,
+) -> PyResult<PyDendrogram> {
+    let observations = sets.len();
+    let similarity = similarity_cache::cached(
+        PySimilarity::new(similarity_method, kind)?,
+        kind,
+        similarity_method,
+    );
     let combiner = StandardCombiner::try_from(combine)
         .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
 
-    let sim = GroupSimilarity::new(combiner, similarity);
+    let on_empty = EmptySetPolicy::try_from(on_empty)?;
+    let nan_policy = NanPolicy::new(nan_policy, nan_value)?;
+    if nan_policy == NanPolicy::Skip {
+        return Err(PyValueError::new_err(
+            "nan_policy `skip` is not supported for linkage",
+        ));
+    }
+    let dtype = Dtype::try_from(dtype)?;
 
-    let distance = |combs: Combinations<HpoSet<'_>>| {
-        let x: Vec<(&HpoSet, &HpoSet)> = combs.collect();
-        x.par_iter()
-            .map(|comp| 1.0 - sim.calculate(comp.0, comp.1))
-            .collect()
-    };
     let ont = get_ontology()?;
 
-    let sets = sets.iter().map(|pyset| pyset.set(ont));
+    // Clusters are never empty if none of the sets is empty, so
+    // `Raise` can be checked upfront instead of for every comparison
+    if on_empty == EmptySetPolicy::Raise {
+        for set in &sets {
+            on_empty.score(set.set(ont).is_empty(), || 0.0)?;
+        }
+    }
 
-    let res = match method {
-        "single" => Linkage::single(sets, distance),
-        "union" => Linkage::union(sets, distance),
-        "complete" => Linkage::complete(sets, distance),
-        "average" => Linkage::average(sets, distance),
-        _ => return Err(PyRuntimeError::new_err("Not yet implemented")),
+    // The distance function cannot fail, so `NaN` scores are only
+    // recorded here and raised after the clustering
+    let nan_found = AtomicBool::new(false);
+
+    let pair_distances = SetDistance {
+        similarity,
+        combiner,
+        on_empty,
+        nan_policy,
+        nan_found: &nan_found,
     };
-    Ok(res
-        .cluster()
-        .map(|cluster| {
-            (
-                cluster.lhs(),
-                cluster.rhs(),
-                cluster.distance(),
-                cluster.len(),
-            )
+    let distances =
+        |x: Vec<(&HpoSet<'_>, &HpoSet<'_>)>| worker::install(py, || pair_distances.calculate(&x));
+    let distance = |combs: Combinations<HpoSet<'_>>| distances(combs.collect());
+
+    let sets = sets.iter().map(|pyset| pyset.set(ont));
+
+    // `Linkage` only supports `f32` distances, so `float64` clusters
+    // are always merged in a fixed order
+    let clusters: Vec<Cluster> = if dtype == Dtype::Float64 {
+        ordered_linkage::<f64, _>(sets.collect(), merge_fn(method)?, |x| {
+            worker::install(py, || pair_distances.calculate(&x))
         })
-        .collect())
+    } else if deterministic {
+        ordered_linkage::<f32, _>(sets.collect(), merge_fn(method)?, distances)
+    } else {
+        let res = match method {
+            "single" => Linkage::single(sets, distance),
+            "union" => Linkage::union(sets, distance),
+            "complete" => Linkage::complete(sets, distance),
+            "average" => Linkage::average(sets, distance),
+            _ => return Err(PyRuntimeError::new_err("Not yet implemented")),
+        };
+        res.cluster()
+            .map(|cluster| {
+                (
+                    cluster.lhs(),
+                    cluster.rhs(),
+                    f64::from(cluster.distance()),
+                    cluster.len(),
+                )
+            })
+            .collect()
+    };
+    if nan_found.load(Ordering::Relaxed) {
+        return Err(PyValueError::new_err(
+            "The similarity score of at least one pair of sets is NaN",
+        ));
+    }
+    Ok(PyDendrogram {
+        observations,
+        clusters,
+    })
+}
+
+/// Calculates the distance `1 - similarity` of pairs of sets
+///
+/// Failed comparisons and `NaN` scores are recorded in `nan_found`.
+struct SetDistance<'a, S> {
+    similarity: S,
+    combiner: StandardCombiner,
+    on_empty: EmptySetPolicy,
+    nan_policy: NanPolicy,
+    nan_found: &'a AtomicBool,
+}
+
+impl<S: Similarity + Sync> SetDistance<'_, S> {
+    /// Returns the distance of every pair, calculated in parallel
+    fn calculate<T: Score>(&self, pairs: &[(&HpoSet<'_>, &HpoSet<'_>)]) -> Vec<T> {
+        pairs
+            .par_iter()
+            .map(|(a, b)| {
+                let score = self
+                    .on_empty
+                    .score(a.is_empty() || b.is_empty(), || {
+                        T::set_similarity(&self.similarity, &self.combiner, a, b)
+                    })
+                    .unwrap_or(f32::NAN.into());
+                match self.nan_policy.check(0, score) {
+                    Ok(Some(score)) => T::from(1.0) - score,
+                    _ => {
+                        self.nan_found.store(true, Ordering::Relaxed);
+                        f32::NAN.into()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Returns the distance of a new cluster from the distances of its two
+/// nodes with `method`, `None` for the `union` method
+///
+/// # Errors
+///
+/// - PyRuntimeError: Invalid `method`
+#[allow(clippy::type_complexity)]
+fn merge_fn<T: Score>(method: &str) -> PyResult<Option<fn(T, T) -> T>> {
+    match method {
+        "single" => Ok(Some(|a, b| if a < b { a } else { b })),
+        "union" => Ok(None),
+        "complete" => Ok(Some(|a, b| if a > b { a } else { b })),
+        "average" => Ok(Some(|a, b| (a + b) / T::from(2.0))),
+        _ => Err(PyRuntimeError::new_err("Not yet implemented")),
+    }
+}
+
+/// Clusters `sets` like [`Linkage`], but merges clusters in a fixed order
+///
+/// [`Linkage`] keeps the distances in a `HashMap` and merges the first
+/// closest pair of its random iteration order. Here, the distances are
+/// ordered by the indices of both clusters, so that the pair with the
+/// lowest indices is merged first if several pairs have the same distance.
+///
+/// `merge` returns the distance of a new cluster from the distances of its
+/// two nodes, `None` calculates the distance of the union of both sets
+/// instead. `distances` returns the distance of every pair of sets.
+fn ordered_linkage<'a, T, F>(
+    sets: Vec<HpoSet<'a>>,
+    merge: Option<fn(T, T) -> T>,
+    distances: F,
+) -> Vec<Cluster>
+where
+    T: Score,
+    F: for<'b> Fn(Vec<(&'b HpoSet<'a>, &'b HpoSet<'a>)>) -> Vec<T>,
+{
+    let observations = sets.len();
+    let mut sets: Vec<Option<HpoSet<'a>>> = sets.into_iter().map(Some).collect();
+    let mut clusters: Vec<Cluster> = Vec::with_capacity(observations);
+    let size = |clusters: &[Cluster], idx: usize| {
+        if idx < observations {
+            1
+        } else {
+            clusters[idx - observations].3
+        }
+    };
+
+    let pairs: Vec<(usize, usize)> = (0..observations)
+        .flat_map(|a| (a + 1..observations).map(move |b| (a, b)))
+        .collect();
+    let scores = distances(
+        pairs
+            .iter()
+            .filter_map(|(a, b)| Some((sets[*a].as_ref()?, sets[*b].as_ref()?)))
+            .collect(),
+    );
+    let mut matrix: BTreeMap<(usize, usize), T> = pairs.into_iter().zip(scores).collect();
+
+    while let Some(((a, b), distance)) = matrix
+        .iter()
+        .reduce(|min, elmt| if elmt.1 < min.1 { elmt } else { min })
+        .map(|(key, distance)| (*key, *distance))
+    {
+        clusters.push((
+            a,
+            b,
+            distance.into(),
+            size(&clusters, a) + size(&clusters, b),
+        ));
+        let new_idx = sets.len();
+        let lhs = sets[a].take().expect("merged set must be active");
+        let rhs = sets[b].take().expect("merged set must be active");
+        let active: Vec<usize> = (0..new_idx).filter(|idx| sets[*idx].is_some()).collect();
+
+        let new_distances: Vec<T> = match merge {
+            Some(merge) => {
+                let scores = active
+                    .iter()
+                    .map(|idx| {
+                        let key = |other: usize| (*idx.min(&other), *idx.max(&other));
+                        merge(matrix[&key(a)], matrix[&key(b)])
+                    })
+                    .collect();
+                // Placeholder of the cluster, its terms are never used
+                sets.push(Some(lhs));
+                scores
+            }
+            None => {
+                let mut union = lhs;
+                union.extend(&rhs);
+                let scores = distances(
+                    active
+                        .iter()
+                        .filter_map(|idx| Some((&union, sets[*idx].as_ref()?)))
+                        .collect(),
+                );
+                sets.push(Some(union));
+                scores
+            }
+        };
+
+        matrix.retain(|(x, y), _| ![a, b].contains(x) && ![a, b].contains(y));
+        matrix.extend(
+            active
+                .into_iter()
+                .zip(new_distances)
+                .map(|(idx, distance)| ((idx, new_idx), distance)),
+        );
+    }
+    clusters
+}
+
+/// A single cluster of a ``Dendrogram``: `(lhs, rhs, distance, size)`
+type Cluster = (usize, usize, f64, usize);
+
+/// The result of a hierarchical clustering by :func:`pyhpo.stats.linkage`
+///
+/// The dendrogram behaves like a linkage matrix in the format of ``scipy``:
+/// Every cluster is a row of ``(lhs, rhs, distance, size)``, where ``lhs``
+/// and ``rhs`` are the indices of the two merged nodes. Indices below the
+/// number of observations refer to the input ``HPOSet``\s, all other indices
+/// ``i`` refer to the cluster in row ``i - n_observations``.
+///
+/// It can be passed directly to ``scipy.cluster.hierarchy`` functions.
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     import pyhpo
+///     from pyhpo import Ontology
+///     Ontology()
+///
+///     sets = [gene.hpo_set() for gene in list(Ontology.genes)[:10]]
+///     dendrogram = pyhpo.stats.linkage(sets)
+///
+///     dendrogram.distances
+///     # >> [0.4153, 0.4635, 0.4785, ...]
+///
+///     # Indices of the HPOSets of the cluster in the last row
+///     dendrogram.members(len(dendrogram) - 1)
+///     # >> [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
+///
+#[pyclass(name = "Dendrogram")]
+pub(crate) struct PyDendrogram {
+    observations: usize,
+    clusters: Vec<Cluster>,
+}
+
+#[pymethods]
+impl PyDendrogram {
+    /// The number of clustered ``HPOSet``\s
+    #[getter(n_observations)]
+    fn n_observations(&self) -> usize {
+        self.observations
+    }
+
+    /// All clusters as ``(lhs, rhs, distance, size)`` tuples
+    #[getter(clusters)]
+    fn clusters(&self) -> Vec<Cluster> {
+        self.clusters.clone()
+    }
+
+    /// The distance of the two merged nodes of every cluster
+    #[getter(distances)]
+    fn distances(&self) -> Vec<f64> {
+        self.clusters.iter().map(|cluster| cluster.2).collect()
+    }
+
+    /// Returns the indices of all ``HPOSet``\s of a cluster
+    ///
+    /// Parameters
+    /// ----------
+    /// cluster: int
+    ///     The row index of the cluster
+    ///
+    /// Returns
+    /// -------
+    /// list[int]
+    ///     The sorted indices of the input ``HPOSet``\s within the cluster
+    ///
+    /// Raises
+    /// ------
+    /// IndexError
+    ///     ``cluster`` is out of bounds
+    ///
+    fn members(&self, cluster: usize) -> PyResult<Vec<usize>> {
+        if cluster >= self.clusters.len() {
+            return Err(PyIndexError::new_err("Cluster index out of bounds"));
+        }
+        let mut members = Vec::new();
+        let mut nodes = vec![cluster + self.observations];
+        while let Some(node) = nodes.pop() {
+            if node < self.observations {
+                members.push(node);
+            } else {
+                let (lhs, rhs, _, _) = self.clusters[node - self.observations];
+                nodes.push(lhs);
+                nodes.push(rhs);
+            }
+        }
+        members.sort_unstable();
+        Ok(members)
+    }
+
+    /// Assigns every ``HPOSet`` to a flat cluster
+    ///
+    /// This is equivalent to ``scipy.cluster.hierarchy.fcluster`` with the
+    /// ``distance`` or ``maxclust`` criterion. Exactly one of ``threshold``
+    /// or ``k`` must be provided.
+    ///
+    /// Parameters
+    /// ----------
+    /// threshold: float, optional
+    ///     The maximum distance between the ``HPOSet``\s of a cluster.
+    ///     All clusters whose distance is not higher are merged.
+    /// k: int, optional
+    ///     The number of clusters to form. Fewer clusters are formed if
+    ///     there are fewer ``HPOSet``\s.
+    ///
+    /// Returns
+    /// -------
+    /// list[int]
+    ///     The cluster label of each input ``HPOSet``. Labels start at ``1``
+    ///     and are numbered in the order of the first ``HPOSet`` of each cluster.
+    ///
+    /// Raises
+    /// ------
+    /// ValueError
+    ///     None or both of ``threshold`` and ``k`` are provided, or ``k`` is ``0``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     dendrogram = pyhpo.stats.linkage(sets)
+    ///     dendrogram.fcluster(threshold=0.5)
+    ///     # >> [1, 1, 1, 2, 1, 3, 1, 4, 1, 5]
+    ///     dendrogram.fcluster(k=2)
+    ///     # >> [1, 1, 1, 1, 1, 2, 1, 1, 1, 1]
+    ///
+    #[pyo3(signature = (threshold = None, k = None))]
+    #[pyo3(text_signature = "($self, threshold, k)")]
+    fn fcluster(&self, threshold: Option<f64>, k: Option<usize>) -> PyResult<Vec<usize>> {
+        // The highest distance within every cluster. It can be higher than the
+        // distance of the cluster itself for non-monotonic methods, e.g. `union`
+        let mut heights: Vec<f64> = Vec::with_capacity(self.clusters.len());
+        for (lhs, rhs, distance, _) in &self.clusters {
+            let height = |node: usize| {
+                node.checked_sub(self.observations)
+                    .map_or(f64::NEG_INFINITY, |row| heights[row])
+            };
+            heights.push(distance.max(height(*lhs)).max(height(*rhs)));
+        }
+
+        let merged: Vec<usize> = match (threshold, k) {
+            (Some(threshold), None) => (0..self.clusters.len())
+                .filter(|row| heights[*row] <= threshold)
+                .collect(),
+            (None, Some(0)) => return Err(PyValueError::new_err("k must be at least 1")),
+            (None, Some(k)) => {
+                // Sub-clusters are never higher than their parent and are
+                // formed before, so the stable sort keeps them in front
+                let mut rows: Vec<usize> = (0..self.clusters.len()).collect();
+                rows.sort_by(|a, b| heights[*a].total_cmp(&heights[*b]));
+                rows.truncate(self.observations.saturating_sub(k));
+                rows
+            }
+            _ => {
+                return Err(PyValueError::new_err(
+                    "Provide exactly one of threshold or k",
+                ))
+            }
+        };
+
+        // Every node points to the cluster that it is merged into. Clusters
+        // always have a higher index than their nodes, so the roots can be
+        // resolved in reverse order
+        let mut roots: Vec<usize> = (0..self.observations + self.clusters.len()).collect();
+        for row in merged {
+            let (lhs, rhs, _, _) = self.clusters[row];
+            roots[lhs] = row + self.observations;
+            roots[rhs] = row + self.observations;
+        }
+        for node in (0..roots.len()).rev() {
+            roots[node] = roots[roots[node]];
+        }
+
+        let mut label_of_root: HashMap<usize, usize> = HashMap::new();
+        let labels = roots[..self.observations]
+            .iter()
+            .map(|root| {
+                let next = label_of_root.len() + 1;
+                *label_of_root.entry(*root).or_insert(next)
+            })
+            .collect();
+        Ok(labels)
+    }
+
+    /// Returns the linkage matrix as ``numpy`` array
+    ///
+    /// Returns
+    /// -------
+    /// ``numpy.ndarray``
+    ///     A ``float64`` array with one ``(lhs, rhs, distance, size)`` row per cluster
+    ///
+    /// Raises
+    /// ------
+    /// ImportError
+    ///     ``numpy`` is not installed
+    ///
+    fn to_numpy(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let rows: Vec<[f64; 4]> = self
+            .clusters
+            .iter()
+            .map(|(lhs, rhs, distance, size)| [*lhs as f64, *rhs as f64, *distance, *size as f64])
+            .collect();
+        let numpy = py.import("numpy")?;
+        Ok(numpy
+            .getattr("array")?
+            .call1((rows, numpy.getattr("float64")?))?
+            .into())
+    }
+
+    #[pyo3(signature = (dtype = None, copy = None))]
+    fn __array__(
+        &self,
+        py: Python<'_>,
+        dtype: Option<PyObject>,
+        copy: Option<bool>,
+    ) -> PyResult<PyObject> {
+        // A new array is always created, so `copy` can be ignored
+        let _ = copy;
+        let array = self.to_numpy(py)?;
+        match dtype {
+            Some(dtype) => Ok(array.call_method1(py, "astype", (dtype,))?),
+            None => Ok(array),
+        }
+    }
+
+    fn __len__(&self) -> usize {
+        self.clusters.len()
+    }
+
+    fn __getitem__(&self, idx: usize) -> PyResult<Cluster> {
+        self.clusters
+            .get(idx)
+            .copied()
+            .ok_or_else(|| PyIndexError::new_err("Cluster index out of bounds"))
+    }
+
+    fn __iter__(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(self
+            .clusters
+            .clone()
+            .into_pyobject(py)?
+            .try_iter()?
+            .into_any()
+            .unbind())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Dendrogram ({} observations, {} clusters)>",
+            self.observations,
+            self.clusters.len()
+        )
+    }
 }
@@ -0,0 +1,661 @@
+//! Builds the Ontology from the JAX source files
+//!
+//! The `hpo` crate only supports loading the source files from a folder
+//! with fixed filenames. This module parses the contents of the files
+//! directly, so they can come from arbitrary paths, raw bytes
+//! or file-like objects.
+//!
+//! The terms and their hierarchy are still loaded by `Ontology::from_standard`,
+//! which preserves the HPO version, obsolete terms and the full term names.
+//! The parsed terms are written to a temporary folder in the OBO format,
+//! together with empty annotation files. The public builder methods
+//! (`insert_term` and `add_parent`) can neither set the version nor mark
+//! terms as obsolete or replaced. Genes and diseases are then linked with
+//! the builder methods of the Ontology.
+//!
+//! The parser is tested against the fixture files in `tests/data/jax`.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use pyo3::exceptions::{PyFileNotFoundError, PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyByteArray, PyBytes, PyString};
+
+use hpo::annotations::{AnnotationId, Disease, GeneId};
+use hpo::term::HpoGroup;
+use hpo::{HpoError, HpoResult, HpoTermId, Ontology};
+
+use crate::hpoa::{
+    parse_frequency, DiseaseAnnotations, HpoaRecord, EVIDENCE_COLUMN, FREQUENCY_COLUMN,
+};
+use crate::term_details::{quoted, TermDetails};
+
+/// Filename of the OBO file inside a data folder
+pub(crate) const OBO_FILENAME: &str = "hp.obo";
+/// Filename of the disease annotations inside a data folder
+pub(crate) const DISEASE_FILENAME: &str = "phenotype.hpoa";
+/// Filename of the direct gene annotations inside a data folder
+pub(crate) const GENE_TO_PHENO_FILENAME: &str = "genes_to_phenotype.txt";
+/// Filename of the transitive gene annotations inside a data folder
+pub(crate) const GENE_FILENAME: &str = "phenotype_to_genes.txt";
+
+/// A source file, provided as path, raw bytes or file-like object
+pub(crate) enum Source {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+    Reader(PyObject),
+}
+
+impl<'py> FromPyObject<'py> for Source {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(bytes) = ob.downcast::<PyBytes>() {
+            Ok(Source::Bytes(bytes.as_bytes().to_vec()))
+        } else if let Ok(bytes) = ob.downcast::<PyByteArray>() {
+            Ok(Source::Bytes(bytes.to_vec()))
+        } else if ob.hasattr("read")? {
+            Ok(Source::Reader(ob.clone().unbind()))
+        } else {
+            ob.extract::<PathBuf>().map(Source::Path).map_err(|_| {
+                PyTypeError::new_err("Source must be a path, bytes or a file-like object")
+            })
+        }
+    }
+}
+
+impl Source {
+    /// Returns `true` if the source is an empty path
+    pub fn is_empty_path(&self) -> bool {
+        matches!(self, Source::Path(path) if path.as_os_str().is_empty())
+    }
+
+    /// Returns the filename of the source or `default` if it has no name
+    pub fn filename(&self, py: Python<'_>, default: &str) -> String {
+        let path = match self {
+            Source::Path(path) => Some(path.clone()),
+            Source::Reader(reader) => reader
+                .getattr(py, "name")
+                .and_then(|name| name.extract::<PathBuf>(py))
+                .ok(),
+            Source::Bytes(_) => None,
+        };
+        path.and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| default.to_string())
+    }
+
+    /// Reads the full content of the source
+    ///
+    /// # Errors
+    ///
+    /// - PyFileNotFoundError: The file cannot be opened
+    /// - PyTypeError: The file-like object does not return `str` or `bytes`
+    pub fn read(&self, py: Python<'_>) -> PyResult<Vec<u8>> {
+        match self {
+            Source::Path(path) => fs::read(path).map_err(|_| {
+                PyFileNotFoundError::new_err(format!(
+                    "Unable to open {}. Please check if you specified the correct path and all files are present.",
+                    path.display()
+                ))
+            }),
+            Source::Bytes(bytes) => Ok(bytes.clone()),
+            Source::Reader(reader) => {
+                let content = reader.call_method0(py, "read")?.into_bound(py);
+                if let Ok(text) = content.downcast::<PyString>() {
+                    Ok(text.to_str()?.as_bytes().to_vec())
+                } else if let Ok(bytes) = content.downcast::<PyBytes>() {
+                    Ok(bytes.as_bytes().to_vec())
+                } else {
+                    Err(PyTypeError::new_err(
+                        "The file-like object must return `str` or `bytes`",
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Reads the full content of the source as UTF-8 text
+    ///
+    /// # Errors
+    ///
+    /// - PyFileNotFoundError: The file cannot be opened
+    /// - PyValueError: The content is not valid UTF-8
+    pub fn read_text(&self, py: Python<'_>) -> PyResult<String> {
+        String::from_utf8(self.read(py)?)
+            .map_err(|_| PyValueError::new_err("The source data must be UTF-8 encoded text"))
+    }
+}
+
+/// Selects which gene annotation files are used to link genes to terms
+///
+/// Genes are always linked to the annotated terms and all their ancestors.
+/// The mode determines the ``HPOTerm`` that are directly associated with
+/// each gene.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum GeneAnnotationMode {
+    /// Only the directly annotated terms from `genes_to_phenotype.txt`
+    Direct,
+    /// The transitively annotated terms from `phenotype_to_genes.txt`
+    Transitive,
+    /// The terms of both `genes_to_phenotype.txt` and `phenotype_to_genes.txt`
+    Union,
+}
+
+impl TryFrom<&str> for GeneAnnotationMode {
+    type Error = PyErr;
+    fn try_from(value: &str) -> PyResult<Self> {
+        match value {
+            "direct" => Ok(Self::Direct),
+            "transitive" => Ok(Self::Transitive),
+            "union" => Ok(Self::Union),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid gene_annotation_mode {value}. Use `direct`, `transitive` or `union`"
+            ))),
+        }
+    }
+}
+
+impl GeneAnnotationMode {
+    /// Returns whether each gene annotation file is in the
+    /// `phenotype_to_genes.txt` format
+    fn transitive_files(self) -> &'static [bool] {
+        match self {
+            Self::Direct => &[false],
+            Self::Transitive => &[true],
+            Self::Union => &[false, true],
+        }
+    }
+}
+
+/// The terms that are directly linked to genes in `genes_to_phenotype.txt`
+pub(crate) enum DirectGeneTerms {
+    /// The terms of every gene are the direct terms, because no
+    /// transitive annotations were loaded
+    Annotated,
+    /// The direct terms of every gene, because the terms of the genes
+    /// also contain transitive annotations
+    Recorded(HashMap<GeneId, HpoGroup>),
+    /// Only transitive annotations were loaded
+    Unavailable,
+}
+
+/// A source file of gene annotations
+pub(crate) struct GeneSource {
+    pub source: Source,
+    /// `source` is in the `phenotype_to_genes.txt` format
+    pub transitive: bool,
+}
+
+impl GeneSource {
+    /// The default filename of the gene annotations
+    pub fn default_filename(transitive: bool) -> &'static str {
+        if transitive {
+            GENE_FILENAME
+        } else {
+            GENE_TO_PHENO_FILENAME
+        }
+    }
+
+    /// Reads the content of the gene annotations
+    fn read_text(&self, py: Python<'_>) -> PyResult<String> {
+        self.source.read_text(py).map_err(|err| match &self.source {
+            Source::Path(path) if !self.transitive && path.ends_with(GENE_TO_PHENO_FILENAME) => {
+                PyFileNotFoundError::new_err("Starting with v1.2.0, hpo3 changed the way \
+                    how the ontology is build from JAX-OBO source. It now requires the `genes_to_phenotype.txt` \
+                    file. Please check the documentation for more info or add the `transitive=True` argument.")
+            }
+            _ => err,
+        })
+    }
+}
+
+/// All source files to build the Ontology
+pub(crate) struct JaxSources {
+    pub obo: Source,
+    pub hpoa: Source,
+    /// One or two gene annotation files, depending on the `GeneAnnotationMode`
+    pub genes: Vec<GeneSource>,
+    /// Whether to load gene and disease annotations, otherwise
+    /// `hpoa` and `genes` are ignored
+    pub annotations: bool,
+    /// Selects which disease annotations are loaded
+    pub filter: AnnotationFilter,
+}
+
+impl JaxSources {
+    /// Uses the default filenames inside `folder`
+    pub fn from_folder(folder: &Path, mode: GeneAnnotationMode) -> Self {
+        Self {
+            obo: Source::Path(folder.join(OBO_FILENAME)),
+            hpoa: Source::Path(folder.join(DISEASE_FILENAME)),
+            genes: mode
+                .transitive_files()
+                .iter()
+                .map(|&transitive| GeneSource {
+                    source: Source::Path(folder.join(GeneSource::default_filename(transitive))),
+                    transitive,
+                })
+                .collect(),
+            annotations: true,
+            filter: AnnotationFilter::default(),
+        }
+    }
+}
+
+/// The contents of all source files
+pub(crate) struct JaxContents {
+    pub obo: String,
+    pub hpoa: String,
+    /// The content of each gene annotation file and whether
+    /// it is in the `phenotype_to_genes.txt` format
+    pub genes: Vec<(String, bool)>,
+    pub annotations: bool,
+    pub filter: AnnotationFilter,
+}
+
+impl JaxContents {
+    /// Reads the contents of all sources
+    ///
+    /// The annotation sources are not read at all if
+    /// `sources.annotations` is `false`
+    pub fn read(py: Python<'_>, sources: &JaxSources) -> PyResult<Self> {
+        if !sources.annotations {
+            return Ok(Self {
+                obo: sources.obo.read_text(py)?,
+                hpoa: String::new(),
+                genes: Vec::new(),
+                annotations: false,
+                filter: sources.filter.clone(),
+            });
+        }
+        Ok(Self {
+            obo: sources.obo.read_text(py)?,
+            hpoa: sources.hpoa.read_text(py)?,
+            genes: sources
+                .genes
+                .iter()
+                .map(|genes| Ok((genes.read_text(py)?, genes.transitive)))
+                .collect::<PyResult<_>>()?,
+            annotations: true,
+            filter: sources.filter.clone(),
+        })
+    }
+
+    /// The total size of all contents in bytes
+    pub fn size(&self) -> usize {
+        self.obo.len()
+            + self.hpoa.len()
+            + self
+                .genes
+                .iter()
+                .map(|(genes, _)| genes.len())
+                .sum::<usize>()
+    }
+}
+
+/// Criteria to select which disease annotations of `phenotype.hpoa` are loaded
+#[derive(Clone, Default)]
+pub(crate) struct AnnotationFilter {
+    /// Annotations with a lower frequency are skipped.
+    /// Annotations without frequency are always loaded.
+    pub min_frequency: Option<f32>,
+    /// Only annotations with one of these evidence codes are loaded
+    pub evidence_codes: Option<Vec<String>>,
+}
+
+impl AnnotationFilter {
+    /// Returns `true` if the annotation with all columns `cols` is loaded
+    pub fn accepts(&self, cols: &[&str]) -> bool {
+        if let Some(codes) = &self.evidence_codes {
+            let evidence = cols.get(EVIDENCE_COLUMN).copied().unwrap_or_default();
+            if !codes.iter().any(|code| code == evidence) {
+                return false;
+            }
+        }
+        match (
+            self.min_frequency,
+            cols.get(FREQUENCY_COLUMN)
+                .and_then(|value| parse_frequency(value)),
+        ) {
+            (Some(min_frequency), Some(frequency)) => frequency >= min_frequency,
+            _ => true,
+        }
+    }
+}
+
+/// Converts errors during Ontology building into Python exceptions
+pub(crate) fn load_error(err: HpoError) -> PyErr {
+    PyRuntimeError::new_err(format!(
+        "Error loading the ontology. Please check if the data is correct: {err}"
+    ))
+}
+
+/// Builds the Ontology with all terms and annotations
+///
+/// `progress` is called after each build stage with the name of the stage
+/// and the number of processed items:
+///
+/// - `terms`: Number of terms
+/// - `genes`: Number of genes (skipped without annotations)
+/// - `diseases`: Number of Omim and Orpha diseases (skipped without annotations)
+/// - `information_content`: Number of terms
+pub(crate) fn build<F>(contents: &JaxContents, mut progress: F) -> PyResult<Build>
+where
+    F: FnMut(&str, usize) -> PyResult<()>,
+{
+    let (mut ont, term_details) = terms_from_obo(&contents.obo).map_err(load_error)?;
+    progress("terms", ont.len())?;
+
+    let mut details = DiseaseAnnotations::default();
+    let mut direct_gene_terms = DirectGeneTerms::Annotated;
+    if contents.annotations {
+        let mut direct = HashMap::new();
+        for (genes, transitive) in &contents.genes {
+            add_genes(&mut ont, genes, *transitive, &mut direct).map_err(load_error)?;
+        }
+        if contents.genes.iter().any(|(_, transitive)| *transitive) {
+            direct_gene_terms = if contents.genes.iter().any(|(_, transitive)| !transitive) {
+                DirectGeneTerms::Recorded(direct)
+            } else {
+                DirectGeneTerms::Unavailable
+            };
+        }
+        progress("genes", ont.genes().count())?;
+
+        add_diseases(&mut ont, &mut details, &contents.hpoa, &contents.filter)
+            .map_err(load_error)?;
+        progress(
+            "diseases",
+            ont.omim_diseases().count() + ont.orpha_diseases().count(),
+        )?;
+    }
+
+    ont.calculate_information_content().map_err(load_error)?;
+    progress("information_content", ont.len())?;
+    Ok(Build {
+        ontology: ont,
+        disease_annotations: details,
+        term_details,
+        direct_gene_terms,
+    })
+}
+
+/// The Ontology and all additional data from the JAX source files
+pub(crate) struct Build {
+    pub ontology: Ontology,
+    pub disease_annotations: DiseaseAnnotations,
+    pub term_details: HashMap<u32, TermDetails>,
+    pub direct_gene_terms: DirectGeneTerms,
+}
+
+/// A single `[Term]` stanza of the OBO file
+#[derive(Default)]
+struct OboTerm {
+    id: u32,
+    name: String,
+    obsolete: bool,
+    replaced_by: Option<u32>,
+    parents: Vec<u32>,
+    details: TermDetails,
+}
+
+/// Parses an `HP:0000001` formatted term id
+fn term_id(value: &str) -> HpoResult<u32> {
+    value
+        .trim()
+        .strip_prefix("HP:")
+        .and_then(|id| id.parse::<u32>().ok())
+        .ok_or_else(|| HpoError::InvalidInput(format!("Invalid HPO term id: {value}")))
+}
+
+/// Parses the `data-version` header, e.g. `hp/releases/2024-03-06`
+fn version_from_header(value: &str) -> Option<(u16, u8, u8)> {
+    let date = value.trim().rsplit('/').next()?;
+    if date.len() != 10 {
+        return None;
+    }
+    Some((
+        date[0..4].parse().ok()?,
+        date[5..7].parse().ok()?,
+        date[8..10].parse().ok()?,
+    ))
+}
+
+/// Parses all terms and the HPO version from the OBO file
+fn parse_obo(obo: &str) -> HpoResult<((u16, u8, u8), Vec<OboTerm>)> {
+    let mut version = (0u16, 0u8, 0u8);
+    let mut terms: Vec<OboTerm> = Vec::new();
+    let mut current: Option<OboTerm> = None;
+    let mut in_header = true;
+
+    for line in obo.lines() {
+        let line = line.trim_end();
+        if line.starts_with('[') {
+            in_header = false;
+            terms.extend(current.take());
+            if line == "[Term]" {
+                current = Some(OboTerm::default());
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(": ") else {
+            continue;
+        };
+        if in_header {
+            if key == "data-version" {
+                version = version_from_header(value).unwrap_or(version);
+            }
+            continue;
+        }
+        let Some(term) = current.as_mut() else {
+            continue;
+        };
+        match key {
+            "id" => term.id = term_id(value)?,
+            "name" => term.name = value.to_string(),
+            "is_obsolete" => term.obsolete = value == "true",
+            "replaced_by" => term.replaced_by = Some(term_id(value)?),
+            "def" => term.details.definition = quoted(value),
+            "synonym" => term.details.synonyms.extend(quoted(value)),
+            "comment" => term.details.comment = Some(value.to_string()),
+            "xref" => term.details.xrefs.push(value.to_string()),
+            "is_a" => {
+                let parent = value.split_once(' ').map_or(value, |(id, _)| id);
+                term.parents.push(term_id(parent)?);
+            }
+            _ => {}
+        }
+    }
+    terms.extend(current);
+    terms.retain(|term| term.id != 0 && !term.name.is_empty());
+    Ok((version, terms))
+}
+
+/// Returns the terms in the OBO format, as expected by `Ontology::from_standard`
+///
+/// Only the ID, name, obsolete flag, replacement and parents of each term
+/// are included
+fn terms_as_obo(version: (u16, u8, u8), terms: &[OboTerm]) -> String {
+    let mut obo = String::from("format-version: 1.2\n");
+    if version != (0, 0, 0) {
+        obo.push_str(&format!(
+            "data-version: hp/releases/{:04}-{:02}-{:02}\n",
+            version.0, version.1, version.2
+        ));
+    }
+    for term in terms {
+        obo.push_str(&format!(
+            "\n[Term]\nid: HP:{:07}\nname: {}\n",
+            term.id, term.name
+        ));
+        if term.obsolete {
+            obo.push_str("is_obsolete: true\n");
+        }
+        if let Some(replacement) = term.replaced_by {
+            obo.push_str(&format!("replaced_by: HP:{replacement:07}\n"));
+        }
+        for parent in &term.parents {
+            obo.push_str(&format!("is_a: HP:{parent:07} !\n"));
+        }
+    }
+    obo
+}
+
+/// Loads the terms with `Ontology::from_standard` from a temporary folder
+///
+/// The folder contains `obo` and empty annotation files, it is removed afterwards
+fn ontology_from_obo(obo: &str) -> HpoResult<Ontology> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.subsec_nanos());
+    let folder = std::env::temp_dir().join(format!("pyhpo-{}-{nanos}", std::process::id()));
+    let io_error = |err: std::io::Error| {
+        HpoError::InvalidInput(format!("Cannot write temporary OBO file: {err}"))
+    };
+
+    fs::create_dir(&folder).map_err(io_error)?;
+    let ont = [
+        (OBO_FILENAME, obo),
+        (GENE_TO_PHENO_FILENAME, "#header\n"),
+        (DISEASE_FILENAME, ""),
+    ]
+    .iter()
+    .try_for_each(|(filename, content)| fs::write(folder.join(filename), content))
+    .map_err(io_error)
+    .and_then(|()| {
+        let path = folder.to_str().ok_or_else(|| {
+            HpoError::InvalidInput("Temporary folder is not valid UTF-8".to_string())
+        })?;
+        Ontology::from_standard(path)
+    });
+    // The folder is only a temporary copy, so failures to remove it are ignored
+    let _ = fs::remove_dir_all(&folder);
+    ont
+}
+
+/// Builds an Ontology with only the terms and their hierarchy
+///
+/// Also returns the definition, synonyms, comment and xrefs of each term
+fn terms_from_obo(obo: &str) -> HpoResult<(Ontology, HashMap<u32, TermDetails>)> {
+    let (version, mut terms) = parse_obo(obo)?;
+    let mut ids: Vec<u32> = terms.iter().map(|term| term.id).collect();
+    ids.sort_unstable();
+    for term in &terms {
+        for parent in &term.parents {
+            if ids.binary_search(parent).is_err() {
+                return Err(HpoError::InvalidInput(format!(
+                    "Parent term HP:{parent:07} of HP:{:07} does not exist",
+                    term.id
+                )));
+            }
+        }
+    }
+    let ont = ontology_from_obo(&terms_as_obo(version, &terms))?;
+    let details = terms
+        .iter_mut()
+        .map(|term| (term.id, std::mem::take(&mut term.details)))
+        .collect();
+    Ok((ont, details))
+}
+
+/// Links genes to terms from `genes_to_phenotype.txt`
+/// or `phenotype_to_genes.txt` (`transitive`)
+///
+/// The terms of `genes_to_phenotype.txt` are also added to `direct`
+fn add_genes(
+    ont: &mut Ontology,
+    content: &str,
+    transitive: bool,
+    direct: &mut HashMap<GeneId, HpoGroup>,
+) -> HpoResult<()> {
+    let mut lines = content.lines();
+    match lines.next() {
+        Some(header)
+            if header.starts_with('#')
+                || header.starts_with("ncbi_gene_id")
+                || header.starts_with("hpo_id") => {}
+        _ => {
+            return Err(HpoError::InvalidInput(
+                "genes_to_phenotype.txt file must contain a header".to_string(),
+            ))
+        }
+    }
+
+    for line in lines.filter(|line| !line.trim().is_empty()) {
+        let cols: Vec<&str> = line.split('\t').collect();
+        let (ncbi_id, symbol, hpo) = if transitive {
+            match cols[..] {
+                [hpo, _, ncbi_id, symbol, ..] => (ncbi_id, symbol, hpo),
+                _ => return Err(HpoError::InvalidInput(line.to_string())),
+            }
+        } else {
+            match cols[..] {
+                [ncbi_id, symbol, hpo, ..] => (ncbi_id, symbol, hpo),
+                _ => return Err(HpoError::InvalidInput(line.to_string())),
+            }
+        };
+        let hpo = HpoTermId::from(term_id(hpo)?);
+        let gene_id = GeneId::try_from(ncbi_id)?;
+
+        ont.add_gene(symbol, gene_id);
+        ont.link_gene_term(hpo, gene_id)?;
+        ont.gene_mut(&gene_id)
+            .expect("Gene is present because it was just added")
+            .add_term(hpo);
+        if !transitive {
+            direct.entry(gene_id).or_default().insert(hpo);
+        }
+    }
+    Ok(())
+}
+
+/// Links Omim and Orpha diseases to terms from `phenotype.hpoa`
+///
+/// Annotations with a `NOT` qualifier, from other sources or
+/// not accepted by `filter` are skipped. The details of all loaded
+/// annotations are added to `details`.
+fn add_diseases(
+    ont: &mut Ontology,
+    details: &mut DiseaseAnnotations,
+    content: &str,
+    filter: &AnnotationFilter,
+) -> HpoResult<()> {
+    for line in content.lines() {
+        let is_omim = line.starts_with("OMIM");
+        if !is_omim && !line.starts_with("ORPHA") {
+            continue;
+        }
+        let cols: Vec<&str> = line.split('\t').collect();
+        let (id_col, name, qualifier, hpo) = match cols[..] {
+            [id_col, name, qualifier, hpo, ..] => (id_col, name, qualifier, hpo.trim()),
+            _ => return Err(HpoError::InvalidInput(line.to_string())),
+        };
+        if qualifier == "NOT" || !filter.accepts(&cols) {
+            continue;
+        }
+        let Some((_, disease_id)) = id_col.split_once(':') else {
+            return Err(HpoError::InvalidInput(line.to_string()));
+        };
+        let hpo = HpoTermId::from(term_id(hpo)?);
+        let record = HpoaRecord::new(hpo.as_u32(), &cols, ont);
+
+        if is_omim {
+            let id = ont.add_omim_disease(name, disease_id)?;
+            ont.link_omim_disease_term(hpo, id)?;
+            ont.omim_disease_mut(&id)
+                .ok_or(HpoError::DoesNotExist)?
+                .add_term(hpo);
+            details.add_omim(id.as_u32(), record);
+        } else {
+            let id = ont.add_orpha_disease(name, disease_id)?;
+            ont.link_orpha_disease_term(hpo, id)?;
+            ont.orpha_disease_mut(&id)
+                .ok_or(HpoError::DoesNotExist)?
+                .add_term(hpo);
+            details.add_orpha(id.as_u32(), record);
+        }
+    }
+    Ok(())
+}
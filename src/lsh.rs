@@ -0,0 +1,284 @@
+use std::collections::{HashMap, HashSet};
+
+use rayon::prelude::*;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::IntoPyObjectExt;
+
+use hpo::annotations::AnnotationId;
+use hpo::Ontology;
+
+use crate::cohort::PyCohort;
+use crate::get_ontology;
+use crate::ontology::ROOT;
+use crate::random::{mix, Rng};
+use crate::set::PyHpoSet;
+use crate::worker;
+
+/// Returns the key of every band of the MinHash signature of `set`
+///
+/// The signature is based on all terms of the set and their ancestors,
+/// except the root term. Empty sets have no signature.
+fn band_keys(ont: &Ontology, set: &PyHpoSet, seeds: &[u64], rows: usize) -> Option<Vec<u64>> {
+    let mut features = HashSet::new();
+    for term in &set.set(ont) {
+        features.insert(term.id().as_u32());
+        features.extend(term.all_parent_ids().iter().map(|id| id.as_u32()));
+    }
+    features.remove(&ROOT);
+    if features.is_empty() {
+        return None;
+    }
+    let signature: Vec<u64> = seeds
+        .iter()
+        .map(|seed| {
+            features
+                .iter()
+                .map(|feature| mix(u64::from(*feature) ^ seed))
+                .min()
+                .expect("features must not be empty")
+        })
+        .collect();
+    Some(
+        signature
+            .chunks(rows)
+            .map(|band| band.iter().fold(0, |key, value| mix(key ^ value)))
+            .collect(),
+    )
+}
+
+/// An approximate nearest-neighbor index of ``HPOSet``
+///
+/// The index uses locality-sensitive hashing (LSH) of MinHash signatures
+/// to find pairs of sets with many shared terms and ancestors, without
+/// comparing all pairs. It returns candidate pairs for an exact
+/// re-scoring, e.g. with :func:`pyhpo.helper.batch_set_similarity`.
+/// This makes searches in cohorts with millions of sets feasible.
+///
+/// Every set is represented by its terms and all their ancestors. The
+/// MinHash signature of ``num_perm`` hash values is split into ``bands``
+/// bands of ``num_perm / bands`` rows. Two sets are candidates if all
+/// rows of at least one band are identical. The probability is
+/// ``1 - (1 - s ** rows) ** bands`` for sets with a Jaccard
+/// similarity ``s`` of their ancestors. More bands increase the recall,
+/// but also the number of false positive candidates. ``threshold``
+/// approximates the Jaccard similarity at which half of the pairs
+/// become candidates.
+///
+/// Empty sets are never candidates.
+///
+/// Parameters
+/// ----------
+/// sets: list[:class:`pyhpo.HPOSet`] or :class:`pyhpo.Cohort`
+///     The sets of the index, referenced by their index
+/// num_perm: int, default: ``128``
+///     The number of hash values of each signature
+/// bands: int, default: ``32``
+///     The number of bands, must be a divisor of ``num_perm``
+/// seed: int, default: ``0``
+///     Seed of the hash functions
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+/// ValueError
+///     ``bands`` is ``0`` or not a divisor of ``num_perm``
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, helper
+///     Ontology()
+///
+///     sets = [disease.hpo_set() for disease in Ontology.omim_diseases]
+///     index = helper.MinHashIndex(sets, num_perm=128, bands=32)
+///     pairs = index.candidate_pairs(output="list")
+///     scores = helper.batch_set_similarity([(sets[a], sets[b]) for a, b in pairs])
+///
+#[pyclass(name = "MinHashIndex", module = "pyhpo.helper")]
+pub(crate) struct PyMinHashIndex {
+    seeds: Vec<u64>,
+    rows: usize,
+    /// The sets of every bucket, for each band
+    buckets: Vec<HashMap<u64, Vec<usize>>>,
+    len: usize,
+}
+
+#[pymethods]
+impl PyMinHashIndex {
+    #[new]
+    #[pyo3(signature = (sets, num_perm = 128, bands = 32, seed = 0))]
+    #[pyo3(text_signature = "(sets, num_perm, bands, seed)")]
+    fn new(
+        py: Python<'_>,
+        sets: &Bound<'_, PyAny>,
+        num_perm: usize,
+        bands: usize,
+        seed: u64,
+    ) -> PyResult<Self> {
+        if bands == 0 || !num_perm.is_multiple_of(bands) {
+            return Err(PyValueError::new_err(format!(
+                "bands must be a divisor of num_perm ({num_perm})"
+            )));
+        }
+        let ont = get_ontology()?;
+        let sets: Vec<PyHpoSet> = match sets.downcast::<PyCohort>() {
+            Ok(cohort) => cohort.borrow().sets().to_vec(),
+            Err(_) => sets.extract()?,
+        };
+        let mut rng = Rng::new(seed, 0);
+        let seeds: Vec<u64> = (0..num_perm).map(|_| rng.next_u64()).collect();
+        let rows = num_perm / bands;
+
+        let buckets = worker::install(py, || {
+            let keys: Vec<Option<Vec<u64>>> = sets
+                .par_iter()
+                .map(|set| band_keys(ont, set, &seeds, rows))
+                .collect();
+            (0..bands)
+                .into_par_iter()
+                .map(|band| {
+                    let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+                    for (idx, keys) in keys.iter().enumerate() {
+                        if let Some(keys) = keys {
+                            buckets.entry(keys[band]).or_default().push(idx);
+                        }
+                    }
+                    buckets
+                })
+                .collect()
+        });
+        Ok(Self {
+            seeds,
+            rows,
+            buckets,
+            len: sets.len(),
+        })
+    }
+
+    /// The approximate Jaccard similarity at which sets become candidates
+    ///
+    /// Calculated as ``(1 / bands) ** (1 / rows)``
+    #[getter(threshold)]
+    fn threshold(&self) -> f64 {
+        (1.0 / self.buckets.len() as f64).powf(1.0 / self.rows as f64)
+    }
+
+    /// Returns the candidate neighbors of ``query``
+    ///
+    /// Parameters
+    /// ----------
+    /// query: :class:`pyhpo.HPOSet`
+    ///     The set to search for. It does not have to be part of the index
+    ///
+    /// Returns
+    /// -------
+    /// list[int]
+    ///     The sorted indices of all sets that share a bucket with ``query``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    #[pyo3(text_signature = "($self, query)")]
+    fn candidates(&self, query: &PyHpoSet) -> PyResult<Vec<usize>> {
+        let ont = get_ontology()?;
+        let Some(keys) = band_keys(ont, query, &self.seeds, self.rows) else {
+            return Ok(Vec::new());
+        };
+        let mut candidates: Vec<usize> = keys
+            .iter()
+            .zip(&self.buckets)
+            .filter_map(|(key, buckets)| buckets.get(key))
+            .flatten()
+            .copied()
+            .collect::<HashSet<usize>>()
+            .into_iter()
+            .collect();
+        candidates.sort_unstable();
+        Ok(candidates)
+    }
+
+    /// Returns all pairs of sets that share a bucket
+    ///
+    /// Parameters
+    /// ----------
+    /// output: str, default ``numpy``
+    ///     The type of the pairs, ``numpy`` for an ``int64`` numpy array
+    ///     with two columns or ``list`` for a list of tuples
+    ///
+    /// Returns
+    /// -------
+    /// numpy.ndarray or list[tuple[int, int]]
+    ///     The indices of both sets of every candidate pair, sorted and
+    ///     with the lower index first
+    ///
+    /// Raises
+    /// ------
+    /// ValueError
+    ///     Invalid ``output``
+    /// ImportError
+    ///     ``numpy`` is not installed and ``output`` is ``numpy``
+    ///
+    #[pyo3(signature = (output = "numpy"))]
+    #[pyo3(text_signature = "($self, output)")]
+    fn candidate_pairs(&self, py: Python<'_>, output: &str) -> PyResult<PyObject> {
+        let numpy = match output {
+            "numpy" => true,
+            "list" => false,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid output {output}. Use `numpy` or `list`"
+                )))
+            }
+        };
+        let pairs: Vec<(usize, usize)> = worker::install(py, || {
+            let mut pairs: Vec<(usize, usize)> =
+                self.buckets
+                    .par_iter()
+                    .flat_map_iter(|buckets| {
+                        buckets.values().flat_map(|members| {
+                            members.iter().enumerate().flat_map(|(pos, a)| {
+                                members[pos + 1..].iter().map(move |b| (*a, *b))
+                            })
+                        })
+                    })
+                    .collect();
+            pairs.par_sort_unstable();
+            pairs.dedup();
+            pairs
+        });
+
+        if !numpy {
+            return pairs.into_py_any(py);
+        }
+        let numpy = py.import("numpy")?;
+        let pairs: Vec<[usize; 2]> = pairs.into_iter().map(|(a, b)| [a, b]).collect();
+        let array = numpy
+            .getattr("array")?
+            .call1((pairs, numpy.getattr("int64")?))?;
+        if array.getattr("ndim")?.extract::<usize>()? == 1 {
+            // `numpy` cannot infer the shape of an empty array
+            return Ok(array.call_method1("reshape", ((0, 2),))?.unbind());
+        }
+        Ok(array.unbind())
+    }
+
+    fn __len__(&self) -> usize {
+        self.len
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<MinHashIndex ({} sets, {} bands of {} rows)>",
+            self.len,
+            self.buckets.len(),
+            self.rows
+        )
+    }
+}
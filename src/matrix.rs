@@ -0,0 +1,332 @@
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+use pyo3::PyResult;
+
+use hpo::annotations::AnnotationId;
+use hpo::term::HpoGroup;
+
+use crate::get_ontology;
+use crate::set::PyHpoSet;
+use crate::term::PyHpoTerm;
+
+/// A sparse matrix in coordinate (COO) format
+///
+/// The matrix is stored as three parallel arrays ``row``, ``col``
+/// and ``data``, holding the row index, column index and value of
+/// each non-zero cell. It can be passed directly to
+/// ``scipy.sparse.coo_matrix`` or converted via :func:`to_scipy`.
+///
+/// ``index`` and ``columns`` hold the integer representation
+/// of the ``HPOTerm`` of each row and column, respectively.
+#[pyclass(name = "SparseMatrix")]
+pub(crate) struct PySparseMatrix {
+    index: Vec<u32>,
+    columns: Vec<u32>,
+    row: Vec<usize>,
+    col: Vec<usize>,
+    data: Vec<f32>,
+}
+
+impl PySparseMatrix {
+    /// Builds a square matrix where rows and columns are the same `ids`
+    ///
+    /// `cells` yields `(row_id, col_id)` pairs of the non-zero cells,
+    /// all with a value of `1.0`. IDs not present in `ids` are ignored.
+    pub(crate) fn square<I>(ids: Vec<u32>, cells: I) -> Self
+    where
+        I: IntoIterator<Item = (u32, u32)>,
+    {
+        let mut row = Vec::new();
+        let mut col = Vec::new();
+        for (row_id, col_id) in cells {
+            if let (Ok(r), Ok(c)) = (ids.binary_search(&row_id), ids.binary_search(&col_id)) {
+                row.push(r);
+                col.push(c);
+            }
+        }
+        let data = vec![1.0; row.len()];
+        Self {
+            columns: ids.clone(),
+            index: ids,
+            row,
+            col,
+            data,
+        }
+    }
+
+    /// Builds a binary matrix with one row per `HpoGroup` in `groups`
+    ///
+    /// A cell is `1.0` if the column ID is part of the row's group.
+    /// IDs not present in `columns` are ignored.
+    fn binary(index: Vec<u32>, columns: Vec<u32>, groups: &[HpoGroup]) -> Self {
+        let mut row = Vec::new();
+        let mut col = Vec::new();
+        for (r, group) in groups.iter().enumerate() {
+            for id in group {
+                if let Ok(c) = columns.binary_search(&id.as_u32()) {
+                    row.push(r);
+                    col.push(c);
+                }
+            }
+        }
+        let data = vec![1.0; row.len()];
+        Self {
+            index,
+            columns,
+            row,
+            col,
+            data,
+        }
+    }
+}
+
+#[pymethods]
+impl PySparseMatrix {
+    /// The row indices of all non-zero cells
+    #[getter(row)]
+    fn row(&self) -> Vec<usize> {
+        self.row.clone()
+    }
+
+    /// The column indices of all non-zero cells
+    #[getter(col)]
+    fn col(&self) -> Vec<usize> {
+        self.col.clone()
+    }
+
+    /// The values of all non-zero cells
+    #[getter(data)]
+    fn data(&self) -> Vec<f32> {
+        self.data.clone()
+    }
+
+    /// The integer IDs of the ``HPOTerm`` of each row
+    #[getter(index)]
+    fn index(&self) -> Vec<u32> {
+        self.index.clone()
+    }
+
+    /// The integer IDs of the ``HPOTerm`` of each column
+    #[getter(columns)]
+    fn columns(&self) -> Vec<u32> {
+        self.columns.clone()
+    }
+
+    /// The shape of the matrix as ``(rows, columns)``
+    #[getter(shape)]
+    fn shape(&self) -> (usize, usize) {
+        (self.index.len(), self.columns.len())
+    }
+
+    /// The number of non-zero cells
+    #[getter(nnz)]
+    fn nnz(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Converts the matrix into a ``scipy.sparse`` matrix
+    ///
+    /// .. important::
+    ///
+    ///     This method requires ``scipy`` to be installed
+    ///
+    /// Parameters
+    /// ----------
+    /// format: str, default: ``coo``
+    ///     The sparse format of the returned matrix. Possible options:
+    ///
+    ///     * **coo**
+    ///     * **csr**
+    ///     * **csc**
+    ///
+    /// Returns
+    /// -------
+    /// ``scipy.sparse.coo_matrix``, ``scipy.sparse.csr_matrix`` or ``scipy.sparse.csc_matrix``
+    ///     The sparse matrix
+    ///
+    /// Raises
+    /// ------
+    /// ImportError
+    ///     ``scipy`` is not installed
+    /// KeyError
+    ///     Invalid ``format``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     matrix = Ontology.ancestor_matrix().to_scipy("csr")
+    ///
+    #[pyo3(signature = (format = "coo"))]
+    #[pyo3(text_signature = "($self, format)")]
+    fn to_scipy(&self, py: Python<'_>, format: &str) -> PyResult<PyObject> {
+        if !["coo", "csr", "csc"].contains(&format) {
+            return Err(PyKeyError::new_err(format!(
+                "Unknown sparse matrix format {}",
+                format
+            )));
+        }
+        let sparse = py.import("scipy.sparse")?;
+        let coords = (self.row(), self.col());
+        let matrix = sparse
+            .getattr("coo_matrix")?
+            .call1(((self.data(), coords), self.shape()))?;
+        match format {
+            "coo" => Ok(matrix.into()),
+            _ => Ok(matrix
+                .call_method0(format!("to{}", format).as_str())?
+                .into()),
+        }
+    }
+
+    /// Returns the matrix as compressed sparse row (CSR) components
+    ///
+    /// The components can be passed to ``scipy.sparse.csr_matrix``
+    /// or to any other library that accepts the CSR format.
+    /// This method does not require ``scipy``.
+    ///
+    /// Returns
+    /// -------
+    /// tuple[list[float], list[int], list[int]]
+    ///     ``data``, ``indices`` and ``indptr`` of the CSR matrix.
+    ///     The column indices of each row are sorted ascending
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from scipy.sparse import csr_matrix
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     matrix = Ontology.ancestor_matrix()
+    ///     data, indices, indptr = matrix.to_csr()
+    ///     csr = csr_matrix((data, indices, indptr), shape=matrix.shape)
+    ///
+    fn to_csr(&self) -> (Vec<f32>, Vec<usize>, Vec<usize>) {
+        let mut cells: Vec<(usize, usize, f32)> = self
+            .row
+            .iter()
+            .zip(self.col.iter())
+            .zip(self.data.iter())
+            .map(|((r, c), d)| (*r, *c, *d))
+            .collect();
+        cells.sort_unstable_by_key(|a| (a.0, a.1));
+
+        let mut indptr = vec![0; self.index.len() + 1];
+        for (r, _, _) in &cells {
+            indptr[r + 1] += 1;
+        }
+        for i in 1..indptr.len() {
+            indptr[i] += indptr[i - 1];
+        }
+        let indices = cells.iter().map(|(_, c, _)| *c).collect();
+        let data = cells.iter().map(|(_, _, d)| *d).collect();
+        (data, indices, indptr)
+    }
+
+    fn __repr__(&self) -> String {
+        let (rows, cols) = self.shape();
+        format!(
+            "<SparseMatrix ({} x {}, {} non-zero)>",
+            rows,
+            cols,
+            self.nnz()
+        )
+    }
+}
+
+/// An ``HPOTerm`` or ``HPOSet`` that is embedded as one row
+#[derive(FromPyObject)]
+pub(crate) enum TermOrSet {
+    Term(PyHpoTerm),
+    Set(PyHpoSet),
+}
+
+/// Embeds terms or sets as binary vectors over all terms of the Ontology
+///
+/// Each item becomes one row of a sparse matrix. The columns represent
+/// all terms of the Ontology, sorted by their integer ID. A cell is ``1``
+/// if the column's term is an ancestor of the ``HPOTerm`` (or of any
+/// term of the ``HPOSet``) or the term itself.
+///
+/// This is the common featurization of phenotypes for machine learning
+/// models. Use :func:`pyhpo.types.SparseMatrix.to_csr` to retrieve the
+/// CSR components or :func:`pyhpo.types.SparseMatrix.to_scipy` to create
+/// a ``scipy.sparse`` matrix.
+///
+/// Parameters
+/// ----------
+/// items: list[:class:`pyhpo.HPOTerm` | :class:`pyhpo.HPOSet`]
+///     The terms or sets to embed, one row per item
+/// include_self: bool, default: ``True``
+///     Whether the terms themselves are set as well, in addition
+///     to their ancestors
+///
+/// Returns
+/// -------
+/// :class:`pyhpo.types.SparseMatrix`
+///     The embedding matrix. ``index`` holds the position of each
+///     item in ``items``, ``columns`` the integer IDs of all terms
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, HPOSet, helper
+///     Ontology()
+///
+///     patients = [
+///         HPOSet.from_queries(["HP:0002943", "HP:0008458"]),
+///         HPOSet.from_queries(["HP:0100884", "HP:0002944"]),
+///     ]
+///     embedding = helper.ancestor_embedding(patients)
+///     embedding.shape
+///     # >> (2, 18961)
+///
+///     data, indices, indptr = embedding.to_csr()
+///
+#[pyfunction]
+#[pyo3(signature = (items, include_self = true))]
+#[pyo3(text_signature = "(items, include_self)")]
+pub(crate) fn ancestor_embedding(
+    items: Vec<TermOrSet>,
+    include_self: bool,
+) -> PyResult<PySparseMatrix> {
+    let ont = get_ontology()?;
+    let mut columns: Vec<u32> = ont.into_iter().map(|term| term.id().as_u32()).collect();
+    columns.sort_unstable();
+
+    let groups: Vec<HpoGroup> = items
+        .iter()
+        .map(|item| {
+            let ids: HpoGroup = match item {
+                TermOrSet::Term(term) => std::iter::once(term.hpo_term_id()).collect(),
+                TermOrSet::Set(set) => set.set(ont).into_iter().map(|t| t.id()).collect(),
+            };
+            let mut group = HpoGroup::new();
+            for id in &ids {
+                if let Some(term) = ont.hpo(id) {
+                    group = &group | term.all_parent_ids();
+                    if include_self {
+                        group.insert(id);
+                    }
+                }
+            }
+            group
+        })
+        .collect();
+    let index = (0..groups.len() as u32).collect();
+    Ok(PySparseMatrix::binary(index, columns, &groups))
+}
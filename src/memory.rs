@@ -0,0 +1,125 @@
+//! Approximation of the memory used by the Ontology
+//!
+//! The sizes are estimated from the number of items and the memory layout
+//! of the `hpo` data structures. Allocator overhead and alignment padding
+//! are ignored, so the actual memory usage is slightly higher.
+use std::collections::HashSet;
+use std::mem::size_of;
+
+use hpo::annotations::{AnnotationId, Disease, GeneId};
+use hpo::term::{HpoGroup, InformationContent};
+use hpo::{HpoTermId, Ontology};
+
+use crate::hpoa::DISEASE_ANNOTATIONS;
+use crate::information_content::custom_ic_capacity;
+use crate::term_details::TERM_DETAILS;
+
+/// Number of term IDs that an `HpoGroup` stores without heap allocation
+const GROUP_INLINE_CAPACITY: usize = 30;
+
+/// Approximate memory usage of the Ontology in bytes, by category
+pub(crate) struct MemoryUsage {
+    /// Terms, their names and hierarchy
+    pub terms: usize,
+    /// Genes, diseases and their links to terms
+    pub annotations: usize,
+    /// Information content of all terms
+    pub information_content: usize,
+    /// Data that `hpo3` stores in addition to the `hpo` Ontology
+    pub extra: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.terms + self.annotations + self.information_content + self.extra
+    }
+}
+
+/// Returns the heap size of an `HpoGroup`
+fn group_bytes(group: &HpoGroup) -> usize {
+    if group.len() > GROUP_INLINE_CAPACITY {
+        group.len() * size_of::<HpoTermId>()
+    } else {
+        0
+    }
+}
+
+/// Returns the heap size of a hash set or map with the given capacity
+///
+/// Each bucket holds the entry and one control byte
+fn hashed_bytes<T>(capacity: usize) -> usize {
+    capacity * (size_of::<T>() + 1)
+}
+
+/// Returns the size of an annotation item, e.g. a gene or disease
+fn annotation_bytes<T>(name: &str, terms: &HpoGroup) -> usize {
+    size_of::<T>() + size_of::<String>() + size_of::<HpoGroup>() + name.len() + group_bytes(terms)
+}
+
+pub(crate) fn memory_usage(ont: &Ontology) -> MemoryUsage {
+    let term_size = size_of::<HpoTermId>()
+        + size_of::<String>()
+        + 3 * size_of::<HpoGroup>()
+        + 3 * size_of::<HashSet<GeneId>>()
+        + size_of::<bool>()
+        + size_of::<Option<HpoTermId>>();
+
+    let mut terms = 0;
+    let mut annotations = 0;
+    let mut max_id = 0;
+    for term in ont {
+        max_id = max_id.max(term.id().as_u32() as usize);
+        terms += term_size
+            + term.name().len()
+            + group_bytes(term.parent_ids())
+            + group_bytes(term.all_parent_ids())
+            + group_bytes(term.children_ids());
+        annotations += hashed_bytes::<u32>(term.gene_ids().capacity())
+            + hashed_bytes::<u32>(term.omim_disease_ids().capacity())
+            + hashed_bytes::<u32>(term.orpha_disease_ids().capacity());
+    }
+    // The terms are looked up by their ID via an index over all possible IDs
+    terms += (max_id + 1) * size_of::<usize>();
+
+    for gene in ont.genes() {
+        annotations += annotation_bytes::<u32>(gene.name(), gene.hpo_terms()) + size_of::<u32>();
+    }
+    for disease in ont.omim_diseases() {
+        annotations +=
+            annotation_bytes::<u32>(disease.name(), disease.hpo_terms()) + size_of::<u32>();
+    }
+    for disease in ont.orpha_diseases() {
+        annotations +=
+            annotation_bytes::<u32>(disease.name(), disease.hpo_terms()) + size_of::<u32>();
+    }
+
+    let information_content = ont.len() * size_of::<InformationContent>();
+
+    let mut extra = hashed_bytes::<(u32, f32)>(custom_ic_capacity());
+    if let Some(details) = TERM_DETAILS.get() {
+        extra += hashed_bytes::<(u32, crate::term_details::TermDetails)>(details.capacity());
+        extra += details
+            .values()
+            .map(|detail| {
+                detail.definition.as_ref().map_or(0, String::len)
+                    + detail.comment.as_ref().map_or(0, String::len)
+                    + detail
+                        .synonyms
+                        .iter()
+                        .chain(&detail.xrefs)
+                        .map(|text| size_of::<String>() + text.len())
+                        .sum::<usize>()
+            })
+            .sum::<usize>();
+    }
+    if let Some(records) = DISEASE_ANNOTATIONS.get() {
+        extra += records.memory_usage();
+    }
+
+    MemoryUsage {
+        terms,
+        annotations,
+        information_content,
+        extra,
+    }
+}
@@ -0,0 +1,104 @@
+use once_cell::sync::OnceCell;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::loader::AnnotationFilter;
+
+/// Metadata of the annotation source files, only set when the Ontology
+/// is built from the JAX source files
+pub(crate) static ANNOTATION_METADATA: OnceCell<Vec<FileMetadata>> = OnceCell::new();
+
+/// Metadata of a single annotation source file
+///
+/// Header lines have the format `#key: value`, e.g. `#date: 2024-03-06`
+/// or `#version: 2024-03-06`.
+pub(crate) struct FileMetadata {
+    filename: String,
+    version: Option<String>,
+    date: Option<String>,
+    records: usize,
+    skipped: usize,
+}
+
+impl FileMetadata {
+    /// Scans the content of the `phenotype.hpoa` file
+    ///
+    /// Only `OMIM` and `ORPHA` annotations without a `NOT` qualifier that are
+    /// accepted by `filter` are loaded into the Ontology, all other data lines
+    /// are counted as skipped
+    pub fn from_disease_file(filename: String, content: &str, filter: &AnnotationFilter) -> Self {
+        Self::scan(filename, content, |line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            let source = cols[0];
+            if source == "database_id" {
+                return None;
+            }
+            let qualifier = cols.get(2).copied().unwrap_or_default();
+            Some(
+                (source.starts_with("OMIM") || source.starts_with("ORPHA"))
+                    && qualifier != "NOT"
+                    && filter.accepts(&cols),
+            )
+        })
+    }
+
+    /// Scans the content of the `genes_to_phenotype.txt` or `phenotype_to_genes.txt` file
+    pub fn from_gene_file(filename: String, content: &str) -> Self {
+        Self::scan(filename, content, |line| {
+            if line.starts_with("ncbi_gene_id") || line.starts_with("hpo_id") {
+                None
+            } else {
+                Some(true)
+            }
+        })
+    }
+
+    /// Reads header and data lines of an annotation file
+    ///
+    /// `is_record` returns `None` for column header lines, otherwise whether
+    /// the line is loaded (`true`) or skipped (`false`)
+    fn scan<F: Fn(&str) -> Option<bool>>(filename: String, content: &str, is_record: F) -> Self {
+        let mut metadata = Self {
+            filename,
+            version: None,
+            date: None,
+            records: 0,
+            skipped: 0,
+        };
+        for line in content.lines() {
+            if let Some(header) = line.strip_prefix('#') {
+                if let Some((key, value)) = header.split_once(':') {
+                    let value = Some(value.trim().to_string());
+                    match key.trim() {
+                        "version" => metadata.version = value,
+                        "date" => metadata.date = value,
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            match is_record(line) {
+                Some(true) => metadata.records += 1,
+                Some(false) => metadata.skipped += 1,
+                None => {}
+            }
+        }
+        metadata
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn to_dict<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("version", &self.version)?;
+        dict.set_item("date", &self.date)?;
+        dict.set_item("records", self.records)?;
+        dict.set_item("skipped", self.skipped)?;
+        Ok(dict)
+    }
+}
@@ -0,0 +1,396 @@
+//! Mapping of MONDO diseases to Omim and Orpha diseases
+//!
+//! The mapping is loaded from the MONDO SSSOM cross-reference table
+//! (`mondo.sssom.tsv`), which is part of every MONDO release. Only
+//! `skos:exactMatch` mappings to `OMIM:` and `Orphanet:` are used.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use pyo3::class::basic::CompareOp;
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
+use pyo3::{prelude::*, types::PyType};
+
+use hpo::annotations::{AnnotationId, Disease, OmimDiseaseId, OrphaDiseaseId};
+use hpo::term::HpoGroup;
+
+use crate::annotations::{PyOmimDisease, PyOrphaDisease};
+use crate::loader::Source;
+use crate::set::PyHpoSet;
+use crate::{get_ontology, PyQuery};
+
+/// The default filename of the MONDO cross-reference table
+pub(crate) const MONDO_FILENAME: &str = "mondo.sssom.tsv";
+
+/// The loaded MONDO mapping, empty until `Ontology.load_mondo` is called
+static MONDO: Lazy<RwLock<MondoMapping>> = Lazy::new(Default::default);
+
+/// The Omim and Orpha diseases of a single MONDO disease
+#[derive(Clone, Default)]
+struct MondoEntry {
+    name: String,
+    omim: BTreeSet<u32>,
+    orpha: BTreeSet<u32>,
+}
+
+/// MONDO diseases and the reverse mapping of Omim and Orpha diseases
+#[derive(Default)]
+struct MondoMapping {
+    diseases: BTreeMap<u32, MondoEntry>,
+    omim: HashMap<u32, BTreeSet<u32>>,
+    orpha: HashMap<u32, BTreeSet<u32>>,
+}
+
+impl MondoMapping {
+    /// Parses the SSSOM table
+    ///
+    /// Lines starting with `#` contain the SSSOM metadata and are skipped.
+    /// The first other line is the header with the column names.
+    ///
+    /// # Errors
+    ///
+    /// - PyValueError: A required column is missing or a MONDO ID is invalid
+    fn parse(content: &str) -> PyResult<Self> {
+        let mut lines = content
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.trim().is_empty());
+        let header: Vec<&str> = lines
+            .next()
+            .ok_or_else(|| PyValueError::new_err("The MONDO mapping is empty"))?
+            .split('\t')
+            .map(str::trim)
+            .collect();
+        let column = |name: &str| {
+            header.iter().position(|col| *col == name).ok_or_else(|| {
+                PyValueError::new_err(format!("The MONDO mapping has no {} column", name))
+            })
+        };
+        let subject = column("subject_id")?;
+        let predicate = column("predicate_id")?;
+        let object = column("object_id")?;
+        let label = column("subject_label").ok();
+
+        let mut mapping = Self::default();
+        for line in lines {
+            let cols: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let value = |idx: usize| cols.get(idx).copied().unwrap_or_default();
+            if value(predicate) != "skos:exactMatch" {
+                continue;
+            }
+            let Some(mondo) = value(subject).strip_prefix("MONDO:") else {
+                continue;
+            };
+            let mondo: u32 = mondo
+                .parse()
+                .map_err(|_| PyValueError::new_err(format!("Invalid MONDO ID: {}", mondo)))?;
+
+            let entry = mapping.diseases.entry(mondo).or_default();
+            if let Some(name) = label.map(value).filter(|name| !name.is_empty()) {
+                entry.name = name.to_string();
+            }
+            let object = value(object);
+            if let Some(omim) = object.strip_prefix("OMIM:").and_then(|id| id.parse().ok()) {
+                entry.omim.insert(omim);
+                mapping.omim.entry(omim).or_default().insert(mondo);
+            } else if let Some(orpha) = object
+                .strip_prefix("Orphanet:")
+                .or_else(|| object.strip_prefix("ORPHA:"))
+                .and_then(|id| id.parse().ok())
+            {
+                entry.orpha.insert(orpha);
+                mapping.orpha.entry(orpha).or_default().insert(mondo);
+            }
+        }
+        mapping
+            .diseases
+            .retain(|_, entry| !entry.omim.is_empty() || !entry.orpha.is_empty());
+        Ok(mapping)
+    }
+}
+
+/// Loads the MONDO mapping from `source` and replaces a previously loaded mapping
+///
+/// Returns the number of MONDO diseases with at least one Omim or Orpha mapping
+pub(crate) fn load(py: Python<'_>, source: Source) -> PyResult<usize> {
+    let mapping = MondoMapping::parse(&source.read_text(py)?)?;
+    let count = mapping.diseases.len();
+    *MONDO.write().expect("MONDO lock must not be poisoned") = mapping;
+    Ok(count)
+}
+
+/// Returns the MONDO diseases that are mapped to the Omim disease
+pub(crate) fn from_omim(id: u32) -> Vec<PyMondoDisease> {
+    let mapping = MONDO.read().expect("MONDO lock must not be poisoned");
+    mapping.omim.get(&id).map_or_else(Vec::new, |ids| {
+        ids.iter()
+            .filter_map(|id| PyMondoDisease::from_mapping(&mapping, *id))
+            .collect()
+    })
+}
+
+/// Returns the MONDO diseases that are mapped to the Orpha disease
+pub(crate) fn from_orpha(id: u32) -> Vec<PyMondoDisease> {
+    let mapping = MONDO.read().expect("MONDO lock must not be poisoned");
+    mapping.orpha.get(&id).map_or_else(Vec::new, |ids| {
+        ids.iter()
+            .filter_map(|id| PyMondoDisease::from_mapping(&mapping, *id))
+            .collect()
+    })
+}
+
+/// A MONDO disease that aggregates equivalent Omim and Orpha diseases
+///
+/// MONDO diseases are only available after loading the MONDO
+/// cross-reference table via :func:`pyhpo.Ontology.load_mondo`.
+#[pyclass(name = "Mondo", module = "pyhpo")]
+#[derive(Clone)]
+pub(crate) struct PyMondoDisease {
+    id: u32,
+    name: String,
+    omim: Vec<u32>,
+    orpha: Vec<u32>,
+}
+
+impl PyMondoDisease {
+    fn from_mapping(mapping: &MondoMapping, id: u32) -> Option<Self> {
+        mapping.diseases.get(&id).map(|entry| Self {
+            id,
+            name: entry.name.clone(),
+            omim: entry.omim.iter().copied().collect(),
+            orpha: entry.orpha.iter().copied().collect(),
+        })
+    }
+
+    /// Returns the IDs of all terms of all linked Omim and Orpha diseases
+    ///
+    /// # Errors
+    ///
+    /// - PyNameError: Ontology not yet constructed
+    fn terms(&self) -> PyResult<HpoGroup> {
+        let ont = get_ontology()?;
+        let mut group = HpoGroup::new();
+        for disease in self
+            .omim
+            .iter()
+            .filter_map(|id| ont.omim_disease(&OmimDiseaseId::from(*id)))
+        {
+            group = &group | disease.hpo_terms();
+        }
+        for disease in self
+            .orpha
+            .iter()
+            .filter_map(|id| ont.orpha_disease(&OrphaDiseaseId::from(*id)))
+        {
+            group = &group | disease.hpo_terms();
+        }
+        Ok(group)
+    }
+}
+
+#[pymethods]
+impl PyMondoDisease {
+    /// Returns the MONDO-ID
+    ///
+    /// Returns
+    /// -------
+    /// int
+    ///     The integer part of the MONDO-ID, e.g. ``7739``
+    ///     for ``MONDO:0007739``
+    ///
+    #[getter(id)]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the name of the disease
+    ///
+    /// The name is empty if the mapping does not contain labels
+    #[getter(name)]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns all Omim diseases that are equivalent to the MONDO disease
+    ///
+    /// Only diseases that are present in the Ontology are returned
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.Omim`]
+    ///     The equivalent Omim diseases
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    #[getter(omim_diseases)]
+    fn omim_diseases(&self) -> PyResult<Vec<PyOmimDisease>> {
+        let ont = get_ontology()?;
+        Ok(self
+            .omim
+            .iter()
+            .filter_map(|id| ont.omim_disease(&OmimDiseaseId::from(*id)))
+            .map(PyOmimDisease::from)
+            .collect())
+    }
+
+    /// Returns all Orpha diseases that are equivalent to the MONDO disease
+    ///
+    /// Only diseases that are present in the Ontology are returned
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.Orpha`]
+    ///     The equivalent Orpha diseases
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    #[getter(orpha_diseases)]
+    fn orpha_diseases(&self) -> PyResult<Vec<PyOrphaDisease>> {
+        let ont = get_ontology()?;
+        Ok(self
+            .orpha
+            .iter()
+            .filter_map(|id| ont.orpha_disease(&OrphaDiseaseId::from(*id)))
+            .map(PyOrphaDisease::from)
+            .collect())
+    }
+
+    /// Returns the IDs of all ``HPOTerm`` of the equivalent diseases
+    ///
+    /// Returns
+    /// -------
+    /// set(int)
+    ///     The union of the HPO-IDs of all equivalent Omim
+    ///     and Orpha diseases
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    #[getter(hpo)]
+    fn hpo(&self) -> PyResult<HashSet<u32>> {
+        Ok(self.terms()?.iter().map(|id| id.as_u32()).collect())
+    }
+
+    /// Returns a ``HPOSet`` of all ``HPOTerm`` of the equivalent diseases
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOSet`
+    ///     The union of the terms of all equivalent Omim and Orpha diseases
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    fn hpo_set(&self) -> PyResult<PyHpoSet> {
+        Ok(self.terms()?.into_iter().collect())
+    }
+
+    /// Returns the MONDO disease based on the MONDO-ID
+    ///
+    /// Parameters
+    /// ----------
+    /// query: str or int
+    ///
+    ///     * **str** MONDO-ID (e.g.: ``MONDO:0007739`` or ``7739``)
+    ///     * **int** MONDO-ID (e.g.: ``7739``)
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.Mondo`
+    ///     A ``Mondo`` disease
+    ///
+    /// Raises
+    /// ------
+    /// KeyError
+    ///     No MONDO disease found for the query or
+    ///     the MONDO mapping is not loaded
+    /// ValueError
+    ///     The provided MONDO-ID cannot be converted to the correct
+    ///     integer representation
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Mondo
+    ///     Ontology()
+    ///     Ontology.load_mondo("/path/to/mondo.sssom.tsv")
+    ///
+    ///     disease = Mondo.get("MONDO:0007739")
+    ///     disease.omim_diseases
+    ///     # >> [<OmimDisease (143100)>]
+    ///     disease.orpha_diseases
+    ///     # >> [<OrphaDisease (399)>]
+    ///
+    #[classmethod]
+    fn get(_cls: &Bound<'_, PyType>, query: PyQuery) -> PyResult<PyMondoDisease> {
+        let id = match query {
+            PyQuery::Id(id) => id,
+            PyQuery::Str(query) => query
+                .strip_prefix("MONDO:")
+                .unwrap_or(&query)
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| PyValueError::new_err(format!("Invalid id: {}", query)))?,
+        };
+        let mapping = MONDO.read().expect("MONDO lock must not be poisoned");
+        PyMondoDisease::from_mapping(&mapping, id)
+            .ok_or(PyKeyError::new_err("'No disease found for query'"))
+    }
+
+    fn __str__(&self) -> String {
+        format!("MONDO:{:07} | {}", self.id, self.name)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<MondoDisease (MONDO:{:07})>", self.id)
+    }
+
+    fn __int__(&self) -> u32 {
+        self.id
+    }
+
+    fn __hash__(&self) -> u32 {
+        self.id
+    }
+
+    /// Raises
+    /// ------
+    /// TypeError
+    ///     Invalid comparison. Only == and != is supported
+    ///
+    fn __richcmp__(&self, other: &Self, op: CompareOp) -> PyResult<bool> {
+        match op {
+            CompareOp::Eq => Ok(self == other),
+            CompareOp::Ne => Ok(self != other),
+            _ => Err(PyTypeError::new_err(
+                "Only == and != is supported for Mondo instances",
+            )),
+        }
+    }
+}
+
+impl PartialEq for PyMondoDisease {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl Eq for PyMondoDisease {}
+
+impl Hash for PyMondoDisease {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_u32(self.id)
+    }
+}
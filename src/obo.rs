@@ -0,0 +1,105 @@
+//! Export of the Ontology in the OBO format
+//!
+//! The exported file contains one `[Term]` stanza per term with its name,
+//! hierarchy and obsolete status. Definitions, comments, synonyms and xrefs
+//! are only available if the Ontology was built from the JAX source files.
+//! The scope of synonyms is not stored and written as `EXACT`.
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+
+use hpo::annotations::AnnotationId;
+use hpo::{HpoTerm, Ontology};
+
+use crate::term_details::term_details;
+
+/// Returns `text` as quoted OBO string, escaping quotes and backslashes
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Returns `text` on a single line
+fn single_line(text: &str) -> String {
+    text.replace('\n', "\\n")
+}
+
+/// Appends the `[Term]` stanza of `term`
+fn write_term(obo: &mut String, term: &HpoTerm) {
+    let id = term.id().as_u32();
+    let details = term_details(id);
+
+    // writing to a `String` cannot fail
+    let _ = writeln!(obo, "[Term]");
+    let _ = writeln!(obo, "id: {}", term.id());
+    let _ = writeln!(obo, "name: {}", single_line(term.name()));
+    if let Some(definition) = details.and_then(|details| details.definition.as_ref()) {
+        let _ = writeln!(obo, "def: {} []", quote(definition));
+    }
+    if let Some(comment) = details.and_then(|details| details.comment.as_ref()) {
+        let _ = writeln!(obo, "comment: {}", single_line(comment));
+    }
+    for synonym in details.iter().flat_map(|details| &details.synonyms) {
+        let _ = writeln!(obo, "synonym: {} EXACT []", quote(synonym));
+    }
+    for xref in details.iter().flat_map(|details| &details.xrefs) {
+        let _ = writeln!(obo, "xref: {}", single_line(xref));
+    }
+    let mut parents: Vec<HpoTerm> = term.parents().collect();
+    parents.sort_by_key(|parent| parent.id());
+    for parent in parents {
+        let _ = writeln!(
+            obo,
+            "is_a: {} ! {}",
+            parent.id(),
+            single_line(parent.name())
+        );
+    }
+    if term.is_obsolete() {
+        let _ = writeln!(obo, "is_obsolete: true");
+    }
+    if let Some(replacement) = term.replaced_by() {
+        let _ = writeln!(obo, "replaced_by: {}", replacement.id());
+    }
+    obo.push('\n');
+}
+
+/// Returns the Ontology in the OBO format
+pub(crate) fn as_obo(ont: &Ontology) -> String {
+    let mut obo = String::new();
+    obo.push_str("format-version: 1.2\n");
+    let _ = writeln!(obo, "data-version: hp/releases/{}", ont.hpo_version());
+    obo.push_str("ontology: hp\n\n");
+
+    let mut terms: Vec<HpoTerm> = ont.iter().collect();
+    terms.sort_by_key(|term| term.id());
+    for term in &terms {
+        write_term(&mut obo, term);
+    }
+    obo
+}
+
+/// Writes the Ontology in the OBO format to `path`
+///
+/// # Errors
+///
+/// - PyOSError: The file cannot be written
+pub(crate) fn save(ont: &Ontology, path: &Path) -> PyResult<()> {
+    fs::write(path, as_obo(ont))
+        .map_err(|err| PyOSError::new_err(format!("Unable to write {}: {err}", path.display())))
+}
@@ -1,23 +1,209 @@
 use hpo::annotations::Disease;
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
 
-use hpo::HpoError;
-use pyo3::exceptions::PyFileNotFoundError;
+use once_cell::sync::OnceCell;
+use pyo3::exceptions::PyKeyError;
 use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::IntoPyObjectExt;
 use pyo3::PyResult;
 
 use hpo::annotations::AnnotationId;
+use hpo::term::HpoGroup;
+use hpo::HpoTerm;
 
 use crate::annotations::PyOmimDisease;
 use crate::annotations::PyOrphaDisease;
+use crate::binary;
+use crate::fhir;
 use crate::from_builtin;
-use crate::{from_binary, from_obo, get_ontology, pyterm_from_id, term_from_query, PyQuery};
+use crate::hpoa::EVIDENCE_CODES;
+use crate::loader::{GeneAnnotationMode, JaxSources, Source};
+use crate::matrix::PySparseMatrix;
+use crate::memory;
+use crate::metadata::ANNOTATION_METADATA;
+use crate::mondo::{self, MONDO_FILENAME};
+use crate::obo;
+use crate::output::{columns, TableOutput};
+use crate::owl;
+use crate::similarity::IcSource;
+use crate::term_details::{all_term_details, terms_by_xref};
+use crate::worker::{self, BuildArgs, BUILD_ARGS};
+use crate::{
+    from_binary_bytes, from_obo, get_ontology, id_from_query, pyterm_from_id, term_from_query,
+    term_from_term_or_id, PyQuery, TermOrId,
+};
 
 use crate::PyGene;
 use crate::PyHpoTerm;
 
-#[pyclass(name = "_Ontology")]
+/// Returns the integer IDs of all terms, sorted ascending
+fn sorted_term_ids(ont: &hpo::Ontology) -> Vec<u32> {
+    let mut ids: Vec<u32> = ont.into_iter().map(|term| term.id().as_u32()).collect();
+    ids.sort_unstable();
+    ids
+}
+
+/// Returns the common ancestors of all `terms`
+///
+/// The terms themselves are only included if `include_self` is set.
+/// Returns an empty group if `terms` is empty.
+pub(crate) fn common_ancestor_ids<'a, I>(terms: I, include_self: bool) -> HpoGroup
+where
+    I: IntoIterator<Item = HpoTerm<'a>>,
+{
+    let ancestors = |term: &HpoTerm| {
+        let mut group = term.all_parent_ids().clone();
+        if include_self {
+            group.insert(term.id());
+        }
+        group
+    };
+    let mut terms = terms.into_iter();
+    let Some(first) = terms.next() else {
+        return HpoGroup::new();
+    };
+    let mut common = ancestors(&first);
+    for term in terms {
+        if common.is_empty() {
+            break;
+        }
+        common = &common & &ancestors(&term);
+    }
+    common
+}
+
+/// Returns the term of `ids` with the highest information content
+///
+/// Ties are resolved by the depth of the terms, preferring the deeper
+/// term, and then by the lower ID.
+///
+/// # Errors
+///
+/// - PyNameError: Ontology not yet constructed
+/// - PyKeyError: Invalid `kind`
+pub(crate) fn most_informative(ids: &HpoGroup, kind: &str) -> PyResult<Option<PyHpoTerm>> {
+    let ont = get_ontology()?;
+    let source = IcSource::new(kind)?;
+    let depths = term_depths()?;
+    Ok(ids
+        .iter()
+        .filter_map(|id| ont.hpo(id))
+        .map(|term| {
+            let depth = depths.get(&term.id().as_u32()).copied().unwrap_or_default();
+            (source.ic(&term), depth, term)
+        })
+        .max_by(|a, b| {
+            a.0.total_cmp(&b.0)
+                .then(a.1.cmp(&b.1))
+                .then(b.2.id().cmp(&a.2.id()))
+        })
+        .map(|(_, _, term)| PyHpoTerm::from(term)))
+}
+
+/// The columns of [`PyOntology::to_table`]
+const TERM_TABLE_COLUMNS: [&str; 11] = [
+    "id", "name", "parents", "children", "obsolete", "ic_omim", "ic_orpha", "ic_gene", "n_omim",
+    "n_orpha", "n_genes",
+];
+
+/// Returns the row of `term` in [`PyOntology::to_table`]
+fn term_row<'py>(py: Python<'py>, term: &HpoTerm) -> PyResult<Bound<'py, PyDict>> {
+    let ids = |group: &HpoGroup| group.iter().map(|id| id.as_u32()).collect::<Vec<u32>>();
+    let ic = term.information_content();
+    let row = PyDict::new(py);
+    row.set_item("id", term.id().as_u32())?;
+    row.set_item("name", term.name())?;
+    row.set_item("parents", ids(term.parent_ids()))?;
+    row.set_item("children", ids(term.children_ids()))?;
+    row.set_item("obsolete", term.is_obsolete())?;
+    row.set_item("ic_omim", ic.omim_disease())?;
+    row.set_item("ic_orpha", ic.orpha_disease())?;
+    row.set_item("ic_gene", ic.gene())?;
+    row.set_item("n_omim", term.omim_disease_ids().len())?;
+    row.set_item("n_orpha", term.orpha_disease_ids().len())?;
+    row.set_item("n_genes", term.gene_ids().len())?;
+    Ok(row)
+}
+
+/// The root term of the Ontology, `HP:0000001`
+pub(crate) const ROOT: u32 = 1;
+
+/// The top-level branches of the Ontology, i.e. the children of the root term
+pub(crate) const BRANCHES: [(&str, u32); 7] = [
+    ("phenotypic_abnormality", 118),
+    ("mode_of_inheritance", 5),
+    ("clinical_modifier", 12823),
+    ("frequency", 40279),
+    ("past_medical_history", 32443),
+    ("blood_group", 32223),
+    ("biospecimen_phenotypic_feature", 20228),
+];
+
+/// Returns the term ID of a top-level branch
+///
+/// # Panics
+///
+/// Panics if `name` is not a known branch
+fn branch_id(name: &str) -> u32 {
+    branch_from_name(name).expect("branch must be defined")
+}
+
+/// The depth of each term, i.e. the length of its shortest path to the root
+static TERM_DEPTHS: OnceCell<HashMap<u32, usize>> = OnceCell::new();
+
+/// Returns the depth of all terms, the root term has a depth of `0`
+///
+/// The depths are calculated once with a breadth-first search from the root
+///
+/// # Errors
+///
+/// - PyNameError: Ontology not yet constructed
+pub(crate) fn term_depths() -> PyResult<&'static HashMap<u32, usize>> {
+    let ont = get_ontology()?;
+    Ok(TERM_DEPTHS.get_or_init(|| {
+        let mut depths = HashMap::with_capacity(ont.len());
+        let mut queue = VecDeque::from([(ROOT, 0)]);
+        while let Some((id, depth)) = queue.pop_front() {
+            if depths.contains_key(&id) {
+                continue;
+            }
+            depths.insert(id, depth);
+            if let Some(term) = ont.hpo(id) {
+                queue.extend(
+                    term.children_ids()
+                        .iter()
+                        .map(|child| (child.as_u32(), depth + 1)),
+                );
+            }
+        }
+        depths
+    }))
+}
+
+/// Returns the term ID of a top-level branch, e.g. ``mode_of_inheritance``
+///
+/// # Errors
+///
+/// - PyKeyError: `name` is not a known branch
+pub(crate) fn branch_from_name(name: &str) -> PyResult<u32> {
+    BRANCHES
+        .iter()
+        .find(|(branch, _)| *branch == name)
+        .map(|(_, id)| *id)
+        .ok_or_else(|| {
+            let names: Vec<&str> = BRANCHES.iter().map(|(branch, _)| *branch).collect();
+            PyKeyError::new_err(format!(
+                "Unknown branch {name}. Valid branches are {}",
+                names.join(", ")
+            ))
+        })
+}
+
+#[pyclass(name = "_Ontology", module = "pyhpo")]
 pub struct PyOntology {}
 
 impl PyOntology {
@@ -28,7 +214,7 @@ impl PyOntology {
 
 #[pymethods]
 impl PyOntology {
-    /// A list of all genes included in the ontology
+    /// A list of all genes included in the ontology, sorted by ID
     ///
     /// Returns
     /// -------
@@ -53,10 +239,11 @@ impl PyOntology {
         for gene in ont.genes() {
             res.push(PyGene::new(*gene.id(), gene.name().into()))
         }
+        res.sort_unstable_by_key(|gene| gene.id());
         Ok(res)
     }
 
-    /// A list of all Omim Diseases included in the ontology
+    /// A list of all Omim Diseases included in the ontology, sorted by ID
     ///
     /// Returns
     /// -------
@@ -81,10 +268,11 @@ impl PyOntology {
         for disease in ont.omim_diseases() {
             res.push(PyOmimDisease::new(*disease.id(), disease.name().into()))
         }
+        res.sort_unstable_by_key(|disease| disease.id());
         Ok(res)
     }
 
-    /// A list of all Orpha Diseases included in the ontology
+    /// A list of all Orpha Diseases included in the ontology, sorted by ID
     ///
     /// Returns
     /// -------
@@ -109,9 +297,222 @@ impl PyOntology {
         for disease in ont.orpha_diseases() {
             res.push(PyOrphaDisease::new(*disease.id(), disease.name().into()))
         }
+        res.sort_unstable_by_key(|disease| disease.id());
         Ok(res)
     }
 
+    /// A filtered and sorted list of genes
+    ///
+    /// Parameters
+    /// ----------
+    /// sorted_by: str, optional
+    ///     Sort the genes by
+    ///
+    ///     * **id** - The HGNC ID of the gene
+    ///     * **name** - The gene symbol
+    ///     * **terms** - The number of associated ``HPOTerm`` (descending)
+    ///
+    ///     The genes are sorted by ID by default
+    /// having_term: :class:`pyhpo.HPOTerm` or int, optional
+    ///     Only include genes that are associated to this term, either
+    ///     directly or via one of its children
+    /// min_terms: int, default: ``0``
+    ///     Only include genes that are associated to at least
+    ///     ``min_terms`` ``HPOTerm``
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.Gene`]
+    ///     All genes matching the criteria
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``sorted_by`` value or ``having_term`` does not exist
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     genes = Ontology.filter_genes(sorted_by="name", having_term=2650)
+    ///     genes[2]
+    ///     # >> <Gene (ABCB7)>
+    ///
+    #[pyo3(signature = (sorted_by = None, having_term = None, min_terms = 0))]
+    #[pyo3(text_signature = "($self, sorted_by, having_term, min_terms)")]
+    fn filter_genes(
+        &self,
+        sorted_by: Option<&str>,
+        having_term: Option<TermOrId>,
+        min_terms: usize,
+    ) -> PyResult<Vec<PyGene>> {
+        let ont = get_ontology()?;
+        let term = having_term.map(term_from_term_or_id).transpose()?;
+
+        let genes = ont
+            .genes()
+            .filter(|gene| term.is_none_or(|term| term.gene_ids().contains(gene.id())));
+        let res = filter_annotations(
+            genes.map(|gene| (gene.id().as_u32(), gene.name(), gene.hpo_terms().len())),
+            sorted_by,
+            min_terms,
+        )?;
+        Ok(res
+            .into_iter()
+            .map(|(id, name)| PyGene::new(id.into(), name.into()))
+            .collect())
+    }
+
+    /// A filtered and sorted list of Omim diseases
+    ///
+    /// Parameters
+    /// ----------
+    /// sorted_by: str, optional
+    ///     Sort the diseases by
+    ///
+    ///     * **id** - The Omim ID of the disease
+    ///     * **name** - The name of the disease
+    ///     * **terms** - The number of associated ``HPOTerm`` (descending)
+    ///
+    ///     The diseases are sorted by ID by default
+    /// having_term: :class:`pyhpo.HPOTerm` or int, optional
+    ///     Only include diseases that are associated to this term, either
+    ///     directly or via one of its children
+    /// min_terms: int, default: ``0``
+    ///     Only include diseases that are associated to at least
+    ///     ``min_terms`` ``HPOTerm``
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.Omim`]
+    ///     All Omim diseases matching the criteria
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``sorted_by`` value or ``having_term`` does not exist
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     diseases = Ontology.filter_omim_diseases(sorted_by="terms", min_terms=5)
+    ///
+    #[pyo3(signature = (sorted_by = None, having_term = None, min_terms = 0))]
+    #[pyo3(text_signature = "($self, sorted_by, having_term, min_terms)")]
+    fn filter_omim_diseases(
+        &self,
+        sorted_by: Option<&str>,
+        having_term: Option<TermOrId>,
+        min_terms: usize,
+    ) -> PyResult<Vec<PyOmimDisease>> {
+        let ont = get_ontology()?;
+        let term = having_term.map(term_from_term_or_id).transpose()?;
+
+        let diseases = ont.omim_diseases().filter(|disease| {
+            term.is_none_or(|term| term.omim_disease_ids().contains(disease.id()))
+        });
+        let res = filter_annotations(
+            diseases.map(|disease| {
+                (
+                    disease.id().as_u32(),
+                    disease.name(),
+                    disease.hpo_terms().len(),
+                )
+            }),
+            sorted_by,
+            min_terms,
+        )?;
+        Ok(res
+            .into_iter()
+            .map(|(id, name)| PyOmimDisease::new(id.into(), name.into()))
+            .collect())
+    }
+
+    /// A filtered and sorted list of Orpha diseases
+    ///
+    /// Parameters
+    /// ----------
+    /// sorted_by: str, optional
+    ///     Sort the diseases by
+    ///
+    ///     * **id** - The Orpha ID of the disease
+    ///     * **name** - The name of the disease
+    ///     * **terms** - The number of associated ``HPOTerm`` (descending)
+    ///
+    ///     The diseases are sorted by ID by default
+    /// having_term: :class:`pyhpo.HPOTerm` or int, optional
+    ///     Only include diseases that are associated to this term, either
+    ///     directly or via one of its children
+    /// min_terms: int, default: ``0``
+    ///     Only include diseases that are associated to at least
+    ///     ``min_terms`` ``HPOTerm``
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.Orpha`]
+    ///     All Orpha diseases matching the criteria
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``sorted_by`` value or ``having_term`` does not exist
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     diseases = Ontology.filter_orpha_diseases(sorted_by="name", having_term=2650)
+    ///
+    #[pyo3(signature = (sorted_by = None, having_term = None, min_terms = 0))]
+    #[pyo3(text_signature = "($self, sorted_by, having_term, min_terms)")]
+    fn filter_orpha_diseases(
+        &self,
+        sorted_by: Option<&str>,
+        having_term: Option<TermOrId>,
+        min_terms: usize,
+    ) -> PyResult<Vec<PyOrphaDisease>> {
+        let ont = get_ontology()?;
+        let term = having_term.map(term_from_term_or_id).transpose()?;
+
+        let diseases = ont.orpha_diseases().filter(|disease| {
+            term.is_none_or(|term| term.orpha_disease_ids().contains(disease.id()))
+        });
+        let res = filter_annotations(
+            diseases.map(|disease| {
+                (
+                    disease.id().as_u32(),
+                    disease.name(),
+                    disease.hpo_terms().len(),
+                )
+            }),
+            sorted_by,
+            min_terms,
+        )?;
+        Ok(res
+            .into_iter()
+            .map(|(id, name)| PyOrphaDisease::new(id.into(), name.into()))
+            .collect())
+    }
+
     /// Returns a single `HPOTerm` based on its name or id
     ///
     /// Parameters
@@ -223,6 +624,9 @@ impl PyOntology {
     /// query2: str or int
     ///     Name, HPO-ID (HP:0040064) or integer ID of target term
     ///     e.g: ``Abnormality of the nervous system``
+    /// replace_obsolete: bool, default: ``False``
+    ///     Replace obsolete terms by their replacement term before
+    ///     searching the path, see :func:`pyhpo.HPOTerm.path_to_other`
     ///
     /// Returns
     /// -------
@@ -242,7 +646,8 @@ impl PyOntology {
     /// NameError
     ///     Ontology not yet constructed
     /// RuntimeError
-    ///     No HPO term is found for the provided query
+    ///     No HPO term is found for the provided query, no path found,
+    ///     or an obsolete term has no replacement
     /// TypeError
     ///     The provided query is an unsupported type and can't be properly
     ///     converted
@@ -272,23 +677,35 @@ impl PyOntology {
     ///     # >>     0
     ///     # >> )
     ///
-    #[pyo3(text_signature = "($self, query1, query2)")]
+    #[pyo3(signature = (query1, query2, replace_obsolete = false))]
+    #[pyo3(text_signature = "($self, query1, query2, replace_obsolete)")]
     fn path(
         &self,
         query1: PyQuery,
         query2: PyQuery,
+        replace_obsolete: bool,
     ) -> PyResult<(usize, Vec<PyHpoTerm>, usize, usize)> {
         let t1: PyHpoTerm = term_from_query(query1)?.into();
         let t2: PyHpoTerm = term_from_query(query2)?.into();
-        t1.path_to_other(&t2)
+        t1.path_to_other(&t2, replace_obsolete)
     }
 
-    /// Returns a list of HPOTerms that match the query
+    /// Returns a list of HPOTerms that match the query, sorted by ID
     ///
     /// Parameters
     /// ----------
     /// query: str
     ///     Query for substring search of HPOTerms
+    /// fields: list[str], optional
+    ///     The text fields of the terms to search. Defaults to ``["name"]``.
+    ///     Available fields:
+    ///
+    ///     * **name** - The name of the term
+    ///     * **synonym** - All synonyms of the term
+    ///     * **definition** - The definition of the term
+    ///
+    ///     Synonyms and definitions are only available if the Ontology
+    ///     was built from the JAX source files
     ///
     /// Returns
     /// -------
@@ -305,6 +722,10 @@ impl PyOntology {
     /// ------
     /// NameError
     ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid field
+    /// RuntimeError
+    ///     ``synonym`` or ``definition`` field without the JAX source files
     ///
     /// Examples
     /// --------
@@ -324,37 +745,79 @@ impl PyOntology {
     ///     # >> HP:0012625 | Stage 3 chronic kidney disease
     ///     # >> HP:0012626 | Stage 4 chronic kidney disease
     ///
-    #[pyo3(text_signature = "($self, query)")]
-    fn search(&self, query: &str) -> PyResult<Vec<PyHpoTerm>> {
-        let mut res = Vec::new();
+    ///     # include synonyms and definitions
+    ///     Ontology.search("fits", fields=["name", "synonym", "definition"])
+    ///
+    #[pyo3(signature = (query, fields = None))]
+    #[pyo3(text_signature = "($self, query, fields)")]
+    fn search(&self, query: &str, fields: Option<Vec<String>>) -> PyResult<Vec<PyHpoTerm>> {
+        let mut name = fields.is_none();
+        let mut synonym = false;
+        let mut definition = false;
+        for field in fields.iter().flatten() {
+            match field.as_str() {
+                "name" => name = true,
+                "synonym" => synonym = true,
+                "definition" => definition = true,
+                _ => {
+                    return Err(PyKeyError::new_err(format!(
+                        "Invalid search field {field}. Use `name`, `synonym` or `definition`"
+                    )))
+                }
+            }
+        }
+
         let ont = get_ontology()?;
+        let details = if synonym || definition {
+            Some(all_term_details()?)
+        } else {
+            None
+        };
+        let mut res = Vec::new();
         for term in ont {
-            if term.name().contains(query) {
+            let details = details.and_then(|details| details.get(&term.id().as_u32()));
+            let matches = (name && term.name().contains(query))
+                || (synonym
+                    && details.is_some_and(|details| {
+                        details.synonyms.iter().any(|text| text.contains(query))
+                    }))
+                || (definition
+                    && details
+                        .and_then(|details| details.definition.as_ref())
+                        .is_some_and(|text| text.contains(query)));
+            if matches {
                 res.push(PyHpoTerm::from(term))
             }
         }
-
+        res.sort_unstable_by_key(|term| term.hpo_term_id());
         Ok(res)
     }
 
-    /// Returns the HpoTerm with the provided `id`
+    /// Returns the common ancestors of any number of ``HPOTerm``
+    ///
+    /// This is the intersection of the ancestors of all terms and
+    /// the same as :func:`pyhpo.HPOTerm.common_ancestors` for two terms.
     ///
     /// Parameters
     /// ----------
-    /// id: int
-    ///     ID of the term as int (``HP:0000123`` --> ``123``)
+    /// terms: list[:class:`pyhpo.HPOTerm` or int]
+    ///     The terms or their integer IDs
+    /// include_self: bool, default: ``False``
+    ///     Whether each term is considered its own ancestor, i.e. whether
+    ///     a term can be a common ancestor of itself and its descendants
     ///
     /// Returns
     /// -------
-    /// :class:`pyhpo.HPOTerm`
-    ///     The HPO-Term
+    /// set[:class:`pyhpo.HPOTerm`]
+    ///     All terms that are ancestors of all ``terms``.
+    ///     Empty if ``terms`` is empty
     ///
     /// Raises
     /// ------
     /// NameError
     ///     Ontology not yet constructed
     /// KeyError
-    ///     No HPO term is found for the provided query
+    ///     No HPO term is found for a provided ID
     ///
     /// Examples
     /// --------
@@ -362,30 +825,64 @@ impl PyOntology {
     /// .. code-block:: python
     ///
     ///     from pyhpo import Ontology
-    ///     
     ///     Ontology()
-    ///     
-    ///     term = Ontology.hpo(11968)
-    ///     term.name()  # >> 'Feeding difficulties'
-    ///     term.id()    # >> 'HP:0011968'
-    ///     int(tern)    # >> 11968
     ///
-    #[pyo3(text_signature = "($self, id)")]
-    fn hpo(&self, id: u32) -> PyResult<PyHpoTerm> {
-        pyterm_from_id(id)
+    ///     Ontology.common_ancestors([2650, 9121, 2808])
+    ///     # >> {<HpoTerm (HP:0000001)>, <HpoTerm (HP:0011842)>,
+    ///     # >> <HpoTerm (HP:0033127)>, <HpoTerm (HP:0000118)>,
+    ///     # >> <HpoTerm (HP:0000924)>}
+    ///
+    #[pyo3(signature = (terms, include_self = false))]
+    #[pyo3(text_signature = "($self, terms, include_self)")]
+    fn common_ancestors(
+        &self,
+        terms: Vec<TermOrId>,
+        include_self: bool,
+    ) -> PyResult<HashSet<PyHpoTerm>> {
+        let terms = terms
+            .into_iter()
+            .map(term_from_term_or_id)
+            .collect::<PyResult<Vec<_>>>()?;
+        common_ancestor_ids(terms, include_self)
+            .iter()
+            .map(|id| pyterm_from_id(id.as_u32()))
+            .collect()
     }
 
-    /// Returns the HPO version
+    /// Returns the most informative common ancestor of any number of ``HPOTerm``
+    ///
+    /// The common ancestor with the highest information content. If several
+    /// ancestors have the same information content, the deepest of them is
+    /// returned.
+    ///
+    /// Parameters
+    /// ----------
+    /// terms: list[:class:`pyhpo.HPOTerm` or int]
+    ///     The terms or their integer IDs
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use. Possible options:
+    ///
+    ///     * **omim**
+    ///     * **orpha**
+    ///     * **gene**
+    ///     * **custom** or any other named custom information content
+    ///
+    /// include_self: bool, default: ``False``
+    ///     Whether each term is considered its own ancestor,
+    ///     see :func:`pyhpo.Ontology.common_ancestors`
     ///
     /// Returns
     /// -------
-    /// str
-    ///     The HPO version, e.g. ``2023-04-05``
+    /// :class:`pyhpo.HPOTerm` or None
+    ///     The most informative common ancestor. ``None`` if
+    ///     the terms do not have any common ancestor
     ///
     /// Raises
     /// ------
     /// NameError
     ///     Ontology not yet constructed
+    /// KeyError
+    ///     No HPO term is found for a provided ID or invalid ``kind``
     ///
     /// Examples
     /// --------
@@ -393,77 +890,53 @@ impl PyOntology {
     /// .. code-block:: python
     ///
     ///     from pyhpo import Ontology
-    ///     
     ///     Ontology()
-    ///     
-    ///     Ontology.version()
-    ///     # >> "2023-04-05"
     ///
-    fn version(&self) -> PyResult<String> {
-        Ok(get_ontology()?.hpo_version())
+    ///     Ontology.most_informative_common_ancestor([2650, 9121, 2808])
+    ///     # >> <HpoTerm (HP:0011842)>
+    ///
+    #[pyo3(signature = (terms, kind = "omim", include_self = false))]
+    #[pyo3(text_signature = "($self, terms, kind, include_self)")]
+    fn most_informative_common_ancestor(
+        &self,
+        terms: Vec<TermOrId>,
+        kind: &str,
+        include_self: bool,
+    ) -> PyResult<Option<PyHpoTerm>> {
+        let terms = terms
+            .into_iter()
+            .map(term_from_term_or_id)
+            .collect::<PyResult<Vec<_>>>()?;
+        most_informative(&common_ancestor_ids(terms, include_self), kind)
     }
 
-    /// Constructs the ontology based on provided ontology files
+    /// Returns all ``HPOTerm`` with the provided cross reference
     ///
-    /// The ontology files can be in the standard format as provided
-    /// by Jax or as a binary file as generated by `hpo`
+    /// This allows to map codes of other terminologies, such as UMLS
+    /// or SNOMED CT, to HPO terms. Several terms can share the same
+    /// cross reference.
+    ///
+    /// .. note::
+    ///
+    ///     Cross references are only available if the Ontology was built
+    ///     from the JAX source files.
     ///
     /// Parameters
     /// ----------
-    /// data_folder: str
-    ///     Path to the source files (default: `./ontology.hpo`)
-    /// binary: bool
-    ///     Whether the input format is binary (default true)
-    /// transitive: bool
-    ///     Whether to associate HPOTerms transitively to genes.
-    ///     You must provide the `phenotype_to_genes.txt` input file.
-
-    ///    # This requires the files:
-    /// # - Actual OBO data: hp.obo from https://hpo.jax.org/app/data/ontology
-    /// # - Links between HPO and OMIM diseases: phenotype.hpoa from https://hpo.jax.org/app/data/annotations
-    /// # - Links between HPO and Genes: [`genes_to_phenotype.txt`](http://purl.obolibrary.org/obo/hp/hpoa/genes_to_phenotype.txt)
-    /// #
-
-    #[pyo3(signature = (data_folder = "", from_obo_file = true, transitive = false))]
-    fn __call__(&self, data_folder: &str, from_obo_file: bool, transitive: bool) -> PyResult<()> {
-        if get_ontology().is_ok() {
-            println!("The Ontology has been built before already");
-            return Ok(());
-        }
-        if data_folder.is_empty() {
-            from_builtin();
-            Ok(())
-        } else if from_obo_file {
-            match from_obo(data_folder, transitive) {
-                Ok(_) => return Ok(()),
-                Err(HpoError::CannotOpenFile(filename)) => {
-                    if filename.ends_with("genes_to_phenotype.txt") {
-                        return Err(PyFileNotFoundError::new_err("Starting with v1.2.0, hpo3 changed the way \
-                            how the ontology is build from JAX-OBO source. It now requires the `genes_to_phenotype.txt` \
-                            file. Please check the documentation for more info or add the `transitive=True` argument."));
-                    }
-                    return Err(PyFileNotFoundError::new_err(
-                        format!("Unable to open {filename}. Please check if you specified the correct path and all files are present.")
-                    ));
-                }
-                Err(err) => {
-                    return Err(PyRuntimeError::new_err(format!(
-                        "Error loading the ontology. Please check if the data is correct: {err}"
-                    )));
-                }
-            }
-        } else {
-            from_binary(data_folder);
-            return Ok(());
-        }
-    }
-
-    /// Returns the number of HPO-Terms in the Ontology
+    /// xref: str
+    ///     The cross reference, including its system, e.g.: ``UMLS:C0024796``
     ///
     /// Returns
     /// -------
-    /// int
-    ///     The number of HPO-Terms in the Ontology
+    /// list[:class:`pyhpo.HPOTerm`]
+    ///     All terms with the cross reference, sorted by their ID
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// RuntimeError
+    ///     The Ontology was not built from the JAX source files
     ///
     /// Examples
     /// --------
@@ -471,36 +944,33 @@ impl PyOntology {
     /// .. code-block:: python
     ///
     ///     from pyhpo import Ontology
-    ///     Ontology()
-    ///     len(Ontology)  # >> 17059
-    ///
-    /// Raises
-    /// ------
-    /// NameError
-    ///     Ontology not yet constructed
-    ///
-    fn __len__(&self) -> PyResult<usize> {
-        Ok(get_ontology()?.len())
+    ///     Ontology("/path/to/jax/files/")
+    ///
+    ///     Ontology.term_by_xref("UMLS:C0024796")
+    ///     # >> [<HpoTerm (HP:0001166)>]
+    ///
+    #[pyo3(text_signature = "($self, xref)")]
+    fn term_by_xref(&self, xref: &str) -> PyResult<Vec<PyHpoTerm>> {
+        get_ontology()?;
+        terms_by_xref(xref)?
+            .iter()
+            .map(|id| pyterm_from_id(*id))
+            .collect()
     }
 
-    fn __repr__(&self) -> String {
-        match get_ontology() {
-            Ok(ont) => format!("<pyhpo.Ontology with {} terms>", ont.len()),
-            _ => String::from("<pyhpo.Ontology (no data loaded, yet)>"),
-        }
-    }
-
-    /// Subset the Ontology to retrieve a single ``HPOTerm``
+    /// Returns the HpoTerm with the provided `id`
     ///
     /// Parameters
     /// ----------
-    /// id: int
-    ///     The integer representation of the HPO-ID
+    /// id: int or str
+    ///
+    ///     * **int** ID of the term as int (``HP:0000123`` --> ``123``)
+    ///     * **str** HPO-ID of the term (e.g.: ``HP:0000123``)
     ///
     /// Returns
     /// -------
-    /// :class:`HPOTerm`
-    ///     The ``HPOTerm``
+    /// :class:`pyhpo.HPOTerm`
+    ///     The HPO-Term
     ///
     /// Raises
     /// ------
@@ -508,16 +978,1262 @@ impl PyOntology {
     ///     Ontology not yet constructed
     /// KeyError
     ///     No HPO term is found for the provided query
+    /// ValueError
+    ///     The provided HPO ID cannot be converted to the correct
+    ///     integer representation
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     
+    ///     Ontology()
+    ///     
+    ///     term = Ontology.hpo(11968)
+    ///     term.name()  # >> 'Feeding difficulties'
+    ///     term.id()    # >> 'HP:0011968'
+    ///     int(tern)    # >> 11968
+    ///
+    ///     term = Ontology.hpo("HP:0011968")
+    ///     term.name()  # >> 'Feeding difficulties'
     ///
-    fn __getitem__(&self, id: u32) -> PyResult<PyHpoTerm> {
-        self.hpo(id)
+    #[pyo3(text_signature = "($self, id)")]
+    fn hpo(&self, id: PyQuery) -> PyResult<PyHpoTerm> {
+        pyterm_from_id(id_from_query(id)?)
     }
 
-    /// Iterate all ``HPOTerms`` within the Ontology
+    /// Returns a list of HpoTerms with the provided `ids`
+    ///
+    /// This method is faster than calling :func:`pyhpo.Ontology.hpo`
+    /// repeatedly, because all IDs are resolved in one call.
+    ///
+    /// Parameters
+    /// ----------
+    /// ids: list[int or str]
+    ///
+    ///     * **int** ID of the term as int (``HP:0000123`` --> ``123``)
+    ///     * **str** HPO-ID of the term (e.g.: ``HP:0000123``)
     ///
     /// Returns
     /// -------
-    /// Iterator[:class:`HPOTerm`]
+    /// list[:class:`pyhpo.HPOTerm`]
+    ///     The HPO-Terms, in the same order as the ``ids``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     No HPO term is found for one of the provided ids
+    /// ValueError
+    ///     One of the provided HPO IDs cannot be converted to the correct
+    ///     integer representation
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology()
+    ///
+    ///     Ontology.hpos([118, 2650, "HP:0000925"])
+    ///     # >> [
+    ///     # >>     <HpoTerm (HP:0000118)>,
+    ///     # >>     <HpoTerm (HP:0002650)>,
+    ///     # >>     <HpoTerm (HP:0000925)>
+    ///     # >> ]
+    ///
+    #[pyo3(text_signature = "($self, ids)")]
+    fn hpos(&self, ids: Vec<PyQuery>) -> PyResult<Vec<PyHpoTerm>> {
+        ids.into_iter()
+            .map(|id| pyterm_from_id(id_from_query(id)?))
+            .collect()
+    }
+
+    /// Returns the HPOTerm of a FHIR ``Coding`` or ``CodeableConcept``
+    ///
+    /// This is the reverse of :func:`pyhpo.HPOTerm.to_fhir_coding` and
+    /// :func:`pyhpo.HPOTerm.to_fhir_codeable_concept`. The first ``Coding``
+    /// with the HPO system ``http://purl.obolibrary.org/obo/hp.owl``
+    /// of a ``CodeableConcept`` is used.
+    ///
+    /// Parameters
+    /// ----------
+    /// fragment: dict
+    ///     The FHIR ``Coding`` or ``CodeableConcept`` JSON fragment
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOTerm`
+    ///     The HPO term of the coding
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// TypeError
+    ///     ``fragment`` is not a dict
+    /// ValueError
+    ///     ``fragment`` does not contain a valid HPO coding
+    /// KeyError
+    ///     No HPO term is found for the code
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology()
+    ///     Ontology.hpo_from_fhir({
+    ///         "system": "http://purl.obolibrary.org/obo/hp.owl",
+    ///         "code": "HP:0002650",
+    ///         "display": "Scoliosis"
+    ///     })
+    ///     # >> <HpoTerm (HP:0002650)>
+    ///
+    #[pyo3(text_signature = "($self, fragment)")]
+    fn hpo_from_fhir(&self, fragment: &Bound<'_, PyAny>) -> PyResult<PyHpoTerm> {
+        Ok(PyHpoTerm::from(fhir::term_from_fhir(fragment)?))
+    }
+
+    /// Returns all leaf terms, i.e. terms without any children
+    ///
+    /// Obsolete terms are not included.
+    ///
+    /// Parameters
+    /// ----------
+    /// term: :class:`pyhpo.HPOTerm` or int, optional
+    ///     Only return leaf terms that are descendants of ``term``
+    ///     (or the term itself, if it does not have any children)
+    ///
+    /// Returns
+    /// -------
+    /// Iterator[:class:`pyhpo.HPOTerm`]
+    ///     All leaf terms, sorted by ID
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     ``term`` does not exist
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     for term in Ontology.leaves(2650):
+    ///         print(term)
+    ///
+    ///     # >> HP:0003423 | Thoracolumbar kyphoscoliosis
+    ///     # >> ...
+    ///
+    #[pyo3(signature = (term = None))]
+    #[pyo3(text_signature = "($self, term)")]
+    fn leaves(&self, term: Option<TermOrId>) -> PyResult<OntologyIterator> {
+        let ont = get_ontology()?;
+        let root = term.map(term_from_term_or_id).transpose()?;
+        let ids: Vec<u32> = ont
+            .into_iter()
+            .filter(|term| term.children_ids().is_empty() && !term.is_obsolete())
+            .filter(|term| {
+                root.is_none_or(|root| {
+                    term.id() == root.id() || term.all_parent_ids().contains(&root.id())
+                })
+            })
+            .map(|term| term.id().as_u32())
+            .collect();
+        Ok(OntologyIterator::from_ids(ids))
+    }
+
+    /// The root term of the Ontology (``HP:0000001``)
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOTerm`
+    ///     The ``All`` root term
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     The term is not present in the Ontology
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     Ontology.root
+    ///     # >> <HpoTerm (HP:0000001)>
+    ///
+    ///     Ontology.phenotypic_abnormality
+    ///     # >> <HpoTerm (HP:0000118)>
+    ///
+    #[getter(root)]
+    fn root(&self) -> PyResult<PyHpoTerm> {
+        pyterm_from_id(ROOT)
+    }
+
+    /// The ``Phenotypic abnormality`` branch (``HP:0000118``)
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOTerm`
+    ///     The ``Phenotypic abnormality`` term
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     The term is not present in the Ontology
+    ///
+    #[getter(phenotypic_abnormality)]
+    fn phenotypic_abnormality(&self) -> PyResult<PyHpoTerm> {
+        pyterm_from_id(branch_id("phenotypic_abnormality"))
+    }
+
+    /// The ``Mode of inheritance`` branch (``HP:0000005``)
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOTerm`
+    ///     The ``Mode of inheritance`` term
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     The term is not present in the Ontology
+    ///
+    #[getter(mode_of_inheritance)]
+    fn mode_of_inheritance(&self) -> PyResult<PyHpoTerm> {
+        pyterm_from_id(branch_id("mode_of_inheritance"))
+    }
+
+    /// The ``Clinical modifier`` branch (``HP:0012823``)
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOTerm`
+    ///     The ``Clinical modifier`` term
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     The term is not present in the Ontology
+    ///
+    #[getter(clinical_modifier)]
+    fn clinical_modifier(&self) -> PyResult<PyHpoTerm> {
+        pyterm_from_id(branch_id("clinical_modifier"))
+    }
+
+    /// The ``Frequency`` branch (``HP:0040279``)
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOTerm`
+    ///     The ``Frequency`` term
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     The term is not present in the Ontology
+    ///
+    #[getter(frequency)]
+    fn frequency(&self) -> PyResult<PyHpoTerm> {
+        pyterm_from_id(branch_id("frequency"))
+    }
+
+    /// The ``Past medical history`` branch (``HP:0032443``)
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOTerm`
+    ///     The ``Past medical history`` term
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     The term is not present in the Ontology
+    ///
+    #[getter(past_medical_history)]
+    fn past_medical_history(&self) -> PyResult<PyHpoTerm> {
+        pyterm_from_id(branch_id("past_medical_history"))
+    }
+
+    /// All top-level branches of the Ontology by name
+    ///
+    /// Returns
+    /// -------
+    /// dict[str, :class:`pyhpo.HPOTerm`]
+    ///     The top-level terms, i.e. the direct children of the root term.
+    ///     Branches that are not present in the Ontology are omitted
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     Ontology.branches["mode_of_inheritance"]
+    ///     # >> <HpoTerm (HP:0000005)>
+    ///
+    #[getter(branches)]
+    fn branches<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+        let ont = get_ontology()?;
+        let dict = PyDict::new(py);
+        for (name, id) in BRANCHES {
+            if let Some(term) = ont.hpo(id) {
+                dict.set_item(name, PyHpoTerm::from(term))?;
+            }
+        }
+        Ok(dict)
+    }
+
+    /// Returns a sparse matrix of all ancestor relationships
+    ///
+    /// Rows and columns represent all terms of the Ontology, sorted
+    /// by their integer ID. A cell ``[i, j]`` is ``1`` if the term ``j``
+    /// is an ancestor of term ``i``.
+    ///
+    /// Parameters
+    /// ----------
+    /// include_self: bool, default: ``False``
+    ///     Whether each term is considered its own ancestor,
+    ///     i.e. whether the diagonal is set as well
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.types.SparseMatrix`
+    ///     The ancestor matrix in COO format. Use
+    ///     :func:`pyhpo.types.SparseMatrix.to_scipy` to convert it into
+    ///     a ``scipy.sparse`` matrix
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     matrix = Ontology.ancestor_matrix()
+    ///     matrix.shape
+    ///     # >> (18961, 18961)
+    ///
+    ///     row = matrix.index.index(int(Ontology.hpo(7)))
+    ///     [matrix.columns[c] for r, c in zip(matrix.row, matrix.col) if r == row]
+    ///     # >> [1, 5, 34345]
+    ///
+    #[pyo3(signature = (include_self = false))]
+    #[pyo3(text_signature = "($self, include_self)")]
+    fn ancestor_matrix(&self, include_self: bool) -> PyResult<PySparseMatrix> {
+        let ont = get_ontology()?;
+        let ids = sorted_term_ids(ont);
+        let cells = ont.into_iter().flat_map(|term| {
+            let id = term.id().as_u32();
+            let own = include_self.then_some((id, id));
+            own.into_iter()
+                .chain(term.all_parent_ids().iter().map(move |p| (id, p.as_u32())))
+                .collect::<Vec<_>>()
+        });
+        Ok(PySparseMatrix::square(ids, cells))
+    }
+
+    /// Returns the sparse adjacency matrix of the Ontology
+    ///
+    /// Rows and columns represent all terms of the Ontology, sorted
+    /// by their integer ID. A cell ``[i, j]`` is ``1`` if the term ``j``
+    /// is a direct parent of term ``i``.
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.types.SparseMatrix`
+    ///     The adjacency matrix in COO format. Use
+    ///     :func:`pyhpo.types.SparseMatrix.to_scipy` to convert it into
+    ///     a ``scipy.sparse`` matrix
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     matrix = Ontology.adjacency_matrix()
+    ///     matrix.nnz
+    ///     # >> 22932
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn adjacency_matrix(&self) -> PyResult<PySparseMatrix> {
+        let ont = get_ontology()?;
+        let ids = sorted_term_ids(ont);
+        let cells = ont.into_iter().flat_map(|term| {
+            let id = term.id().as_u32();
+            term.parent_ids()
+                .iter()
+                .map(move |p| (id, p.as_u32()))
+                .collect::<Vec<_>>()
+        });
+        Ok(PySparseMatrix::square(ids, cells))
+    }
+
+    /// Returns the number of terms and annotations of each category
+    ///
+    /// Categories are the top-level terms that are used to group
+    /// all other terms, i.e. the direct children of
+    /// ``Phenotypic abnormality`` (organ systems) and the other
+    /// top-level branches (see :func:`pyhpo.HPOTerm.categories`).
+    ///
+    /// Returns
+    /// -------
+    /// list[dict]
+    ///     One dict for each category, sorted by the category's ID,
+    ///     with the following keys:
+    ///
+    ///     * **category** :class:`pyhpo.HPOTerm` - The category term
+    ///     * **terms** `int` - The number of descendant terms
+    ///     * **genes** `int` - The number of genes associated to the category
+    ///     * **omim** `int` - The number of Omim diseases associated to the category
+    ///     * **orpha** `int` - The number of Orpha diseases associated to the category
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     for row in Ontology.category_counts():
+    ///         print(row["category"].name, row["terms"], row["genes"])
+    ///
+    ///     # >> Mode of inheritance 40 4758
+    ///     # >> Abnormality of the genitourinary system 1631 2745
+    ///     # >> ...
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn category_counts<'a>(&self, py: Python<'a>) -> PyResult<Vec<Bound<'a, PyDict>>> {
+        let ont = get_ontology()?;
+        let categories = ont.categories();
+        let mut term_counts = vec![0usize; categories.len()];
+        for term in ont {
+            let parents = term.all_parent_ids();
+            for (count, category) in term_counts.iter_mut().zip(categories) {
+                if parents.contains(&category) {
+                    *count += 1;
+                }
+            }
+        }
+
+        categories
+            .iter()
+            .zip(term_counts)
+            .filter_map(|(id, count)| ont.hpo(id).map(|term| (term, count)))
+            .map(|(term, count)| {
+                let dict = PyDict::new(py);
+                dict.set_item("category", PyHpoTerm::from(term))?;
+                dict.set_item("terms", count)?;
+                dict.set_item("genes", term.gene_ids().len())?;
+                dict.set_item("omim", term.omim_disease_ids().len())?;
+                dict.set_item("orpha", term.orpha_disease_ids().len())?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Returns the HPO version
+    ///
+    /// Returns
+    /// -------
+    /// str
+    ///     The HPO version, e.g. ``2023-04-05``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     
+    ///     Ontology()
+    ///     
+    ///     Ontology.version()
+    ///     # >> "2023-04-05"
+    ///
+    fn version(&self) -> PyResult<String> {
+        Ok(get_ontology()?.hpo_version())
+    }
+
+    /// Saves the Ontology in the binary format
+    ///
+    /// The binary file contains all terms and annotations and can be
+    /// loaded much faster than the JAX source files. It starts with
+    /// a header that identifies the format version.
+    ///
+    /// The file can be compressed with zstd to reduce its size considerably.
+    /// Compressed files are detected and decompressed automatically when
+    /// loading the Ontology.
+    ///
+    /// Parameters
+    /// ----------
+    /// path: str
+    ///     Path of the output file
+    /// compress: bool, optional
+    ///     Whether to compress the file with zstd.
+    ///     Defaults to compressing if ``path`` has a ``.zst`` extension
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// OSError
+    ///     The file cannot be written
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology("/path/to/jax/files/")
+    ///     Ontology.save("ontology.hpo")
+    ///
+    ///     # in another process
+    ///     Ontology("ontology.hpo", from_obo_file=False)
+    ///
+    ///     # compressed
+    ///     Ontology.save("ontology.hpo.zst")
+    ///     Ontology("ontology.hpo.zst", from_obo_file=False)
+    ///
+    #[pyo3(signature = (path, compress = None))]
+    #[pyo3(text_signature = "($self, path, compress)")]
+    fn save(&self, path: PathBuf, compress: Option<bool>) -> PyResult<()> {
+        binary::save(
+            get_ontology()?,
+            &path,
+            binary::should_compress(&path, compress),
+        )
+    }
+
+    /// Saves the Ontology in the OBO format
+    ///
+    /// The file contains all terms of the loaded Ontology, including
+    /// obsolete terms and their replacements, and can be used with other
+    /// tools that read the ``hp.obo`` file.
+    ///
+    /// Definitions, comments, synonyms and xrefs are only included if the
+    /// Ontology was built from the JAX source files. Gene and disease
+    /// annotations are not part of the OBO format.
+    ///
+    /// Parameters
+    /// ----------
+    /// path: str
+    ///     Path of the output file
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// OSError
+    ///     The file cannot be written
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology()
+    ///     Ontology.to_obo("hp.obo")
+    ///
+    #[pyo3(text_signature = "($self, path)")]
+    fn to_obo(&self, path: PathBuf) -> PyResult<()> {
+        obo::save(get_ontology()?, &path)
+    }
+
+    /// Returns a table of all terms
+    ///
+    /// Every row contains one term, sorted by ID, with the columns:
+    ///
+    /// * **id** : `int` - The integer ID of the term
+    /// * **name** : `str` - The name of the term
+    /// * **parents** : `list[int]` - The IDs of the direct parents
+    /// * **children** : `list[int]` - The IDs of the direct children
+    /// * **obsolete** : `bool` - Whether the term is obsolete
+    /// * **ic_omim**, **ic_orpha**, **ic_gene** : `float` - The information content
+    /// * **n_omim**, **n_orpha**, **n_genes** : `int` - The number of
+    ///   annotated diseases and genes, including inherited annotations
+    ///
+    /// Parameters
+    /// ----------
+    /// output: str, default ``polars``
+    ///     The type of the table, ``polars`` for a ``polars.DataFrame``,
+    ///     ``arrow`` for a ``pyarrow.RecordBatch`` or ``list`` for a
+    ///     list of dicts
+    ///
+    /// Returns
+    /// -------
+    /// polars.DataFrame or pyarrow.RecordBatch or list[dict]
+    ///     The table of all terms
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// ValueError
+    ///     Invalid ``output``
+    /// ImportError
+    ///     ``polars`` or ``pyarrow`` is not installed for the ``output``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology()
+    ///     df = Ontology.to_table()
+    ///     df.filter(df["n_genes"] > 100).sort("ic_omim", descending=True)
+    ///
+    #[pyo3(signature = (output = "polars"))]
+    #[pyo3(text_signature = "($self, output)")]
+    fn to_table(&self, py: Python<'_>, output: &str) -> PyResult<PyObject> {
+        let ont = get_ontology()?;
+        let output = TableOutput::try_from(output)?;
+        let mut terms: Vec<HpoTerm> = ont.iter().collect();
+        terms.sort_unstable_by_key(|term| term.id());
+
+        let rows = terms
+            .iter()
+            .map(|term| term_row(py, term))
+            .collect::<PyResult<Vec<_>>>()?;
+        if output == TableOutput::List {
+            return rows.into_py_any(py);
+        }
+        output.table(py, &columns(py, &rows, &TERM_TABLE_COLUMNS)?)
+    }
+
+    /// Saves the Ontology as OWL, in Turtle or functional syntax
+    ///
+    /// The exported ontology uses the same IRIs and annotation properties
+    /// as the official ``hp.owl``, e.g. ``obo:HP_0000118``, so it can be
+    /// used with semantic-web tools and validated against ``hp.owl``.
+    /// It contains all terms with their labels, ``subClassOf`` axioms,
+    /// obsolete status and replacements.
+    ///
+    /// Definitions, comments, synonyms and xrefs are only included if the
+    /// Ontology was built from the JAX source files.
+    ///
+    /// Parameters
+    /// ----------
+    /// path: str
+    ///     Path of the output file
+    /// syntax: str, optional
+    ///     ``turtle`` or ``functional``. Defaults to the functional syntax
+    ///     if ``path`` has a ``.ofn`` extension, otherwise Turtle
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// ValueError
+    ///     Invalid ``syntax``
+    /// OSError
+    ///     The file cannot be written
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology()
+    ///     Ontology.to_owl("hp.ttl")
+    ///     Ontology.to_owl("hp.ofn")
+    ///
+    #[pyo3(signature = (path, syntax = None))]
+    #[pyo3(text_signature = "($self, path, syntax)")]
+    fn to_owl(&self, path: PathBuf, syntax: Option<&str>) -> PyResult<()> {
+        let syntax = owl::Syntax::new(&path, syntax)?;
+        owl::save(get_ontology()?, &path, syntax)
+    }
+
+    /// Returns the approximate memory used by the Ontology
+    ///
+    /// The numbers are estimated from the number of terms and annotations
+    /// and do not include allocator overhead. They are useful to compare
+    /// different Ontology configurations, e.g. with or without annotations.
+    ///
+    /// Returns
+    /// -------
+    /// dict[str, int]
+    ///     The approximate memory usage in bytes, with the keys:
+    ///
+    ///     * **terms** - Terms, their names and hierarchy
+    ///     * **annotations** - Genes, diseases and their links to terms
+    ///     * **information_content** - The information content of all terms
+    ///     * **extra** - Additional data, e.g. definitions and synonyms
+    ///       or the custom information content
+    ///     * **total** - The sum of all of the above
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology()
+    ///     Ontology.memory_usage()
+    ///     # >> {'terms': 59808811, 'annotations': 27182939, ...}
+    ///
+    fn memory_usage(&self) -> PyResult<HashMap<&'static str, usize>> {
+        let usage = memory::memory_usage(get_ontology()?);
+        Ok(HashMap::from([
+            ("terms", usage.terms),
+            ("annotations", usage.annotations),
+            ("information_content", usage.information_content),
+            ("extra", usage.extra),
+            ("total", usage.total()),
+        ]))
+    }
+
+    /// Returns the arguments that were used to build the Ontology
+    ///
+    /// The arguments can be passed to :func:`pyhpo.helper.init_worker`
+    /// to build the same Ontology in ``multiprocessing`` worker processes
+    /// that are started with ``spawn`` or ``forkserver``.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///     The keyword arguments for ``Ontology()``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// ValueError
+    ///     The Ontology was built from ``bytes`` or a file-like object
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology(annotations=False)
+    ///     Ontology.build_kwargs()
+    ///     # >> {'data_folder': None, 'from_obo_file': True, ..., 'annotations': False, ...}
+    ///
+    fn build_kwargs<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        worker::build_kwargs(py)
+    }
+
+    /// Returns metadata of the annotation source files
+    ///
+    /// The metadata is only available if the Ontology was built from
+    /// the JAX source files (``phenotype.hpoa`` and
+    /// ``genes_to_phenotype.txt`` or ``phenotype_to_genes.txt``).
+    /// Use it to record which annotation build produced a result.
+    ///
+    /// Returns
+    /// -------
+    /// dict[str, dict]
+    ///     The metadata of each annotation file, with the filename as key.
+    ///     Each value is a dict with the following keys:
+    ///
+    ///     * **version** `str | None` - The version from the ``#version`` header
+    ///     * **date** `str | None` - The date from the ``#date`` header
+    ///     * **records** `int` - The number of loaded annotation records
+    ///     * **skipped** `int` - The number of skipped lines, e.g.
+    ///       annotations with a ``NOT`` qualifier or from unsupported sources
+    ///
+    ///     The dict is empty if the Ontology was loaded from a binary file
+    ///     or the builtin data
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology("/path/to/jax/files/")
+    ///
+    ///     Ontology.annotation_metadata()
+    ///     # >> {
+    ///     # >>     'phenotype.hpoa': {'version': '2024-03-06', 'date': None, 'records': 269612, 'skipped': 4532},
+    ///     # >>     'genes_to_phenotype.txt': {'version': None, 'date': None, 'records': 298217, 'skipped': 0}
+    ///     # >> }
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn annotation_metadata<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+        get_ontology()?;
+        let dict = PyDict::new(py);
+        for metadata in ANNOTATION_METADATA.get().into_iter().flatten() {
+            dict.set_item(metadata.filename(), metadata.to_dict(py)?)?;
+        }
+        Ok(dict)
+    }
+
+    /// Loads the mapping of MONDO diseases to Omim and Orpha diseases
+    ///
+    /// The mapping is read from the MONDO SSSOM cross-reference table
+    /// (``mondo.sssom.tsv``) that is part of every MONDO release. Only
+    /// exact matches (``skos:exactMatch``) to ``OMIM:`` and ``Orphanet:``
+    /// are used. A previously loaded mapping is replaced.
+    ///
+    /// Once loaded, MONDO diseases are available via :func:`pyhpo.Mondo.get`,
+    /// :func:`pyhpo.Omim.mondo` and :func:`pyhpo.Orpha.mondo`.
+    ///
+    /// Parameters
+    /// ----------
+    /// source: str, bytes or file-like
+    ///     Path to, or content of, the SSSOM table. If the path is a
+    ///     directory, ``mondo.sssom.tsv`` inside the directory is used
+    ///
+    /// Returns
+    /// -------
+    /// int
+    ///     The number of MONDO diseases with at least one Omim
+    ///     or Orpha disease
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// FileNotFoundError
+    ///     The file does not exist
+    /// ValueError
+    ///     The table does not contain the ``subject_id``, ``predicate_id``
+    ///     and ``object_id`` columns
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Mondo
+    ///     Ontology()
+    ///
+    ///     Ontology.load_mondo("/path/to/mondo.sssom.tsv")
+    ///     # >> 10981
+    ///
+    ///     Mondo.get("MONDO:0007739").omim_diseases
+    ///     # >> [<OmimDisease (143100)>]
+    ///
+    #[pyo3(text_signature = "($self, source)")]
+    fn load_mondo(&self, py: Python<'_>, source: Source) -> PyResult<usize> {
+        get_ontology()?;
+        let source = match source {
+            Source::Path(path) if path.is_dir() => Source::Path(path.join(MONDO_FILENAME)),
+            source => source,
+        };
+        mondo::load(py, source)
+    }
+
+    /// Constructs the ontology based on provided ontology files
+    ///
+    /// The ontology files can be in the standard format as provided
+    /// by Jax or as a binary file as generated by `hpo`
+    ///
+    /// Parameters
+    /// ----------
+    /// data_folder: str
+    ///     Path to the source files (default: `./ontology.hpo`).
+    ///     Binary data can also be provided as ``bytes`` or a file-like object
+    /// binary: bool
+    ///     Whether the input format is binary (default true)
+    /// transitive: bool
+    ///     Whether to associate HPOTerms transitively to genes.
+    ///     You must provide the `phenotype_to_genes.txt` input file.
+    /// obo: str, bytes or file-like, optional
+    ///     Explicit path to, or content of, the OBO file,
+    ///     instead of ``hp.obo`` in ``data_folder``
+    /// hpoa: str, bytes or file-like, optional
+    ///     Explicit path to, or content of, the disease annotations,
+    ///     instead of ``phenotype.hpoa`` in ``data_folder``
+    /// gene_file: str, bytes or file-like, optional
+    ///     Explicit path to, or content of, the gene annotations, instead of
+    ///     ``genes_to_phenotype.txt`` (or ``phenotype_to_genes.txt`` if ``transitive``)
+    ///     in ``data_folder``. In ``union`` mode, it replaces ``genes_to_phenotype.txt``
+    ///
+    ///    # This requires the files:
+    /// # - Actual OBO data: hp.obo from https://hpo.jax.org/app/data/ontology
+    /// # - Links between HPO and OMIM diseases: phenotype.hpoa from https://hpo.jax.org/app/data/annotations
+    /// # - Links between HPO and Genes: [`genes_to_phenotype.txt`](http://purl.obolibrary.org/obo/hp/hpoa/genes_to_phenotype.txt)
+    /// #
+    ///
+    /// If ``data_folder`` is not specified, ``obo``, ``hpoa`` and ``gene_file``
+    /// must all be provided.
+    ///
+    /// progress: Callable[[str, int], None], optional
+    ///     Called after each stage of building the Ontology from the JAX source
+    ///     files with the name of the stage and the number of processed items:
+    ///
+    ///     * **read** - Number of bytes read from all source files
+    ///     * **terms** - Number of parsed terms
+    ///     * **genes** - Number of linked genes
+    ///     * **diseases** - Number of linked Omim and Orpha diseases
+    ///     * **information_content** - Number of terms with calculated information content
+    ///
+    /// annotations: bool, default: ``True``
+    ///     Whether to load genes and diseases. Set to ``False`` to only load the
+    ///     terms and their hierarchy, which is faster and uses less memory. The
+    ///     ``hpoa`` and ``gene_file`` sources are not required in this case.
+    /// min_frequency: float, optional
+    ///     Only load disease annotations with at least this frequency (``0.0`` - ``1.0``).
+    ///     HPO frequency terms use the lower bound of their range, e.g. **Occasional**
+    ///     (``HP:0040283``, 5-29%) counts as ``0.05``. Annotations without a frequency
+    ///     are always loaded. Only supported for the JAX source files.
+    /// evidence_codes: list[str], optional
+    ///     Only load disease annotations with one of these evidence codes
+    ///     (``IEA``, ``PCS`` or ``TAS``), e.g. ``["PCS", "TAS"]`` to exclude
+    ///     inferred annotations. Only supported for the JAX source files.
+    /// gene_annotation_mode: str, optional
+    ///     Which gene annotation files are used to associate ``HPOTerm`` to genes.
+    ///     Genes are always linked to the annotated terms and all their ancestors,
+    ///     the mode determines the terms of each :func:`pyhpo.Gene.hpo_set`:
+    ///
+    ///     * **direct** - Only the directly annotated terms from ``genes_to_phenotype.txt``
+    ///       (default)
+    ///     * **transitive** - All terms from ``phenotype_to_genes.txt``, same as
+    ///       ``transitive=True``. This reproduces the gene counts of ``pyhpo``
+    ///     * **union** - The terms of both files. Requires ``data_folder``
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (data_folder = None, from_obo_file = true, transitive = false, obo = None, hpoa = None, gene_file = None, progress = None, annotations = true, min_frequency = None, evidence_codes = None, gene_annotation_mode = None))]
+    fn __call__(
+        &self,
+        py: Python<'_>,
+        data_folder: Option<Source>,
+        from_obo_file: bool,
+        transitive: bool,
+        obo: Option<Source>,
+        hpoa: Option<Source>,
+        gene_file: Option<Source>,
+        progress: Option<PyObject>,
+        annotations: bool,
+        min_frequency: Option<f32>,
+        evidence_codes: Option<Vec<String>>,
+        gene_annotation_mode: Option<&str>,
+    ) -> PyResult<()> {
+        if get_ontology().is_ok() {
+            println!("The Ontology has been built before already");
+            return Ok(());
+        }
+        if min_frequency.is_some_and(|frequency| !(0.0..=1.0).contains(&frequency)) {
+            return Err(PyValueError::new_err(
+                "`min_frequency` must be between 0.0 and 1.0",
+            ));
+        }
+        let mode = match (gene_annotation_mode, transitive) {
+            (None, true) => GeneAnnotationMode::Transitive,
+            (None, false) => GeneAnnotationMode::Direct,
+            (Some(mode), _) => GeneAnnotationMode::try_from(mode)?,
+        };
+        if transitive && mode != GeneAnnotationMode::Transitive {
+            return Err(PyValueError::new_err(
+                "`transitive=True` can only be combined with `gene_annotation_mode=\"transitive\"`",
+            ));
+        }
+        let data_folder = data_folder.filter(|source| !source.is_empty_path());
+        let explicit_sources = obo.is_some() || hpoa.is_some() || gene_file.is_some();
+        if let Some(code) = evidence_codes
+            .iter()
+            .flatten()
+            .find(|code| !EVIDENCE_CODES.contains(&code.as_str()))
+        {
+            return Err(PyValueError::new_err(format!(
+                "Unknown evidence code {code}. Valid codes are {}",
+                EVIDENCE_CODES.join(", ")
+            )));
+        }
+        let filtered = min_frequency.is_some() || evidence_codes.is_some();
+        if filtered && !(from_obo_file && (data_folder.is_some() || explicit_sources)) {
+            return Err(PyValueError::new_err(
+                "`min_frequency` and `evidence_codes` are only supported for the JAX source files",
+            ));
+        }
+        let build_args = BuildArgs::from_sources(
+            data_folder.as_ref(),
+            obo.as_ref(),
+            hpoa.as_ref(),
+            gene_file.as_ref(),
+        )
+        .map(|args| BuildArgs {
+            from_obo_file,
+            transitive,
+            annotations,
+            min_frequency,
+            evidence_codes: evidence_codes.clone(),
+            gene_annotation_mode: gene_annotation_mode.map(String::from),
+            ..args
+        });
+        if data_folder.is_none() && !explicit_sources {
+            from_builtin(annotations);
+        } else if from_obo_file {
+            let folder = match data_folder {
+                Some(Source::Path(path)) => path,
+                Some(_) => {
+                    return Err(PyValueError::new_err(
+                        "`data_folder` must be a path when loading the JAX source files. \
+                        Use `obo`, `hpoa` and `gene_file` to provide the data directly.",
+                    ))
+                }
+                None if !annotations && obo.is_none() => {
+                    return Err(PyValueError::new_err(
+                        "`obo` must be specified if no `data_folder` is provided",
+                    ))
+                }
+                None if annotations && mode == GeneAnnotationMode::Union => {
+                    return Err(PyValueError::new_err(
+                        "`gene_annotation_mode=\"union\"` requires a `data_folder`",
+                    ))
+                }
+                None if annotations && (obo.is_none() || hpoa.is_none() || gene_file.is_none()) => {
+                    return Err(PyValueError::new_err(
+                        "`obo`, `hpoa` and `gene_file` must all be specified if no `data_folder` is provided",
+                    ))
+                }
+                None => PathBuf::new(),
+            };
+            let mut sources = JaxSources::from_folder(&folder, mode);
+            sources.annotations = annotations;
+            sources.filter.min_frequency = min_frequency;
+            sources.filter.evidence_codes = evidence_codes;
+            if let Some(source) = obo {
+                sources.obo = source;
+            }
+            if let Some(source) = hpoa {
+                sources.hpoa = source;
+            }
+            if let Some(source) = gene_file {
+                sources.genes[0].source = source;
+            }
+            from_obo(py, &sources, progress.as_ref())?;
+        } else if explicit_sources {
+            return Err(PyValueError::new_err(
+                "`obo`, `hpoa` and `gene_file` are only supported for the JAX source files",
+            ));
+        } else {
+            let bytes = data_folder
+                .expect("data_folder is present if no explicit sources are provided")
+                .read(py)?;
+            from_binary_bytes(&bytes, annotations)?;
+        }
+        let _ = BUILD_ARGS.set(build_args);
+        Ok(())
+    }
+
+    /// Returns the number of HPO-Terms in the Ontology
+    ///
+    /// Returns
+    /// -------
+    /// int
+    ///     The number of HPO-Terms in the Ontology
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     len(Ontology)  # >> 17059
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    fn __len__(&self) -> PyResult<usize> {
+        Ok(get_ontology()?.len())
+    }
+
+    /// The Ontology is pickled as a reference to ``pyhpo.Ontology``
+    fn __reduce__(&self) -> &'static str {
+        "Ontology"
+    }
+
+    fn __repr__(&self) -> String {
+        match get_ontology() {
+            Ok(ont) => format!("<pyhpo.Ontology with {} terms>", ont.len()),
+            _ => String::from("<pyhpo.Ontology (no data loaded, yet)>"),
+        }
+    }
+
+    /// Subset the Ontology to retrieve a single ``HPOTerm``
+    ///
+    /// Parameters
+    /// ----------
+    /// query: str or int
+    ///
+    ///     * **str** HPO term (e.g.: ``Scoliosis``)
+    ///     * **str** HPO-ID (e.g.: ``HP:0002650``)
+    ///     * **int** HPO term id (e.g.: ``2650``)
+    ///
+    /// Returns
+    /// -------
+    /// :class:`HPOTerm`
+    ///     The ``HPOTerm``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     No HPO term is found for the provided query
+    /// ValueError
+    ///     The provided HPO ID cannot be converted to the correct
+    ///     integer representation
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     Ontology[2650]
+    ///     # >> HP:0002650 | Scoliosis
+    ///
+    ///     Ontology["HP:0002650"]
+    ///     # >> HP:0002650 | Scoliosis
+    ///
+    ///     Ontology["Scoliosis"]
+    ///     # >> HP:0002650 | Scoliosis
+    ///
+    fn __getitem__(&self, py: Python<'_>, query: PyQuery) -> PyResult<PyHpoTerm> {
+        term_from_query(query).map(PyHpoTerm::from).map_err(|err| {
+            if err.is_instance_of::<PyRuntimeError>(py) {
+                PyKeyError::new_err(err.value(py).to_string())
+            } else {
+                err
+            }
+        })
+    }
+
+    /// Checks if an ``HPOTerm`` is present in the Ontology
+    ///
+    /// Parameters
+    /// ----------
+    /// query: :class:`HPOTerm`, str or int
+    ///
+    ///     * **HPOTerm** an ``HPOTerm`` instance
+    ///     * **str** HPO term (e.g.: ``Scoliosis``)
+    ///     * **str** HPO-ID (e.g.: ``HP:0002650``)
+    ///     * **int** HPO term id (e.g.: ``2650``)
+    ///
+    /// Returns
+    /// -------
+    /// bool
+    ///     Whether the term is present in the Ontology. Invalid
+    ///     queries return ``False``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///
+    ///     118 in Ontology  # >> True
+    ///     "HP:0000118" in Ontology  # >> True
+    ///     "Scoliosis" in Ontology  # >> True
+    ///     "HP:9999999" in Ontology  # >> False
+    ///
+    fn __contains__(&self, query: &Bound<'_, PyAny>) -> PyResult<bool> {
+        let ont = get_ontology()?;
+        if let Ok(term) = query.extract::<PyHpoTerm>() {
+            return Ok(ont.hpo(term.hpo_term_id()).is_some());
+        }
+        match query.extract::<PyQuery>() {
+            Ok(PyQuery::Id(id)) => Ok(ont.hpo(id).is_some()),
+            Ok(query) => Ok(term_from_query(query).is_ok()),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Iterate all ``HPOTerms`` within the Ontology, sorted by ID
+    ///
+    /// Returns
+    /// -------
+    /// Iterator[:class:`HPOTerm`]
     ///     An iterator of ``HPOTerm``\s
     ///
     /// Raises
@@ -530,6 +2246,37 @@ impl PyOntology {
     }
 }
 
+/// Filters and sorts annotations, provided as `(id, name, number of terms)`
+///
+/// # Errors
+///
+/// - PyKeyError: Invalid `sorted_by` value
+fn filter_annotations<'a, I>(
+    annotations: I,
+    sorted_by: Option<&str>,
+    min_terms: usize,
+) -> PyResult<Vec<(u32, &'a str)>>
+where
+    I: Iterator<Item = (u32, &'a str, usize)>,
+{
+    let mut res: Vec<(u32, &str, usize)> = annotations
+        .filter(|(_, _, n_terms)| *n_terms >= min_terms)
+        .collect();
+
+    match sorted_by {
+        None | Some("id") => res.sort_unstable_by_key(|(id, _, _)| *id),
+        Some("name") => res.sort_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(&b.0))),
+        Some("terms") => res.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0))),
+        Some(other) => {
+            return Err(PyKeyError::new_err(format!(
+                "Invalid sort option {other}. Use `id`, `name` or `terms`"
+            )))
+        }
+    }
+
+    Ok(res.into_iter().map(|(id, name, _)| (id, name)).collect())
+}
+
 #[pyclass(name = "OntologyIterator")]
 struct OntologyIterator {
     ids: VecDeque<u32>,
@@ -537,11 +2284,17 @@ struct OntologyIterator {
 
 impl OntologyIterator {
     fn new() -> PyResult<Self> {
-        let ids: VecDeque<u32> = get_ontology()?
+        let ids: Vec<u32> = get_ontology()?
             .into_iter()
             .map(|term| term.id().as_u32())
             .collect();
-        Ok(Self { ids })
+        Ok(Self::from_ids(ids))
+    }
+
+    /// Returns an iterator of the provided term IDs, sorted by ID
+    fn from_ids(mut ids: Vec<u32>) -> Self {
+        ids.sort_unstable();
+        Self { ids: ids.into() }
     }
 }
 
@@ -0,0 +1,379 @@
+//! Tabular results
+//!
+//! Large results, e.g. the pairwise similarity of a whole cohort, are
+//! written block by block, so that they never have to fit into memory.
+//! CSV and `.npy` files are written from Rust, Parquet files through
+//! `pyarrow`, which must be installed for that format.
+//!
+//! Smaller results can be returned as `polars.DataFrame` or
+//! `pyarrow.RecordBatch` instead of Python lists.
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3::IntoPyObjectExt;
+
+/// Length of the `.npy` header, including magic string and padding
+///
+/// The header is written with a fixed length, so that it can be
+/// overwritten with the final shape once all values are written
+const NPY_HEADER_LEN: usize = 128;
+
+/// The file format of streamed results
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Csv,
+    Npy,
+    Parquet,
+}
+
+impl Format {
+    /// Returns the format for writing to `path`
+    ///
+    /// If not specified explicitly, the format is derived from the file
+    /// extension, using CSV for unknown extensions.
+    ///
+    /// # Errors
+    ///
+    /// - PyValueError: Invalid format
+    pub fn new(path: &Path, format: Option<&str>) -> PyResult<Self> {
+        match format {
+            Some("csv") => Ok(Self::Csv),
+            Some("npy") => Ok(Self::Npy),
+            Some("parquet") => Ok(Self::Parquet),
+            Some(other) => Err(PyValueError::new_err(format!(
+                "Invalid format {other}. Use `csv`, `npy` or `parquet`"
+            ))),
+            None => match path.extension().and_then(|ext| ext.to_str()) {
+                Some("npy") => Ok(Self::Npy),
+                Some("parquet") => Ok(Self::Parquet),
+                _ => Ok(Self::Csv),
+            },
+        }
+    }
+}
+
+/// The type of returned tabular results
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TableOutput {
+    /// A Python list of rows
+    List,
+    /// A `polars.DataFrame`
+    Polars,
+    /// A `pyarrow.RecordBatch`
+    Arrow,
+}
+
+impl TryFrom<&str> for TableOutput {
+    type Error = PyErr;
+    fn try_from(value: &str) -> PyResult<Self> {
+        match value {
+            "list" => Ok(Self::List),
+            "polars" => Ok(Self::Polars),
+            "arrow" => Ok(Self::Arrow),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid output {value}. Use `list`, `polars` or `arrow`"
+            ))),
+        }
+    }
+}
+
+impl TableOutput {
+    /// Returns the `columns` as `polars.DataFrame` or `pyarrow.RecordBatch`
+    ///
+    /// # Errors
+    ///
+    /// - ImportError: `polars` or `pyarrow` is not installed
+    ///
+    /// # Panics
+    ///
+    /// Tables of [`TableOutput::List`] must be built by the caller
+    pub fn table(self, py: Python<'_>, columns: &Bound<'_, PyDict>) -> PyResult<PyObject> {
+        match self {
+            Self::Polars => Ok(PyModule::import(py, "polars")?
+                .getattr("DataFrame")?
+                .call1((columns,))?
+                .unbind()),
+            Self::Arrow => Ok(PyModule::import(py, "pyarrow")?
+                .getattr("RecordBatch")?
+                .call_method1("from_pydict", (columns,))?
+                .unbind()),
+            Self::List => unreachable!("lists are not converted to tables"),
+        }
+    }
+}
+
+/// Returns the values of `keys` of all `records` as columns
+///
+/// # Errors
+///
+/// - PyKeyError: A record does not contain one of `keys`
+pub(crate) fn columns<'py>(
+    py: Python<'py>,
+    records: &[Bound<'py, PyDict>],
+    keys: &[&str],
+) -> PyResult<Bound<'py, PyDict>> {
+    let columns = PyDict::new(py);
+    for key in keys {
+        let values = records
+            .iter()
+            .map(|record| record.as_any().get_item(key))
+            .collect::<PyResult<Vec<_>>>()?;
+        columns.set_item(key, values)?;
+    }
+    Ok(columns)
+}
+
+/// Returns the `records` as columns
+///
+/// The `item` of every record, e.g. a gene or disease, is replaced by
+/// its integer `id` and its `name`, followed by the values of `keys`.
+///
+/// # Errors
+///
+/// - PyKeyError: A record does not contain `item` or one of `keys`
+pub(crate) fn record_columns<'py>(
+    py: Python<'py>,
+    records: &[Bound<'py, PyDict>],
+    keys: &[&str],
+) -> PyResult<Bound<'py, PyDict>> {
+    let ids = PyList::empty(py);
+    let names = PyList::empty(py);
+    for record in records {
+        let item = record.as_any().get_item("item")?;
+        ids.append(item.call_method0("__int__")?)?;
+        names.append(item.getattr("name")?)?;
+    }
+    let table = PyDict::new(py);
+    table.set_item("id", ids)?;
+    table.set_item("name", names)?;
+    table.update(columns(py, records, keys)?.as_mapping())?;
+    Ok(table)
+}
+
+/// A single value of a result row
+pub(crate) enum Value<'a> {
+    Str(Cow<'a, str>),
+    Int(u64),
+    Float(f64),
+}
+
+impl Value<'_> {
+    /// Returns the value as CSV field, quoting it if required
+    fn csv(&self) -> String {
+        match self {
+            Value::Str(text) if text.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            }
+            Value::Str(text) => text.to_string(),
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) => format!("{value:?}"),
+        }
+    }
+
+    /// Returns the value as Python object
+    fn to_object(&self, py: Python<'_>) -> PyResult<PyObject> {
+        match self {
+            Value::Str(text) => text.into_py_any(py),
+            Value::Int(value) => value.into_py_any(py),
+            Value::Float(value) => value.into_py_any(py),
+        }
+    }
+}
+
+/// The open output file of a [`TableWriter`]
+enum Sink {
+    Csv(BufWriter<File>),
+    /// `.npy` files only contain the float columns as `float32` array
+    Npy {
+        file: BufWriter<File>,
+        rows: u64,
+        width: usize,
+    },
+    /// The `ParquetWriter`, `None` until the first rows are written
+    Parquet(PyObject),
+}
+
+/// Writes rows of results to a CSV, `.npy` or Parquet file
+pub(crate) struct TableWriter {
+    path: PathBuf,
+    columns: Vec<String>,
+    sink: Sink,
+}
+
+impl TableWriter {
+    /// Creates the file at `path` and writes its header
+    ///
+    /// # Errors
+    ///
+    /// - PyOSError: The file cannot be written
+    /// - ImportError: `pyarrow` is not installed (Parquet only)
+    pub fn new(
+        py: Python<'_>,
+        path: PathBuf,
+        format: Format,
+        columns: impl IntoIterator<Item = impl Into<String>>,
+    ) -> PyResult<Self> {
+        let sink = match format {
+            Format::Parquet => {
+                // The schema is only known with the first rows, so the
+                // writer is created in `write`
+                PyModule::import(py, "pyarrow.parquet")?;
+                Sink::Parquet(py.None())
+            }
+            Format::Csv => Sink::Csv(BufWriter::new(create(&path)?)),
+            Format::Npy => Sink::Npy {
+                file: BufWriter::new(create(&path)?),
+                rows: 0,
+                width: 0,
+            },
+        };
+        let mut writer = Self {
+            path,
+            columns: columns.into_iter().map(Into::into).collect(),
+            sink,
+        };
+        match &mut writer.sink {
+            Sink::Csv(file) => {
+                let header = writer.columns.join(",");
+                writeln!(file, "{header}").map_err(|err| os_error(&writer.path, err))?;
+            }
+            Sink::Npy { file, .. } => {
+                file.write_all(&npy_header(0, 1))
+                    .map_err(|err| os_error(&writer.path, err))?;
+            }
+            Sink::Parquet(_) => {}
+        }
+        Ok(writer)
+    }
+
+    /// Appends `rows` to the file
+    ///
+    /// Every row must contain one value per column
+    ///
+    /// # Errors
+    ///
+    /// - PyOSError: The file cannot be written
+    pub fn write(&mut self, py: Python<'_>, rows: &[Vec<Value<'_>>]) -> PyResult<()> {
+        let path = &self.path;
+        match &mut self.sink {
+            Sink::Csv(file) => {
+                for row in rows {
+                    let line: Vec<String> = row.iter().map(Value::csv).collect();
+                    writeln!(file, "{}", line.join(",")).map_err(|err| os_error(path, err))?;
+                }
+            }
+            Sink::Npy {
+                file,
+                rows: count,
+                width,
+            } => {
+                for row in rows {
+                    let values: Vec<f32> = row
+                        .iter()
+                        .filter_map(|value| match value {
+                            Value::Float(value) => Some(*value as f32),
+                            Value::Str(_) | Value::Int(_) => None,
+                        })
+                        .collect();
+                    *width = values.len();
+                    for value in values {
+                        file.write_all(&value.to_le_bytes())
+                            .map_err(|err| os_error(path, err))?;
+                    }
+                }
+                *count += rows.len() as u64;
+            }
+            Sink::Parquet(writer) => {
+                let table = PyDict::new(py);
+                for (idx, column) in self.columns.iter().enumerate() {
+                    let values = rows
+                        .iter()
+                        .map(|row| row[idx].to_object(py))
+                        .collect::<PyResult<Vec<PyObject>>>()?;
+                    table.set_item(column, values)?;
+                }
+                let table = PyModule::import(py, "pyarrow")?
+                    .getattr("Table")?
+                    .call_method1("from_pydict", (table,))?;
+                if writer.is_none(py) {
+                    *writer = PyModule::import(py, "pyarrow.parquet")?
+                        .getattr("ParquetWriter")?
+                        .call1((path.clone(), table.getattr("schema")?))?
+                        .unbind();
+                }
+                writer.call_method1(py, "write_table", (table,))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Completes and closes the file
+    ///
+    /// # Errors
+    ///
+    /// - PyOSError: The file cannot be written
+    pub fn finish(self, py: Python<'_>) -> PyResult<()> {
+        let path = &self.path;
+        match self.sink {
+            Sink::Csv(mut file) => file.flush().map_err(|err| os_error(path, err)),
+            Sink::Npy {
+                mut file,
+                rows,
+                width,
+            } => file
+                .seek(SeekFrom::Start(0))
+                .and_then(|_| file.write_all(&npy_header(rows, width)))
+                .and_then(|()| file.flush())
+                .map_err(|err| os_error(path, err)),
+            Sink::Parquet(writer) => {
+                if writer.is_none(py) {
+                    // No rows were written, so the schema is still unknown
+                    let table = PyDict::new(py);
+                    for column in &self.columns {
+                        table.set_item(column, Vec::<PyObject>::new())?;
+                    }
+                    let table = PyModule::import(py, "pyarrow")?
+                        .getattr("Table")?
+                        .call_method1("from_pydict", (table,))?;
+                    PyModule::import(py, "pyarrow.parquet")?
+                        .call_method1("write_table", (table, path.clone()))?;
+                    return Ok(());
+                }
+                writer.call_method0(py, "close")?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Returns the `.npy` header of a `float32` array with `rows` rows
+///
+/// The array is one-dimensional, unless rows contain more than one value
+fn npy_header(rows: u64, width: usize) -> Vec<u8> {
+    let shape = if width > 1 {
+        format!("({rows}, {width})")
+    } else {
+        format!("({rows},)")
+    };
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend(((NPY_HEADER_LEN - 10) as u16).to_le_bytes());
+    header.extend(
+        format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {shape}, }}").as_bytes(),
+    );
+    header.resize(NPY_HEADER_LEN - 1, b' ');
+    header.push(b'\n');
+    header
+}
+
+fn create(path: &Path) -> PyResult<File> {
+    File::create(path).map_err(|err| os_error(path, err))
+}
+
+fn os_error(path: &Path, err: impl std::fmt::Display) -> PyErr {
+    PyOSError::new_err(format!("Unable to write {}: {err}", path.display()))
+}
@@ -0,0 +1,233 @@
+//! Export of the Ontology as OWL, in Turtle or functional syntax
+//!
+//! The exported ontology uses the same IRIs and annotation properties as
+//! the official `hp.owl`, so that it can be compared or merged with it:
+//!
+//! - Every term is an `owl:Class` with an `rdfs:label`
+//! - The hierarchy is expressed as `rdfs:subClassOf` axioms
+//! - Definitions use `IAO:0000115`, comments `rdfs:comment`
+//! - Synonyms and xrefs use `oboInOwl:hasExactSynonym` and `oboInOwl:hasDbXref`
+//! - Obsolete terms are `owl:deprecated`, their replacement is `IAO:0100001`
+//!
+//! Definitions, comments, synonyms and xrefs are only available if the
+//! Ontology was built from the JAX source files.
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+
+use hpo::annotations::AnnotationId;
+use hpo::{HpoTerm, Ontology};
+
+use crate::term_details::{term_details, TermDetails};
+
+const OBO: &str = "http://purl.obolibrary.org/obo/";
+
+/// The prefixes used in both syntaxes
+const PREFIXES: [(&str, &str); 6] = [
+    ("obo", OBO),
+    ("owl", "http://www.w3.org/2002/07/owl#"),
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+    ("xsd", "http://www.w3.org/2001/XMLSchema#"),
+    ("oboInOwl", "http://www.geneontology.org/formats/oboInOwl#"),
+];
+
+/// The annotation properties that are not part of OWL itself
+const ANNOTATION_PROPERTIES: [&str; 4] = [
+    "obo:IAO_0000115",
+    "obo:IAO_0100001",
+    "oboInOwl:hasDbXref",
+    "oboInOwl:hasExactSynonym",
+];
+
+/// The syntax of the exported OWL ontology
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Syntax {
+    Turtle,
+    Functional,
+}
+
+impl Syntax {
+    /// Returns the syntax for exporting to `path`
+    ///
+    /// If not specified explicitly, files with a `.ofn` extension
+    /// use the functional syntax, all others Turtle.
+    ///
+    /// # Errors
+    ///
+    /// - PyValueError: Invalid syntax
+    pub fn new(path: &Path, syntax: Option<&str>) -> PyResult<Self> {
+        match syntax {
+            Some("turtle") => Ok(Self::Turtle),
+            Some("functional") => Ok(Self::Functional),
+            Some(other) => Err(PyValueError::new_err(format!(
+                "Invalid OWL syntax {other}. Use `turtle` or `functional`"
+            ))),
+            None => match path.extension().and_then(|ext| ext.to_str()) {
+                Some("ofn") => Ok(Self::Functional),
+                _ => Ok(Self::Turtle),
+            },
+        }
+    }
+}
+
+/// Returns the prefixed IRI of a term, e.g. `obo:HP_0000118`
+fn iri(term: &HpoTerm) -> String {
+    format!("obo:HP_{:07}", term.id().as_u32())
+}
+
+/// Returns `text` as quoted literal, escaping quotes and backslashes
+///
+/// Line breaks are escaped as well in Turtle, the functional
+/// syntax allows them in literals.
+fn literal(text: &str, syntax: Syntax) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' if syntax == Syntax::Turtle => quoted.push_str("\\n"),
+            '\r' if syntax == Syntax::Turtle => quoted.push_str("\\r"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Returns the annotations of `term` as `(property, value)` pairs
+fn annotations(
+    term: &HpoTerm,
+    details: Option<&TermDetails>,
+    syntax: Syntax,
+) -> Vec<(&'static str, String)> {
+    let mut annotations = vec![("rdfs:label", literal(term.name(), syntax))];
+    if let Some(details) = details {
+        annotations.extend(
+            details
+                .definition
+                .iter()
+                .map(|definition| ("obo:IAO_0000115", literal(definition, syntax))),
+        );
+        annotations.extend(
+            details
+                .comment
+                .iter()
+                .map(|comment| ("rdfs:comment", literal(comment, syntax))),
+        );
+        annotations.extend(
+            details
+                .synonyms
+                .iter()
+                .map(|synonym| ("oboInOwl:hasExactSynonym", literal(synonym, syntax))),
+        );
+        annotations.extend(
+            details
+                .xrefs
+                .iter()
+                .map(|xref| ("oboInOwl:hasDbXref", literal(xref, syntax))),
+        );
+    }
+    if term.is_obsolete() {
+        annotations.push(("owl:deprecated", "\"true\"^^xsd:boolean".to_string()));
+    }
+    if let Some(replacement) = term.replaced_by() {
+        annotations.push(("obo:IAO_0100001", iri(&replacement)));
+    }
+    annotations
+}
+
+/// Returns the IRIs of the parents of `term`, sorted by ID
+fn parents(term: &HpoTerm) -> Vec<String> {
+    let mut parents: Vec<HpoTerm> = term.parents().collect();
+    parents.sort_by_key(|parent| parent.id());
+    parents.iter().map(iri).collect()
+}
+
+/// Appends the Turtle statements of `term`
+fn write_turtle_term(owl: &mut String, term: &HpoTerm) {
+    let details = term_details(term.id().as_u32());
+
+    // writing to a `String` cannot fail
+    let _ = write!(owl, "{} a owl:Class", iri(term));
+    for (property, value) in annotations(term, details, Syntax::Turtle) {
+        let _ = write!(owl, " ;\n    {property} {value}");
+    }
+    for parent in parents(term) {
+        let _ = write!(owl, " ;\n    rdfs:subClassOf {parent}");
+    }
+    owl.push_str(" .\n\n");
+}
+
+/// Appends the axioms of `term` in functional syntax
+fn write_functional_term(owl: &mut String, term: &HpoTerm) {
+    let iri = iri(term);
+    let details = term_details(term.id().as_u32());
+
+    let _ = writeln!(owl, "Declaration(Class({iri}))");
+    for (property, value) in annotations(term, details, Syntax::Functional) {
+        let _ = writeln!(owl, "AnnotationAssertion({property} {iri} {value})");
+    }
+    for parent in parents(term) {
+        let _ = writeln!(owl, "SubClassOf({iri} {parent})");
+    }
+}
+
+/// Returns the Ontology in the OWL `syntax`
+pub(crate) fn as_owl(ont: &Ontology, syntax: Syntax) -> String {
+    let ontology_iri = format!("<{OBO}hp.owl>");
+    let version_iri = format!("<{OBO}hp/releases/{}/hp.owl>", ont.hpo_version());
+
+    let mut terms: Vec<HpoTerm> = ont.iter().collect();
+    terms.sort_by_key(|term| term.id());
+
+    let mut owl = String::new();
+    match syntax {
+        Syntax::Turtle => {
+            for (prefix, namespace) in PREFIXES {
+                let _ = writeln!(owl, "@prefix {prefix}: <{namespace}> .");
+            }
+            let _ = writeln!(
+                owl,
+                "\n{ontology_iri} a owl:Ontology ;\n    owl:versionIRI {version_iri} .\n"
+            );
+            for property in ANNOTATION_PROPERTIES {
+                let _ = writeln!(owl, "{property} a owl:AnnotationProperty .");
+            }
+            owl.push('\n');
+            for term in &terms {
+                write_turtle_term(&mut owl, term);
+            }
+        }
+        Syntax::Functional => {
+            for (prefix, namespace) in PREFIXES {
+                let _ = writeln!(owl, "Prefix({prefix}:=<{namespace}>)");
+            }
+            let _ = writeln!(owl, "\nOntology({ontology_iri} {version_iri}");
+            for property in ANNOTATION_PROPERTIES {
+                let _ = writeln!(owl, "Declaration(AnnotationProperty({property}))");
+            }
+            for term in &terms {
+                write_functional_term(&mut owl, term);
+            }
+            owl.push_str(")\n");
+        }
+    }
+    owl
+}
+
+/// Writes the Ontology in the OWL `syntax` to `path`
+///
+/// # Errors
+///
+/// - PyOSError: The file cannot be written
+pub(crate) fn save(ont: &Ontology, path: &Path, syntax: Syntax) -> PyResult<()> {
+    fs::write(path, as_owl(ont, syntax))
+        .map_err(|err| PyOSError::new_err(format!("Unable to write {}: {err}", path.display())))
+}
@@ -0,0 +1,441 @@
+use rayon::prelude::*;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyType};
+use pyo3::IntoPyObjectExt;
+
+use hpo::annotations::Disease;
+use hpo::similarity::{GroupSimilarity, StandardCombiner};
+use hpo::term::HpoGroup;
+use hpo::HpoSet;
+
+use crate::annotations::{PyGene, PyOmimDisease, PyOrphaDisease};
+use crate::get_ontology;
+use crate::output::{record_columns, TableOutput};
+use crate::set::PyHpoSet;
+use crate::similarity_cache;
+use crate::worker;
+
+/// A ranked annotation and its similarity score
+type Ranked = Vec<(PyObject, f32)>;
+
+/// A patient with an ID, an ``HPOSet`` of phenotypes and metadata
+///
+/// Parameters
+/// ----------
+/// id: str
+///     The identifier of the patient
+/// hpo_set: :class:`pyhpo.HPOSet`
+///     The phenotypes of the patient
+/// metadata: dict, optional
+///     Any additional data, e.g. sex, age or diagnosis
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, HPOSet, Patient
+///
+///     Ontology()
+///     patient = Patient(
+///         "P1",
+///         HPOSet.from_queries(["HP:0002650", "HP:0001166", "HP:0001083"]),
+///         {"sex": "female"},
+///     )
+///     patient.rank_diseases(limit=3)
+///     # >> [(<OmimDisease (154700)>, 0.8...), ...]
+///
+#[pyclass(name = "Patient", module = "pyhpo")]
+pub(crate) struct PyPatient {
+    id: String,
+    hpo_set: PyHpoSet,
+    metadata: Py<PyDict>,
+}
+
+#[pymethods]
+impl PyPatient {
+    #[new]
+    #[pyo3(signature = (id, hpo_set, metadata = None))]
+    fn new(
+        py: Python<'_>,
+        id: String,
+        hpo_set: PyHpoSet,
+        metadata: Option<Bound<'_, PyDict>>,
+    ) -> Self {
+        Self {
+            id,
+            hpo_set,
+            metadata: metadata.unwrap_or_else(|| PyDict::new(py)).unbind(),
+        }
+    }
+
+    /// The identifier of the patient
+    ///
+    /// Returns
+    /// -------
+    /// str
+    ///     The ID of the patient
+    #[getter(id)]
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The phenotypes of the patient
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOSet`
+    ///     The ``HPOSet`` of the patient
+    #[getter(hpo_set)]
+    fn hpo_set(&self) -> PyHpoSet {
+        self.hpo_set.clone()
+    }
+
+    #[setter(hpo_set)]
+    fn set_hpo_set(&mut self, hpo_set: PyHpoSet) {
+        self.hpo_set = hpo_set;
+    }
+
+    /// Additional data of the patient
+    ///
+    /// The dict can be modified in place
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///     The metadata of the patient
+    #[getter(metadata)]
+    fn metadata<'py>(&self, py: Python<'py>) -> Bound<'py, PyDict> {
+        self.metadata.bind(py).clone()
+    }
+
+    /// Calculate the similarity to another patient
+    ///
+    /// Parameters
+    /// ----------
+    /// other: :class:`pyhpo.Patient`
+    ///     The patient to compare to
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use for similarity calculation
+    /// method: str, default ``graphic``
+    ///     The method to use to calculate the similarity.
+    ///     See :func:`pyhpo.HPOSet.similarity` for available options
+    /// combine: str, default ``funSimAvg``
+    ///     The method to combine individual term similarity
+    ///     to HPOSet similarities.
+    /// on_empty: str, default ``zero``
+    ///     The similarity if one of the patients has no phenotypes
+    ///
+    /// Returns
+    /// -------
+    /// float
+    ///     The similarity of the ``HPOSet`` of both patients
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// AttributeError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method`` or ``combine``
+    /// ValueError
+    ///     Invalid ``on_empty`` or no phenotypes with ``on_empty="raise"``
+    ///
+    #[pyo3(signature = (other, kind = "omim", method = "graphic", combine = "funSimAvg", on_empty = "zero"))]
+    #[pyo3(text_signature = "($self, other, kind, method, combine, on_empty)")]
+    fn similarity(
+        &self,
+        other: &PyPatient,
+        kind: &str,
+        method: &str,
+        combine: &str,
+        on_empty: &str,
+    ) -> PyResult<f32> {
+        self.hpo_set
+            .set_similarity(&other.hpo_set, kind, method, combine, on_empty)
+    }
+
+    /// Returns the diseases that are most similar to the patient
+    ///
+    /// The ``HPOSet`` of the patient is compared to the annotated terms of
+    /// every disease in parallel. Diseases without annotations have a
+    /// similarity of ``0.0``.
+    ///
+    /// Parameters
+    /// ----------
+    /// source: str, default: ``omim``
+    ///     The diseases to rank, ``omim`` or ``orpha``
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use for similarity calculation
+    /// method: str, default ``graphic``
+    ///     The method to use to calculate the similarity.
+    ///     See :func:`pyhpo.HPOSet.similarity` for available options
+    /// combine: str, default ``funSimAvg``
+    ///     The method to combine individual term similarity
+    ///     to HPOSet similarities.
+    /// limit: int, optional, default: ``10``
+    ///     The number of diseases to return, ``None`` returns all diseases
+    /// output: str, default ``list``
+    ///     The type of the results, ``list``, ``polars`` for a
+    ///     ``polars.DataFrame`` or ``arrow`` for a ``pyarrow.RecordBatch``.
+    ///     Tables have the columns ``id``, ``name`` and ``similarity``
+    ///
+    /// Returns
+    /// -------
+    /// list[tuple[:class:`pyhpo.Omim` | :class:`pyhpo.Orpha`, float]] or polars.DataFrame or pyarrow.RecordBatch
+    ///     The diseases and their similarity, sorted from most to least similar
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method`` or ``combine``
+    /// ValueError
+    ///     Invalid ``source`` or ``output``
+    /// ImportError
+    ///     ``polars`` or ``pyarrow`` is not installed for the ``output``
+    ///
+    #[pyo3(signature = (source = "omim", kind = "omim", method = "graphic", combine = "funSimAvg", limit = Some(10), output = "list"))]
+    #[pyo3(text_signature = "($self, source, kind, method, combine, limit, output)")]
+    #[allow(clippy::too_many_arguments)]
+    fn rank_diseases(
+        &self,
+        py: Python<'_>,
+        source: &str,
+        kind: &str,
+        method: &str,
+        combine: &str,
+        limit: Option<usize>,
+        output: &str,
+    ) -> PyResult<PyObject> {
+        let ont = get_ontology()?;
+        let output = TableOutput::try_from(output)?;
+        let ranked: Ranked = match source {
+            "omim" => {
+                let diseases: Vec<_> = ont.omim_diseases().collect();
+                rank(
+                    py,
+                    &self.hpo_set,
+                    &diseases,
+                    |disease| disease.hpo_terms(),
+                    kind,
+                    method,
+                    combine,
+                    limit,
+                )?
+                .into_iter()
+                .map(|(idx, score)| {
+                    Ok((PyOmimDisease::from(diseases[idx]).into_py_any(py)?, score))
+                })
+                .collect()
+            }
+            "orpha" => {
+                let diseases: Vec<_> = ont.orpha_diseases().collect();
+                rank(
+                    py,
+                    &self.hpo_set,
+                    &diseases,
+                    |disease| disease.hpo_terms(),
+                    kind,
+                    method,
+                    combine,
+                    limit,
+                )?
+                .into_iter()
+                .map(|(idx, score)| {
+                    Ok((PyOrphaDisease::from(diseases[idx]).into_py_any(py)?, score))
+                })
+                .collect()
+            }
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid source {source}. Use `omim` or `orpha`"
+            ))),
+        }?;
+        ranked_output(py, ranked, output)
+    }
+
+    /// Returns the genes that are most similar to the patient
+    ///
+    /// The ``HPOSet`` of the patient is compared to the annotated terms of
+    /// every gene in parallel.
+    ///
+    /// Parameters
+    /// ----------
+    /// kind: str, default: ``gene``
+    ///     Which kind of information content to use for similarity calculation
+    /// method: str, default ``graphic``
+    ///     The method to use to calculate the similarity.
+    ///     See :func:`pyhpo.HPOSet.similarity` for available options
+    /// combine: str, default ``funSimAvg``
+    ///     The method to combine individual term similarity
+    ///     to HPOSet similarities.
+    /// limit: int, optional, default: ``10``
+    ///     The number of genes to return, ``None`` returns all genes
+    /// output: str, default ``list``
+    ///     The type of the results, ``list``, ``polars`` for a
+    ///     ``polars.DataFrame`` or ``arrow`` for a ``pyarrow.RecordBatch``.
+    ///     Tables have the columns ``id``, ``name`` and ``similarity``
+    ///
+    /// Returns
+    /// -------
+    /// list[tuple[:class:`pyhpo.Gene`, float]] or polars.DataFrame or pyarrow.RecordBatch
+    ///     The genes and their similarity, sorted from most to least similar
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method`` or ``combine``
+    /// ValueError
+    ///     Invalid ``output``
+    /// ImportError
+    ///     ``polars`` or ``pyarrow`` is not installed for the ``output``
+    ///
+    #[pyo3(signature = (kind = "gene", method = "graphic", combine = "funSimAvg", limit = Some(10), output = "list"))]
+    #[pyo3(text_signature = "($self, kind, method, combine, limit, output)")]
+    fn rank_genes(
+        &self,
+        py: Python<'_>,
+        kind: &str,
+        method: &str,
+        combine: &str,
+        limit: Option<usize>,
+        output: &str,
+    ) -> PyResult<PyObject> {
+        let ont = get_ontology()?;
+        let output = TableOutput::try_from(output)?;
+        let genes: Vec<_> = ont.genes().collect();
+        let ranked: Ranked = rank(
+            py,
+            &self.hpo_set,
+            &genes,
+            |gene| gene.hpo_terms(),
+            kind,
+            method,
+            combine,
+            limit,
+        )?
+        .into_iter()
+        .map(|(idx, score)| Ok((PyGene::from(genes[idx]).into_py_any(py)?, score)))
+        .collect::<PyResult<_>>()?;
+        ranked_output(py, ranked, output)
+    }
+
+    /// The patient is pickled with its ID, ``HPOSet`` and metadata
+    fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> (Bound<'py, PyType>, (String, PyHpoSet, Bound<'py, PyDict>)) {
+        (
+            py.get_type::<Self>(),
+            (self.id.clone(), self.hpo_set.clone(), self.metadata(py)),
+        )
+    }
+
+    fn __len__(&self) -> usize {
+        self.hpo_set.__len__()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Patient ({}) with {} terms>", self.id, self.__len__())
+    }
+}
+
+impl PyPatient {
+    /// Returns a patient that shares the `metadata` dict
+    pub(crate) fn from_parts(id: String, hpo_set: PyHpoSet, metadata: Py<PyDict>) -> Self {
+        Self {
+            id,
+            hpo_set,
+            metadata,
+        }
+    }
+
+    /// Returns the ID, `HPOSet` and metadata of the patient
+    pub(crate) fn parts(&self, py: Python<'_>) -> (String, PyHpoSet, Py<PyDict>) {
+        (
+            self.id.clone(),
+            self.hpo_set.clone(),
+            self.metadata.clone_ref(py),
+        )
+    }
+}
+
+/// Returns the `ranked` annotations as list or table of `output`
+///
+/// # Errors
+///
+/// - ImportError: `polars` or `pyarrow` is not installed
+fn ranked_output(py: Python<'_>, ranked: Ranked, output: TableOutput) -> PyResult<PyObject> {
+    if output == TableOutput::List {
+        return ranked.into_py_any(py);
+    }
+    let records = ranked
+        .into_iter()
+        .map(|(item, score)| {
+            let record = PyDict::new(py);
+            record.set_item("item", item)?;
+            record.set_item("similarity", score)?;
+            Ok(record)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    output.table(py, &record_columns(py, &records, &["similarity"])?)
+}
+
+/// Returns the index and similarity of the `limit` most similar `candidates`
+///
+/// # Errors
+///
+/// - PyNameError: Ontology not yet constructed
+/// - PyKeyError: Invalid `kind`
+/// - PyRuntimeError: Invalid `method` or `combine`
+#[allow(clippy::too_many_arguments)]
+fn rank<A, F>(
+    py: Python<'_>,
+    hpo_set: &PyHpoSet,
+    candidates: &[A],
+    terms: F,
+    kind: &str,
+    method: &str,
+    combine: &str,
+    limit: Option<usize>,
+) -> PyResult<Vec<(usize, f32)>>
+where
+    A: Sync,
+    F: Fn(&A) -> &HpoGroup + Sync,
+{
+    let ont = get_ontology()?;
+    let similarity = similarity_cache::builtin(method, kind)?;
+    let combiner = StandardCombiner::try_from(combine)
+        .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+    let g_sim = GroupSimilarity::new(combiner, similarity);
+    let set_a = hpo_set.set(ont);
+
+    let mut scores: Vec<(usize, f32)> = worker::install(py, || {
+        candidates
+            .par_iter()
+            .enumerate()
+            .map(|(idx, candidate)| {
+                let set_b = HpoSet::new(ont, terms(candidate).clone());
+                let score = if set_a.is_empty() || set_b.is_empty() {
+                    0.0
+                } else {
+                    g_sim.calculate(&set_a, &set_b)
+                };
+                (idx, score)
+            })
+            .collect()
+    });
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    scores.truncate(limit.unwrap_or(scores.len()));
+    Ok(scores)
+}
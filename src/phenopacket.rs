@@ -0,0 +1,250 @@
+//! Import of GA4GH phenopackets
+//!
+//! Supports single `Phenopacket`, `Family` and `Cohort` messages in the
+//! JSON format of the phenopacket schema (v1 and v2). Only the observed
+//! phenotypic features are used, excluded (or negated) features are ignored.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use serde::Deserialize;
+
+use pyo3::exceptions::{PyKeyError, PyOSError, PyValueError};
+use pyo3::prelude::*;
+
+use hpo::term::HpoGroup;
+use hpo::HpoTermId;
+
+use crate::get_ontology;
+use crate::loader::Source;
+use crate::set::PyHpoSet;
+use crate::worker;
+
+/// File extension of phenopackets in a directory
+const EXTENSION: &str = "json";
+
+/// A phenopacket, family or cohort message
+///
+/// The fields of all message types are combined, so that the
+/// type of the message does not have to be known in advance
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Message {
+    #[serde(default)]
+    id: String,
+    subject: Option<Subject>,
+    #[serde(default)]
+    phenotypic_features: Vec<Feature>,
+    proband: Option<Box<Message>>,
+    #[serde(default)]
+    relatives: Vec<Message>,
+    #[serde(default)]
+    members: Vec<Message>,
+}
+
+#[derive(Deserialize)]
+struct Subject {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct Feature {
+    #[serde(rename = "type")]
+    term: OntologyClass,
+    #[serde(default, alias = "negated")]
+    excluded: bool,
+}
+
+#[derive(Deserialize)]
+struct OntologyClass {
+    id: String,
+}
+
+impl Message {
+    /// Returns all phenopackets of the message
+    ///
+    /// Families and cohorts return the phenopackets of all their
+    /// members, a phenopacket returns itself
+    fn phenopackets(self) -> Vec<Message> {
+        if self.proband.is_none() && self.relatives.is_empty() && self.members.is_empty() {
+            return vec![self];
+        }
+        self.proband
+            .map(|proband| *proband)
+            .into_iter()
+            .chain(self.relatives)
+            .chain(self.members)
+            .flat_map(Message::phenopackets)
+            .collect()
+    }
+
+    /// Returns the ID of the subject, or of the phenopacket if
+    /// it does not contain a subject
+    fn subject_id(&self) -> String {
+        self.subject
+            .as_ref()
+            .map_or_else(|| self.id.clone(), |subject| subject.id.clone())
+    }
+
+    /// Returns the `(subject_id, HPOSet)` of a phenopacket
+    ///
+    /// # Errors
+    ///
+    /// - PyNameError: Ontology not yet constructed
+    /// - PyKeyError: Invalid or unknown HPO term, unless `skip_invalid` is set
+    fn hpo_set(&self, skip_invalid: bool) -> PyResult<(String, PyHpoSet)> {
+        let ont = get_ontology()?;
+        let subject = self.subject_id();
+        let mut ids = HpoGroup::new();
+        for feature in self.phenotypic_features.iter().filter(|f| !f.excluded) {
+            let id = HpoTermId::try_from(feature.term.id.as_str())
+                .ok()
+                .filter(|id| ont.hpo(*id).is_some());
+            match id {
+                Some(id) => {
+                    ids.insert(id);
+                }
+                None if skip_invalid => {}
+                None => {
+                    return Err(PyKeyError::new_err(format!(
+                        "Unknown HPO term {} in phenopacket of {subject}",
+                        feature.term.id
+                    )))
+                }
+            }
+        }
+        Ok((subject, ids.into_iter().collect()))
+    }
+}
+
+/// Parses all phenopackets of a JSON message
+///
+/// # Errors
+///
+/// - PyValueError: The message is not valid phenopacket JSON
+fn parse(bytes: &[u8], name: &str) -> PyResult<Vec<Message>> {
+    serde_json::from_slice::<Message>(bytes)
+        .map(Message::phenopackets)
+        .map_err(|err| PyValueError::new_err(format!("Invalid phenopacket {name}: {err}")))
+}
+
+/// Returns all phenopacket files of a directory, sorted by name
+///
+/// # Errors
+///
+/// - PyOSError: The directory cannot be read
+fn files(dir: &Path) -> PyResult<Vec<PathBuf>> {
+    let read_error = |err: std::io::Error| {
+        PyOSError::new_err(format!("Unable to read {}: {err}", dir.display()))
+    };
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(read_error)? {
+        let path = entry.map_err(read_error)?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == EXTENSION) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Loads all phenopackets of a directory in parallel
+fn from_dir(py: Python<'_>, dir: &Path, skip_invalid: bool) -> PyResult<Vec<(String, PyHpoSet)>> {
+    let files = files(dir)?;
+    let subjects: Vec<PyResult<Vec<(String, PyHpoSet)>>> = worker::install(py, || {
+        files
+            .par_iter()
+            .map(|path| {
+                let bytes = fs::read(path).map_err(|err| {
+                    PyOSError::new_err(format!("Unable to read {}: {err}", path.display()))
+                })?;
+                parse(&bytes, &path.display().to_string())?
+                    .iter()
+                    .map(|packet| packet.hpo_set(skip_invalid))
+                    .collect()
+            })
+            .collect()
+    });
+    let mut sets = Vec::with_capacity(subjects.len());
+    for subject in subjects {
+        sets.extend(subject?);
+    }
+    Ok(sets)
+}
+
+/// Loads the ``HPOSet`` of all subjects of GA4GH phenopackets
+///
+/// Supports ``Phenopacket``, ``Family`` and ``Cohort`` messages in JSON
+/// format, or a directory with one ``.json`` file per message. All files
+/// are parsed and validated in parallel. The ``HPOSet`` of each subject
+/// contains the observed ``phenotypicFeatures``, excluded features
+/// are ignored.
+///
+/// Parameters
+/// ----------
+/// source: str, bytes or file-like
+///     Path to a phenopacket JSON file or a directory of them,
+///     or the content of a single JSON message
+/// skip_invalid: bool, default: ``False``
+///     Ignore features with an invalid or unknown HPO term,
+///     instead of raising a ``KeyError``
+///
+/// Returns
+/// -------
+/// list[tuple[str, :class:`pyhpo.HPOSet`]]
+///     The ID of the subject (or of the phenopacket, if it has no
+///     subject) and the ``HPOSet`` of its phenotypic features.
+///     Family members are returned in the order proband, relatives.
+///     Files in a directory are sorted by name.
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+/// ValueError
+///     Invalid phenopacket JSON
+/// KeyError
+///     A feature is not a known HPO term and ``skip_invalid`` is not set
+/// OSError
+///     The directory or a file cannot be read
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, helper
+///
+///     Ontology()
+///
+///     cohort = helper.load_phenopackets("cohort.json")
+///     for subject, hpo_set in cohort:
+///         print(subject, hpo_set.serialize())
+///
+///     # one phenopacket file per patient
+///     patients = helper.load_phenopackets("/path/to/phenopackets/")
+///
+#[pyfunction]
+#[pyo3(signature = (source, skip_invalid = false))]
+#[pyo3(text_signature = "(source, skip_invalid)")]
+pub(crate) fn load_phenopackets(
+    py: Python<'_>,
+    source: Source,
+    skip_invalid: bool,
+) -> PyResult<Vec<(String, PyHpoSet)>> {
+    get_ontology()?;
+    if let Source::Path(path) = &source {
+        if path.is_dir() {
+            return from_dir(py, path, skip_invalid);
+        }
+    }
+    let bytes = source.read(py)?;
+    let name = source.filename(py, "phenopacket");
+    let packets = parse(&bytes, &name)?;
+    worker::install(py, || {
+        packets
+            .par_iter()
+            .map(|packet| packet.hpo_set(skip_invalid))
+            .collect()
+    })
+}
@@ -0,0 +1,400 @@
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rayon::prelude::*;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use hpo::similarity::{GroupSimilarity, StandardCombiner};
+use hpo::term::HpoTermId;
+use hpo::{HpoTerm, Ontology};
+
+use crate::get_ontology;
+use crate::ontology::branch_from_name;
+use crate::set::PyHpoSet;
+use crate::similarity::IcSource;
+use crate::similarity_cache;
+use crate::worker;
+
+/// A small and fast pseudo-random number generator (SplitMix64)
+///
+/// It is not cryptographically secure, but good enough for sampling
+/// and produces the same numbers for the same seed on all platforms.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator for the `stream`-th sequence of `seed`
+    ///
+    /// Parallel tasks use different streams of the same seed, so that
+    /// the results do not depend on the number of threads.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self(seed ^ stream.wrapping_mul(0xD1B5_4A32_D192_ED03));
+        rng.next_u64();
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.0)
+    }
+
+    /// Returns a random index below `len`
+    pub fn index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+}
+
+/// Scrambles the bits of `z`, so that similar inputs result in unrelated outputs
+pub(crate) fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Returns `seed` or a seed based on the current time
+fn seed_or_random(seed: Option<u64>) -> u64 {
+    seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64)
+    })
+}
+
+/// Draws `size` distinct terms, one from each of the `pools`
+fn draw(rng: &mut Rng, pools: &[&[HpoTermId]]) -> PyHpoSet {
+    let mut ids = HashSet::with_capacity(pools.len());
+    for pool in pools {
+        loop {
+            let id = pool[rng.index(pool.len())];
+            if ids.insert(id) {
+                break;
+            }
+        }
+    }
+    ids.into_iter().collect()
+}
+
+/// The candidate terms of random sets, sorted by information content
+struct Candidates {
+    ics: Vec<f32>,
+    ids: Vec<HpoTermId>,
+}
+
+impl Candidates {
+    /// Collects all non-obsolete terms of the ``Phenotypic abnormality`` branch
+    fn new(ont: &Ontology, source: &IcSource) -> PyResult<Self> {
+        let branch = HpoTermId::from(branch_from_name("phenotypic_abnormality")?);
+        let mut candidates: Vec<(f32, HpoTermId)> = ont
+            .into_iter()
+            .filter(|term| !term.is_obsolete() && term.all_parent_ids().contains(&branch))
+            .map(|term| (source.ic(&term), term.id()))
+            .collect();
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        Ok(Self {
+            ics: candidates.iter().map(|(ic, _)| *ic).collect(),
+            ids: candidates.into_iter().map(|(_, id)| id).collect(),
+        })
+    }
+
+    /// Returns one pool of terms for each term of a random set
+    ///
+    /// Without a `reference`, all terms are drawn from all candidates.
+    /// Otherwise each term is drawn from the IC bin of one term of `reference`.
+    fn pools(
+        &self,
+        ont: &Ontology,
+        source: &IcSource,
+        size: usize,
+        reference: Option<&PyHpoSet>,
+        bins: usize,
+    ) -> PyResult<Vec<&[HpoTermId]>> {
+        if bins == 0 {
+            return Err(PyValueError::new_err("bins must be greater than 0"));
+        }
+        if size > self.ids.len() {
+            return Err(PyValueError::new_err(format!(
+                "A set cannot contain more than {} terms",
+                self.ids.len()
+            )));
+        }
+        let Some(reference) = reference else {
+            return Ok(vec![&self.ids[..]; size]);
+        };
+
+        let bin_size = self.ids.len().div_ceil(bins);
+        let upper: Vec<f32> = self
+            .ics
+            .chunks(bin_size)
+            .map(|chunk| chunk.last().copied().unwrap_or(f32::INFINITY))
+            .collect();
+        let pools: Vec<&[HpoTermId]> = reference
+            .set(ont)
+            .iter()
+            .map(|term: HpoTerm| {
+                let ic = source.ic(&term);
+                let bin = upper
+                    .iter()
+                    .position(|max| ic <= *max)
+                    .unwrap_or(upper.len() - 1);
+                self.ids.chunks(bin_size).nth(bin).expect("bin must exist")
+            })
+            .collect();
+
+        // Drawing distinct terms never terminates if a bin is too small
+        let too_small = pools
+            .iter()
+            .map(|pool| {
+                pools
+                    .iter()
+                    .filter(|other| other.as_ptr() == pool.as_ptr())
+                    .count()
+            })
+            .zip(&pools)
+            .find(|(draws, pool)| *draws > pool.len());
+        if let Some((draws, pool)) = too_small {
+            return Err(PyValueError::new_err(format!(
+                "Unable to draw {draws} distinct terms from an IC bin with {} terms, use fewer bins",
+                pool.len()
+            )));
+        }
+        Ok(pools)
+    }
+}
+
+/// Generates random ``HPOSet``, optionally matching the information
+/// content of the terms of another set
+///
+/// The terms are drawn from all non-obsolete terms of the
+/// ``Phenotypic abnormality`` branch. Random sets are the basis of
+/// empirical null distributions, e.g. to assess the significance of
+/// a similarity score.
+///
+/// To match the information content, all candidate terms are split into
+/// ``bins`` bins of equal size, sorted by their information content.
+/// Each term of the reference set is replaced by a random term of the
+/// same bin.
+///
+/// Parameters
+/// ----------
+/// n_sets: int
+///     The number of random sets
+/// size: int, optional
+///     The number of terms of each set. Defaults to the size of
+///     ``match_ic_distribution_of``
+/// match_ic_distribution_of: :class:`pyhpo.HPOSet`, optional
+///     The reference set. The terms of the random sets have the same
+///     distribution of information content
+/// kind: str, default: ``omim``
+///     Which kind of information content to match
+/// bins: int, default: ``20``
+///     The number of information content bins
+/// seed: int, optional
+///     Seed of the random number generator, for reproducible sets.
+///     The same seed always generates the same sets
+///
+/// Returns
+/// -------
+/// list[:class:`pyhpo.HPOSet`]
+///     The random sets
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+/// KeyError
+///     Invalid ``kind``
+/// ValueError
+///     Neither ``size`` nor ``match_ic_distribution_of`` is provided, ``size``
+///     differs from the size of ``match_ic_distribution_of``, a set cannot
+///     contain ``size`` distinct terms or ``bins`` is ``0``
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, Omim, helper
+///     Ontology()
+///
+///     patient = Omim.get(620402).hpo_set()
+///     null_sets = helper.random_sets(1000, match_ic_distribution_of=patient, seed=42)
+///     null_scores = [patient.similarity(s) for s in null_sets]
+///
+#[pyfunction]
+#[pyo3(signature = (n_sets, size = None, match_ic_distribution_of = None, kind = "omim", bins = 20, seed = None))]
+#[pyo3(text_signature = "(n_sets, size, match_ic_distribution_of, kind, bins, seed)")]
+pub(crate) fn random_sets(
+    py: Python<'_>,
+    n_sets: usize,
+    size: Option<usize>,
+    match_ic_distribution_of: Option<PyHpoSet>,
+    kind: &str,
+    bins: usize,
+    seed: Option<u64>,
+) -> PyResult<Vec<PyHpoSet>> {
+    let ont = get_ontology()?;
+    let source = IcSource::new(kind)?;
+    let size = match (size, &match_ic_distribution_of) {
+        (Some(size), Some(reference)) if size != reference.__len__() => {
+            return Err(PyValueError::new_err(
+                "size must be the same as the size of match_ic_distribution_of",
+            ))
+        }
+        (Some(size), _) => size,
+        (None, Some(reference)) => reference.__len__(),
+        (None, None) => {
+            return Err(PyValueError::new_err(
+                "Either size or match_ic_distribution_of must be provided",
+            ))
+        }
+    };
+    let candidates = Candidates::new(ont, &source)?;
+    let pools = candidates.pools(ont, &source, size, match_ic_distribution_of.as_ref(), bins)?;
+
+    let seed = seed_or_random(seed);
+    Ok(worker::install(py, || {
+        (0..n_sets)
+            .into_par_iter()
+            .map(|idx| draw(&mut Rng::new(seed, idx as u64), &pools))
+            .collect()
+    }))
+}
+
+/// Calculates the similarity of two ``HPOSet`` and its empirical significance
+///
+/// ``set1`` is compared to ``n_permutations`` random sets with the same
+/// size and distribution of information content (see
+/// :func:`pyhpo.helper.random_sets`). The similarity of these random
+/// sets to ``set2`` is the null distribution of the similarity score.
+/// All comparisons run in parallel.
+///
+/// Parameters
+/// ----------
+/// set1: :class:`pyhpo.HPOSet`
+///     The query set, e.g. the clinical features of a patient.
+///     The random sets are matched to this set
+/// set2: :class:`pyhpo.HPOSet`
+///     The target set, e.g. the features of a disease
+/// n_permutations: int, default: ``1000``
+///     The number of random sets
+/// kind: str, default: ``omim``
+///     Which kind of information content to use for similarity calculation
+///     and for matching the random sets
+/// method: str, default ``graphic``
+///     The method to use to calculate the similarity.
+///     See :func:`pyhpo.HPOSet.similarity` for all options
+/// combine: str, default ``funSimAvg``
+///     The method to combine individual term similarity
+///     to HPOSet similarities.
+///     See :func:`pyhpo.HPOSet.similarity` for all options
+/// bins: int, default: ``20``
+///     The number of information content bins to match the random sets
+/// seed: int, optional
+///     Seed of the random number generator, for reproducible results
+///
+/// Returns
+/// -------
+/// dict
+///     * **similarity** - The similarity of ``set1`` and ``set2``
+///     * **pvalue** - The fraction of random sets with the same or a higher
+///       similarity, calculated as ``(r + 1) / (n_permutations + 1)``
+///     * **zscore** - The number of standard deviations of the similarity
+///       above the mean of the null distribution
+///     * **null_mean** - The mean similarity of the random sets
+///     * **null_std** - The standard deviation of the similarity of the random sets
+///     * **n_permutations** - The number of random sets
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+/// KeyError
+///     Invalid ``kind``
+/// ValueError
+///     One of the sets is empty, ``n_permutations`` is ``0`` or
+///     ``set1`` cannot be matched by random sets
+/// RuntimeError
+///     Invalid ``method`` or ``combine``
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, HPOSet, Omim, helper
+///     Ontology()
+///
+///     patient = HPOSet.from_queries(["HP:0002650", "HP:0009121", "HP:0002808"])
+///     disease = Omim.get(620402).hpo_set()
+///     result = helper.similarity_significance(patient, disease, seed=42)
+///     result["pvalue"]
+///
+#[pyfunction]
+#[pyo3(signature = (set1, set2, n_permutations = 1000, kind = "omim", method = "graphic", combine = "funSimAvg", bins = 20, seed = None))]
+#[pyo3(text_signature = "(set1, set2, n_permutations, kind, method, combine, bins, seed)")]
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn similarity_significance<'py>(
+    py: Python<'py>,
+    set1: PyHpoSet,
+    set2: PyHpoSet,
+    n_permutations: usize,
+    kind: &str,
+    method: &str,
+    combine: &str,
+    bins: usize,
+    seed: Option<u64>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let ont = get_ontology()?;
+    let source = IcSource::new(kind)?;
+    if n_permutations == 0 {
+        return Err(PyValueError::new_err(
+            "n_permutations must be greater than 0",
+        ));
+    }
+    if set1.__len__() == 0 || set2.__len__() == 0 {
+        return Err(PyValueError::new_err(
+            "Unable to calculate the similarity of an empty HPOSet",
+        ));
+    }
+    let similarity = similarity_cache::builtin(method, kind)?;
+    let combiner = StandardCombiner::try_from(combine)
+        .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+    let g_sim = GroupSimilarity::new(combiner, similarity);
+
+    let candidates = Candidates::new(ont, &source)?;
+    let pools = candidates.pools(ont, &source, set1.__len__(), Some(&set1), bins)?;
+
+    let target = set2.set(ont);
+    let score = g_sim.calculate(&set1.set(ont), &target);
+    let seed = seed_or_random(seed);
+    let null: Vec<f32> = worker::install(py, || {
+        (0..n_permutations)
+            .into_par_iter()
+            .map(|idx| {
+                let random = draw(&mut Rng::new(seed, idx as u64), &pools);
+                g_sim.calculate(&random.set(ont), &target)
+            })
+            .collect()
+    });
+
+    let n = n_permutations as f64;
+    let mean = null.iter().map(|x| f64::from(*x)).sum::<f64>() / n;
+    let std = (null
+        .iter()
+        .map(|x| (f64::from(*x) - mean).powi(2))
+        .sum::<f64>()
+        / n)
+        .sqrt();
+    let exceeding = null.iter().filter(|x| **x >= score).count();
+
+    let dict = PyDict::new(py);
+    dict.set_item("similarity", score)?;
+    dict.set_item("pvalue", (exceeding + 1) as f64 / (n + 1.0))?;
+    dict.set_item("zscore", (f64::from(score) - mean) / std)?;
+    dict.set_item("null_mean", mean)?;
+    dict.set_item("null_std", std)?;
+    dict.set_item("n_permutations", n_permutations)?;
+    Ok(dict)
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+
+use hpo::HpoTermId;
+
+use crate::term::PyHpoTerm;
+use crate::worker;
+use crate::{get_ontology, PyQuery};
+
+/// Resolved terms, `None` for failed queries, and the `(index, error)` of all failed queries
+type Resolved = (Vec<Option<PyHpoTerm>>, Vec<(usize, String)>);
+
+/// Resolves a single query to the ID of a term
+///
+/// Behaves like `term_from_query`, but looks up names in the prebuilt `names` index
+///
+/// # Errors
+///
+/// - PyValueError: Invalid HPO-ID
+/// - PyRuntimeError: No term found for the query
+fn resolve_query(query: &PyQuery, names: &HashMap<String, HpoTermId>) -> PyResult<HpoTermId> {
+    let ont = get_ontology()?;
+    let id = match query {
+        PyQuery::Id(id) => Some(HpoTermId::from_u32(*id)),
+        PyQuery::Str(query) if query.starts_with("HP:") => Some(
+            HpoTermId::try_from(query.as_str())
+                .map_err(|_| PyValueError::new_err(format!("Invalid id: {}", query)))?,
+        ),
+        PyQuery::Str(query) => names.get(query).copied(),
+    };
+    id.filter(|id| ont.hpo(*id).is_some())
+        .ok_or_else(|| PyRuntimeError::new_err("Unknown HPO term"))
+}
+
+/// Resolves a large list of queries to ``HPOTerm`` in parallel
+///
+/// This method runs parallelized on all avaible CPU
+///
+/// The queries can be mixed HPO-IDs (e.g. ``HP:0000118``), integer IDs and
+/// term names, just like :func:`pyhpo.Ontology.get_hpo_object`. Instead of
+/// scanning all terms for every name, the names are looked up in an index
+/// that is built once for all queries.
+///
+/// Queries that cannot be resolved do not raise an error, but are returned
+/// as ``None`` and reported in the list of errors.
+///
+/// Parameters
+/// ----------
+/// queries: list[int | str]
+///     The queries to resolve
+///
+/// Returns
+/// -------
+/// tuple[list[:class:`pyhpo.HPOTerm` | None], list[tuple[int, str]]]
+///     The resolved terms in the same order as ``queries``, with ``None``
+///     for every failed query, and the ``(index, error)`` of all failed queries
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, helper
+///
+///     Ontology()
+///     terms, errors = helper.resolve([118, "HP:0002650", "Scoliosis", "Foo"])
+///     terms
+///     # >> [<HpoTerm (HP:0000118)>, <HpoTerm (HP:0002650)>, <HpoTerm (HP:0002650)>, None]
+///     errors
+///     # >> [(3, 'RuntimeError: Unknown HPO term')]
+///
+#[pyfunction]
+#[pyo3(text_signature = "(queries)")]
+pub(crate) fn resolve(py: Python<'_>, queries: Vec<Bound<'_, PyAny>>) -> PyResult<Resolved> {
+    let ont = get_ontology()?;
+    let mut names: HashMap<String, HpoTermId> = HashMap::with_capacity(ont.len());
+    for term in ont {
+        names.entry(term.name().to_string()).or_insert(term.id());
+    }
+
+    let queries: Vec<PyResult<PyQuery>> = queries
+        .iter()
+        .map(|query| {
+            query.extract().map_err(|_| {
+                PyTypeError::new_err(format!("Invalid query {query}, must be an int or str"))
+            })
+        })
+        .collect();
+
+    let resolved: Vec<PyResult<HpoTermId>> = worker::install(py, || {
+        queries
+            .into_par_iter()
+            .map(|query| resolve_query(&query?, &names))
+            .collect()
+    });
+
+    let mut terms = Vec::with_capacity(resolved.len());
+    let mut errors = Vec::new();
+    for (idx, id) in resolved.into_iter().enumerate() {
+        match id {
+            Ok(id) => {
+                let term = ont
+                    .hpo(id)
+                    .expect("resolved term must be present in Ontology");
+                terms.push(Some(PyHpoTerm::new(id, term.name().to_string())));
+            }
+            Err(err) => {
+                terms.push(None);
+                errors.push((idx, err.to_string()));
+            }
+        }
+    }
+    Ok((terms, errors))
+}
@@ -1,27 +1,44 @@
 use std::collections::{HashSet, VecDeque};
+use std::ffi::CString;
 use std::num::ParseIntError;
 
 use rayon::prelude::*;
 
-use pyo3::exceptions::{PyAttributeError, PyRuntimeError};
-use pyo3::types::PyDict;
-use pyo3::{prelude::*, types::PyType};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{
+    PyAttributeError, PyIndexError, PyKeyError, PyRuntimeError, PyUserWarning, PyValueError,
+};
+use pyo3::types::{PyDict, PyFloat, PyList, PySlice, PyString, PyTuple};
+use pyo3::{prelude::*, types::PyType, IntoPyObjectExt};
 
 use hpo::annotations::{AnnotationId, Disease};
-use hpo::similarity::{GroupSimilarity, StandardCombiner};
+use hpo::similarity::{Similarity, StandardCombiner};
 use hpo::Ontology;
 use hpo::{term::HpoGroup, HpoSet, HpoTermId};
 
 use crate::annotations::PyOrphaDisease;
-use crate::term::PyHpoTerm;
+use crate::batch::NanPolicy;
+use crate::compact;
+use crate::fhir;
+use crate::ontology::{branch_from_name, common_ancestor_ids, most_informative, term_depths};
+use crate::similarity::{Dtype, IcSource, Score};
+use crate::similarity_cache;
+use crate::term::{PyHpoTerm, TermJson};
+use crate::worker;
 use crate::{
     annotations::{PyGene, PyOmimDisease},
     get_ontology,
-    information_content::PyInformationContentKind,
+    information_content::{is_custom_kind, PyInformationContentKind},
+};
+use crate::{
+    pyterm_from_id, term_from_id, term_from_query, term_from_term_or_id, to_json_string, PyQuery,
+    TermOrId,
 };
-use crate::{pyterm_from_id, term_from_id, term_from_query, PyQuery, TermOrId};
 
-#[pyclass(name = "HPOSet")]
+/// Nodes and `(child, parent)` edges of a subgraph
+type Subgraph = (Vec<PyHpoTerm>, Vec<(u32, u32)>);
+
+#[pyclass(name = "HPOSet", module = "pyhpo")]
 #[derive(Clone)]
 pub(crate) struct PyHpoSet {
     ids: HpoGroup,
@@ -59,8 +76,10 @@ impl PyHpoSet {
     ///
     /// Parameters
     /// ----------
-    /// terms: List[int | :class:`pyhpo.HPOTerm`]
-    ///     The terms that make up the set
+    /// terms: List[int | :class:`pyhpo.HPOTerm`] or array of int
+    ///     The terms that make up the set. Integer IDs can also be provided as
+    ///     a numpy array or any other object supporting the buffer protocol,
+    ///     which is considerably faster for large sets.
     ///
     /// Returns
     /// -------
@@ -72,6 +91,8 @@ impl PyHpoSet {
     ///     Ontology not yet constructed
     /// KeyError
     ///     (only when ``int`` are used as input): HPOTerm does not exist
+    /// ValueError
+    ///     The array is not one-dimensional or contains negative IDs
     ///
     /// Examples
     /// --------
@@ -84,8 +105,21 @@ impl PyHpoSet {
     ///     len(s)  
     ///     # >> 2
     ///
+    ///     import numpy as np
+    ///     s = HPOSet(np.array([1, 118, 2650]))
+    ///     len(s)
+    ///     # >> 3
+    ///
     #[new]
-    fn new(terms: Vec<TermOrId>) -> PyResult<Self> {
+    fn new(terms: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Some(ids) = ids_from_buffer(terms)? {
+            if let Some(id) = ids.iter().find(|id| term_from_id(**id).is_err()) {
+                return Err(PyKeyError::new_err(format!("No HPOTerm for index {id}")));
+            }
+            return Ok(ids.into_iter().map(HpoTermId::from_u32).collect());
+        }
+
+        let terms: Vec<TermOrId> = terms.extract()?;
         let mut ids = HpoGroup::new();
         for id in terms {
             match id {
@@ -180,18 +214,35 @@ impl PyHpoSet {
 
     /// Returns a new HPOSet that does not contain any modifier terms
     ///
-    /// This method removes all terms that are not children of
-    /// ``HP:0000118 | Phenotypic abnormality``
+    /// By default, this method removes all terms that are not children of
+    /// ``HP:0000118 | Phenotypic abnormality``. Use ``remove`` or ``keep``
+    /// to select the top-level branches (see :func:`pyhpo.Ontology.branches`)
+    /// to remove or retain instead.
+    ///
+    /// Parameters
+    /// ----------
+    /// remove: list[str], optional
+    ///     Only remove terms of these branches, e.g.
+    ///     ``["frequency", "clinical_modifier"]``. All other terms are kept.
+    /// keep: list[str], optional
+    ///     Only keep terms of these branches, e.g.
+    ///     ``["phenotypic_abnormality", "mode_of_inheritance"]``.
+    ///     All other terms are removed.
     ///
     /// Returns
     /// -------
     /// :class:`pyhpo.HPOSet`
     ///     A new ``HPOSet`` that contains only phenotype terms
+    ///     or the terms of the selected branches
     ///
     /// Raises
     /// ------
     /// NameError
     ///     Ontology not yet constructed
+    /// KeyError
+    ///     Unknown branch name
+    /// ValueError
+    ///     Both ``remove`` and ``keep`` are specified
     ///
     /// Examples
     /// --------
@@ -213,11 +264,278 @@ impl PyHpoSet {
     ///     len(my_set) # >> 5
     ///     len(pheno_set) # >> 4
     ///
-    fn remove_modifier(&self) -> PyResult<Self> {
+    ///     # keep the mode of inheritance
+    ///     my_set.remove_modifier(keep=["phenotypic_abnormality", "mode_of_inheritance"])
+    ///
+    ///     # only remove frequency terms
+    ///     my_set.remove_modifier(remove=["frequency"])
+    ///
+    #[pyo3(signature = (remove = None, keep = None))]
+    #[pyo3(text_signature = "($self, remove, keep)")]
+    fn remove_modifier(
+        &self,
+        remove: Option<Vec<String>>,
+        keep: Option<Vec<String>>,
+    ) -> PyResult<Self> {
         let ont = get_ontology()?;
-        let mut new_set = HpoSet::new(ont, self.ids.clone());
-        new_set.remove_modifier();
-        Ok(new_set.into())
+        let (branches, keep) = match (remove, keep) {
+            (None, None) => {
+                let mut new_set = HpoSet::new(ont, self.ids.clone());
+                new_set.remove_modifier();
+                return Ok(new_set.into());
+            }
+            (Some(_), Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "`remove` and `keep` cannot be used together",
+                ))
+            }
+            (Some(remove), None) => (remove, false),
+            (None, Some(keep)) => (keep, true),
+        };
+        let branches = branches
+            .iter()
+            .map(|name| branch_from_name(name).map(HpoTermId::from))
+            .collect::<PyResult<Vec<HpoTermId>>>()?;
+
+        Ok(self
+            .ids
+            .iter()
+            .filter(|term_id| {
+                let term = ont
+                    .hpo(*term_id)
+                    .expect("term must be present in the ontology if it is included in the set");
+                let in_branch = branches
+                    .iter()
+                    .any(|branch| *branch == term.id() || term.all_parent_ids().contains(branch));
+                in_branch == keep
+            })
+            .collect())
+    }
+
+    /// Returns a new HPOSet with only the terms of a category
+    ///
+    /// A term is part of the category if it is the category term itself
+    /// or any of its descendants.
+    ///
+    /// Parameters
+    /// ----------
+    /// category: :class:`pyhpo.HPOTerm` or int
+    ///     The category term, e.g. ``HP:0001626 | Abnormality of the cardiovascular system``.
+    ///     Any term can be used, not only the default categories
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOSet`
+    ///     A new ``HPOSet`` that contains only terms of the category
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     ``category`` does not exist
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///
+    ///     my_set = HPOSet.from_queries([
+    ///         'HP:0001631',
+    ///         'HP:0001250',
+    ///         'HP:0030148',
+    ///     ])
+    ///
+    ///     my_set.filter_category(1626)
+    ///     # >> HPOSet.from_serialized(1631+30148)
+    ///
+    #[pyo3(text_signature = "($self, category)")]
+    fn filter_category(&self, category: TermOrId) -> PyResult<Self> {
+        let ont = get_ontology()?;
+        let category = term_from_term_or_id(category)?.id();
+        Ok(self
+            .ids
+            .iter()
+            .filter(|term_id| {
+                *term_id == category
+                    || ont
+                        .hpo(*term_id)
+                        .expect("term must be present in the ontology if it is included in the set")
+                        .all_parent_ids()
+                        .contains(&category)
+            })
+            .collect())
+    }
+
+    /// Returns a new HPOSet with all terms generalized to a given depth
+    ///
+    /// Each term that is deeper in the Ontology than ``level`` is replaced
+    /// by its ancestor(s) at that depth. The depth of a term is the length
+    /// of its shortest path to the root term (see
+    /// :func:`pyhpo.HPOTerm.shortest_path_to_root`), so the root term
+    /// is at level ``0``, ``HP:0000118 | Phenotypic abnormality`` at level ``1``
+    /// and the organ system categories at level ``2``.
+    ///
+    /// Terms at or above ``level`` are kept as they are. A term can have
+    /// multiple ancestors at the same level, all of them are included.
+    ///
+    /// Parameters
+    /// ----------
+    /// level: int
+    ///     The depth to map the terms to
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOSet`
+    ///     A new ``HPOSet`` with the generalized terms
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///
+    ///     my_set = HPOSet.from_queries([
+    ///         'HP:0001631',
+    ///         'HP:0001250',
+    ///         'HP:0030148',
+    ///     ])
+    ///
+    ///     for term in my_set.map_to_level(2):
+    ///         print(term.name)
+    ///     # >> Abnormality of the nervous system
+    ///     # >> Abnormality of the cardiovascular system
+    ///
+    #[pyo3(text_signature = "($self, level)")]
+    fn map_to_level(&self, level: usize) -> PyResult<Self> {
+        let ont = get_ontology()?;
+        let depths = term_depths()?;
+        let depth = |id: HpoTermId| depths.get(&id.as_u32()).copied().unwrap_or(usize::MAX);
+
+        let mut mapped = HpoGroup::new();
+        for term_id in &self.ids {
+            if depth(term_id) <= level {
+                mapped.insert(term_id);
+                continue;
+            }
+            let term = ont
+                .hpo(term_id)
+                .expect("term must be present in the ontology if it is included in the set");
+            for ancestor in term.all_parent_ids() {
+                if depth(ancestor) == level {
+                    mapped.insert(ancestor);
+                }
+            }
+        }
+        Ok(Self { ids: mapped })
+    }
+
+    /// Returns a new HPOSet with all terms mapped to a slim
+    ///
+    /// A slim is a reduced set of terms, e.g. the organ system categories.
+    /// Every term is replaced by its closest ancestors that are part of
+    /// the slim (GO-slim style). Terms that are part of the slim are kept.
+    /// If multiple slim terms have the same distance to a term, all of
+    /// them are included.
+    ///
+    /// Parameters
+    /// ----------
+    /// slim_terms: Iterable[:class:`pyhpo.HPOTerm` or int]
+    ///     The terms of the slim, e.g. a list or an ``HPOSet``
+    /// keep_unmapped: bool, default: ``False``
+    ///     Whether to keep terms that do not have any ancestor in the slim.
+    ///     By default, those terms are removed
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOSet`
+    ///     A new ``HPOSet`` with the mapped slim terms
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     A slim term does not exist
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///
+    ///     my_set = HPOSet.from_queries([
+    ///         'HP:0001631',
+    ///         'HP:0001250',
+    ///         'HP:0030148',
+    ///     ])
+    ///
+    ///     slim = [1626, 707, 1627]
+    ///     for term in my_set.map_to_slim(slim):
+    ///         print(term.name)
+    ///     # >> Abnormality of the nervous system
+    ///     # >> Abnormality of the cardiovascular system
+    ///     # >> Abnormal heart morphology
+    ///
+    #[pyo3(signature = (slim_terms, keep_unmapped = false))]
+    #[pyo3(text_signature = "($self, slim_terms, keep_unmapped)")]
+    fn map_to_slim(&self, slim_terms: &Bound<'_, PyAny>, keep_unmapped: bool) -> PyResult<Self> {
+        let ont = get_ontology()?;
+        let mut slim = HpoGroup::new();
+        for term in slim_terms.try_iter()? {
+            slim.insert(term_from_term_or_id(term?.extract::<TermOrId>()?)?.id());
+        }
+
+        let mut mapped = HpoGroup::new();
+        for term_id in &self.ids {
+            // Breadth-first search through the ancestors, until
+            // the first generation contains any slim terms
+            let mut generation = HpoGroup::new();
+            generation.insert(term_id);
+            let mut visited = generation.clone();
+            loop {
+                let hits: Vec<HpoTermId> =
+                    generation.iter().filter(|id| slim.contains(id)).collect();
+                if !hits.is_empty() {
+                    for hit in hits {
+                        mapped.insert(hit);
+                    }
+                    break;
+                }
+                let mut parents = HpoGroup::new();
+                for id in &generation {
+                    let term = ont.hpo(id).expect(
+                        "term must be present in the ontology if it is included in the set",
+                    );
+                    for parent in term.parent_ids() {
+                        if !visited.contains(&parent) {
+                            parents.insert(parent);
+                            visited.insert(parent);
+                        }
+                    }
+                }
+                if parents.is_empty() {
+                    if keep_unmapped {
+                        mapped.insert(term_id);
+                    }
+                    break;
+                }
+                generation = parents;
+            }
+        }
+        Ok(Self { ids: mapped })
     }
 
     /// Returns a new HPOSet that replaces all obsolete terms with
@@ -432,7 +750,7 @@ impl PyHpoSet {
         &'a self,
         py: Python<'a>,
         kind: &str,
-    ) -> PyResult<Bound<'_, PyDict>> {
+    ) -> PyResult<Bound<'a, PyDict>> {
         let kind = PyInformationContentKind::try_from(kind)?;
         let ont = get_ontology()?;
         let ics: Vec<f32> = self
@@ -448,7 +766,7 @@ impl PyHpoSet {
 
         let total: f32 = ics.iter().sum();
 
-        let dict = PyDict::new_bound(py);
+        let dict = PyDict::new(py);
         dict.set_item("mean", total / ics.len() as f32)?;
         dict.set_item("total", total)?;
         dict.set_item(
@@ -461,165 +779,861 @@ impl PyHpoSet {
         Ok(dict)
     }
 
-    /// Calculates the distances between all its term-pairs. It also provides
-    /// basic calculations for variances among the pairs.
+    /// Returns the induced subgraph of the set
+    ///
+    /// The subgraph contains all terms of the set and all of their
+    /// ancestors as nodes and all ``is_a`` relationships between them as edges.
+    /// It can be used to visualize the phenotype profile, e.g. with
+    /// ``networkx`` or ``cytoscape``.
     ///
     /// Returns
     /// -------
+    /// list[:class:`pyhpo.HPOTerm`]
+    ///     All nodes of the subgraph, sorted by ID
+    /// list[tuple[int, int]]
+    ///     All edges of the subgraph as ``(child, parent)`` tuples of term IDs
     ///
-    /// tuple of (int, int, int, list of int)
-    ///     Tuple with the variance metrices
-    ///
-    ///     * **float** Average distance between pairs
-    ///     * **int** Smallest distance between pairs
-    ///     * **int** Largest distance between pairs
-    ///     * **list of int** List of all distances between pairs
-    fn variance(&self) -> Self {
-        unimplemented!()
-    }
-
-    /// Helper generator function that returns all possible two-pair
-    /// combination between all its terms
-    ///
-    /// This function is direction dependent. That means that every
-    /// pair will appear twice. Once for each direction
-    ///
-    /// .. seealso:: :func:`pyhpo.HPOSet.combinations_one_way`
-    ///
-    /// Yields
+    /// Raises
     /// ------
-    /// Tuple of :class:`pyhpo.HPOTerm`
+    /// NameError
+    ///     Ontology not yet constructed
     ///
-    ///     Tuple containing the follow items
-    ///     * **HPOTerm** 1 of the pair
-    ///     * **HPOTerm** 2 of the pair
+    /// Examples
+    /// --------
     ///
-    fn combinations(&self) -> Self {
-        unimplemented!()
-    }
-
-    /// Helper generator function that returns all possible two-pair
-    /// combination between all its terms
+    /// .. code-block:: python
     ///
-    /// This methow will report each pair only once
+    ///     import networkx as nx
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
     ///
-    /// .. seealso:: :func:`pyhpo.HPOSet.combinations`
+    ///     my_set = HPOSet.from_queries([2650, 3])
+    ///     nodes, edges = my_set.subgraph()
     ///
-    /// Yields
-    /// ------
-    /// Tuple of :class:`term.HPOTerm`
-    ///     Tuple containing the follow items
+    ///     graph = nx.DiGraph()
+    ///     graph.add_nodes_from((int(term), {"name": term.name}) for term in nodes)
+    ///     graph.add_edges_from(edges)
     ///
-    ///     * **HPOTerm** instance 1 of the pair
-    ///     * **HPOTerm** instance 2 of the pair
-    fn combinations_one_way(&self) -> Self {
-        unimplemented!()
+    fn subgraph(&self) -> PyResult<Subgraph> {
+        let ont = get_ontology()?;
+        let mut node_ids = HpoGroup::new();
+        for id in &self.ids {
+            let term = ont
+                .hpo(id)
+                .expect("term must be present in the ontology if it is included in the set");
+            node_ids.insert(id);
+            for parent in term.all_parent_ids() {
+                node_ids.insert(parent);
+            }
+        }
+
+        let mut nodes = Vec::with_capacity(node_ids.len());
+        let mut edges = Vec::new();
+        for id in &node_ids {
+            let term = ont
+                .hpo(id)
+                .expect("term must be present in the ontology if it is a parent term");
+            for parent in term.parent_ids() {
+                edges.push((id.as_u32(), parent.as_u32()));
+            }
+            nodes.push(PyHpoTerm::from(term));
+        }
+        Ok((nodes, edges))
     }
 
-    /// Calculate similarity between this and another `HPOSet`
-    ///
-    /// This method runs parallelized on all avaible CPU
+    /// Returns the HPOTerms of the set, sorted by their information content
     ///
     /// Parameters
     /// ----------
-    /// other: :class:`pyhpo.HPOSet`
-    ///     The ``HPOSet`` to calculate the similarity to
     /// kind: str, default: ``omim``
-    ///     Which kind of information content to use for similarity calculation
-    ///     
-    ///     Available options:
-    ///
-    ///     * **omim**
-    ///     * **orpha**
-    ///     * **gene**
-    ///
-    /// method: str, default ``graphic``
-    ///     The method to use to calculate the similarity.
-    ///
-    ///     Available options:
-    ///
-    ///     * **resnik** - Resnik P, Proceedings of the 14th IJCAI, (1995)
-    ///     * **lin** - Lin D, Proceedings of the 15th ICML, (1998)
-    ///     * **jc** - Jiang J, Conrath D, ROCLING X, (1997)
-    ///       This is different to PyHPO
-    ///     * **jc2** - Jiang J, Conrath D, ROCLING X, (1997)
-    ///       Same as `jc`, but kept for backwards compatibility
-    ///     * **rel** - Relevance measure - Schlicker A, et.al.,
-    ///       BMC Bioinformatics, (2006)
-    ///     * **ic** - Information coefficient - Li B, et. al., arXiv, (2010)
-    ///     * **graphic** - Graph based Information coefficient -
-    ///       Deng Y, et. al., PLoS One, (2015)
-    ///     * **dist** - Distance between terms
-    ///
-    /// combine: str, default ``funSimAvg``
-    ///     The method to combine individual term similarity
-    ///     to HPOSet similarities.
-    ///
-    ///     Available options:
-    ///
-    ///     * **funSimAvg**
-    ///     * **funSimMax**
-    ///     * **BMA**
+    ///     Which kind of information content should be used for sorting.
+    ///     Options are ['omim', 'orpha', 'gene']
+    /// descending: bool, default: ``True``
+    ///     Sort the terms from the most specific (highest information content)
+    ///     to the least specific term. Terms with the same information content
+    ///     are sorted by their ID
     ///
     /// Returns
     /// -------
-    /// float
-    ///     Similarity scores
+    /// list[:class:`pyhpo.HPOTerm`]
+    ///     All terms of the set, sorted by information content
     ///
     /// Raises
     /// ------
     /// NameError
     ///     Ontology not yet constructed
-    /// AttributeError
+    /// KeyError
     ///     Invalid ``kind``
-    /// RuntimeError
-    ///     Invalid ``method`` or ``combine``
     ///
     /// Examples
     /// --------
     ///
     /// .. code-block:: python
     ///
-    ///     from pyhpo import Ontology
+    ///     from pyhpo import Ontology, HPOSet
     ///     Ontology()
-    ///     gene_sets = [g.hpo_set() for g in Ontology.genes]
-    ///     gene_sets[0].similarity(gene_sets[1])
-    ///     # >> 0.29546087980270386
     ///
-    #[pyo3(signature = (other, kind = "omim", method = "graphic", combine = "funSimAvg"))]
-    #[pyo3(text_signature = "($self, other, kind, method, combine)")]
-    fn similarity(
-        &self,
-        other: &PyHpoSet,
-        kind: &str,
-        method: &str,
-        combine: &str,
-    ) -> PyResult<f32> {
+    ///     my_set = HPOSet.from_queries([118, 2650, 7])
+    ///     my_set.terms_by_ic()
+    ///     # >> [<HpoTerm (HP:0002650)>, <HpoTerm (HP:0000007)>, <HpoTerm (HP:0000118)>]
+    ///
+    #[pyo3(signature = (kind = "omim", descending = true))]
+    #[pyo3(text_signature = "($self, kind, descending)")]
+    fn terms_by_ic(&self, kind: &str, descending: bool) -> PyResult<Vec<PyHpoTerm>> {
+        let kind = PyInformationContentKind::try_from(kind)?.into();
         let ont = get_ontology()?;
-        let set_a = HpoSet::new(ont, self.ids.clone());
-        let set_b = HpoSet::new(ont, other.ids.clone());
+        let mut terms: Vec<(f32, hpo::HpoTerm)> = self
+            .ids
+            .iter()
+            .map(|term_id| {
+                let term = ont
+                    .hpo(term_id)
+                    .expect("term must be present in the ontology if it is included in the set");
+                (term.information_content().get_kind(&kind), term)
+            })
+            .collect();
 
-        let kind = PyInformationContentKind::try_from(kind)
-            .map_err(|_| PyAttributeError::new_err("Invalid Information content"))?;
+        terms.sort_by(|a, b| {
+            let ic = if descending {
+                b.0.total_cmp(&a.0)
+            } else {
+                a.0.total_cmp(&b.0)
+            };
+            ic.then(a.1.id().cmp(&b.1.id()))
+        });
 
-        let similarity = hpo::similarity::Builtins::new(method, kind.into())
-            .map_err(|_| PyRuntimeError::new_err("Unknown method to calculate similarity"))?;
-        let combiner = StandardCombiner::try_from(combine)
-            .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+        Ok(terms
+            .into_iter()
+            .map(|(_, term)| PyHpoTerm::from(term))
+            .collect())
+    }
+
+    /// Selects a small number of terms that best represent the set
+    ///
+    /// The terms are selected greedily: Each step selects the term with
+    /// the highest information content that is not already covered by
+    /// the previously selected terms. The covered information content
+    /// is the highest information content of a common ancestor
+    /// (see ``resnik`` similarity) with any previously selected term.
+    /// Ancestors of selected terms therefore never add information.
+    ///
+    /// Parameters
+    /// ----------
+    /// n: int
+    ///     The maximum number of selected terms
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use. Possible options:
+    ///
+    ///     * **omim**
+    ///     * **orpha**
+    ///     * **gene**
+    ///     * **custom** or any other named custom information content
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.HPOTerm`]
+    ///     The selected terms, in the order of selection. Fewer than ``n``
+    ///     terms are returned if no other term adds information
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, Omim
+    ///     Ontology()
+    ///
+    ///     disease = Omim.get(620402)
+    ///     for term in disease.hpo_set().representative_terms(5):
+    ///         print(term.name)
+    ///
+    #[pyo3(signature = (n, kind = "omim"))]
+    #[pyo3(text_signature = "($self, n, kind)")]
+    fn representative_terms(
+        &self,
+        py: Python<'_>,
+        n: usize,
+        kind: &str,
+    ) -> PyResult<Vec<PyHpoTerm>> {
+        let ont = get_ontology()?;
+        let source = IcSource::new(kind)?;
+        let terms: Vec<hpo::HpoTerm> = self.ids.iter().filter_map(|id| ont.hpo(id)).collect();
+        let selected = worker::install(py, || {
+            // The information content of every term that is not yet
+            // covered by the selected terms
+            let mut gains: Vec<f32> = terms.iter().map(|term| source.ic(term)).collect();
+            let mut selected: Vec<usize> = Vec::new();
+            while selected.len() < n {
+                let Some((best, gain)) = gains
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, _)| !selected.contains(idx))
+                    .max_by(|a, b| a.1.total_cmp(b.1).then(b.0.cmp(&a.0)))
+                else {
+                    break;
+                };
+                if *gain <= 0.0 {
+                    break;
+                }
+                selected.push(best);
+                let covered: Vec<f32> = terms
+                    .par_iter()
+                    .map(|term| {
+                        term.all_common_ancestors(&terms[best])
+                            .iter()
+                            .map(|ancestor| source.ic(&ancestor))
+                            .fold(0.0, f32::max)
+                    })
+                    .collect();
+                for ((gain, covered), term) in gains.iter_mut().zip(covered).zip(&terms) {
+                    *gain = gain.min(source.ic(term) - covered);
+                }
+            }
+            selected
+        });
+        Ok(selected
+            .into_iter()
+            .map(|idx| PyHpoTerm::from(terms[idx]))
+            .collect())
+    }
+
+    /// Returns the redundancy of the terms of the set
+    ///
+    /// The redundancy is the average similarity of all pairs of distinct
+    /// terms of the set. Sets with many closely related terms, e.g. a term
+    /// together with its parents and siblings, have a high redundancy.
+    ///
+    /// Parameters
+    /// ----------
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use for similarity calculation,
+    ///     see :func:`pyhpo.HPOSet.similarity`
+    /// method: str, default ``graphic``
+    ///     The method to use to calculate the similarity of two terms,
+    ///     see :func:`pyhpo.HPOSet.similarity`
+    ///
+    /// Returns
+    /// -------
+    /// float
+    ///     The average pairwise similarity. ``0.0`` if the
+    ///     set contains less than two terms
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///
+    ///     HPOSet.from_queries([2650, 9121, 2808]).redundancy()
+    ///     # >> 0.6288
+    ///
+    #[pyo3(signature = (kind = "omim", method = "graphic"))]
+    #[pyo3(text_signature = "($self, kind, method)")]
+    fn redundancy(&self, py: Python<'_>, kind: &str, method: &str) -> PyResult<f32> {
+        let scores = self.pairwise_similarity(py, kind, method)?;
+        if scores.is_empty() {
+            return Ok(0.0);
+        }
+        Ok(scores.iter().sum::<f32>() / scores.len() as f32)
+    }
+
+    /// Returns the semantic diversity of the terms of the set
+    ///
+    /// The diversity is the effective number of distinct terms of the set,
+    /// based on the similarity of all term pairs (Leinster T, Cobbold C,
+    /// Ecology, (2012), order ``2``). It ranges from ``1.0`` if all terms
+    /// are identical to the number of terms if all terms are completely
+    /// unrelated.
+    ///
+    /// .. note::
+    ///
+    ///     The diversity requires a similarity ``method`` that ranges
+    ///     from ``0`` to ``1``, such as ``graphic``, ``lin`` or ``rel``.
+    ///
+    /// Parameters
+    /// ----------
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use for similarity calculation,
+    ///     see :func:`pyhpo.HPOSet.similarity`
+    /// method: str, default ``graphic``
+    ///     The method to use to calculate the similarity of two terms,
+    ///     see :func:`pyhpo.HPOSet.similarity`
+    ///
+    /// Returns
+    /// -------
+    /// float
+    ///     The effective number of distinct terms. ``0.0`` for an empty set
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///
+    ///     HPOSet.from_queries([2650, 9121, 2808]).diversity()
+    ///     # >> 1.3289
+    ///
+    #[pyo3(signature = (kind = "omim", method = "graphic"))]
+    #[pyo3(text_signature = "($self, kind, method)")]
+    fn diversity(&self, py: Python<'_>, kind: &str, method: &str) -> PyResult<f32> {
+        let n = self.ids.len() as f32;
+        if n == 0.0 {
+            return Ok(0.0);
+        }
+        // The similarity of each term with itself is `1.0`, all
+        // other pairs are part of the matrix twice
+        let total = n + 2.0
+            * self
+                .pairwise_similarity(py, kind, method)?
+                .iter()
+                .sum::<f32>();
+        Ok(n * n / total)
+    }
+
+    /// Returns the common ancestors of all terms of the set
+    ///
+    /// See :func:`pyhpo.Ontology.common_ancestors`
+    ///
+    /// Parameters
+    /// ----------
+    /// include_self: bool, default: ``False``
+    ///     Whether each term is considered its own ancestor
+    ///
+    /// Returns
+    /// -------
+    /// set[:class:`pyhpo.HPOTerm`]
+    ///     All terms that are ancestors of every term of the set.
+    ///     Empty if the set is empty
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///
+    ///     my_set = HPOSet.from_queries([2650, 9121, 2808])
+    ///     my_set.common_ancestors()
+    ///     # >> {<HpoTerm (HP:0000001)>, <HpoTerm (HP:0011842)>,
+    ///     # >> <HpoTerm (HP:0033127)>, <HpoTerm (HP:0000118)>,
+    ///     # >> <HpoTerm (HP:0000924)>}
+    ///
+    #[pyo3(signature = (include_self = false))]
+    #[pyo3(text_signature = "($self, include_self)")]
+    fn common_ancestors(&self, include_self: bool) -> PyResult<HashSet<PyHpoTerm>> {
+        let ont = get_ontology()?;
+        Ok(
+            common_ancestor_ids(self.ids.iter().filter_map(|id| ont.hpo(id)), include_self)
+                .iter()
+                .filter_map(|id| ont.hpo(id))
+                .map(PyHpoTerm::from)
+                .collect(),
+        )
+    }
+
+    /// Returns the most informative common ancestor of all terms of the set
+    ///
+    /// See :func:`pyhpo.Ontology.most_informative_common_ancestor`
+    ///
+    /// Parameters
+    /// ----------
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use. Possible options:
+    ///
+    ///     * **omim**
+    ///     * **orpha**
+    ///     * **gene**
+    ///     * **custom** or any other named custom information content
+    ///
+    /// include_self: bool, default: ``False``
+    ///     Whether each term is considered its own ancestor
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOTerm` or None
+    ///     The most informative common ancestor. ``None`` if the
+    ///     terms do not have any common ancestor or the set is empty
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///
+    ///     my_set = HPOSet.from_queries([2650, 9121, 2808])
+    ///     my_set.most_informative_common_ancestor()
+    ///     # >> <HpoTerm (HP:0011842)>
+    ///
+    #[pyo3(signature = (kind = "omim", include_self = false))]
+    #[pyo3(text_signature = "($self, kind, include_self)")]
+    fn most_informative_common_ancestor(
+        &self,
+        kind: &str,
+        include_self: bool,
+    ) -> PyResult<Option<PyHpoTerm>> {
+        let ont = get_ontology()?;
+        most_informative(
+            &common_ancestor_ids(self.ids.iter().filter_map(|id| ont.hpo(id)), include_self),
+            kind,
+        )
+    }
+
+    /// Groups the terms of the set by their categories
+    ///
+    /// Categories are the top-level terms of the Ontology, e.g. organ systems
+    /// such as ``Abnormality of the eye``. See :func:`pyhpo.HPOTerm.categories`.
+    /// A term that belongs to multiple categories is listed in each of them.
+    ///
+    /// Parameters
+    /// ----------
+    /// counts: bool, default: ``False``
+    ///     Return the number of terms per category instead of the terms
+    ///
+    /// Returns
+    /// -------
+    /// dict[:class:`pyhpo.HPOTerm`, list[:class:`pyhpo.HPOTerm`]] or dict[:class:`pyhpo.HPOTerm`, int]
+    ///     The categories, sorted by their ID, and the terms of the set
+    ///     belonging to them (also sorted by ID). Categories without any
+    ///     terms of the set are omitted
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///
+    ///     my_set = HPOSet.from_queries([2650, 7, 1250])
+    ///     my_set.categories()
+    ///     # >> {
+    ///     # >>     <HpoTerm (HP:0000005)>: [<HpoTerm (HP:0000007)>],
+    ///     # >>     <HpoTerm (HP:0000707)>: [<HpoTerm (HP:0001250)>],
+    ///     # >>     <HpoTerm (HP:0033127)>: [<HpoTerm (HP:0002650)>]
+    ///     # >> }
+    ///
+    ///     my_set.categories(counts=True)
+    ///     # >> {<HpoTerm (HP:0000005)>: 1, <HpoTerm (HP:0000707)>: 1, <HpoTerm (HP:0033127)>: 1}
+    ///
+    #[pyo3(signature = (counts = false))]
+    #[pyo3(text_signature = "($self, counts)")]
+    fn categories<'a>(&self, py: Python<'a>, counts: bool) -> PyResult<Bound<'a, PyDict>> {
+        let ont = get_ontology()?;
+        let mut groups: Vec<(HpoTermId, Vec<PyHpoTerm>)> = ont
+            .categories()
+            .iter()
+            .map(|category| (category, Vec::new()))
+            .collect();
+
+        for term_id in &self.ids {
+            let term = ont
+                .hpo(term_id)
+                .expect("term must be present in the ontology if it is included in the set");
+            for category in term.categories() {
+                if let Some((_, members)) = groups.iter_mut().find(|(id, _)| *id == category) {
+                    members.push(PyHpoTerm::from(term));
+                }
+            }
+        }
+
+        let dict = PyDict::new(py);
+        for (category, members) in groups.into_iter().filter(|(_, m)| !m.is_empty()) {
+            let key = pyterm_from_id(category.as_u32())?;
+            if counts {
+                dict.set_item(key, members.len())?;
+            } else {
+                dict.set_item(key, members)?;
+            }
+        }
+        Ok(dict)
+    }
+
+    /// Calculates the distances between all its term-pairs. It also provides
+    /// basic calculations for variances among the pairs.
+    ///
+    /// Returns
+    /// -------
+    ///
+    /// tuple of (int, int, int, list of int)
+    ///     Tuple with the variance metrices
+    ///
+    ///     * **float** Average distance between pairs
+    ///     * **int** Smallest distance between pairs
+    ///     * **int** Largest distance between pairs
+    ///     * **list of int** List of all distances between pairs
+    fn variance(&self) -> Self {
+        unimplemented!()
+    }
+
+    /// Helper generator function that returns all possible two-pair
+    /// combination between all its terms
+    ///
+    /// This function is direction dependent. That means that every
+    /// pair will appear twice. Once for each direction
+    ///
+    /// .. seealso:: :func:`pyhpo.HPOSet.combinations_one_way`
+    ///
+    /// Yields
+    /// ------
+    /// Tuple of :class:`pyhpo.HPOTerm`
+    ///
+    ///     Tuple containing the follow items
+    ///     * **HPOTerm** 1 of the pair
+    ///     * **HPOTerm** 2 of the pair
+    ///
+    fn combinations(&self) -> Self {
+        unimplemented!()
+    }
 
-        let g_sim = GroupSimilarity::new(combiner, similarity);
+    /// Helper generator function that returns all possible two-pair
+    /// combination between all its terms
+    ///
+    /// This methow will report each pair only once
+    ///
+    /// .. seealso:: :func:`pyhpo.HPOSet.combinations`
+    ///
+    /// Yields
+    /// ------
+    /// Tuple of :class:`term.HPOTerm`
+    ///     Tuple containing the follow items
+    ///
+    ///     * **HPOTerm** instance 1 of the pair
+    ///     * **HPOTerm** instance 2 of the pair
+    fn combinations_one_way(&self) -> Self {
+        unimplemented!()
+    }
 
-        Ok(g_sim.calculate(&set_a, &set_b))
+    /// Calculate similarity between this and another `HPOSet`
+    ///
+    /// This method runs parallelized on all avaible CPU
+    ///
+    /// Parameters
+    /// ----------
+    /// other: :class:`pyhpo.HPOSet`
+    ///     The ``HPOSet`` to calculate the similarity to
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use for similarity calculation
+    ///     
+    ///     Available options:
+    ///
+    ///     * **omim**
+    ///     * **orpha**
+    ///     * **gene**
+    ///     * **custom** - or the name of any other custom information
+    ///       content, see :func:`pyhpo.HPOTerm.set_ic`. Only supported by the
+    ///       information content based ``method``\s
+    ///
+    /// method: str, default ``graphic``
+    ///     The method to use to calculate the similarity.
+    ///
+    ///     Available options:
+    ///
+    ///     * **resnik** - Resnik P, Proceedings of the 14th IJCAI, (1995)
+    ///     * **lin** - Lin D, Proceedings of the 15th ICML, (1998)
+    ///     * **jc** - Jiang J, Conrath D, ROCLING X, (1997)
+    ///       This is different to PyHPO
+    ///     * **jc2** - Jiang J, Conrath D, ROCLING X, (1997)
+    ///       Same as `jc`, but kept for backwards compatibility
+    ///     * **rel** - Relevance measure - Schlicker A, et.al.,
+    ///       BMC Bioinformatics, (2006)
+    ///     * **ic** - Information coefficient - Li B, et. al., arXiv, (2010)
+    ///     * **graphic** - Graph based Information coefficient -
+    ///       Deng Y, et. al., PLoS One, (2015)
+    ///     * **dist** - Distance between terms
+    ///
+    /// combine: str, default ``funSimAvg``
+    ///     The method to combine individual term similarity
+    ///     to HPOSet similarities.
+    ///
+    ///     Available options:
+    ///
+    ///     * **funSimAvg**
+    ///     * **funSimMax**
+    ///     * **BMA**
+    ///
+    /// on_empty: str, default ``zero``
+    ///     The similarity if one of the sets is empty
+    ///
+    ///     Available options:
+    ///
+    ///     * **zero** - The similarity is ``0.0``
+    ///     * **nan** - The similarity is ``NaN``
+    ///     * **raise** - Raise a ``ValueError``
+    ///
+    /// explain: bool, default ``False``
+    ///     Return the best matching term pairs together with the score
+    ///
+    /// dtype: str, default ``float32``
+    ///     The precision to combine the term similarities with, ``float32``
+    ///     or ``float64``. The similarity of every term pair is always
+    ///     calculated with ``float32``, but summing up hundreds of them
+    ///     with ``float64`` avoids rounding errors
+    ///
+    /// Returns
+    /// -------
+    /// float or dict
+    ///     The similarity score. With ``explain=True``, a dict of
+    ///
+    ///     * **similarity** - The similarity score
+    ///     * **matches** - The best match of every term of this set
+    ///     * **reverse_matches** - The best match of every term of ``other``
+    ///
+    ///     Every match is a dict with the ``term``, its best matching
+    ///     term ``match`` of the other set, their ``similarity`` and their
+    ///     most informative common ancestor ``mica`` (or ``None``)
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// AttributeError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method`` or ``combine``
+    /// ValueError
+    ///     Invalid ``on_empty`` or ``dtype`` or an empty set with
+    ///     ``on_empty="raise"``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     gene_sets = [g.hpo_set() for g in Ontology.genes]
+    ///     gene_sets[0].similarity(gene_sets[1])
+    ///     # >> 0.29546087980270386
+    ///
+    ///     # Combine the term similarities with double precision
+    ///     gene_sets[0].similarity(gene_sets[1], dtype="float64")
+    ///
+    ///     explanation = gene_sets[0].similarity(gene_sets[1], explain=True)
+    ///     for match in explanation["matches"]:
+    ///         print(match["term"], match["match"], match["similarity"], match["mica"])
+    ///
+    #[pyo3(signature = (other, kind = "omim", method = "graphic", combine = "funSimAvg", on_empty = "zero", explain = false, dtype = "float32"))]
+    #[pyo3(text_signature = "($self, other, kind, method, combine, on_empty, explain, dtype)")]
+    #[allow(clippy::too_many_arguments)]
+    fn similarity(
+        &self,
+        py: Python<'_>,
+        other: &PyHpoSet,
+        kind: &str,
+        method: &str,
+        combine: &str,
+        on_empty: &str,
+        explain: bool,
+        dtype: &str,
+    ) -> PyResult<PyObject> {
+        let score = match Dtype::try_from(dtype)? {
+            Dtype::Float32 => self
+                .set_similarity::<f32>(other, kind, method, combine, on_empty)?
+                .into_py_any(py)?,
+            Dtype::Float64 => self
+                .set_similarity::<f64>(other, kind, method, combine, on_empty)?
+                .into_py_any(py)?,
+        };
+        if !explain {
+            return score.into_py_any(py);
+        }
+        let dict = PyDict::new(py);
+        dict.set_item("similarity", score)?;
+        dict.set_item("matches", self.best_matches(py, other, kind, method)?)?;
+        dict.set_item(
+            "reverse_matches",
+            other.best_matches(py, self, kind, method)?,
+        )?;
+        dict.into_py_any(py)
+    }
+
+    /// Calculate similarity between this `HPOSet` and a list of other `HPOSet`
+    ///
+    /// This method runs parallelized on all avaible CPU
+    ///
+    /// Parameters
+    /// ----------
+    /// other: list[:class:`pyhpo.HPOSet`]
+    ///     Calculate similarity between ``self`` and every provided ``HPOSet``
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use for similarity calculation
+    ///     
+    ///     Available options:
+    ///
+    ///     * **omim**
+    ///     * **orpha**
+    ///     * **gene**
+    ///
+    /// method: str, default ``graphic``
+    ///     The method to use to calculate the similarity.
+    ///
+    ///     Available options:
+    ///
+    ///     * **resnik** - Resnik P, Proceedings of the 14th IJCAI, (1995)
+    ///     * **lin** - Lin D, Proceedings of the 15th ICML, (1998)
+    ///     * **jc** - Jiang J, Conrath D, ROCLING X, (1997)
+    ///       This is different to PyHPO
+    ///     * **jc2** - Jiang J, Conrath D, ROCLING X, (1997)
+    ///       Same as `jc`, but kept for backwards compatibility
+    ///     * **rel** - Relevance measure - Schlicker A, et.al.,
+    ///       BMC Bioinformatics, (2006)
+    ///     * **ic** - Information coefficient - Li B, et. al., arXiv, (2010)
+    ///     * **graphic** - Graph based Information coefficient -
+    ///       Deng Y, et. al., PLoS One, (2015)
+    ///     * **dist** - Distance between terms
+    ///
+    /// combine: str, default ``funSimAvg``
+    ///     The method to combine individual term similarity
+    ///     to HPOSet similarities.
+    ///
+    ///     Available options:
+    ///
+    ///     * **funSimAvg**
+    ///     * **funSimMax**
+    ///     * **BMA**
+    ///
+    /// on_empty: str, default ``zero``
+    ///     The similarity if one of the compared sets is empty
+    ///
+    ///     Available options:
+    ///
+    ///     * **zero** - The similarity is ``0.0``
+    ///     * **nan** - The similarity is ``NaN``
+    ///     * **raise** - Raise a ``ValueError``
+    ///
+    /// nan_policy: str, default ``propagate``
+    ///     How to handle similarity scores that are ``NaN``
+    ///
+    ///     Available options:
+    ///
+    ///     * **propagate** - Return ``NaN`` scores
+    ///     * **raise** - Raise a ``ValueError``
+    ///     * **skip** - Omit ``NaN`` scores from the result. The result
+    ///       does not align with ``other`` any longer
+    ///     * **substitute** - Replace ``NaN`` scores with ``nan_value``
+    ///
+    /// nan_value: float, default ``0.0``
+    ///     The score that replaces ``NaN`` if ``nan_policy`` is ``substitute``
+    ///
+    /// dtype: str, default ``float32``
+    ///     The precision to combine the term similarities with, ``float32``
+    ///     or ``float64``. See :func:`pyhpo.HPOSet.similarity`
+    ///
+    /// Returns
+    /// -------
+    /// list[float]
+    ///     Similarity scores for every comparison
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method`` or ``combine``
+    /// ValueError
+    ///     Invalid ``on_empty``, ``nan_policy`` or ``dtype``, an empty set with
+    ///     ``on_empty="raise"`` or a ``NaN`` score with ``nan_policy="raise"``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     gene_sets = [g.hpo_set() for g in Ontology.genes]
+    ///     similarities = gene_sets[0].similarity_scores(gene_sets)
+    ///     similarities[0:4]
+    ///     # >> [1.0, 0.5000048279762268, 0.29546087980270386, 0.5000059008598328]
+    ///
+    #[pyo3(signature =(other, kind = "omim", method = "graphic", combine = "funSimAvg", on_empty = "zero", nan_policy = "propagate", nan_value = 0.0, dtype = "float32"))]
+    #[pyo3(
+        text_signature = "($self, other, kind, method, combine, on_empty, nan_policy, nan_value, dtype)"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn similarity_scores(
+        &self,
+        py: Python<'_>,
+        other: Vec<PyHpoSet>,
+        kind: &str,
+        method: &str,
+        combine: &str,
+        on_empty: &str,
+        nan_policy: &str,
+        nan_value: f32,
+        dtype: &str,
+    ) -> PyResult<PyObject> {
+        let nan_policy = NanPolicy::new(nan_policy, nan_value)?;
+        match Dtype::try_from(dtype)? {
+            Dtype::Float32 => nan_policy
+                .apply(self.scores::<f32>(py, &other, kind, method, combine, on_empty)?)?
+                .into_py_any(py),
+            Dtype::Float64 => nan_policy
+                .apply(self.scores::<f64>(py, &other, kind, method, combine, on_empty)?)?
+                .into_py_any(py),
+        }
     }
 
-    /// Calculate similarity between this `HPOSet` and a list of other `HPOSet`
+    /// Calculate the similarity between this `HPOSet` and a single `HPOTerm`
     ///
-    /// This method runs parallelized on all avaible CPU
+    /// The term is compared to every term in the set and the individual
+    /// similarity scores are combined into one score.
     ///
     /// Parameters
     /// ----------
-    /// other: list[:class:`pyhpo.HPOSet`]
-    ///     Calculate similarity between ``self`` and every provided ``HPOSet``
+    /// term: :class:`pyhpo.HPOTerm`
+    ///     The ``HPOTerm`` to calculate the similarity to
     /// kind: str, default: ``omim``
     ///     Which kind of information content to use for similarity calculation
     ///     
@@ -631,36 +1645,29 @@ impl PyHpoSet {
     ///
     /// method: str, default ``graphic``
     ///     The method to use to calculate the similarity.
+    ///     See :func:`pyhpo.HPOSet.similarity` for available options
+    ///
+    /// combine: str, default ``max``
+    ///     The method to combine the individual term similarities
     ///
     ///     Available options:
     ///
-    ///     * **resnik** - Resnik P, Proceedings of the 14th IJCAI, (1995)
-    ///     * **lin** - Lin D, Proceedings of the 15th ICML, (1998)
-    ///     * **jc** - Jiang J, Conrath D, ROCLING X, (1997)
-    ///       This is different to PyHPO
-    ///     * **jc2** - Jiang J, Conrath D, ROCLING X, (1997)
-    ///       Same as `jc`, but kept for backwards compatibility
-    ///     * **rel** - Relevance measure - Schlicker A, et.al.,
-    ///       BMC Bioinformatics, (2006)
-    ///     * **ic** - Information coefficient - Li B, et. al., arXiv, (2010)
-    ///     * **graphic** - Graph based Information coefficient -
-    ///       Deng Y, et. al., PLoS One, (2015)
-    ///     * **dist** - Distance between terms
+    ///     * **max** - The similarity of the best matching term
+    ///     * **mean** - The average similarity of all terms
     ///
-    /// combine: str, default ``funSimAvg``
-    ///     The method to combine individual term similarity
-    ///     to HPOSet similarities.
+    /// on_empty: str, default ``zero``
+    ///     The similarity if the set is empty
     ///
     ///     Available options:
     ///
-    ///     * **funSimAvg**
-    ///     * **funSimMax**
-    ///     * **BMA**
+    ///     * **zero** - The similarity is ``0.0``
+    ///     * **nan** - The similarity is ``NaN``
+    ///     * **raise** - Raise a ``ValueError``
     ///
     /// Returns
     /// -------
-    /// list[float]
-    ///     Similarity scores for every comparison
+    /// float
+    ///     Similarity score. See ``on_empty`` for empty sets
     ///
     /// Raises
     /// ------
@@ -670,46 +1677,50 @@ impl PyHpoSet {
     ///     Invalid ``kind``
     /// RuntimeError
     ///     Invalid ``method`` or ``combine``
+    /// ValueError
+    ///     Invalid ``on_empty`` or an empty set with ``on_empty="raise"``
     ///
     /// Examples
     /// --------
     ///
     /// .. code-block:: python
     ///
-    ///     from pyhpo import Ontology
+    ///     from pyhpo import Ontology, HPOSet
     ///     Ontology()
-    ///     gene_sets = [g.hpo_set() for g in Ontology.genes]
-    ///     similarities = gene_sets[0].similarity_scores(gene_sets)
-    ///     similarities[0:4]
-    ///     # >> [1.0, 0.5000048279762268, 0.29546087980270386, 0.5000059008598328]
     ///
-    #[pyo3(signature =(other, kind = "omim", method = "graphic", combine = "funSimAvg"))]
-    #[pyo3(text_signature = "($self, other, kind, method, combine)")]
-    fn similarity_scores(
+    ///     my_set = HPOSet.from_queries([2650, 7])
+    ///     my_set.similarity_to_term(Ontology.hpo(2651))
+    ///
+    #[pyo3(signature = (term, kind = "omim", method = "graphic", combine = "max", on_empty = "zero"))]
+    #[pyo3(text_signature = "($self, term, kind, method, combine, on_empty)")]
+    fn similarity_to_term(
         &self,
-        other: Vec<PyHpoSet>,
+        term: &PyHpoTerm,
         kind: &str,
         method: &str,
         combine: &str,
-    ) -> PyResult<Vec<f32>> {
+        on_empty: &str,
+    ) -> PyResult<f32> {
         let ont = get_ontology()?;
-        let set_a = HpoSet::new(ont, self.ids.clone());
-
-        let kind = PyInformationContentKind::try_from(kind)?;
-        let similarity = hpo::similarity::Builtins::new(method, kind.into())
-            .map_err(|_| PyRuntimeError::new_err("Unknown method to calculate similarity"))?;
-        let combiner = StandardCombiner::try_from(combine)
-            .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
-
-        let g_sim = GroupSimilarity::new(combiner, similarity);
+        let similarity = similarity_cache::builtin(method, kind)?;
+        let on_empty = EmptySetPolicy::try_from(on_empty)?;
+        if self.ids.is_empty() {
+            return on_empty.score(true, || 0.0);
+        }
 
-        Ok(other
-            .par_iter()
-            .map(|sb| {
-                let set_b = HpoSet::new(ont, sb.ids.clone());
-                g_sim.calculate(&set_a, &set_b)
-            })
-            .collect())
+        let other = term_from_id(term.hpo_term_id().as_u32())?;
+        let scores = self.ids.iter().map(|id| {
+            let term = ont
+                .hpo(id)
+                .expect("term must be present in the ontology if it is included in the set");
+            similarity.calculate(&term, &other)
+        });
+
+        match combine {
+            "max" => Ok(scores.reduce(f32::max).unwrap_or(0.0)),
+            "mean" => Ok(scores.sum::<f32>() / self.ids.len() as f32),
+            _ => Err(PyRuntimeError::new_err("Invalid combine method specified")),
+        }
     }
 
     /// Returns a dict/JSON representation the HPOSet
@@ -758,41 +1769,118 @@ impl PyHpoSet {
     #[pyo3(signature = (verbose = false))]
     #[pyo3(text_signature = "($self, verbose)")]
     #[allow(non_snake_case)]
-    fn toJSON<'a>(&'a self, py: Python<'a>, verbose: bool) -> PyResult<Vec<Bound<'_, PyDict>>> {
+    fn toJSON<'a>(&'a self, py: Python<'a>, verbose: bool) -> PyResult<Vec<Bound<'a, PyDict>>> {
         self.ids
+            .iter()
+            .map(|id| TermJson::new(&term_from_id(id.as_u32())?, verbose).to_dict(py))
+            .collect()
+    }
+
+    /// Returns a JSON string representation of the HPOSet
+    ///
+    /// The JSON is generated directly in Rust and has the same
+    /// structure as :func:`pyhpo.HPOSet.toJSON`. This is much faster than
+    /// serializing the output of ``toJSON`` in Python.
+    ///
+    /// Parameters
+    /// ----------
+    /// verbose: bool, default: ``False``
+    ///     Include extra properties of each ``HPOTerm``
+    ///
+    /// Returns
+    /// -------
+    /// str
+    ///     JSON array with one object per ``HPOTerm``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///     HPOSet.from_queries([118]).to_json_str()
+    ///     # >> '[{"name":"Phenotypic abnormality","id":"HP:0000118","int":118}]'
+    ///
+    #[pyo3(signature = (verbose = false))]
+    #[pyo3(text_signature = "($self, verbose)")]
+    fn to_json_str(&self, verbose: bool) -> PyResult<String> {
+        let ont = get_ontology()?;
+        let terms: Vec<TermJson> = self
+            .ids
             .iter()
             .map(|id| {
-                let dict = PyDict::new_bound(py);
-                let term = term_from_id(id.as_u32())?;
-                dict.set_item("name", term.name())?;
-                dict.set_item("id", term.id().to_string())?;
-                dict.set_item("int", term.id().as_u32())?;
-
-                if verbose {
-                    let ic = PyDict::new_bound(py);
-                    ic.set_item("gene", term.information_content().gene())?;
-                    ic.set_item("omim", term.information_content().omim_disease())?;
-                    ic.set_item("orpha", 0.0)?;
-                    ic.set_item("decipher", 0.0)?;
-                    dict.set_item::<&str, Vec<&str>>("synonym", vec![])?;
-                    dict.set_item("comment", "")?;
-                    dict.set_item("definition", "")?;
-                    dict.set_item::<&str, Vec<&str>>("xref", vec![])?;
-                    dict.set_item::<&str, Vec<&str>>("is_a", vec![])?;
-                    dict.set_item("ic", ic)?;
-                }
-                Ok(dict)
+                let term = ont
+                    .hpo(id)
+                    .expect("term must be present in the ontology if it is included in the set");
+                TermJson::new(&term, verbose)
             })
+            .collect();
+        to_json_string(&terms)
+    }
+
+    /// Returns the FHIR ``CodeableConcept`` of every HPOTerm in the set
+    ///
+    /// Returns
+    /// -------
+    /// list[dict]
+    ///     One ``CodeableConcept`` per term,
+    ///     see :func:`pyhpo.HPOTerm.to_fhir_codeable_concept`
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///     HPOSet.from_queries([2650]).to_fhir()
+    ///     # >> [{
+    ///     # >>     'coding': [{
+    ///     # >>         'system': 'http://purl.obolibrary.org/obo/hp.owl',
+    ///     # >>         'code': 'HP:0002650',
+    ///     # >>         'display': 'Scoliosis'
+    ///     # >>     }],
+    ///     # >>     'text': 'Scoliosis'
+    ///     # >> }]
+    ///
+    fn to_fhir<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.ids
+            .iter()
+            .map(|id| fhir::codeable_concept(py, &term_from_id(id.as_u32())?))
             .collect()
     }
 
     /// Returns a serialized string representing the HPOSet
     ///
+    /// Parameters
+    /// ----------
+    /// include_version: bool, default: ``False``
+    ///     Prefix the serialized terms with the release of the Ontology,
+    ///     e.g.: ``2024-08-13:3+118+2650``. This allows
+    ///     :func:`pyhpo.HPOSet.from_serialized` to detect if the set is
+    ///     loaded with a different HPO release.
+    ///
     /// Returns
     /// -------
     /// str
     ///     A serialized string uniquely representing the HPOSet,
-    ///     e.g.: ``3+118+2650```
+    ///     e.g.: ``3+118+2650``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed (only if ``include_version`` is used)
     ///
     /// Examples
     /// --------
@@ -805,7 +1893,12 @@ impl PyHpoSet {
     ///     gene_sets[0].serialize()
     ///     # >> 7+118+152+234+271+315+478+479+492+496.....
     ///
-    fn serialize(&self) -> String {
+    ///     gene_sets[0].serialize(include_version=True)
+    ///     # >> 2024-08-13:7+118+152+234+271+315+478+479+492+496.....
+    ///
+    #[pyo3(signature = (include_version = false))]
+    #[pyo3(text_signature = "($self, include_version)")]
+    fn serialize(&self, include_version: bool) -> PyResult<String> {
         let mut ids = self
             .ids
             .iter()
@@ -814,21 +1907,60 @@ impl PyHpoSet {
         ids.sort();
 
         let id_strings: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
-        id_strings.join("+")
+        let terms = id_strings.join("+");
+        if include_version {
+            Ok(format!("{}:{}", get_ontology()?.hpo_version(), terms))
+        } else {
+            Ok(terms)
+        }
     }
 
-    /// Returns the HPOTerms in the set
+    /// Returns a compact serialized string representing the HPOSet
+    ///
+    /// The terms are encoded as a compressed bitset in URL-safe base64,
+    /// which is considerably smaller than :func:`pyhpo.HPOSet.serialize`.
+    /// It is intended for storing large numbers of sets.
     ///
     /// Returns
     /// -------
-    /// list[:class:`pyhpo.HPOTerm`]
-    ///     A list of every term in the set
+    /// str
+    ///     A compact string uniquely representing the HPOSet
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     HPOSet.from_queries([118, 2650]).serialize_compact()
+    ///     # >> 'duQT'
+    ///
+    fn serialize_compact(&self) -> String {
+        let mut ids = self
+            .ids
+            .iter()
+            .map(|tid| tid.as_u32())
+            .collect::<Vec<u32>>();
+        ids.sort();
+        compact::encode(&ids)
+    }
+
+    /// Returns the HPOTerms in the set
     ///
+    /// The terms are returned lazily, sorted by their ID
     ///
-    /// .. important::
+    /// Parameters
+    /// ----------
+    /// as_list: bool, default: ``False``
+    ///     Return a list of terms instead of an Iterator
+    ///     (the behaviour before version 1.4)
     ///
-    ///    The return type of this method will very likely change
-    ///    into an Iterator of ``HPOTerm``. (:doc:`api_changes`)
+    /// Returns
+    /// -------
+    /// Iterator[:class:`pyhpo.HPOTerm`]
+    ///     An iterator of every term in the set
+    ///     (or a ``list`` if ``as_list`` is ``True``)
     ///
     /// Raises
     /// ------
@@ -848,11 +1980,22 @@ impl PyHpoSet {
     ///     for term in my_set.terms():
     ///         print(term.name)
     ///
-    fn terms(&self) -> PyResult<Vec<PyHpoTerm>> {
-        self.ids
-            .iter()
-            .map(|id| pyterm_from_id(id.as_u32()))
-            .collect()
+    ///     terms = my_set.terms(as_list=True)
+    ///     terms[0]
+    ///
+    #[pyo3(signature = (as_list = false))]
+    #[pyo3(text_signature = "($self, as_list)")]
+    fn terms(&self, py: Python<'_>, as_list: bool) -> PyResult<PyObject> {
+        if as_list {
+            let terms = self
+                .ids
+                .iter()
+                .map(|id| pyterm_from_id(id.as_u32()))
+                .collect::<PyResult<Vec<PyHpoTerm>>>()?;
+            terms.into_py_any(py)
+        } else {
+            Iter::new(&self.ids).into_py_any(py)
+        }
     }
 
     /// Instantiate an HPOSet from various inputs
@@ -864,11 +2007,125 @@ impl PyHpoSet {
     ///
     /// Parameters
     /// ----------
-    /// queries: list[str or int]
-    ///
-    ///     * **str** HPO term (e.g.: ``Scoliosis``)
-    ///     * **str** HPO-ID (e.g.: ``HP:0002650``)
-    ///     * **int** HPO term id (e.g.: ``2650``)
+    /// queries: list[str or int]
+    ///
+    ///     * **str** HPO term (e.g.: ``Scoliosis``)
+    ///     * **str** HPO-ID (e.g.: ``HP:0002650``)
+    ///     * **int** HPO term id (e.g.: ``2650``)
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOSet`
+    ///     A new ``HPOSet``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// ValueError
+    ///     query cannot be converted to HpoTermId
+    /// RuntimeError
+    ///     No HPO term is found for the provided query
+    ///
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     my_set = HPOSet.from_queries([
+    ///         "HP:0002650",
+    ///         118,
+    ///         "Thoracolumbar scoliosis"
+    ///     ])
+    ///     len(my_set)
+    ///     # >> 3
+    ///
+    #[classmethod]
+    fn from_queries(_cls: &Bound<'_, PyType>, queries: Vec<PyQuery>) -> PyResult<Self> {
+        let mut ids: Vec<HpoTermId> = Vec::with_capacity(queries.len());
+        for q in queries {
+            ids.push(term_from_query(q)?.id());
+        }
+        Ok(ids.into_iter().collect::<PyHpoSet>())
+    }
+
+    /// Instantiate an HPOSet from a pandas Series or any other iterable
+    ///
+    /// This is the typical shape of a phenotype column of a patient table.
+    /// The items are handled like in :func:`pyhpo.HPOSet.from_queries`,
+    /// but missing values (``None``, ``NaN``, ``pd.NA`` and empty strings)
+    /// are skipped and surrounding whitespace of strings is removed.
+    ///
+    /// Parameters
+    /// ----------
+    /// series: pandas.Series or Iterable[str | int | :class:`pyhpo.HPOTerm` | None]
+    ///
+    ///     * **str** HPO term (e.g.: ``Scoliosis``)
+    ///     * **str** HPO-ID (e.g.: ``HP:0002650``)
+    ///     * **int** HPO term id (e.g.: ``2650``)
+    ///     * :class:`pyhpo.HPOTerm`
+    ///
+    /// skip_invalid: bool, default: ``False``
+    ///     Skip items that do not match an HPO term, instead of raising an error
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOSet`
+    ///     A new ``HPOSet``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// TypeError
+    ///     ``series`` is not iterable or an item has an invalid type
+    /// ValueError
+    ///     query cannot be converted to HpoTermId
+    /// RuntimeError
+    ///     No HPO term is found for the provided query
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     import pandas as pd
+    ///     from pyhpo import Ontology, HPOSet
+    ///
+    ///     Ontology()
+    ///     phenotypes = pd.Series(["HP:0002650", None, " Scoliosis", pd.NA, "HP:0000118"])
+    ///     len(HPOSet.from_series(phenotypes))
+    ///     # >> 2
+    ///
+    #[classmethod]
+    #[pyo3(signature = (series, skip_invalid = false))]
+    #[pyo3(text_signature = "(series, skip_invalid)")]
+    fn from_series(
+        _cls: &Bound<'_, PyType>,
+        series: &Bound<'_, PyAny>,
+        skip_invalid: bool,
+    ) -> PyResult<Self> {
+        Ok(ids_from_series(series, skip_invalid)?
+            .into_iter()
+            .collect::<PyHpoSet>())
+    }
+
+    /// Instantiate an HPOSet from FHIR ``Coding`` or ``CodeableConcept``
+    ///
+    /// This is the reverse of :func:`pyhpo.HPOSet.to_fhir`. Each item can be a
+    /// ``Coding`` or a ``CodeableConcept``. The first ``Coding`` with the HPO
+    /// system ``http://purl.obolibrary.org/obo/hp.owl`` of a
+    /// ``CodeableConcept`` is used.
+    ///
+    /// Parameters
+    /// ----------
+    /// fragments: list[dict]
+    ///     The FHIR ``Coding`` or ``CodeableConcept`` JSON fragments
+    /// skip_invalid: bool, default: ``False``
+    ///     Ignore fragments without a valid HPO coding instead of raising an error
     ///
     /// Returns
     /// -------
@@ -879,34 +2136,40 @@ impl PyHpoSet {
     /// ------
     /// NameError
     ///     Ontology not yet constructed
+    /// TypeError
+    ///     A fragment is not a dict
     /// ValueError
-    ///     query cannot be converted to HpoTermId
-    /// RuntimeError
-    ///     No HPO term is found for the provided query
-    ///
+    ///     A fragment does not contain a valid HPO coding
+    /// KeyError
+    ///     No HPO term is found for the code
     ///
     /// Examples
     /// --------
     ///
     /// .. code-block:: python
     ///
-    ///     from pyhpo import Ontology
+    ///     from pyhpo import Ontology, HPOSet
+    ///
     ///     Ontology()
-    ///     my_set = HPOSet.from_queries([
-    ///         "HP:0002650",
-    ///         118,
-    ///         "Thoracolumbar scoliosis"
-    ///     ])
-    ///     len(my_set)
-    ///     # >> 3
+    ///     conditions = [
+    ///         {"coding": [{"system": "http://snomed.info/sct", "code": "298382003"},
+    ///                     {"system": "http://purl.obolibrary.org/obo/hp.owl", "code": "HP:0002650"}]},
+    ///         {"system": "http://purl.obolibrary.org/obo/hp.owl", "code": "HP:0001166"},
+    ///     ]
+    ///     HPOSet.from_fhir(conditions).serialize()
+    ///     # >> '1166+2650'
     ///
     #[classmethod]
-    fn from_queries(_cls: &Bound<'_, PyType>, queries: Vec<PyQuery>) -> PyResult<Self> {
-        let mut ids: Vec<HpoTermId> = Vec::with_capacity(queries.len());
-        for q in queries {
-            ids.push(term_from_query(q)?.id());
-        }
-        Ok(ids.into_iter().collect::<PyHpoSet>())
+    #[pyo3(signature = (fragments, skip_invalid = false))]
+    #[pyo3(text_signature = "(fragments, skip_invalid)")]
+    fn from_fhir(
+        _cls: &Bound<'_, PyType>,
+        fragments: &Bound<'_, PyAny>,
+        skip_invalid: bool,
+    ) -> PyResult<Self> {
+        Ok(ids_from_fhir(fragments, skip_invalid)?
+            .into_iter()
+            .collect::<PyHpoSet>())
     }
 
     /// Instantiate an HPOSet from a serialized HPOSet
@@ -915,10 +2178,22 @@ impl PyHpoSet {
     /// form of the HPOSet to share between applications.
     /// See :func:`pyhpo.HPOSet.serialize`
     ///
+    /// If the serialized string contains the HPO release, e.g.
+    /// ``2024-08-13:118+2650``, it is compared to the release of the
+    /// loaded Ontology, because terms can change their meaning or become
+    /// obsolete between releases.
+    ///
     /// Parameters
     /// ----------
     /// pickle: str
-    ///     A pickled string of all HPOTerms, e.g. ``118+2650``
+    ///     A pickled string of all HPOTerms, e.g. ``118+2650``,
+    ///     optionally prefixed with the HPO release
+    /// on_version_mismatch: str, default: ``warn``
+    ///     How to handle a different HPO release of the serialized set:
+    ///
+    ///     * **warn** - Emit a ``UserWarning``
+    ///     * **raise** - Raise a ``ValueError``
+    ///     * **ignore** - Load the set silently
     ///
     /// Returns
     /// -------
@@ -930,7 +2205,8 @@ impl PyHpoSet {
     /// NameError
     ///     Ontology not yet constructed
     /// ValueError
-    ///     pickled item cannot be converted to HpoTermId
+    ///     pickled item cannot be converted to HpoTermId,
+    ///     or the HPO release differs and ``on_version_mismatch`` is ``raise``
     /// KeyError
     ///     No HPO term is found for the provided query
     ///
@@ -945,12 +2221,20 @@ impl PyHpoSet {
     ///     len(my_set
     ///     # >> 10
     ///
+    ///     my_set = HPOSet.from_serialized(
+    ///         "2023-01-27:7+118+152",
+    ///         on_version_mismatch="raise"
+    ///     )
+    ///     # >> ValueError: The HPOSet was serialized with HPO release 2023-01-27 ...
+    ///
     #[classmethod]
-    fn from_serialized(_cls: &Bound<'_, PyType>, pickle: &str) -> PyResult<Self> {
-        let ids: HpoGroup = pickle
-            .split('+')
-            .map(|id| id.parse::<u32>())
-            .collect::<Result<Vec<u32>, ParseIntError>>()?
+    #[pyo3(signature = (pickle, on_version_mismatch = "warn"))]
+    fn from_serialized(
+        cls: &Bound<'_, PyType>,
+        pickle: &str,
+        on_version_mismatch: &str,
+    ) -> PyResult<Self> {
+        let ids: HpoGroup = parse_serialized(cls.py(), pickle, on_version_mismatch)?
             .iter()
             .map(|id| {
                 // in theory, we could simply call HpoTermId::from(*id)
@@ -965,6 +2249,50 @@ impl PyHpoSet {
         Ok(Self { ids })
     }
 
+    /// Instantiate an HPOSet from a compact serialized HPOSet
+    ///
+    /// See :func:`pyhpo.HPOSet.serialize_compact`
+    ///
+    /// Parameters
+    /// ----------
+    /// data: str
+    ///     A compact serialized HPOSet
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.HPOSet`
+    ///     A new ``HPOSet``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// ValueError
+    ///     ``data`` is not a valid compact HPOSet
+    /// KeyError
+    ///     No HPO term is found for one of the encoded IDs
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     HPOSet.from_compact("duQT")
+    ///     # >> HPOSet: [HP:0000118,HP:0002650]
+    ///
+    #[classmethod]
+    fn from_compact(_cls: &Bound<'_, PyType>, data: &str) -> PyResult<Self> {
+        let ids: HpoGroup = compact::decode(data)?
+            .iter()
+            .map(|id| Ok(term_from_id(*id)?.id().as_u32()))
+            .collect::<PyResult<Vec<u32>>>()?
+            .into();
+
+        Ok(Self { ids })
+    }
+
     /// Instantiate an HPOSet from a Gene
     ///
     /// Parameters
@@ -1074,10 +2402,16 @@ impl PyHpoSet {
         Self::try_from(disease)
     }
 
-    fn __len__(&self) -> usize {
+    pub(crate) fn __len__(&self) -> usize {
         self.ids.len()
     }
 
+    /// The set is pickled as the IDs of its terms
+    fn __reduce__<'py>(&self, py: Python<'py>) -> (Bound<'py, PyType>, (Vec<u32>,)) {
+        let ids = self.ids.iter().map(|id| id.as_u32()).collect();
+        (py.get_type::<Self>(), (ids,))
+    }
+
     fn __repr__(&self) -> String {
         format!(
             "HPOSet.from_serialized(\"{}\")",
@@ -1110,15 +2444,455 @@ impl PyHpoSet {
         Iter::new(&self.ids)
     }
 
+    /// Subset the HPOSet by index or slice
+    ///
+    /// The terms in an HPOSet are sorted by their ID
+    ///
+    /// Parameters
+    /// ----------
+    /// index: int or slice
+    ///     The position of the term in the set or a slice
+    ///
+    /// Returns
+    /// -------
+    /// :class:`HPOTerm` or :class:`HPOSet`
+    ///     A single ``HPOTerm`` for an integer index, or
+    ///     a new ``HPOSet`` for a slice
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// IndexError
+    ///     The index is out of range
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology, HPOSet
+    ///     Ontology()
+    ///     my_set = HPOSet.from_queries([7, 118, 2650])
+    ///     my_set[0]  # >> <HpoTerm (HP:0000007)>
+    ///     my_set[-1]  # >> <HpoTerm (HP:0002650)>
+    ///     my_set[1:]  # >> HPOSet.from_serialized("118+2650")
+    ///
+    fn __getitem__(&self, py: Python<'_>, index: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        if let Ok(slice) = index.downcast::<PySlice>() {
+            let indices = slice.indices(self.ids.len() as isize)?;
+            let mut ids = HpoGroup::new();
+            let mut idx = indices.start;
+            for _ in 0..indices.slicelength {
+                if let Some(id) = self.ids.get(idx as usize) {
+                    ids.insert(*id);
+                }
+                idx += indices.step;
+            }
+            return Self { ids }.into_py_any(py);
+        }
+
+        let idx: isize = index.extract()?;
+        let idx = if idx < 0 {
+            idx + self.ids.len() as isize
+        } else {
+            idx
+        };
+        let id = usize::try_from(idx)
+            .ok()
+            .and_then(|idx| self.ids.get(idx))
+            .ok_or_else(|| PyIndexError::new_err("HPOSet index out of range"))?;
+        pyterm_from_id(id.as_u32())?.into_py_any(py)
+    }
+
     fn __contains__(&self, term: &PyHpoTerm) -> bool {
         self.ids.contains(&term.hpo_term_id())
     }
 }
 
 impl<'a> PyHpoSet {
-    pub fn set(&'a self, ont: &'a Ontology) -> HpoSet {
+    pub fn set(&'a self, ont: &'a Ontology) -> HpoSet<'a> {
         HpoSet::new(ont, self.ids.clone())
     }
+
+    /// Returns the similarity score of this and another set
+    ///
+    /// # Errors
+    ///
+    /// - PyNameError: Ontology not yet constructed
+    /// - PyAttributeError: Invalid `kind`
+    /// - PyRuntimeError: Invalid `method` or `combine`
+    /// - PyValueError: Invalid `on_empty` or an empty set with `on_empty="raise"`
+    pub(crate) fn set_similarity<T: Score>(
+        &self,
+        other: &PyHpoSet,
+        kind: &str,
+        method: &str,
+        combine: &str,
+        on_empty: &str,
+    ) -> PyResult<T> {
+        let ont = get_ontology()?;
+        let set_a = HpoSet::new(ont, self.ids.clone());
+        let set_b = HpoSet::new(ont, other.ids.clone());
+
+        if PyInformationContentKind::try_from(kind).is_err() && !is_custom_kind(kind) {
+            return Err(PyAttributeError::new_err("Invalid Information content"));
+        }
+
+        let similarity = similarity_cache::builtin(method, kind)?;
+        let combiner = StandardCombiner::try_from(combine)
+            .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+
+        let on_empty = EmptySetPolicy::try_from(on_empty)?;
+
+        on_empty.score(set_a.is_empty() || set_b.is_empty(), || {
+            T::set_similarity(&similarity, &combiner, &set_a, &set_b)
+        })
+    }
+
+    /// Returns the similarity scores of this and every other set, calculated in parallel
+    ///
+    /// # Errors
+    ///
+    /// - PyNameError: Ontology not yet constructed
+    /// - PyKeyError: Invalid `kind`
+    /// - PyRuntimeError: Invalid `method` or `combine`
+    /// - PyValueError: Invalid `on_empty` or an empty set with `on_empty="raise"`
+    fn scores<T: Score>(
+        &self,
+        py: Python<'_>,
+        other: &[PyHpoSet],
+        kind: &str,
+        method: &str,
+        combine: &str,
+        on_empty: &str,
+    ) -> PyResult<Vec<T>> {
+        let ont = get_ontology()?;
+        let set_a = HpoSet::new(ont, self.ids.clone());
+
+        let similarity = similarity_cache::builtin(method, kind)?;
+        let combiner = StandardCombiner::try_from(combine)
+            .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+
+        let on_empty = EmptySetPolicy::try_from(on_empty)?;
+
+        worker::install(py, || {
+            other
+                .par_iter()
+                .map(|sb| {
+                    let set_b = HpoSet::new(ont, sb.ids.clone());
+                    on_empty.score(set_a.is_empty() || set_b.is_empty(), || {
+                        T::set_similarity(&similarity, &combiner, &set_a, &set_b)
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the best matching term of `other` for every term, calculated in parallel
+    ///
+    /// Ties are resolved by preferring the term itself, then the lower ID
+    /// of the matching term.
+    ///
+    /// # Errors
+    ///
+    /// - PyNameError: Ontology not yet constructed
+    /// - PyKeyError: Invalid `kind`
+    /// - PyRuntimeError: Invalid `method`
+    fn best_matches<'py>(
+        &self,
+        py: Python<'py>,
+        other: &PyHpoSet,
+        kind: &str,
+        method: &str,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let ont = get_ontology()?;
+        let similarity = similarity_cache::builtin(method, kind)?;
+        let others: Vec<hpo::HpoTerm> = other.ids.iter().filter_map(|id| ont.hpo(id)).collect();
+        let matches: Vec<(hpo::HpoTerm, hpo::HpoTerm, f32)> = worker::install(py, || {
+            self.ids
+                .iter()
+                .filter_map(|id| ont.hpo(id))
+                .collect::<Vec<hpo::HpoTerm>>()
+                .into_par_iter()
+                .filter_map(|term| {
+                    others
+                        .iter()
+                        .map(|other| (*other, similarity.calculate(&term, other)))
+                        .reduce(|best, next| {
+                            let identical = next.0.id() == term.id();
+                            if next.1 > best.1 || (next.1 == best.1 && identical) {
+                                next
+                            } else {
+                                best
+                            }
+                        })
+                        .map(|(other, score)| (term, other, score))
+                })
+                .collect()
+        });
+
+        matches
+            .into_iter()
+            .map(|(term, other, score)| {
+                let mica = most_informative(&common_ancestor_ids([term, other], true), kind)?;
+                let dict = PyDict::new(py);
+                dict.set_item("term", PyHpoTerm::from(term))?;
+                dict.set_item("match", PyHpoTerm::from(other))?;
+                dict.set_item("similarity", score)?;
+                dict.set_item("mica", mica)?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// Returns the similarity of all pairs of distinct terms, calculated in parallel
+    ///
+    /// # Errors
+    ///
+    /// - PyNameError: Ontology not yet constructed
+    /// - PyKeyError: Invalid `kind`
+    /// - PyRuntimeError: Invalid `method`
+    fn pairwise_similarity(&self, py: Python<'_>, kind: &str, method: &str) -> PyResult<Vec<f32>> {
+        let ont = get_ontology()?;
+        let similarity = similarity_cache::builtin(method, kind)?;
+        let terms: Vec<hpo::HpoTerm> = self.ids.iter().filter_map(|id| ont.hpo(id)).collect();
+        let pairs: Vec<(usize, usize)> = (0..terms.len())
+            .flat_map(|a| (a + 1..terms.len()).map(move |b| (a, b)))
+            .collect();
+        Ok(worker::install(py, || {
+            pairs
+                .par_iter()
+                .map(|&(a, b)| similarity.calculate(&terms[a], &terms[b]))
+                .collect()
+        }))
+    }
+}
+
+/// How to score similarity comparisons that involve an empty `HPOSet`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EmptySetPolicy {
+    /// The similarity is `0.0`
+    Zero,
+    /// The similarity is `NaN`
+    Nan,
+    /// The comparison fails with a `ValueError`
+    Raise,
+}
+
+impl TryFrom<&str> for EmptySetPolicy {
+    type Error = PyErr;
+    fn try_from(value: &str) -> PyResult<Self> {
+        match value {
+            "zero" => Ok(Self::Zero),
+            "nan" => Ok(Self::Nan),
+            "raise" => Ok(Self::Raise),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid on_empty policy {value}. Use `zero`, `nan` or `raise`"
+            ))),
+        }
+    }
+}
+
+impl EmptySetPolicy {
+    /// Returns the similarity from `calculate`, unless one of the compared sets is empty
+    ///
+    /// # Errors
+    ///
+    /// - PyValueError: A set is empty and the policy is `Raise`
+    pub fn score<T, F>(self, empty: bool, calculate: F) -> PyResult<T>
+    where
+        T: Score,
+        F: FnOnce() -> T,
+    {
+        match (empty, self) {
+            (false, _) => Ok(calculate()),
+            (true, Self::Zero) => Ok(0.0f32.into()),
+            (true, Self::Nan) => Ok(f32::NAN.into()),
+            (true, Self::Raise) => Err(PyValueError::new_err(
+                "Unable to calculate the similarity of an empty HPOSet",
+            )),
+        }
+    }
+}
+
+/// How to handle serialized sets of a different HPO release
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VersionMismatchPolicy {
+    /// Emit a `UserWarning`
+    Warn,
+    /// Fail with a `ValueError`
+    Raise,
+    /// Load the set silently
+    Ignore,
+}
+
+impl TryFrom<&str> for VersionMismatchPolicy {
+    type Error = PyErr;
+    fn try_from(value: &str) -> PyResult<Self> {
+        match value {
+            "warn" => Ok(Self::Warn),
+            "raise" => Ok(Self::Raise),
+            "ignore" => Ok(Self::Ignore),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid on_version_mismatch policy {value}. Use `warn`, `raise` or `ignore`"
+            ))),
+        }
+    }
+}
+
+/// Returns `true` if `item` is a missing value
+///
+/// Missing values are `None`, `NaN`, pandas `NA` and empty strings
+fn is_missing(item: &Bound<'_, PyAny>) -> PyResult<bool> {
+    if item.is_none() {
+        return Ok(true);
+    }
+    if let Ok(value) = item.downcast::<PyFloat>() {
+        return Ok(value.value().is_nan());
+    }
+    if let Ok(value) = item.downcast::<PyString>() {
+        return Ok(value.to_cow()?.trim().is_empty());
+    }
+    Ok(item.get_type().name()?.to_cow()? == "NAType")
+}
+
+/// Returns the term IDs of all items of a pandas Series or other iterable
+///
+/// Missing values are skipped, see [`is_missing`]
+///
+/// # Errors
+///
+/// - PyTypeError: `series` is not iterable or an item has an invalid type
+/// - PyValueError: query cannot be converted to HpoTermId
+/// - PyRuntimeError: No HPO term is found for the provided query
+/// - PyNameError: Ontology not yet constructed
+///
+/// The type and term errors are ignored if `skip_invalid` is `true`
+fn ids_from_series(series: &Bound<'_, PyAny>, skip_invalid: bool) -> PyResult<Vec<HpoTermId>> {
+    get_ontology()?;
+    let mut ids = Vec::new();
+    for item in series.try_iter()? {
+        let item = item?;
+        if is_missing(&item)? {
+            continue;
+        }
+        let id = if let Ok(term) = item.downcast::<PyHpoTerm>() {
+            Ok(term.borrow().hpo_term_id())
+        } else {
+            item.extract::<PyQuery>()
+                .map(|query| match query {
+                    PyQuery::Str(query) => PyQuery::Str(query.trim().to_string()),
+                    query => query,
+                })
+                .and_then(|query| term_from_query(query).map(|term| term.id()))
+        };
+        match id {
+            Ok(id) => ids.push(id),
+            Err(_) if skip_invalid => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(ids)
+}
+
+/// Returns the term IDs of FHIR `Coding` or `CodeableConcept` fragments
+///
+/// Invalid fragments are ignored if `skip_invalid` is set
+///
+/// # Errors
+///
+/// - PyNameError: Ontology not yet constructed
+/// - PyTypeError: `fragments` is not iterable or a fragment is not a dict
+/// - PyValueError: A fragment does not contain a valid HPO coding
+/// - PyKeyError: No term with that ID present in Ontology
+fn ids_from_fhir(fragments: &Bound<'_, PyAny>, skip_invalid: bool) -> PyResult<Vec<HpoTermId>> {
+    get_ontology()?;
+    let mut ids = Vec::new();
+    for fragment in fragments.try_iter()? {
+        match fhir::term_from_fhir(&fragment?) {
+            Ok(term) => ids.push(term.id()),
+            Err(_) if skip_invalid => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(ids)
+}
+
+/// Returns the term IDs from an object that supports the buffer protocol,
+/// e.g. a numpy array or `array.array`
+///
+/// Returns `None` if the object is not a buffer of integers
+///
+/// # Errors
+///
+/// - PyValueError: The buffer is not one-dimensional or contains invalid IDs
+fn ids_from_buffer(terms: &Bound<'_, PyAny>) -> PyResult<Option<Vec<u32>>> {
+    if terms.is_instance_of::<PyList>() || terms.is_instance_of::<PyTuple>() {
+        return Ok(None);
+    }
+    macro_rules! extract_buffer {
+        ($($ty:ty),*) => {$(
+            if let Ok(buffer) = PyBuffer::<$ty>::get(terms) {
+                if buffer.dimensions() > 1 {
+                    return Err(PyValueError::new_err(
+                        "Term IDs must be a one-dimensional array",
+                    ));
+                }
+                return buffer
+                    .to_vec(terms.py())?
+                    .into_iter()
+                    .map(|id| {
+                        u32::try_from(id)
+                            .map_err(|_| PyValueError::new_err(format!("Invalid id: {id}")))
+                    })
+                    .collect::<PyResult<Vec<u32>>>()
+                    .map(Some);
+            }
+        )*};
+    }
+    extract_buffer!(i64, u64, i32, u32, i16, u16, i8, u8);
+    Ok(None)
+}
+
+/// Parses the term IDs of a serialized `HPOSet`
+///
+/// The serialized set can be prefixed with the HPO release, e.g.
+/// `2024-08-13:118+2650`, which is compared to the release of the Ontology.
+///
+/// # Errors
+///
+/// - PyValueError: Invalid policy name or invalid term ID
+/// - PyValueError: Different HPO release and the policy is `Raise`
+/// - PyNameError: Ontology not yet constructed
+fn parse_serialized(py: Python<'_>, pickle: &str, on_version_mismatch: &str) -> PyResult<Vec<u32>> {
+    let policy = VersionMismatchPolicy::try_from(on_version_mismatch)?;
+    let terms = match pickle.split_once(':') {
+        Some((version, terms)) => {
+            let current = get_ontology()?.hpo_version();
+            if version != current && policy != VersionMismatchPolicy::Ignore {
+                let message = format!(
+                    "The HPOSet was serialized with HPO release {version}, \
+                    but the Ontology is release {current}"
+                );
+                if policy == VersionMismatchPolicy::Raise {
+                    return Err(PyValueError::new_err(message));
+                }
+                PyErr::warn(
+                    py,
+                    &py.get_type::<PyUserWarning>(),
+                    &CString::new(message)?,
+                    1,
+                )?;
+            }
+            terms
+        }
+        None => pickle,
+    };
+    Ok(terms
+        .split('+')
+        .map(|id| id.parse::<u32>())
+        .collect::<Result<Vec<u32>, ParseIntError>>()?)
 }
 
 impl TryFrom<&PyGene> for PyHpoSet {
@@ -1212,11 +2986,7 @@ impl BasicPyHpoSet {
         set.replace_obsolete();
         set.remove_obsolete();
         set.remove_modifier();
-        PyHpoSet::new(
-            set.iter()
-                .map(|term| TermOrId::Id(term.id().as_u32()))
-                .collect(),
-        )
+        Ok(PyHpoSet::from(set))
     }
 }
 
@@ -1236,12 +3006,43 @@ impl BasicPyHpoSet {
     }
 
     #[classmethod]
-    fn from_serialized(_cls: &Bound<'_, PyType>, pickle: &str) -> PyResult<PyHpoSet> {
+    #[pyo3(signature = (series, skip_invalid = false))]
+    fn from_series(
+        _cls: &Bound<'_, PyType>,
+        series: &Bound<'_, PyAny>,
+        skip_invalid: bool,
+    ) -> PyResult<PyHpoSet> {
+        BasicPyHpoSet::build(ids_from_series(series, skip_invalid)?)
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (fragments, skip_invalid = false))]
+    fn from_fhir(
+        _cls: &Bound<'_, PyType>,
+        fragments: &Bound<'_, PyAny>,
+        skip_invalid: bool,
+    ) -> PyResult<PyHpoSet> {
+        BasicPyHpoSet::build(ids_from_fhir(fragments, skip_invalid)?)
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (pickle, on_version_mismatch = "warn"))]
+    fn from_serialized(
+        cls: &Bound<'_, PyType>,
+        pickle: &str,
+        on_version_mismatch: &str,
+    ) -> PyResult<PyHpoSet> {
+        BasicPyHpoSet::build(
+            parse_serialized(cls.py(), pickle, on_version_mismatch)?
+                .iter()
+                .map(|id| HpoTermId::from_u32(*id)),
+        )
+    }
+
+    #[classmethod]
+    fn from_compact(_cls: &Bound<'_, PyType>, data: &str) -> PyResult<PyHpoSet> {
         BasicPyHpoSet::build(
-            pickle
-                .split('+')
-                .map(|id| id.parse::<u32>())
-                .collect::<Result<Vec<u32>, ParseIntError>>()?
+            compact::decode(data)?
                 .iter()
                 .map(|id| HpoTermId::from_u32(*id)),
         )
@@ -1290,11 +3091,7 @@ impl PhenoSet {
         set.replace_obsolete();
         set.remove_obsolete();
         set.remove_modifier();
-        PyHpoSet::new(
-            set.iter()
-                .map(|term| TermOrId::Id(term.id().as_u32()))
-                .collect(),
-        )
+        Ok(PyHpoSet::from(set))
     }
 }
 
@@ -1314,12 +3111,43 @@ impl PhenoSet {
     }
 
     #[classmethod]
-    fn from_serialized(_cls: &Bound<'_, PyType>, pickle: &str) -> PyResult<PyHpoSet> {
+    #[pyo3(signature = (series, skip_invalid = false))]
+    fn from_series(
+        _cls: &Bound<'_, PyType>,
+        series: &Bound<'_, PyAny>,
+        skip_invalid: bool,
+    ) -> PyResult<PyHpoSet> {
+        PhenoSet::build(ids_from_series(series, skip_invalid)?)
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (fragments, skip_invalid = false))]
+    fn from_fhir(
+        _cls: &Bound<'_, PyType>,
+        fragments: &Bound<'_, PyAny>,
+        skip_invalid: bool,
+    ) -> PyResult<PyHpoSet> {
+        PhenoSet::build(ids_from_fhir(fragments, skip_invalid)?)
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (pickle, on_version_mismatch = "warn"))]
+    fn from_serialized(
+        cls: &Bound<'_, PyType>,
+        pickle: &str,
+        on_version_mismatch: &str,
+    ) -> PyResult<PyHpoSet> {
+        PhenoSet::build(
+            parse_serialized(cls.py(), pickle, on_version_mismatch)?
+                .iter()
+                .map(|id| HpoTermId::from_u32(*id)),
+        )
+    }
+
+    #[classmethod]
+    fn from_compact(_cls: &Bound<'_, PyType>, data: &str) -> PyResult<PyHpoSet> {
         PhenoSet::build(
-            pickle
-                .split('+')
-                .map(|id| id.parse::<u32>())
-                .collect::<Result<Vec<u32>, ParseIntError>>()?
+            compact::decode(data)?
                 .iter()
                 .map(|id| HpoTermId::from_u32(*id)),
         )
@@ -0,0 +1,363 @@
+use std::collections::{HashMap, HashSet};
+
+use rayon::prelude::*;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use pyo3::IntoPyObjectExt;
+
+use hpo::annotations::Disease;
+use hpo::similarity::{GroupSimilarity, StandardCombiner};
+use hpo::term::HpoGroup;
+use hpo::{HpoSet, HpoTermId, Ontology};
+
+use crate::annotations::{PyOmimDisease, PyOrphaDisease};
+use crate::cohort::PyCohort;
+use crate::get_ontology;
+use crate::set::PyHpoSet;
+use crate::similarity::{is_sparse, IcSource, PySimilarity};
+use crate::similarity_cache::{self, CachedSimilarity};
+use crate::worker;
+
+/// The search keys of a term: the term itself and all its
+/// ancestors with information content
+fn search_keys(ont: &Ontology, source: &IcSource, id: HpoTermId) -> Vec<HpoTermId> {
+    let Some(term) = ont.hpo(id) else {
+        return Vec::new();
+    };
+    let mut keys = vec![id];
+    keys.extend(term.all_parent_ids().iter().filter(|parent| {
+        ont.hpo(*parent)
+            .is_some_and(|parent| source.ic(&parent) != 0.0)
+    }));
+    keys
+}
+
+/// Returns the term IDs of all `sets`
+fn group_ids(ont: &Ontology, sets: &[PyHpoSet]) -> Vec<HpoGroup> {
+    sets.iter()
+        .map(|set| set.set(ont).iter().map(|term| term.id()).collect())
+        .collect()
+}
+
+/// A database of ``HPOSet`` for fast nearest-neighbor searches
+///
+/// The sets are registered once, e.g. all diseases or the members
+/// of a cohort. The index then returns the ``k`` most similar sets
+/// to any query set, calculated in parallel.
+///
+/// For the ``resnik``, ``lin``, ``rel``, ``ic`` and ``graphic`` methods,
+/// two different terms are only similar if they share an ancestor with
+/// information content. An inverted index of all terms and their
+/// ancestors selects the sets that share such an ancestor with the query,
+/// all other sets have a similarity of ``0.0`` and are not calculated.
+/// All other methods compare the query to every set.
+///
+/// The index uses the information content at the time of its creation.
+///
+/// Parameters
+/// ----------
+/// sets: list[:class:`pyhpo.HPOSet`] or :class:`pyhpo.Cohort`
+///     The sets of the database
+/// keys: list, optional
+///     The keys that identify the sets in search results. Defaults to
+///     the IDs of a cohort or the index of the set
+/// kind: str, default: ``omim``
+///     Which kind of information content to use for similarity calculation
+/// method: str, default ``graphic``
+///     The method to use to calculate the similarity.
+///     See :func:`pyhpo.HPOSet.similarity` for available options
+/// combine: str, default ``funSimAvg``
+///     The method to combine individual term similarity
+///     to HPOSet similarities.
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+/// KeyError
+///     Invalid ``kind``
+/// RuntimeError
+///     Invalid ``method`` or ``combine``
+/// ValueError
+///     ``keys`` and ``sets`` have a different length
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, HPOSet, helper
+///     Ontology()
+///
+///     index = helper.SetIndex.from_diseases("omim")
+///     query = HPOSet.from_queries(["HP:0002650", "HP:0009121", "HP:0002808"])
+///     for disease, score in index.nearest(query, k=5):
+///         print(disease.name, score)
+///
+#[pyclass(name = "SetIndex", module = "pyhpo.helper")]
+pub(crate) struct PySetIndex {
+    keys: Vec<PyObject>,
+    sets: Vec<HpoGroup>,
+    /// The sets that contain a search key, `None` if all sets must be compared
+    index: Option<HashMap<HpoTermId, Vec<usize>>>,
+    kind: String,
+    similarity: GroupSimilarity<CachedSimilarity<PySimilarity>, StandardCombiner>,
+}
+
+#[pymethods]
+impl PySetIndex {
+    #[new]
+    #[pyo3(signature = (sets, keys = None, kind = "omim", method = "graphic", combine = "funSimAvg"))]
+    #[pyo3(text_signature = "(sets, keys, kind, method, combine)")]
+    fn new(
+        py: Python<'_>,
+        sets: &Bound<'_, PyAny>,
+        keys: Option<Vec<PyObject>>,
+        kind: &str,
+        method: &str,
+        combine: &str,
+    ) -> PyResult<Self> {
+        let ont = get_ontology()?;
+        let (sets, default_keys): (Vec<HpoGroup>, Vec<PyObject>) = match sets.downcast::<PyCohort>()
+        {
+            Ok(cohort) => {
+                let cohort = cohort.borrow();
+                let keys = cohort
+                    .ids()
+                    .into_iter()
+                    .map(|id| id.into_py_any(py))
+                    .collect::<PyResult<_>>()?;
+                (group_ids(ont, cohort.sets()), keys)
+            }
+            Err(_) => {
+                let sets: Vec<PyHpoSet> = sets.extract()?;
+                let keys = (0..sets.len())
+                    .map(|idx| idx.into_py_any(py))
+                    .collect::<PyResult<_>>()?;
+                (group_ids(ont, &sets), keys)
+            }
+        };
+        let keys = match keys {
+            Some(keys) if keys.len() != sets.len() => {
+                return Err(PyValueError::new_err(format!(
+                    "Expected {} keys, got {}",
+                    sets.len(),
+                    keys.len()
+                )))
+            }
+            Some(keys) => keys,
+            None => default_keys,
+        };
+        Self::build(py, keys, sets, kind, method, combine)
+    }
+
+    /// Creates the index of all diseases
+    ///
+    /// Parameters
+    /// ----------
+    /// source: str, default: ``omim``
+    ///     The diseases of the index, ``omim`` or ``orpha``. The keys of
+    ///     the sets are :class:`pyhpo.Omim` or :class:`pyhpo.Orpha`
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use for similarity calculation
+    /// method: str, default ``graphic``
+    ///     The method to use to calculate the similarity.
+    /// combine: str, default ``funSimAvg``
+    ///     The method to combine individual term similarity
+    ///     to HPOSet similarities.
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.helper.SetIndex`
+    ///     The index of all diseases
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method`` or ``combine``
+    /// ValueError
+    ///     Invalid ``source``
+    ///
+    #[classmethod]
+    #[pyo3(signature = (source = "omim", kind = "omim", method = "graphic", combine = "funSimAvg"))]
+    #[pyo3(text_signature = "(source, kind, method, combine)")]
+    fn from_diseases(
+        _cls: &Bound<'_, PyType>,
+        py: Python<'_>,
+        source: &str,
+        kind: &str,
+        method: &str,
+        combine: &str,
+    ) -> PyResult<Self> {
+        let ont = get_ontology()?;
+        let mut keys = Vec::new();
+        let mut sets = Vec::new();
+        match source {
+            "omim" => {
+                for disease in ont.omim_diseases() {
+                    keys.push(PyOmimDisease::from(disease).into_py_any(py)?);
+                    sets.push(disease.hpo_terms().clone());
+                }
+            }
+            "orpha" => {
+                for disease in ont.orpha_diseases() {
+                    keys.push(PyOrphaDisease::from(disease).into_py_any(py)?);
+                    sets.push(disease.hpo_terms().clone());
+                }
+            }
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid source {source}. Use `omim` or `orpha`"
+                )))
+            }
+        }
+        Self::build(py, keys, sets, kind, method, combine)
+    }
+
+    /// Returns the ``k`` most similar sets to ``query``
+    ///
+    /// Parameters
+    /// ----------
+    /// query: :class:`pyhpo.HPOSet`
+    ///     The set to search for
+    /// k: int, optional, default: ``10``
+    ///     The number of sets to return, ``None`` returns all sets
+    ///
+    /// Returns
+    /// -------
+    /// list[tuple[Any, float]]
+    ///     The keys of the sets and their similarity, sorted from most to
+    ///     least similar. Sets with the same similarity are sorted in the
+    ///     order of the index
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    #[pyo3(signature = (query, k = Some(10)))]
+    #[pyo3(text_signature = "($self, query, k)")]
+    fn nearest(
+        &self,
+        py: Python<'_>,
+        query: &PyHpoSet,
+        k: Option<usize>,
+    ) -> PyResult<Vec<(PyObject, f32)>> {
+        let ont = get_ontology()?;
+        let k = k.unwrap_or(self.sets.len()).min(self.sets.len());
+        let source = IcSource::new(&self.kind)?;
+        let query_set = query.set(ont);
+
+        let candidates: Vec<usize> = match &self.index {
+            Some(index) => {
+                let mut candidates: Vec<usize> = query_set
+                    .iter()
+                    .flat_map(|term| search_keys(ont, &source, term.id()))
+                    .filter_map(|key| index.get(&key))
+                    .flatten()
+                    .copied()
+                    .collect::<HashSet<usize>>()
+                    .into_iter()
+                    .collect();
+                candidates.sort_unstable();
+                candidates
+            }
+            None => (0..self.sets.len()).collect(),
+        };
+
+        let mut scores: Vec<(usize, f32)> = worker::install(py, || {
+            candidates
+                .into_par_iter()
+                .map(|idx| {
+                    let set = HpoSet::new(ont, self.sets[idx].clone());
+                    let score = if query_set.is_empty() || set.is_empty() {
+                        0.0
+                    } else {
+                        self.similarity.calculate(&query_set, &set)
+                    };
+                    (idx, score)
+                })
+                .collect()
+        });
+        if self.index.is_some() {
+            // All sets that are not a candidate have a similarity of `0.0`
+            scores.retain(|(_, score)| *score != 0.0);
+            let similar: HashSet<usize> = scores.iter().map(|(idx, _)| *idx).collect();
+            scores.extend(
+                (0..self.sets.len())
+                    .filter(|idx| !similar.contains(idx))
+                    .map(|idx| (idx, 0.0)),
+            );
+        }
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores.truncate(k);
+
+        Ok(scores
+            .into_iter()
+            .map(|(idx, score)| (self.keys[idx].clone_ref(py), score))
+            .collect())
+    }
+
+    fn __len__(&self) -> usize {
+        self.sets.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<SetIndex ({} sets)>", self.sets.len())
+    }
+}
+
+impl PySetIndex {
+    /// Creates the index of `sets`
+    ///
+    /// # Errors
+    ///
+    /// - PyNameError: Ontology not yet constructed
+    /// - PyKeyError: Invalid `kind`
+    /// - PyRuntimeError: Invalid `method` or `combine`
+    fn build(
+        py: Python<'_>,
+        keys: Vec<PyObject>,
+        sets: Vec<HpoGroup>,
+        kind: &str,
+        method: &str,
+        combine: &str,
+    ) -> PyResult<Self> {
+        let ont = get_ontology()?;
+        let source = IcSource::new(kind)?;
+        let similarity = similarity_cache::builtin(method, kind)?;
+        let combiner = StandardCombiner::try_from(combine)
+            .map_err(|_| PyRuntimeError::new_err("Invalid combine method specified"))?;
+
+        let index = is_sparse(method).then(|| {
+            py.allow_threads(|| {
+                let mut index: HashMap<HpoTermId, Vec<usize>> = HashMap::new();
+                for (idx, set) in sets.iter().enumerate() {
+                    for id in set {
+                        for key in search_keys(ont, &source, id) {
+                            let entries = index.entry(key).or_default();
+                            if entries.last() != Some(&idx) {
+                                entries.push(idx);
+                            }
+                        }
+                    }
+                }
+                index
+            })
+        });
+
+        Ok(Self {
+            keys,
+            sets,
+            index,
+            kind: kind.to_string(),
+            similarity: GroupSimilarity::new(combiner, similarity),
+        })
+    }
+}
@@ -0,0 +1,591 @@
+//! Similarity methods with support for the custom information content
+//!
+//! The builtin similarity methods of the `hpo` crate only support the
+//! information content that is calculated from the annotations. This module
+//! re-implements the IC-based methods for the custom information content.
+//!
+//! The re-implementation also allows to calculate several methods in a
+//! single pass over the ancestors of each term pair.
+//!
+//! Additional methods can be registered at runtime, either as alias of a
+//! builtin method or as Python function, and are resolved wherever a
+//! similarity method is specified by name.
+//!
+//! The term similarities of a pair of sets can be combined with single or
+//! double precision, see [`Score`].
+use std::collections::HashMap;
+use std::ops::{Add, Div, Sub};
+use std::sync::{Arc, RwLock};
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+
+use hpo::annotations::AnnotationId;
+use hpo::matrix::Matrix;
+use hpo::similarity::{Builtins, Similarity, SimilarityCombiner, StandardCombiner};
+use hpo::term::InformationContentKind;
+use hpo::{HpoSet, HpoTerm};
+
+use crate::buffer::BufferData;
+use crate::information_content::{custom_ic_table, is_custom_kind, PyInformationContentKind};
+use crate::{pyterm_from_id, similarity_cache};
+
+/// The similarity methods that were registered with `register_similarity`, by lowercase name
+static REGISTRY: Lazy<RwLock<HashMap<String, Registered>>> = Lazy::new(Default::default);
+
+/// A registered similarity method
+#[derive(Clone)]
+enum Registered {
+    /// Another name of a builtin method
+    Alias(String),
+    Python(PythonSimilarity),
+}
+
+/// Returns the registered similarity method `method`
+fn registered(method: &str) -> Option<Registered> {
+    REGISTRY
+        .read()
+        .expect("similarity registry lock must not be poisoned")
+        .get(&method.to_lowercase())
+        .cloned()
+}
+
+/// Returns `true` if `method` scores ``0.0`` for all pairs of distinct terms
+/// without a common ancestor with information content
+///
+/// Python methods can return any score and are never sparse.
+pub(crate) fn is_sparse(method: &str) -> bool {
+    let method = match registered(method) {
+        Some(Registered::Python(_)) => return false,
+        Some(Registered::Alias(builtin)) => builtin,
+        None => method.to_string(),
+    };
+    matches!(
+        IcMethod::from_name(&method),
+        Some(
+            IcMethod::GraphIc
+                | IcMethod::InformationCoefficient
+                | IcMethod::Lin
+                | IcMethod::Relevance
+                | IcMethod::Resnik
+        )
+    )
+}
+
+/// A similarity method based on either the builtin or the custom
+/// information content or on a registered Python function
+pub(crate) enum PySimilarity {
+    Builtin(Builtins),
+    Custom(CustomIcSimilarity),
+    Python(PythonSimilarity),
+}
+
+impl PySimilarity {
+    /// Creates the similarity method for the `method` and `kind` of information content
+    ///
+    /// # Errors
+    ///
+    /// - PyKeyError: Invalid `kind`
+    /// - PyRuntimeError: Invalid `method`
+    pub fn new(method: &str, kind: &str) -> PyResult<Self> {
+        let method = match registered(method) {
+            Some(Registered::Python(sim)) => return Ok(Self::Python(sim)),
+            Some(Registered::Alias(builtin)) => builtin,
+            None => method.to_string(),
+        };
+        if is_custom_kind(kind) {
+            return CustomIcSimilarity::new(&method, kind).map(Self::Custom);
+        }
+        Self::with_kind(&method, PyInformationContentKind::try_from(kind)?)
+    }
+
+    /// Creates the builtin or registered similarity method for the builtin `kind`
+    ///
+    /// # Errors
+    ///
+    /// - PyRuntimeError: Invalid `method`
+    pub fn with_kind(method: &str, kind: PyInformationContentKind) -> PyResult<Self> {
+        let method = match registered(method) {
+            Some(Registered::Python(sim)) => return Ok(Self::Python(sim)),
+            Some(Registered::Alias(builtin)) => builtin,
+            None => method.to_string(),
+        };
+        Builtins::new(&method, kind.into())
+            .map(Self::Builtin)
+            .map_err(|_| PyRuntimeError::new_err("Unknown method to calculate similarity"))
+    }
+}
+
+impl Similarity for PySimilarity {
+    fn calculate(&self, a: &HpoTerm, b: &HpoTerm) -> f32 {
+        match self {
+            Self::Builtin(sim) => sim.calculate(a, b),
+            Self::Custom(sim) => sim.calculate(a, b),
+            Self::Python(sim) => sim.calculate(a, b),
+        }
+    }
+}
+
+/// A similarity method that calls a Python function with both terms
+///
+/// The function is called with the GIL, so that parallel calculations
+/// are serialized.
+#[derive(Clone)]
+pub(crate) struct PythonSimilarity(Arc<PyObject>);
+
+impl Similarity for PythonSimilarity {
+    /// Exceptions of the function are reported as unraisable
+    /// and result in a `NaN` score
+    fn calculate(&self, a: &HpoTerm, b: &HpoTerm) -> f32 {
+        Python::with_gil(|py| {
+            let score = pyterm_from_id(a.id().as_u32())
+                .and_then(|a| Ok((a, pyterm_from_id(b.id().as_u32())?)))
+                .and_then(|args| self.0.call1(py, args))
+                .and_then(|score| score.extract::<f32>(py));
+            score.unwrap_or_else(|err| {
+                err.write_unraisable(py, None);
+                f32::NAN
+            })
+        })
+    }
+}
+
+/// Register a named similarity method
+///
+/// The method can then be used by its name wherever a similarity method
+/// is specified, e.g. in :func:`pyhpo.HPOTerm.similarity_score`,
+/// :func:`pyhpo.HPOSet.similarity`, :func:`pyhpo.helper.batch_set_similarity`
+/// or :func:`pyhpo.stats.linkage`. Registering a name again replaces
+/// the previous method and removes its cached scores from
+/// :class:`pyhpo.helper.similarity_cache`.
+///
+/// Parameters
+/// ----------
+/// name: str
+///     The name of the method, case-insensitive. The names of builtin
+///     methods cannot be replaced.
+/// similarity: str or Callable[[HPOTerm, HPOTerm], float]
+///     Either the name of a builtin method, to register an alias, or
+///     a function that returns the similarity of two ``HPOTerm``.
+///
+///     Functions are called with the GIL held, so parallel calculations
+///     are serialized and much slower than with builtin methods.
+///     If the function raises an exception, it is reported via
+///     ``sys.unraisablehook`` and the similarity is ``NaN``.
+///     Functions ignore the ``kind`` of information content
+///
+/// Raises
+/// ------
+/// ValueError
+///     ``name`` is a builtin method or ``similarity`` is not a builtin method
+/// TypeError
+///     ``similarity`` is neither a string nor callable
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     import pyhpo
+///     from pyhpo import Ontology
+///     Ontology()
+///
+///     def shared_parents(a, b):
+///         return len(set(a.parent_ids()) & set(b.parent_ids())) / 2
+///
+///     pyhpo.register_similarity("shared_parents", shared_parents)
+///     pyhpo.register_similarity("default", "graphic")
+///
+///     term = Ontology.hpo(11968)
+///     term.similarity_score(Ontology.hpo(1743), method="shared_parents")
+///
+#[pyfunction]
+#[pyo3(text_signature = "(name, similarity)")]
+pub(crate) fn register_similarity(name: &str, similarity: &Bound<'_, PyAny>) -> PyResult<()> {
+    let is_builtin = |method: &str| Builtins::new(method, InformationContentKind::Omim).is_ok();
+    if is_builtin(name) {
+        return Err(PyValueError::new_err(format!(
+            "The builtin method {name} cannot be replaced"
+        )));
+    }
+    let registered = if let Ok(method) = similarity.downcast::<PyString>() {
+        let method = method.to_cow()?;
+        if !is_builtin(&method) {
+            return Err(PyValueError::new_err(format!(
+                "Unknown builtin method {method}"
+            )));
+        }
+        Registered::Alias(method.into_owned())
+    } else if similarity.is_callable() {
+        Registered::Python(PythonSimilarity(Arc::new(similarity.clone().unbind())))
+    } else {
+        return Err(PyTypeError::new_err(
+            "similarity must be the name of a builtin method or a function",
+        ));
+    };
+    REGISTRY
+        .write()
+        .expect("similarity registry lock must not be poisoned")
+        .insert(name.to_lowercase(), registered);
+    similarity_cache::forget(name);
+    Ok(())
+}
+
+/// The similarity methods that use the information content
+#[derive(Clone, Copy)]
+enum IcMethod {
+    GraphIc,
+    InformationCoefficient,
+    Jc,
+    Lin,
+    Relevance,
+    Resnik,
+}
+
+impl IcMethod {
+    /// Returns the method with the given name, if it uses the information content
+    fn from_name(method: &str) -> Option<Self> {
+        match method.to_lowercase().as_str() {
+            "graphic" => Some(Self::GraphIc),
+            "informationcoefficient" | "ic" => Some(Self::InformationCoefficient),
+            "jc" | "jc2" => Some(Self::Jc),
+            "lin" => Some(Self::Lin),
+            "relevance" | "rel" => Some(Self::Relevance),
+            "resnik" => Some(Self::Resnik),
+            _ => None,
+        }
+    }
+
+    /// Calculates the similarity from the shared information content values of a term pair
+    ///
+    /// The formulas are identical to the builtin methods of the `hpo` crate
+    fn score(self, pair: &PairIc) -> f32 {
+        let lin = || {
+            let ic_combined = pair.ic_a + pair.ic_b;
+            if ic_combined == 0.0 {
+                0.0
+            } else {
+                2.0 * pair.resnik / ic_combined
+            }
+        };
+        match self {
+            Self::GraphIc if pair.same => 1.0,
+            Self::GraphIc if pair.ic_union == 0.0 => 0.0,
+            Self::GraphIc => pair.ic_common / pair.ic_union,
+            Self::InformationCoefficient => lin() * (1.0 - (1.0 / (1.0 + pair.resnik))),
+            Self::Jc if pair.same => 1.0,
+            Self::Jc if pair.ic_a == 0.0 || pair.ic_b == 0.0 => 0.0,
+            Self::Jc => 1.0 / (pair.ic_a + pair.ic_b - 2.0 * pair.resnik + 1.0),
+            Self::Lin => lin(),
+            Self::Relevance => lin() * (1.0 - (-pair.resnik).exp()),
+            Self::Resnik => pair.resnik,
+        }
+    }
+}
+
+/// The source of the information content of terms
+pub(crate) enum IcSource {
+    Builtin(InformationContentKind),
+    Custom(HashMap<u32, f32>),
+}
+
+impl IcSource {
+    /// Returns the source of a builtin or custom information content `kind`
+    ///
+    /// # Errors
+    ///
+    /// - PyKeyError: Invalid `kind`
+    pub(crate) fn new(kind: &str) -> PyResult<Self> {
+        match PyInformationContentKind::try_from(kind) {
+            Ok(kind) => Ok(Self::Builtin(kind.into())),
+            Err(_) if is_custom_kind(kind) => Ok(Self::Custom(custom_ic_table(kind))),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub(crate) fn ic(&self, term: &HpoTerm) -> f32 {
+        match self {
+            Self::Builtin(kind) => term.information_content().get_kind(kind),
+            Self::Custom(ic) => ic.get(&term.id().as_u32()).copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// The information content values of a term pair that all IC-based methods use
+///
+/// Calculating them requires the ancestors of both terms, which is the
+/// expensive part of the similarity calculation. They are calculated once
+/// and shared by all methods.
+struct PairIc {
+    same: bool,
+    ic_a: f32,
+    ic_b: f32,
+    /// The IC of the most informative common ancestor
+    resnik: f32,
+    /// The summed IC of all common ancestors
+    ic_common: f32,
+    /// The summed IC of all ancestors of either term, only required for `GraphIc`
+    ic_union: f32,
+}
+
+impl PairIc {
+    fn new(a: &HpoTerm, b: &HpoTerm, source: &IcSource, with_union: bool) -> Self {
+        let (resnik, ic_common) = a
+            .all_common_ancestors(b)
+            .iter()
+            .map(|term| source.ic(&term))
+            .fold((0.0, 0.0), |(max, sum), ic| (f32::max(max, ic), sum + ic));
+        let ic_union = if with_union {
+            a.all_union_ancestors(b).iter().map(|p| source.ic(&p)).sum()
+        } else {
+            0.0
+        };
+        Self {
+            same: a.id() == b.id(),
+            ic_a: source.ic(a),
+            ic_b: source.ic(b),
+            resnik,
+            ic_common,
+            ic_union,
+        }
+    }
+}
+
+/// IC-based similarity using the custom information content
+pub(crate) struct CustomIcSimilarity {
+    method: IcMethod,
+    source: IcSource,
+}
+
+impl CustomIcSimilarity {
+    /// # Errors
+    ///
+    /// - PyRuntimeError: Invalid `method` or a method that does not use the information content
+    fn new(method: &str, kind: &str) -> PyResult<Self> {
+        Ok(Self {
+            method: custom_ic_method(method)?,
+            source: IcSource::Custom(custom_ic_table(kind)),
+        })
+    }
+}
+
+/// Returns the IC-based `method` for a custom information content
+///
+/// # Errors
+///
+/// - PyRuntimeError: Invalid `method` or a method that does not use the information content
+fn custom_ic_method(method: &str) -> PyResult<IcMethod> {
+    IcMethod::from_name(method).ok_or_else(|| {
+        PyRuntimeError::new_err(format!(
+            "The method {method} does not support the custom information content"
+        ))
+    })
+}
+
+impl Similarity for CustomIcSimilarity {
+    fn calculate(&self, a: &HpoTerm, b: &HpoTerm) -> f32 {
+        let with_union = matches!(self.method, IcMethod::GraphIc);
+        self.method
+            .score(&PairIc::new(a, b, &self.source, with_union))
+    }
+}
+
+/// A single method of a [`MultiSimilarity`]
+enum MultiMethod {
+    Ic(IcMethod),
+    Other(PySimilarity),
+}
+
+/// Calculates the similarity of term pairs with several methods at once
+///
+/// The ancestors of each term pair are only traversed once for all
+/// methods that are based on the information content.
+pub(crate) struct MultiSimilarity {
+    methods: Vec<MultiMethod>,
+    source: IcSource,
+    with_union: bool,
+}
+
+impl MultiSimilarity {
+    /// # Errors
+    ///
+    /// - PyKeyError: Invalid `kind`
+    /// - PyRuntimeError: Invalid `method`
+    pub fn new(methods: &[String], kind: &str) -> PyResult<Self> {
+        let custom = is_custom_kind(kind);
+        let builtin_kind = match custom {
+            true => None,
+            false => Some(PyInformationContentKind::try_from(kind)?),
+        };
+        let methods = methods
+            .iter()
+            .map(|method| {
+                let method = match registered(method) {
+                    Some(Registered::Alias(builtin)) => builtin,
+                    Some(Registered::Python(sim)) => {
+                        return Ok(MultiMethod::Other(PySimilarity::Python(sim)))
+                    }
+                    None => method.to_string(),
+                };
+                match (IcMethod::from_name(&method), builtin_kind) {
+                    (Some(ic_method), _) => Ok(MultiMethod::Ic(ic_method)),
+                    (None, Some(kind)) => {
+                        PySimilarity::with_kind(&method, kind).map(MultiMethod::Other)
+                    }
+                    (None, None) => custom_ic_method(&method).map(MultiMethod::Ic),
+                }
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let with_union = methods
+            .iter()
+            .any(|method| matches!(method, MultiMethod::Ic(IcMethod::GraphIc)));
+        Ok(Self {
+            methods,
+            source: match builtin_kind {
+                Some(kind) => IcSource::Builtin(kind.into()),
+                None => IcSource::Custom(custom_ic_table(kind)),
+            },
+            with_union,
+        })
+    }
+
+    /// Returns the number of methods
+    pub fn len(&self) -> usize {
+        self.methods.len()
+    }
+
+    /// Returns the similarity of the term pair for every method
+    pub fn calculate(&self, a: &HpoTerm, b: &HpoTerm) -> Vec<f32> {
+        let mut pair: Option<PairIc> = None;
+        self.methods
+            .iter()
+            .map(|method| match method {
+                MultiMethod::Ic(ic_method) => ic_method.score(
+                    pair.get_or_insert_with(|| PairIc::new(a, b, &self.source, self.with_union)),
+                ),
+                MultiMethod::Other(sim) => sim.calculate(a, b),
+            })
+            .collect()
+    }
+
+    /// Returns the similarity of two sets for every method
+    pub fn calculate_sets<T: Score>(
+        &self,
+        combiner: &StandardCombiner,
+        a: &HpoSet,
+        b: &HpoSet,
+    ) -> Vec<T> {
+        let mut scores: Vec<Vec<f32>> = vec![Vec::with_capacity(a.len() * b.len()); self.len()];
+        for t1 in a {
+            for t2 in b {
+                for (method_scores, score) in scores.iter_mut().zip(self.calculate(&t1, &t2)) {
+                    method_scores.push(score);
+                }
+            }
+        }
+        scores
+            .iter()
+            .map(|v| T::combine(combiner, &Matrix::new(a.len(), b.len(), v)))
+            .collect()
+    }
+}
+
+/// The floating point type of set similarity scores
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Dtype {
+    Float32,
+    Float64,
+}
+
+impl TryFrom<&str> for Dtype {
+    type Error = PyErr;
+    fn try_from(value: &str) -> PyResult<Self> {
+        match value {
+            "float32" => Ok(Self::Float32),
+            "float64" => Ok(Self::Float64),
+            _ => Err(PyValueError::new_err(format!(
+                "Invalid dtype {value}. Use `float32` or `float64`"
+            ))),
+        }
+    }
+}
+
+/// A set similarity score of single (`f32`) or double (`f64`) precision
+///
+/// The similarity of every term pair is calculated by `hpo` with single
+/// precision. `f64` scores combine them with double precision, so that the
+/// sums over hundreds of term pairs do not accumulate rounding errors
+/// and nearly identical scores can still be ranked.
+pub(crate) trait Score:
+    Copy
+    + Send
+    + Sync
+    + PartialOrd
+    + From<f32>
+    + Into<f64>
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Div<Output = Self>
+{
+    /// Combines the term similarities of two sets like `combiner`
+    fn combine(combiner: &StandardCombiner, scores: &Matrix<f32>) -> Self;
+
+    /// Returns the scores as values of a [`crate::buffer::PyScoreBuffer`]
+    fn buffer_data(values: Vec<Self>) -> BufferData;
+
+    /// Returns the similarity of two sets, like `GroupSimilarity`
+    fn set_similarity<S: Similarity>(
+        similarity: &S,
+        combiner: &StandardCombiner,
+        a: &HpoSet,
+        b: &HpoSet,
+    ) -> Self {
+        let mut scores = Vec::with_capacity(a.len() * b.len());
+        for t1 in a {
+            for t2 in b {
+                scores.push(similarity.calculate(&t1, &t2));
+            }
+        }
+        Self::combine(combiner, &Matrix::new(a.len(), b.len(), &scores))
+    }
+}
+
+impl Score for f32 {
+    fn combine(combiner: &StandardCombiner, scores: &Matrix<f32>) -> Self {
+        combiner.calculate(scores)
+    }
+
+    fn buffer_data(values: Vec<Self>) -> BufferData {
+        BufferData::F32(values)
+    }
+}
+
+impl Score for f64 {
+    fn combine(combiner: &StandardCombiner, scores: &Matrix<f32>) -> Self {
+        if scores.is_empty() {
+            return 0.0;
+        }
+        // Identical to `hpo`, so that `NaN` scores are handled the same way
+        let max = |values: &mut dyn Iterator<Item = &f32>| {
+            values
+                .map(|score| f64::from(*score))
+                .reduce(|a, b| if a > b { a } else { b })
+                .expect("A matrix must contain values")
+        };
+        let (rows, cols) = scores.dim();
+        let row_sum: f64 = scores.rows().map(|mut row| max(&mut row)).sum();
+        let col_sum: f64 = scores.cols().map(|mut col| max(&mut col)).sum();
+        let (rows, cols) = (rows as f64, cols as f64);
+        match combiner {
+            StandardCombiner::FunSimAvg => (row_sum / rows + col_sum / cols) / 2.0,
+            StandardCombiner::FunSimMax => (row_sum / rows).max(col_sum / cols),
+            StandardCombiner::Bma => (row_sum + col_sum) / (rows + cols),
+        }
+    }
+
+    fn buffer_data(values: Vec<Self>) -> BufferData {
+        BufferData::F64(values)
+    }
+}
@@ -0,0 +1,277 @@
+//! Opt-in memoization of term-pair similarity scores
+//!
+//! Comparing many `HPOSet`s calculates the similarity of the same
+//! term pairs over and over again. When the cache is enabled, every
+//! score is stored per kind and method and looked up in all following
+//! calculations.
+//!
+//! The cache is split into shards, so that the parallel batch
+//! calculations do not all wait for the same lock.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use once_cell::sync::Lazy;
+use pyo3::prelude::*;
+
+use hpo::annotations::AnnotationId;
+use hpo::similarity::Similarity;
+use hpo::HpoTerm;
+
+use crate::information_content::PyInformationContentKind;
+use crate::similarity::PySimilarity;
+
+/// Number of independently locked shards of every cache
+const SHARDS: usize = 16;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Maximum number of cached scores across all kinds and methods, `0` is unlimited
+static MAX_SIZE: AtomicUsize = AtomicUsize::new(0);
+static SIZE: AtomicUsize = AtomicUsize::new(0);
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// The caches of all kinds and methods, by `(kind, method)`
+type Caches = HashMap<(String, String), Arc<PairCache>>;
+
+static CACHES: Lazy<RwLock<Caches>> = Lazy::new(Default::default);
+
+/// The cached scores of a single kind and method
+struct PairCache {
+    shards: Vec<RwLock<HashMap<(u32, u32), f32>>>,
+}
+
+impl PairCache {
+    fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| RwLock::default()).collect(),
+        }
+    }
+
+    /// Returns the cached score of the term pair or calculates and caches it
+    ///
+    /// All similarity methods are symmetric, so both orders of a pair share the same score
+    fn score<F: FnOnce() -> f32>(&self, a: u32, b: u32, calculate: F) -> f32 {
+        let key = (a.min(b), a.max(b));
+        let shard = &self.shards[(key.0 ^ key.1) as usize % SHARDS];
+        if let Some(score) = shard
+            .read()
+            .expect("similarity cache lock must not be poisoned")
+            .get(&key)
+        {
+            HITS.fetch_add(1, Ordering::Relaxed);
+            return *score;
+        }
+        MISSES.fetch_add(1, Ordering::Relaxed);
+        let score = calculate();
+        let max_size = MAX_SIZE.load(Ordering::Relaxed);
+        if max_size == 0 || SIZE.load(Ordering::Relaxed) < max_size {
+            let previous = shard
+                .write()
+                .expect("similarity cache lock must not be poisoned")
+                .insert(key, score);
+            if previous.is_none() {
+                SIZE.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        score
+    }
+}
+
+/// A similarity method that looks up its scores in the similarity cache
+pub(crate) struct CachedSimilarity<S> {
+    similarity: S,
+    cache: Option<Arc<PairCache>>,
+}
+
+impl<S: Similarity> Similarity for CachedSimilarity<S> {
+    fn calculate(&self, a: &HpoTerm, b: &HpoTerm) -> f32 {
+        match &self.cache {
+            Some(cache) => cache.score(a.id().as_u32(), b.id().as_u32(), || {
+                self.similarity.calculate(a, b)
+            }),
+            None => self.similarity.calculate(a, b),
+        }
+    }
+}
+
+/// Wraps the `similarity` of `kind` and `method` to use the similarity cache, if it is enabled
+pub(crate) fn cached<S: Similarity>(
+    similarity: S,
+    kind: &str,
+    method: &str,
+) -> CachedSimilarity<S> {
+    // The custom information content can change at any time,
+    // so its scores are never cached
+    let builtin_kind = PyInformationContentKind::try_from(kind).is_ok();
+    let cache = (ENABLED.load(Ordering::Relaxed) && builtin_kind).then(|| {
+        let key = (kind.to_string(), method.to_lowercase());
+        if let Some(cache) = CACHES
+            .read()
+            .expect("similarity cache lock must not be poisoned")
+            .get(&key)
+        {
+            return Arc::clone(cache);
+        }
+        Arc::clone(
+            CACHES
+                .write()
+                .expect("similarity cache lock must not be poisoned")
+                .entry(key)
+                .or_insert_with(|| Arc::new(PairCache::new())),
+        )
+    });
+    CachedSimilarity { similarity, cache }
+}
+
+/// Creates the builtin or registered similarity `method` that uses the
+/// similarity cache, if it is enabled
+///
+/// `kind` is either a builtin or a custom information content
+///
+/// # Errors
+///
+/// - PyKeyError: Invalid `kind`
+/// - PyRuntimeError: Invalid `method`
+pub(crate) fn builtin(method: &str, kind: &str) -> PyResult<CachedSimilarity<PySimilarity>> {
+    let similarity = PySimilarity::new(method, kind)?;
+    Ok(cached(similarity, kind, method))
+}
+
+/// Removes the cached scores of `method` of all kinds
+///
+/// Required when a registered method is replaced
+pub(crate) fn forget(method: &str) {
+    let method = method.to_lowercase();
+    CACHES
+        .write()
+        .expect("similarity cache lock must not be poisoned")
+        .retain(|(_, cached), cache| {
+            if *cached != method {
+                return true;
+            }
+            let removed: usize = cache
+                .shards
+                .iter()
+                .map(|shard| {
+                    shard
+                        .read()
+                        .expect("similarity cache lock must not be poisoned")
+                        .len()
+                })
+                .sum();
+            SIZE.fetch_sub(removed, Ordering::Relaxed);
+            false
+        });
+}
+
+/// Memoizes the similarity scores of term pairs across calls
+///
+/// The cache is disabled by default. Once enabled, the similarity score
+/// of every term pair is stored for each kind of information content and
+/// similarity method. This speeds up repeated comparisons of ``HPOSet``\s
+/// considerably, e.g. in a cohort, where the same term pairs are compared
+/// millions of times.
+///
+/// The cache is used by :func:`pyhpo.HPOTerm.similarity_score`,
+/// :func:`pyhpo.HPOTerm.similarity_scores`, :func:`pyhpo.HPOSet.similarity`,
+/// :func:`pyhpo.HPOSet.similarity_scores`, :func:`pyhpo.HPOSet.similarity_to_term`,
+/// :func:`pyhpo.helper.batch_similarity`, :func:`pyhpo.helper.batch_set_similarity`
+/// and :func:`pyhpo.stats.linkage`. Scores based on a custom
+/// information content are never cached.
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, helper
+///     Ontology()
+///
+///     helper.similarity_cache.enable(max_size=10_000_000)
+///     # ... compare many HPOSets
+///     helper.similarity_cache.stats()
+///     # >> {'enabled': True, 'size': 412233, 'max_size': 10000000, 'hits': 9817232, 'misses': 412233}
+///     helper.similarity_cache.clear()
+///
+#[pyclass(name = "SimilarityCache")]
+#[derive(Clone, Default)]
+pub(crate) struct PySimilarityCache;
+
+#[pymethods]
+impl PySimilarityCache {
+    /// Enables the cache
+    ///
+    /// Parameters
+    /// ----------
+    /// max_size: int, optional
+    ///     The maximum number of cached scores. Once it is reached,
+    ///     new scores are calculated but no longer cached.
+    ///     Defaults to an unlimited size.
+    ///
+    #[pyo3(signature = (max_size = None))]
+    #[pyo3(text_signature = "($self, max_size)")]
+    fn enable(&self, max_size: Option<usize>) {
+        MAX_SIZE.store(max_size.unwrap_or_default(), Ordering::Relaxed);
+        ENABLED.store(true, Ordering::Relaxed);
+    }
+
+    /// Disables the cache
+    ///
+    /// The cached scores are kept and used again if the cache is enabled again.
+    /// Use :func:`clear` to remove them.
+    fn disable(&self) {
+        ENABLED.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether the cache is enabled
+    #[getter(enabled)]
+    fn enabled(&self) -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    /// Removes all cached scores and resets the statistics
+    fn clear(&self) {
+        CACHES
+            .write()
+            .expect("similarity cache lock must not be poisoned")
+            .clear();
+        SIZE.store(0, Ordering::Relaxed);
+        HITS.store(0, Ordering::Relaxed);
+        MISSES.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the statistics of the cache
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///     * **enabled** - Whether the cache is enabled
+    ///     * **size** - The number of cached scores
+    ///     * **max_size** - The maximum number of cached scores, ``None`` if unlimited
+    ///     * **hits** - The number of scores that were looked up in the cache
+    ///     * **misses** - The number of scores that were calculated
+    ///
+    fn stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let stats = pyo3::types::PyDict::new(py);
+        stats.set_item("enabled", self.enabled())?;
+        stats.set_item("size", SIZE.load(Ordering::Relaxed))?;
+        let max_size = MAX_SIZE.load(Ordering::Relaxed);
+        stats.set_item("max_size", (max_size > 0).then_some(max_size))?;
+        stats.set_item("hits", HITS.load(Ordering::Relaxed))?;
+        stats.set_item("misses", MISSES.load(Ordering::Relaxed))?;
+        Ok(stats.into())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<SimilarityCache ({}, {} scores)>",
+            if self.enabled() {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            SIZE.load(Ordering::Relaxed)
+        )
+    }
+}
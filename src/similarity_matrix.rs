@@ -0,0 +1,390 @@
+use std::fs;
+use std::io::Write;
+
+use rayon::prelude::*;
+
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::PyResult;
+
+use hpo::annotations::AnnotationId;
+use hpo::similarity::Similarity;
+use hpo::HpoTerm;
+
+use crate::get_ontology;
+use crate::information_content::PyInformationContentKind;
+use crate::similarity::PySimilarity;
+use crate::term::PyHpoTerm;
+use crate::term_from_id;
+use crate::worker;
+
+/// Identifies the binary file format of a persisted ``SimilarityMatrix``
+const MAGIC: &[u8; 8] = b"HPO3SIM\0";
+/// The current version of the binary file format
+const VERSION: u32 = 1;
+
+/// Precomputed similarity scores of all pairs of a list of ``HPOTerm``
+///
+/// All supported similarity methods are symmetric, so only the
+/// upper triangle (including the diagonal) of the matrix is stored.
+///
+/// The matrix can be saved to disk with :func:`save` and loaded again
+/// with :func:`load` to re-use it across multiple analyses. Pass it
+/// as ``cache`` to :func:`pyhpo.HPOTerm.similarity_score` to look up
+/// scores instead of calculating them.
+#[pyclass(name = "SimilarityMatrix")]
+pub(crate) struct PySimilarityMatrix {
+    kind: String,
+    method: String,
+    ids: Vec<u32>,
+    scores: Vec<f32>,
+}
+
+impl PySimilarityMatrix {
+    /// Calculates the similarity of all pairs of `ids` in parallel
+    ///
+    /// `ids` must be sorted and unique
+    fn compute(py: Python<'_>, ids: Vec<u32>, kind: &str, method: &str) -> PyResult<Self> {
+        let ic_kind = PyInformationContentKind::try_from(kind)?;
+        let similarity = PySimilarity::with_kind(method, ic_kind)?;
+
+        let terms = ids
+            .iter()
+            .map(|id| term_from_id(*id))
+            .collect::<PyResult<Vec<HpoTerm>>>()?;
+
+        let scores: Vec<f32> = worker::install(py, || {
+            (0..terms.len())
+                .into_par_iter()
+                .flat_map_iter(|row| {
+                    let similarity = &similarity;
+                    let terms = &terms;
+                    terms[row..]
+                        .iter()
+                        .map(move |other| similarity.calculate(&terms[row], other))
+                })
+                .collect()
+        });
+
+        Ok(Self {
+            kind: kind.to_string(),
+            method: method.to_string(),
+            ids,
+            scores,
+        })
+    }
+
+    /// Returns the position of the score of `a` and `b` in the upper triangle
+    fn position(&self, a: u32, b: u32) -> Option<usize> {
+        let a = self.ids.binary_search(&a).ok()?;
+        let b = self.ids.binary_search(&b).ok()?;
+        let (row, col) = if a <= b { (a, b) } else { (b, a) };
+        let n = self.ids.len();
+        Some(row * (2 * n - row + 1) / 2 + (col - row))
+    }
+
+    /// Returns the cached similarity score of two terms
+    ///
+    /// # Errors
+    ///
+    /// - PyValueError: `kind` or `method` differ from the precomputed ones
+    pub(crate) fn lookup(&self, a: u32, b: u32, kind: &str, method: &str) -> PyResult<Option<f32>> {
+        if self.kind != kind || self.method != method {
+            return Err(PyValueError::new_err(format!(
+                "SimilarityMatrix was calculated with kind={} and method={}",
+                self.kind, self.method
+            )));
+        }
+        Ok(self.position(a, b).map(|idx| self.scores[idx]))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            MAGIC.len()
+                + 24
+                + self.kind.len()
+                + self.method.len()
+                + 4 * self.ids.len()
+                + 4 * self.scores.len(),
+        );
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        for text in [&self.kind, &self.method] {
+            bytes.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(text.as_bytes());
+        }
+        bytes.extend_from_slice(&(self.ids.len() as u32).to_le_bytes());
+        for id in &self.ids {
+            bytes.extend_from_slice(&id.to_le_bytes());
+        }
+        for score in &self.scores {
+            bytes.extend_from_slice(&score.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let mut reader = ByteReader { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(PyValueError::new_err(
+                "Invalid file format: Not a SimilarityMatrix",
+            ));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(PyValueError::new_err(format!(
+                "Unsupported SimilarityMatrix version {}",
+                version
+            )));
+        }
+        let kind = reader.string()?;
+        let method = reader.string()?;
+        let n = reader.u32()? as usize;
+        let ids = (0..n)
+            .map(|_| reader.u32())
+            .collect::<PyResult<Vec<u32>>>()?;
+        let scores = (0..n * (n + 1) / 2)
+            .map(|_| reader.u32().map(f32::from_bits))
+            .collect::<PyResult<Vec<f32>>>()?;
+        if reader.pos != bytes.len() {
+            return Err(PyValueError::new_err(
+                "Invalid file format: Unexpected trailing data",
+            ));
+        }
+        Ok(Self {
+            kind,
+            method,
+            ids,
+            scores,
+        })
+    }
+}
+
+/// Minimal cursor to parse the binary format of a ``SimilarityMatrix``
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> PyResult<&'a [u8]> {
+        let end = self.pos + len;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or_else(|| PyValueError::new_err("Invalid file format: Unexpected end of file"))?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> PyResult<u32> {
+        let slice = self.take(4)?;
+        Ok(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+    }
+
+    fn string(&mut self) -> PyResult<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| PyValueError::new_err("Invalid file format: Invalid string"))
+    }
+}
+
+#[pymethods]
+impl PySimilarityMatrix {
+    /// The kind of information content used for the scores
+    #[getter(kind)]
+    fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// The similarity method used for the scores
+    #[getter(method)]
+    fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// The integer IDs of all ``HPOTerm`` in the matrix
+    #[getter(ids)]
+    fn ids(&self) -> Vec<u32> {
+        self.ids.clone()
+    }
+
+    /// Returns the precomputed similarity score of two terms
+    ///
+    /// Parameters
+    /// ----------
+    /// a: :class:`pyhpo.HPOTerm`
+    ///     One term
+    /// b: :class:`pyhpo.HPOTerm`
+    ///     The other term
+    ///
+    /// Returns
+    /// -------
+    /// float
+    ///     The similarity score
+    ///
+    /// Raises
+    /// ------
+    /// KeyError
+    ///     One of the terms is not part of the matrix
+    ///
+    #[pyo3(text_signature = "($self, a, b)")]
+    fn get(&self, a: &PyHpoTerm, b: &PyHpoTerm) -> PyResult<f32> {
+        self.position(a.hpo_term_id().as_u32(), b.hpo_term_id().as_u32())
+            .map(|idx| self.scores[idx])
+            .ok_or_else(|| PyKeyError::new_err("Term is not part of the SimilarityMatrix"))
+    }
+
+    /// Saves the matrix in a binary format to disk
+    ///
+    /// Parameters
+    /// ----------
+    /// path: str
+    ///     The path of the output file
+    ///
+    /// Raises
+    /// ------
+    /// OSError
+    ///     The file cannot be written
+    ///
+    #[pyo3(text_signature = "($self, path)")]
+    fn save(&self, path: &str) -> PyResult<()> {
+        let mut file = fs::File::create(path)?;
+        file.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Loads a matrix that was saved with :func:`save`
+    ///
+    /// The matrix is not validated against the currently loaded Ontology.
+    /// Make sure to use the same Ontology version that was used
+    /// to calculate the scores.
+    ///
+    /// Parameters
+    /// ----------
+    /// path: str
+    ///     The path of the input file
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.types.SimilarityMatrix`
+    ///     The precomputed similarity matrix
+    ///
+    /// Raises
+    /// ------
+    /// OSError
+    ///     The file cannot be read
+    /// ValueError
+    ///     The file is not a valid ``SimilarityMatrix``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     from pyhpo.types import SimilarityMatrix
+    ///
+    ///     Ontology()
+    ///     matrix = SimilarityMatrix.load("graphic_omim.sim")
+    ///
+    #[staticmethod]
+    #[pyo3(text_signature = "(path)")]
+    fn load(path: &str) -> PyResult<Self> {
+        let bytes = fs::read(path)?;
+        Self::from_bytes(&bytes)
+    }
+
+    fn __len__(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<SimilarityMatrix ({} terms, kind={}, method={})>",
+            self.ids.len(),
+            self.kind,
+            self.method
+        )
+    }
+}
+
+/// Precomputes the similarity scores of all pairs of terms
+///
+/// The calculation runs parallelized on all avaible CPU.
+///
+/// .. important::
+///
+///     The memory usage grows quadratically with the number of terms.
+///     Using all terms of the Ontology requires ~700 MB of memory.
+///
+/// Parameters
+/// ----------
+/// terms: list[:class:`pyhpo.HPOTerm`], optional
+///     The terms to include in the matrix. Defaults to all terms
+///     of the Ontology
+/// kind: str, default: ``omim``
+///     Which kind of information content to use for similarity calculation
+///
+///     Available options:
+///
+///     * **omim**
+///     * **orpha**
+///     * **gene**
+///
+/// method: str, default ``graphic``
+///     The method to use to calculate the similarity.
+///     See :func:`pyhpo.HPOTerm.similarity_score` for available options
+///
+/// Returns
+/// -------
+/// :class:`pyhpo.types.SimilarityMatrix`
+///     The precomputed similarity matrix
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+/// KeyError
+///     Invalid ``kind``
+/// RuntimeError
+///     Invalid ``method``
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology, helper
+///
+///     Ontology()
+///
+///     matrix = helper.similarity_matrix(kind="omim", method="graphic")
+///     matrix.save("graphic_omim.sim")
+///
+///     term = Ontology.hpo(11968)
+///     term.similarity_score(Ontology.hpo(1743), cache=matrix)
+///
+#[pyfunction]
+#[pyo3(signature = (terms = None, kind = "omim", method = "graphic"))]
+#[pyo3(text_signature = "(terms, kind, method)")]
+pub(crate) fn similarity_matrix(
+    py: Python<'_>,
+    terms: Option<Vec<PyHpoTerm>>,
+    kind: &str,
+    method: &str,
+) -> PyResult<PySimilarityMatrix> {
+    let mut ids: Vec<u32> = match terms {
+        Some(terms) => terms
+            .iter()
+            .map(|term| term.hpo_term_id().as_u32())
+            .collect(),
+        None => get_ontology()?
+            .into_iter()
+            .map(|term| term.id().as_u32())
+            .collect(),
+    };
+    ids.sort_unstable();
+    ids.dedup();
+    PySimilarityMatrix::compute(py, ids, kind, method)
+}
@@ -8,22 +8,31 @@ use pyo3::prelude::*;
 use pyo3::types::PyDict;
 
 use rayon::prelude::*;
+use serde::Serialize;
 
-use hpo::annotations::AnnotationId;
+use hpo::annotations::{AnnotationId, Disease};
 use hpo::similarity::Similarity;
 use hpo::term::HpoTermId;
 
 use crate::annotations::PyOrphaDisease;
+use crate::batch::NanPolicy;
+use crate::fhir;
+use crate::get_ontology;
+use crate::information_content::{set_custom_ic, CUSTOM_KIND};
 use crate::pyterm_from_id;
+use crate::similarity_cache;
+use crate::similarity_matrix::PySimilarityMatrix;
+use crate::term_details::{all_term_details, term_details, xref_id};
 use crate::term_from_id;
+use crate::to_json_string;
+use crate::worker;
 use crate::ONTOLOGY;
 
 use crate::PyGene;
 use crate::PyInformationContent;
-use crate::PyInformationContentKind;
 use crate::PyOmimDisease;
 
-#[pyclass(name = "HPOTerm")]
+#[pyclass(name = "HPOTerm", module = "pyhpo")]
 #[derive(Clone)]
 pub struct PyHpoTerm {
     id: HpoTermId,
@@ -40,7 +49,7 @@ impl PyHpoTerm {
     /// This method assumes that this operation succeeds
     /// because terms cannot be instantiated from Python
     /// and can only be retrieved from the Ontology
-    fn hpo(&self) -> hpo::HpoTerm {
+    fn hpo(&self) -> hpo::HpoTerm<'_> {
         let ont = ONTOLOGY
             .get()
             .expect("ontology must exist when a term is present");
@@ -140,7 +149,77 @@ impl PyHpoTerm {
     ///
     #[getter(information_content)]
     fn information_content(&self) -> PyInformationContent {
-        self.hpo().information_content().into()
+        (&self.hpo()).into()
+    }
+
+    /// Sets a custom information content for the term
+    ///
+    /// The custom information content can be used for clustering
+    /// with ``kind="custom"``, e.g. to use the IC of a specific cohort.
+    /// Terms without a custom information content have an IC of ``0.0``.
+    ///
+    /// Parameters
+    /// ----------
+    /// value: float
+    ///     The information content of the term
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     term = Ontology.hpo(11968)
+    ///     term.set_custom_ic(3.2)
+    ///     term.information_content.custom  # >> 3.200000047683716
+    ///
+    #[pyo3(text_signature = "($self, value)")]
+    fn set_custom_ic(&self, value: f32) -> PyResult<()> {
+        set_custom_ic(CUSTOM_KIND, self.id.as_u32(), value)
+    }
+
+    /// Sets the information content of a named custom kind for the term
+    ///
+    /// Several custom information contents can be used side by side,
+    /// e.g. of a pediatric and an adult cohort. Each one can be used
+    /// with ``kind="<name>"`` for similarity calculations of terms
+    /// and ``HPOSet``\s and for clustering, but only with the information
+    /// content based methods. Terms without an information content
+    /// of the kind have an IC of ``0.0``.
+    ///
+    /// ``term.set_ic("custom", value)`` is identical to
+    /// :func:`pyhpo.HPOTerm.set_custom_ic`.
+    ///
+    /// Parameters
+    /// ----------
+    /// kind: str
+    ///     The name of the custom information content
+    /// value: float
+    ///     The information content of the term
+    ///
+    /// Raises
+    /// ------
+    /// ValueError
+    ///     ``kind`` is a builtin kind (``omim``, ``orpha`` or ``gene``)
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     term = Ontology.hpo(11968)
+    ///     term.set_ic("peds", 3.2)
+    ///     term.set_ic("adult", 1.7)
+    ///     term.information_content["peds"]  # >> 3.200000047683716
+    ///
+    ///     term.similarity_score(Ontology.hpo(1743), kind="peds", method="resnik")
+    ///
+    #[pyo3(text_signature = "($self, kind, value)")]
+    fn set_ic(&self, kind: &str, value: f32) -> PyResult<()> {
+        set_custom_ic(kind, self.id.as_u32(), value)
     }
 
     /// A set of direct parents
@@ -320,6 +399,264 @@ impl PyHpoTerm {
             })
     }
 
+    /// Returns the direct parents, sorted by their ID
+    ///
+    /// Same as :attr:`pyhpo.HPOTerm.parents`, but in a deterministic order
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`HPOTerm`]
+    ///     All direct parents
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     Ontology.hpo(108).sorted_parents()
+    ///     # >> [<HpoTerm (HP:0000107)>, <HpoTerm (HP:0011035)>, <HpoTerm (HP:0100957)>]
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn sorted_parents(&self) -> Vec<PyHpoTerm> {
+        let mut terms: Vec<PyHpoTerm> = self.hpo().parents().map(PyHpoTerm::from).collect();
+        terms.sort_unstable_by_key(PyHpoTerm::hpo_term_id);
+        terms
+    }
+
+    /// Returns the direct children, sorted by their ID
+    ///
+    /// Same as :attr:`pyhpo.HPOTerm.children`, but in a deterministic order
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`HPOTerm`]
+    ///     All direct children
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     Ontology.hpo(1).sorted_children()
+    ///     # >> [<HpoTerm (HP:0000005)>, <HpoTerm (HP:0000118)>, <HpoTerm (HP:0012823)>, ...]
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn sorted_children(&self) -> Vec<PyHpoTerm> {
+        let mut terms: Vec<PyHpoTerm> = self.hpo().children().map(PyHpoTerm::from).collect();
+        terms.sort_unstable_by_key(PyHpoTerm::hpo_term_id);
+        terms
+    }
+
+    /// Returns the associated genes, sorted by their ID
+    ///
+    /// Same as :attr:`pyhpo.HPOTerm.genes`, but in a deterministic order
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.Gene`]
+    ///     All associated genes
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     for gene in Ontology.hpo(188).sorted_genes():
+    ///         print(gene.id, gene.name)
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn sorted_genes(&self) -> Vec<PyGene> {
+        let term = self.hpo();
+        let mut genes: Vec<_> = term.genes().collect();
+        genes.sort_unstable_by_key(|gene| gene.id().as_u32());
+        genes.into_iter().map(PyGene::from).collect()
+    }
+
+    /// Returns the associated OMIM diseases, sorted by their ID
+    ///
+    /// Same as :attr:`pyhpo.HPOTerm.omim_diseases`, but in a deterministic order
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.Omim`]
+    ///     All associated Omim diseases
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     for disease in Ontology.hpo(188).sorted_omim_diseases():
+    ///         print(disease.id, disease.name)
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn sorted_omim_diseases(&self) -> Vec<PyOmimDisease> {
+        let term = self.hpo();
+        let mut diseases: Vec<_> = term.omim_diseases().collect();
+        diseases.sort_unstable_by_key(|disease| disease.id().as_u32());
+        diseases.into_iter().map(PyOmimDisease::from).collect()
+    }
+
+    /// Returns the associated ORPHA diseases, sorted by their ID
+    ///
+    /// Same as :attr:`pyhpo.HPOTerm.orpha_diseases`, but in a deterministic order
+    ///
+    /// Returns
+    /// -------
+    /// list[:class:`pyhpo.Orpha`]
+    ///     All associated Orpha diseases
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     for disease in Ontology.hpo(188).sorted_orpha_diseases():
+    ///         print(disease.id, disease.name)
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn sorted_orpha_diseases(&self) -> Vec<PyOrphaDisease> {
+        let term = self.hpo();
+        let mut diseases: Vec<_> = term.orpha_diseases().collect();
+        diseases.sort_unstable_by_key(|disease| disease.id().as_u32());
+        diseases.into_iter().map(PyOrphaDisease::from).collect()
+    }
+
+    /// Returns a set of genes that are directly linked to the term
+    ///
+    /// In contrast to :attr:`pyhpo.HPOTerm.genes`, this does not include
+    /// the genes that are "inherited" from one of the children. The genes
+    /// depend on the ``gene_annotation_mode`` that was used to build the
+    /// Ontology, with ``transitive`` annotations all genes are direct.
+    ///
+    /// Returns
+    /// -------
+    /// Set[:class:`pyhpo.Gene`]
+    ///     All directly associated genes
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     term = Ontology.hpo(188)
+    ///     inherited = term.genes - term.direct_genes
+    ///
+    #[getter(direct_genes)]
+    fn direct_genes(&self) -> HashSet<PyGene> {
+        let term = self.hpo();
+        term.genes()
+            .filter(|gene| gene.hpo_terms().contains(&self.id))
+            .map(PyGene::from)
+            .collect()
+    }
+
+    /// Returns a set of OMIM diseases that are directly linked to the term
+    ///
+    /// In contrast to :attr:`pyhpo.HPOTerm.omim_diseases`, this does not
+    /// include the diseases that are "inherited" from one of the children.
+    ///
+    /// Returns
+    /// -------
+    /// Set[:class:`pyhpo.Omim`]
+    ///     All directly associated Omim diseases
+    ///
+    #[getter(direct_omim_diseases)]
+    fn direct_omim_diseases(&self) -> HashSet<PyOmimDisease> {
+        let term = self.hpo();
+        term.omim_diseases()
+            .filter(|disease| disease.hpo_terms().contains(&self.id))
+            .map(PyOmimDisease::from)
+            .collect()
+    }
+
+    /// Returns a set of ORPHA diseases that are directly linked to the term
+    ///
+    /// In contrast to :attr:`pyhpo.HPOTerm.orpha_diseases`, this does not
+    /// include the diseases that are "inherited" from one of the children.
+    ///
+    /// Returns
+    /// -------
+    /// Set[:class:`pyhpo.Orpha`]
+    ///     All directly associated Orpha diseases
+    ///
+    #[getter(direct_orpha_diseases)]
+    fn direct_orpha_diseases(&self) -> HashSet<PyOrphaDisease> {
+        let term = self.hpo();
+        term.orpha_diseases()
+            .filter(|disease| disease.hpo_terms().contains(&self.id))
+            .map(PyOrphaDisease::from)
+            .collect()
+    }
+
+    /// The number of associated genes
+    ///
+    /// Same as ``len(term.genes)``, but without creating the
+    /// ``Gene`` objects. Includes "inherited" genes.
+    ///
+    /// Returns
+    /// -------
+    /// int
+    ///     The number of associated genes
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     term = Ontology.hpo(188)
+    ///     term.gene_count == len(term.genes)
+    ///     # >> True
+    ///
+    #[getter(gene_count)]
+    fn gene_count(&self) -> usize {
+        self.hpo().gene_ids().len()
+    }
+
+    /// The number of associated OMIM diseases
+    ///
+    /// Same as ``len(term.omim_diseases)``, but without creating the
+    /// ``Omim`` objects. Includes "inherited" diseases.
+    ///
+    /// Returns
+    /// -------
+    /// int
+    ///     The number of associated Omim diseases
+    ///
+    #[getter(omim_count)]
+    fn omim_count(&self) -> usize {
+        self.hpo().omim_disease_ids().len()
+    }
+
+    /// The number of associated ORPHA diseases
+    ///
+    /// Same as ``len(term.orpha_diseases)``, but without creating the
+    /// ``Orpha`` objects. Includes "inherited" diseases.
+    ///
+    /// Returns
+    /// -------
+    /// int
+    ///     The number of associated Orpha diseases
+    ///
+    #[getter(orpha_count)]
+    fn orpha_count(&self) -> usize {
+        self.hpo().orpha_disease_ids().len()
+    }
+
     /// A list of the root phenotypical or modifier categories the term
     /// belongs to
     ///
@@ -384,6 +721,46 @@ impl PyHpoTerm {
             .collect()
     }
 
+    /// A list of all ancestor terms, in the obo format
+    ///
+    /// In contrast to ``_is_a``, this contains the transitive closure
+    /// of all parents, i.e. the parents, grandparents etc. up to the root term.
+    ///
+    /// Returns
+    /// -------
+    /// list[str]
+    ///     All ancestors, sorted by ID, e.g.: ``HP:0000001 ! All``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     term = Ontology.hpo(10049)
+    ///     for parent in term._all_is_a:
+    ///         print(parent)
+    ///
+    ///     # >> HP:0000001 ! All
+    ///     # >> HP:0000118 ! Phenotypic abnormality
+    ///     # >> ...
+    ///
+    #[getter(_all_is_a)]
+    fn all_is_a(&self) -> PyResult<Vec<String>> {
+        let mut ancestors: Vec<hpo::HpoTerm> = self
+            .hpo()
+            .all_parent_ids()
+            .iter()
+            .map(|id| term_from_id(id.as_u32()))
+            .collect::<PyResult<_>>()?;
+        ancestors.sort_unstable_by_key(|term| term.id());
+        Ok(ancestors
+            .iter()
+            .map(|term| format!("{} ! {}", term.id(), term.name()))
+            .collect())
+    }
+
     /// Indicates if the term is flagged as obsolete
     ///
     /// Obsolete terms are ususally not linked to parents or children
@@ -580,6 +957,8 @@ impl PyHpoTerm {
 
     /// Returns the number of terms between self and the root term
     ///
+    /// Use :func:`pyhpo.HPOTerm.path_to_root` to get the terms of the path.
+    ///
     /// Returns
     /// -------
     /// int
@@ -603,6 +982,46 @@ impl PyHpoTerm {
             .expect("the root term must be an ancestor")
     }
 
+    /// Returns the terms along the shortest path to the root term
+    ///
+    /// The path starts with the term itself and ends with the root
+    /// term ``HP:0000001``. The number of terms after the term itself
+    /// is the same as :func:`pyhpo.HPOTerm.shortest_path_to_root`.
+    ///
+    /// Returns
+    /// -------
+    /// List[:class:`HPOTerm`]
+    ///     The term, all terms of the shortest path and the root term
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     Ontology.hpo(100490).path_to_root()
+    ///     # >> [
+    ///     # >>     <HpoTerm (HP:0100490)>,
+    ///     # >>     <HpoTerm (HP:0006261)>, <HpoTerm (HP:0005918)>,
+    ///     # >>     <HpoTerm (HP:0001167)>, <HpoTerm (HP:0001155)>,
+    ///     # >>     <HpoTerm (HP:0002817)>, <HpoTerm (HP:0040064)>,
+    ///     # >>     <HpoTerm (HP:0000118)>, <HpoTerm (HP:0000001)>
+    ///     # >> ]
+    ///
+    #[pyo3(text_signature = "($self)")]
+    fn path_to_root(&self) -> Vec<PyHpoTerm> {
+        let root = term_from_id(1).expect("the root must exist");
+        let path = self.hpo().path_to_ancestor(&root).unwrap_or_default();
+        std::iter::once(self.id)
+            .chain(path)
+            .map(|id| {
+                pyterm_from_id(id.as_u32())
+                    .expect("the term must exist because its an ancestor term")
+            })
+            .collect()
+    }
+
     /// Calculates the shortest path to an ancestor HPO Term
     ///
     /// If `other` is not a parent term, the distance will be `Inf`.
@@ -661,6 +1080,55 @@ impl PyHpoTerm {
         )
     }
 
+    /// Returns all distinct paths to an ancestor HPO Term
+    ///
+    /// Terms can have multiple parents, so there can be several
+    /// paths from a term to one of its ancestors. Each path has the
+    /// same format as in :func:`pyhpo.HPOTerm.shortest_path_to_parent`.
+    ///
+    /// Parameters
+    /// ----------
+    /// other: :class:`HPOTerm`
+    ///     The ancestor HPOTerm
+    ///
+    /// Returns
+    /// -------
+    /// List[List[:class:`HPOTerm`]]
+    ///     All paths between self and ``other``. Each path contains
+    ///     the terms between self and ``other`` (including ``other``).
+    ///     If ``other`` is not an ancestor, it returns an empty list
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     term = Ontology.hpo(108)
+    ///     paths = term.paths_to_ancestor(Ontology.hpo(118))
+    ///     len(paths)  # >> 3
+    ///     paths[0]
+    ///     # >> [<HpoTerm (HP:0000107)>, <HpoTerm (HP:0012210)>, ... <HpoTerm (HP:0000118)>]
+    ///
+    #[pyo3(text_signature = "($self, other)")]
+    fn paths_to_ancestor(&self, other: &PyHpoTerm) -> PyResult<Vec<Vec<PyHpoTerm>>> {
+        let term = self.hpo();
+        let ancestor = other.hpo();
+        if !term.all_parent_ids().contains(&ancestor.id()) {
+            return Ok(vec![]);
+        }
+
+        let mut paths = Vec::new();
+        let mut path = Vec::new();
+        collect_paths(&term, &ancestor, &mut path, &mut paths);
+
+        paths
+            .into_iter()
+            .map(|path| path.into_iter().map(PyHpoTerm::try_from).collect())
+            .collect()
+    }
+
     /// Calculates the shortest path to another HPO Term
     ///
     /// .. note::
@@ -668,10 +1136,18 @@ impl PyHpoTerm {
     ///     This method is only partially implemented: The returned path is correct,
     ///     but it will always indicate ``0`` for the sub-paths distances.
     ///
+    /// Obsolete terms are not part of the hierarchy of the Ontology,
+    /// so paths never include them and there is no path to or from an
+    /// obsolete term. Use ``replace_obsolete`` to search the path between
+    /// the replacement terms instead.
+    ///
     /// Parameters
     /// ----------
     /// other: :class:`HPOTerm`
     ///     The other HPOTerm
+    /// replace_obsolete: bool, default: ``False``
+    ///     Replace ``self`` and ``other`` by their replacement term
+    ///     (see :func:`pyhpo.HPOTerm.replace`) if they are obsolete
     ///
     /// Returns
     /// -------
@@ -685,6 +1161,12 @@ impl PyHpoTerm {
     /// int
     ///     Always ``0``
     ///
+    /// Raises
+    /// ------
+    /// RuntimeError
+    ///     No path found, or ``replace_obsolete`` is set and
+    ///     an obsolete term has no replacement
+    ///
     /// Examples
     /// --------
     ///
@@ -702,18 +1184,33 @@ impl PyHpoTerm {
     ///     # >>    0
     ///     # >> )
     ///
-    #[pyo3(text_signature = "($self, other)")]
+    ///     obsolete = Ontology.hpo(100637)
+    ///     obsolete.path_to_other(term, replace_obsolete=True)
+    ///     # >> (
+    ///     # >>    5,
+    ///     # >>    [<HpoTerm (HP:0012720)>, ... <HpoTerm (HP:0040064)>],
+    ///     # >>    0,
+    ///     # >>    0
+    ///     # >> )
+    ///
+    #[pyo3(signature = (other, replace_obsolete = false))]
+    #[pyo3(text_signature = "($self, other, replace_obsolete)")]
     pub fn path_to_other(
         &self,
         other: &PyHpoTerm,
+        replace_obsolete: bool,
     ) -> PyResult<(usize, Vec<PyHpoTerm>, usize, usize)> {
-        let mut path = self
-            .hpo()
-            .path_to_term(&other.into())
+        let (start, end) = if replace_obsolete {
+            (replacement(self.hpo())?, replacement(other.hpo())?)
+        } else {
+            (self.hpo(), other.hpo())
+        };
+        let mut path = start
+            .path_to_term(&end)
             .ok_or_else(|| PyRuntimeError::new_err("No path found"))?;
         let len = path.len();
-        if !path.contains(&self.id) {
-            path.insert(0, self.id);
+        if !path.contains(&start.id()) {
+            path.insert(0, start.id());
         }
         Ok((
             len,
@@ -739,6 +1236,9 @@ impl PyHpoTerm {
     ///     * **omim**
     ///     * **orpha**
     ///     * **gene**
+    ///     * **custom** - or the name of any other custom information
+    ///       content, see :func:`pyhpo.HPOTerm.set_ic`. Only supported by the
+    ///       information content based ``method``\s
     ///
     /// method: `str`, default `graphic`
     ///     The method to use to calculate the similarity.
@@ -758,6 +1258,11 @@ impl PyHpoTerm {
     ///       Deng Y, et. al., PLoS One, (2015)
     ///     * **dist** - Distance between terms
     ///
+    /// cache: :class:`pyhpo.types.SimilarityMatrix`, optional
+    ///     A precomputed similarity matrix (see :func:`pyhpo.helper.similarity_matrix`).
+    ///     If both terms are part of the matrix, the score is looked up
+    ///     instead of calculated.
+    ///
     /// Returns
     /// -------
     /// float
@@ -769,6 +1274,8 @@ impl PyHpoTerm {
     ///     Invalid ``kind``
     /// RuntimeError
     ///     Invalid ``method``
+    /// ValueError
+    ///     ``cache`` was calculated with a different ``kind`` or ``method``
     ///
     /// Examples
     /// --------
@@ -785,16 +1292,25 @@ impl PyHpoTerm {
     ///     # compare HP:0011968 and HP:0001743 using Gene
     ///     term.similarity_score(Ontology.hpo(1743), kind="gene")
     ///
-    #[pyo3(signature = (other, kind = "omim", method = "graphic"))]
-    #[pyo3(text_signature = "($self, other, kind, method)")]
-    fn similarity_score(&self, other: &PyHpoTerm, kind: &str, method: &str) -> PyResult<f32> {
-        let kind = PyInformationContentKind::try_from(kind)?;
+    #[pyo3(signature = (other, kind = "omim", method = "graphic", cache = None))]
+    #[pyo3(text_signature = "($self, other, kind, method, cache)")]
+    fn similarity_score(
+        &self,
+        other: &PyHpoTerm,
+        kind: &str,
+        method: &str,
+        cache: Option<PyRef<PySimilarityMatrix>>,
+    ) -> PyResult<f32> {
+        if let Some(cache) = cache {
+            if let Some(score) = cache.lookup(self.id.as_u32(), other.id.as_u32(), kind, method)? {
+                return Ok(score);
+            }
+        }
 
         let term_a = self.hpo();
         let term_b = other.hpo();
 
-        let similarity = hpo::similarity::Builtins::new(method, kind.into())
-            .map_err(|_| PyRuntimeError::new_err("Unknown method to calculate similarity"))?;
+        let similarity = similarity_cache::builtin(method, kind)?;
         Ok(similarity.calculate(&term_a, &term_b))
     }
 
@@ -834,6 +1350,20 @@ impl PyHpoTerm {
     ///       Deng Y, et. al., PLoS One, (2015)
     ///     * **dist** - Distance between terms
     ///
+    /// nan_policy: str, default ``propagate``
+    ///     How to handle similarity scores that are ``NaN``
+    ///
+    ///     Available options:
+    ///
+    ///     * **propagate** - Return ``NaN`` scores
+    ///     * **raise** - Raise a ``ValueError``
+    ///     * **skip** - Omit ``NaN`` scores from the result. The result
+    ///       does not align with ``others`` any longer
+    ///     * **substitute** - Replace ``NaN`` scores with ``nan_value``
+    ///
+    /// nan_value: float, default ``0.0``
+    ///     The score that replaces ``NaN`` if ``nan_policy`` is ``substitute``
+    ///
     /// Returns
     /// -------
     /// List[float]
@@ -845,6 +1375,8 @@ impl PyHpoTerm {
     ///     Invalid ``kind``
     /// RuntimeError
     ///     Invalid ``method``
+    /// ValueError
+    ///     Invalid ``nan_policy`` or a ``NaN`` score with ``nan_policy="raise"``
     ///
     /// Examples
     /// --------
@@ -859,27 +1391,171 @@ impl PyHpoTerm {
     ///     term.similarity_scores(list(Ontology))
     ///
     ///
-    #[pyo3(signature = (others, kind = "omim", method = "graphic"))]
-    #[pyo3(text_signature = "($self, others, kind, method)")]
+    #[pyo3(signature = (others, kind = "omim", method = "graphic", nan_policy = "propagate", nan_value = 0.0))]
+    #[pyo3(text_signature = "($self, others, kind, method, nan_policy, nan_value)")]
     fn similarity_scores(
         &self,
+        py: Python<'_>,
         others: Vec<PyHpoTerm>,
         kind: &str,
         method: &str,
+        nan_policy: &str,
+        nan_value: f32,
     ) -> PyResult<Vec<f32>> {
-        let kind = PyInformationContentKind::try_from(kind)?;
+        let nan_policy = NanPolicy::new(nan_policy, nan_value)?;
+
+        let term_a = self.hpo();
+
+        let similarity = similarity_cache::builtin(method, kind)?;
+
+        let scores = worker::install(py, || {
+            others
+                .par_iter()
+                .map(|term_b| {
+                    let t2: hpo::HpoTerm = term_b.into();
+                    similarity.calculate(&term_a, &t2)
+                })
+                .collect()
+        });
+        nan_policy.apply(scores)
+    }
 
+    /// Calculates the similarity score between the term and all other terms
+    ///
+    /// The term is compared to every other non-obsolete term of the
+    /// Ontology in parallel. Unlike :func:`pyhpo.HPOTerm.similarity_scores`,
+    /// this does not require a list of all ``HPOTerm``\s and only returns
+    /// the IDs of the terms.
+    ///
+    /// Parameters
+    /// ----------
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to use for similarity calculation.
+    ///     See :func:`pyhpo.HPOTerm.similarity_score` for available options
+    /// method: str, default graphic
+    ///     The method to use to calculate the similarity.
+    ///     See :func:`pyhpo.HPOTerm.similarity_score` for available options
+    /// top_n: int, optional
+    ///     Only return the ``top_n`` most similar terms.
+    ///     Defaults to all terms
+    ///
+    /// Returns
+    /// -------
+    /// List[Tuple[int, float]]
+    ///     The ID and similarity score of every term, sorted by descending
+    ///     score. Ties are sorted by ID and ``NaN`` scores are sorted last.
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    /// RuntimeError
+    ///     Invalid ``method``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology()
+    ///     term = Ontology.hpo(11968)
+    ///
+    ///     for term_id, score in term.similarity_all(top_n=5):
+    ///         print(Ontology.hpo(term_id).name, score)
+    ///
+    #[pyo3(signature = (kind = "omim", method = "graphic", top_n = None))]
+    #[pyo3(text_signature = "($self, kind, method, top_n)")]
+    fn similarity_all(
+        &self,
+        py: Python<'_>,
+        kind: &str,
+        method: &str,
+        top_n: Option<usize>,
+    ) -> PyResult<Vec<(u32, f32)>> {
+        let ont = get_ontology()?;
         let term_a = self.hpo();
+        let similarity = similarity_cache::builtin(method, kind)?;
 
-        let similarity = hpo::similarity::Builtins::new(method, kind.into())
-            .map_err(|_| PyRuntimeError::new_err("Unknown method to calculate similarity"))?;
+        let others: Vec<hpo::HpoTerm> = ont
+            .iter()
+            .filter(|term| !term.is_obsolete() && term.id() != self.id)
+            .collect();
+        let mut scores: Vec<(u32, f32)> = worker::install(py, || {
+            others
+                .par_iter()
+                .map(|term_b| (term_b.id().as_u32(), similarity.calculate(&term_a, term_b)))
+                .collect()
+        });
+        scores.sort_by(|a, b| {
+            a.1.is_nan()
+                .cmp(&b.1.is_nan())
+                .then_with(|| b.1.total_cmp(&a.1))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        if let Some(top_n) = top_n {
+            scores.truncate(top_n);
+        }
+        Ok(scores)
+    }
 
-        Ok(others
-            .par_iter()
-            .map(|term_b| {
-                let t2: hpo::HpoTerm = term_b.into();
-                similarity.calculate(&term_a, &t2)
+    /// Returns the cross references of the term to other terminologies
+    ///
+    /// .. note::
+    ///
+    ///     Cross references are only available if the Ontology was built
+    ///     from the JAX source files.
+    ///
+    /// Parameters
+    /// ----------
+    /// system: str, optional
+    ///     Only return cross references of this system, e.g. ``UMLS``
+    ///     or ``SNOMEDCT_US``. The system is not case-sensitive
+    ///
+    /// Returns
+    /// -------
+    /// list[str]
+    ///     The cross references, including their system,
+    ///     e.g.: ``["UMLS:C0024796"]``
+    ///
+    /// Raises
+    /// ------
+    /// RuntimeError
+    ///     The Ontology was not built from the JAX source files
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology("/path/to/jax/files/")
+    ///     term = Ontology.hpo(1166)
+    ///     term.xrefs()
+    ///     # >> ['SNOMEDCT_US:38225001', 'UMLS:C0024796']
+    ///
+    ///     term.xrefs(system="SNOMEDCT_US")
+    ///     # >> ['SNOMEDCT_US:38225001']
+    ///
+    #[pyo3(signature = (system = None))]
+    #[pyo3(text_signature = "($self, system)")]
+    fn xrefs(&self, system: Option<&str>) -> PyResult<Vec<String>> {
+        Ok(all_term_details()?
+            .get(&self.id.as_u32())
+            .map(|details| details.xrefs.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .map(|xref| xref_id(xref))
+            .filter(|xref| {
+                system.is_none_or(|system| {
+                    xref.split_once(':')
+                        .is_some_and(|(prefix, _)| prefix.eq_ignore_ascii_case(system))
+                })
             })
+            .map(String::from)
             .collect())
     }
 
@@ -924,18 +1600,22 @@ impl PyHpoTerm {
     ///     * **int** : `int`
     ///         Integer of the term ID, e.g.: ``265``
     ///     * **synonym** : `list[str]`
-    ///         Not implemented, will always be ``[]``
+    ///         All synonyms of the term
     ///     * **comment** : `str`
-    ///         Not implemented, will always be ``""``
+    ///         The comment of the term
     ///     * **definition** : `str`
-    ///         Not implemented, will always be ``""``
+    ///         The definition of the term
     ///     * **xref** : `list[str]`
-    ///         Not implemented, will always be ``[]``
+    ///         Cross references to other ontologies, e.g.: ``UMLS:C0026364``
     ///     * **is_a** : `list[str]`
-    ///         Not implemented, will always be ``[]``
+    ///         The direct parents of the term, see :attr:`pyhpo.HPOTerm._is_a`
     ///     * **ic** : `dict[str, float]`
     ///         The information content scores, see :class:`pyhpo.InformationContent`
     ///
+    ///     ``synonym``, ``comment``, ``definition`` and ``xref`` are only
+    ///     populated if the Ontology is built from the JAX source files,
+    ///     they are empty when it is loaded from the builtin data.
+    ///
     /// Examples
     /// --------
     ///
@@ -956,7 +1636,7 @@ impl PyHpoTerm {
     ///     # >>     'comment': '',
     ///     # >>     'definition': '',
     ///     # >>     'xref': [],
-    ///     # >>     'is_a': [],
+    ///     # >>     'is_a': ['HP:0000264 ! Abnormal mastoid morphology'],
     ///     # >>     'ic': {
     ///     # >>         'gene': 6.7086944580078125,
     ///     # >>         'omim': 7.392647743225098,
@@ -968,27 +1648,94 @@ impl PyHpoTerm {
     #[pyo3(signature = (verbose = false))]
     #[pyo3(text_signature = "($self, verbose)")]
     #[allow(non_snake_case)]
-    pub fn toJSON<'a>(&'a self, py: Python<'a>, verbose: bool) -> PyResult<Bound<'_, PyDict>> {
-        let term = self.hpo();
-        let dict = PyDict::new_bound(py);
-        dict.set_item("name", term.name())?;
-        dict.set_item("id", term.id().to_string())?;
-        dict.set_item("int", term.id().as_u32())?;
-
-        if verbose {
-            let ic = PyDict::new_bound(py);
-            ic.set_item("gene", term.information_content().gene())?;
-            ic.set_item("omim", term.information_content().omim_disease())?;
-            ic.set_item("orpha", term.information_content().orpha_disease())?;
-            ic.set_item("decipher", 0.0)?;
-            dict.set_item::<&str, Vec<&str>>("synonym", vec![])?;
-            dict.set_item("comment", "")?;
-            dict.set_item("definition", "")?;
-            dict.set_item::<&str, Vec<&str>>("xref", vec![])?;
-            dict.set_item::<&str, Vec<&str>>("is_a", vec![])?;
-            dict.set_item("ic", ic)?;
-        }
-        Ok(dict)
+    pub fn toJSON<'a>(&'a self, py: Python<'a>, verbose: bool) -> PyResult<Bound<'a, PyDict>> {
+        TermJson::new(&self.hpo(), verbose).to_dict(py)
+    }
+
+    /// Returns a JSON string representation of the HPOTerm
+    ///
+    /// The JSON is generated directly in Rust and has the same
+    /// structure as :func:`pyhpo.HPOTerm.toJSON`. This is much faster than
+    /// serializing the output of ``toJSON`` in Python.
+    ///
+    /// Parameters
+    /// ----------
+    /// verbose: bool, default: ``False``
+    ///     Include extra properties
+    ///
+    /// Returns
+    /// -------
+    /// str
+    ///     JSON representation of the ``HPOTerm``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///     Ontology()
+    ///     Ontology.hpo(118).to_json_str()
+    ///     # >> '{"name":"Phenotypic abnormality","id":"HP:0000118","int":118}'
+    ///
+    #[pyo3(signature = (verbose = false))]
+    #[pyo3(text_signature = "($self, verbose)")]
+    fn to_json_str(&self, verbose: bool) -> PyResult<String> {
+        to_json_string(&TermJson::new(&self.hpo(), verbose))
+    }
+
+    /// Returns the FHIR ``Coding`` of the HPOTerm
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///     The ``Coding`` with the HPO code system, e.g.:
+    ///     ``{"system": "http://purl.obolibrary.org/obo/hp.owl", "code": "HP:0000118", "display": "Phenotypic abnormality"}``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     import json
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology()
+    ///     json.dumps(Ontology.hpo(2650).to_fhir_coding())
+    ///     # >> '{"system": "http://purl.obolibrary.org/obo/hp.owl", "code": "HP:0002650", "display": "Scoliosis"}'
+    ///
+    fn to_fhir_coding<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        fhir::coding(py, &self.hpo())
+    }
+
+    /// Returns the FHIR ``CodeableConcept`` of the HPOTerm
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///     The ``CodeableConcept`` with the ``Coding`` of the term
+    ///     (see :func:`pyhpo.HPOTerm.to_fhir_coding`) and its name as ``text``
+    ///
+    /// Examples
+    /// --------
+    ///
+    /// .. code-block:: python
+    ///
+    ///     from pyhpo import Ontology
+    ///
+    ///     Ontology()
+    ///     Ontology.hpo(2650).to_fhir_codeable_concept()
+    ///     # >> {
+    ///     # >>     'coding': [{
+    ///     # >>         'system': 'http://purl.obolibrary.org/obo/hp.owl',
+    ///     # >>         'code': 'HP:0002650',
+    ///     # >>         'display': 'Scoliosis'
+    ///     # >>     }],
+    ///     # >>     'text': 'Scoliosis'
+    ///     # >> }
+    ///
+    fn to_fhir_codeable_concept<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        fhir::codeable_concept(py, &self.hpo())
     }
 
     fn __str__(&self) -> String {
@@ -1007,6 +1754,13 @@ impl PyHpoTerm {
         self.__int__()
     }
 
+    /// The term is pickled by its ID and retrieved
+    /// from the Ontology when it is unpickled
+    fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, (u32,))> {
+        let ontology = PyModule::import(py, "pyhpo")?.getattr("Ontology")?;
+        Ok((ontology.getattr("hpo")?, (self.__int__(),)))
+    }
+
     /// Raises
     /// ------
     /// TypeError
@@ -1032,6 +1786,133 @@ impl PyHpoTerm {
     }
 }
 
+/// Returns the replacement of an obsolete term or the term itself
+///
+/// # Errors
+///
+/// - PyRuntimeError: The term is obsolete and has no replacement
+fn replacement(term: hpo::HpoTerm<'_>) -> PyResult<hpo::HpoTerm<'_>> {
+    if !term.is_obsolete() {
+        return Ok(term);
+    }
+    term.replaced_by().ok_or_else(|| {
+        PyRuntimeError::new_err(format!("{} is obsolete and has no replacement", term.id()))
+    })
+}
+
+/// Recursively collects all paths from `term` to `ancestor`
+///
+/// `path` contains the terms between the original term and `term`
+fn collect_paths(
+    term: &hpo::HpoTerm,
+    ancestor: &hpo::HpoTerm,
+    path: &mut Vec<HpoTermId>,
+    paths: &mut Vec<Vec<HpoTermId>>,
+) {
+    for parent in term.parents() {
+        if parent.id() == ancestor.id() {
+            let mut complete = path.clone();
+            complete.push(parent.id());
+            paths.push(complete);
+        } else if parent.all_parent_ids().contains(&ancestor.id()) {
+            path.push(parent.id());
+            collect_paths(&parent, ancestor, path, paths);
+            path.pop();
+        }
+    }
+}
+
+/// JSON representation of an `HpoTerm`
+///
+/// The structure is identical to the dict from `HPOTerm.toJSON`
+#[derive(Serialize)]
+pub(crate) struct TermJson {
+    name: String,
+    id: String,
+    int: u32,
+    #[serde(flatten)]
+    details: Option<TermJsonDetails>,
+}
+
+#[derive(Serialize)]
+struct TermJsonDetails {
+    synonym: Vec<String>,
+    comment: String,
+    definition: String,
+    xref: Vec<String>,
+    is_a: Vec<String>,
+    ic: TermJsonIc,
+}
+
+#[derive(Serialize)]
+struct TermJsonIc {
+    gene: f64,
+    omim: f64,
+    orpha: f64,
+    decipher: f64,
+}
+
+impl TermJson {
+    /// Returns the JSON representation of `term`
+    ///
+    /// Synonyms, comment, definition and xrefs are empty if the
+    /// Ontology was not built from the JAX source files
+    pub fn new(term: &hpo::HpoTerm, verbose: bool) -> Self {
+        let details = verbose.then(|| {
+            let text = term_details(term.id().as_u32());
+            TermJsonDetails {
+                synonym: text.map(|text| text.synonyms.clone()).unwrap_or_default(),
+                comment: text
+                    .and_then(|text| text.comment.clone())
+                    .unwrap_or_default(),
+                definition: text
+                    .and_then(|text| text.definition.clone())
+                    .unwrap_or_default(),
+                xref: text.map(|text| text.xrefs.clone()).unwrap_or_default(),
+                is_a: term
+                    .parents()
+                    .map(|parent| format!("{} ! {}", parent.id(), parent.name()))
+                    .collect(),
+                ic: TermJsonIc {
+                    gene: term.information_content().gene().into(),
+                    omim: term.information_content().omim_disease().into(),
+                    orpha: term.information_content().orpha_disease().into(),
+                    decipher: 0.0,
+                },
+            }
+        });
+        Self {
+            name: term.name().to_string(),
+            id: term.id().to_string(),
+            int: term.id().as_u32(),
+            details,
+        }
+    }
+
+    /// Returns the representation as Python dict, see `HPOTerm.toJSON`
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("name", &self.name)?;
+        dict.set_item("id", &self.id)?;
+        dict.set_item("int", self.int)?;
+
+        if let Some(details) = &self.details {
+            let ic = PyDict::new(py);
+            ic.set_item("gene", details.ic.gene)?;
+            ic.set_item("omim", details.ic.omim)?;
+            ic.set_item("orpha", details.ic.orpha)?;
+            ic.set_item("decipher", details.ic.decipher)?;
+            dict.set_item("synonym", &details.synonym)?;
+            dict.set_item("comment", &details.comment)?;
+            dict.set_item("definition", &details.definition)?;
+            dict.set_item("xref", &details.xref)?;
+            dict.set_item("is_a", &details.is_a)?;
+            dict.set_item("ic", ic)?;
+        }
+        Ok(dict)
+    }
+}
+
 impl From<&PyHpoTerm> for hpo::HpoTerm<'static> {
     fn from(value: &PyHpoTerm) -> hpo::HpoTerm<'static> {
         term_from_id(value.id.as_u32())
@@ -0,0 +1,350 @@
+use pyo3::exceptions::{PyIndexError, PyKeyError};
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+
+use hpo::annotations::AnnotationId;
+
+use crate::information_content::{custom_ic_table, is_custom_kind};
+use crate::ontology::term_depths;
+use crate::term::PyHpoTerm;
+use crate::{get_ontology, pyterm_from_id, term_from_id, term_from_query, PyQuery};
+
+/// A single item of a ``TermArray``, either an ``HPOTerm`` or a query
+#[derive(FromPyObject)]
+enum TermOrQuery {
+    Term(PyHpoTerm),
+    Query(PyQuery),
+}
+
+/// A compact array of ``HPOTerm``, that can contain missing values
+///
+/// The terms are stored as integer IDs, and all lookups of term
+/// attributes run on the whole array at once. It is the storage
+/// of the pandas extension array in :mod:`pyhpo.pandas`.
+///
+/// Parameters
+/// ----------
+/// terms: list[:class:`pyhpo.HPOTerm` | int | str | None]
+///     The terms of the array, missing values are ``None``
+///
+/// Raises
+/// ------
+/// NameError
+///     Ontology not yet constructed
+/// KeyError
+///     No HPO term is found for the provided query
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     from pyhpo import Ontology
+///     from pyhpo.types import TermArray
+///
+///     Ontology()
+///     terms = TermArray([118, "HP:0002650", None])
+///     terms.names()
+///     # >> ['Phenotypic abnormality', 'Scoliosis', None]
+///
+#[pyclass(name = "TermArray")]
+#[derive(Clone, Default)]
+pub(crate) struct PyTermArray {
+    ids: Vec<Option<u32>>,
+}
+
+impl PyTermArray {
+    /// Returns the position of `idx`, resolving negative indices from the end
+    ///
+    /// # Errors
+    ///
+    /// - PyIndexError: `idx` is out of bounds
+    fn position(&self, idx: isize) -> PyResult<usize> {
+        let pos = if idx < 0 {
+            idx + self.ids.len() as isize
+        } else {
+            idx
+        };
+        if pos < 0 || pos as usize >= self.ids.len() {
+            return Err(PyIndexError::new_err(format!(
+                "Index {idx} out of bounds for TermArray of length {}",
+                self.ids.len()
+            )));
+        }
+        Ok(pos as usize)
+    }
+}
+
+#[pymethods]
+impl PyTermArray {
+    #[new]
+    fn new(terms: Vec<Option<TermOrQuery>>) -> PyResult<Self> {
+        let ids = terms
+            .into_iter()
+            .map(|term| match term {
+                Some(TermOrQuery::Term(term)) => Ok(Some(term.hpo_term_id().as_u32())),
+                Some(TermOrQuery::Query(query)) => Ok(Some(term_from_query(query)?.id().as_u32())),
+                None => Ok(None),
+            })
+            .collect::<PyResult<Vec<Option<u32>>>>()?;
+        Ok(Self { ids })
+    }
+
+    /// Creates an array from integer term IDs
+    ///
+    /// Parameters
+    /// ----------
+    /// ids: list[int | None]
+    ///     The integer representation of the terms,
+    ///     missing values are ``None``
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.types.TermArray`
+    ///     A new ``TermArray``
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     No HPO term is found for an ID
+    ///
+    #[classmethod]
+    fn from_ids(_cls: &Bound<'_, PyType>, ids: Vec<Option<u32>>) -> PyResult<Self> {
+        for id in ids.iter().flatten() {
+            term_from_id(*id)?;
+        }
+        Ok(Self { ids })
+    }
+
+    /// Concatenates several arrays into one
+    ///
+    /// Parameters
+    /// ----------
+    /// arrays: list[:class:`pyhpo.types.TermArray`]
+    ///     The arrays to concatenate
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.types.TermArray`
+    ///     A new ``TermArray``
+    ///
+    #[classmethod]
+    fn concat(_cls: &Bound<'_, PyType>, arrays: Vec<PyRef<'_, Self>>) -> Self {
+        Self {
+            ids: arrays
+                .iter()
+                .flat_map(|array| array.ids.iter().copied())
+                .collect(),
+        }
+    }
+
+    fn __len__(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn __getitem__(&self, idx: isize) -> PyResult<Option<PyHpoTerm>> {
+        self.ids[self.position(idx)?]
+            .map(pyterm_from_id)
+            .transpose()
+    }
+
+    /// Returns a new array with the terms at the given positions
+    ///
+    /// Parameters
+    /// ----------
+    /// indices: list[int]
+    ///     The positions of the terms. Negative positions are
+    ///     counted from the end, unless ``allow_fill`` is used.
+    /// allow_fill: bool, default: ``False``
+    ///     Use ``fill_value`` for all positions of ``-1``
+    /// fill_value: :class:`pyhpo.HPOTerm` | int | str | None, default: ``None``
+    ///     The term to use for positions of ``-1``
+    ///
+    /// Returns
+    /// -------
+    /// :class:`pyhpo.types.TermArray`
+    ///     A new ``TermArray``
+    ///
+    /// Raises
+    /// ------
+    /// IndexError
+    ///     A position is out of bounds
+    /// KeyError
+    ///     No HPO term is found for ``fill_value``
+    ///
+    #[pyo3(signature = (indices, allow_fill = false, fill_value = None))]
+    #[pyo3(text_signature = "($self, indices, allow_fill, fill_value)")]
+    fn take(
+        &self,
+        indices: Vec<isize>,
+        allow_fill: bool,
+        fill_value: Option<TermOrQuery>,
+    ) -> PyResult<Self> {
+        let fill = Self::new(vec![fill_value])?.ids[0];
+        let ids = indices
+            .into_iter()
+            .map(|idx| match idx {
+                -1 if allow_fill => Ok(fill),
+                idx if allow_fill && idx < 0 => Err(PyIndexError::new_err(format!(
+                    "Invalid position {idx} with allow_fill"
+                ))),
+                idx => Ok(self.ids[self.position(idx)?]),
+            })
+            .collect::<PyResult<Vec<Option<u32>>>>()?;
+        Ok(Self { ids })
+    }
+
+    /// Returns a copy of the array
+    fn copy(&self) -> Self {
+        self.clone()
+    }
+
+    /// The number of bytes used by the array
+    #[getter(nbytes)]
+    fn nbytes(&self) -> usize {
+        self.ids.len() * std::mem::size_of::<Option<u32>>()
+    }
+
+    /// Returns the integer representation of all terms
+    ///
+    /// Returns
+    /// -------
+    /// list[int | None]
+    ///     The integer ID of every term, ``None`` for missing values
+    ///
+    fn ids(&self) -> Vec<Option<u32>> {
+        self.ids.clone()
+    }
+
+    /// Returns which items of the array are missing
+    ///
+    /// Returns
+    /// -------
+    /// list[bool]
+    ///     ``True`` for every missing value
+    ///
+    fn isna(&self) -> Vec<bool> {
+        self.ids.iter().map(Option::is_none).collect()
+    }
+
+    /// Returns the names of all terms
+    ///
+    /// Returns
+    /// -------
+    /// list[str | None]
+    ///     The name of every term, ``None`` for missing values
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    fn names(&self) -> PyResult<Vec<Option<String>>> {
+        let ont = get_ontology()?;
+        Ok(self
+            .ids
+            .iter()
+            .map(|id| {
+                id.and_then(|id| ont.hpo(id))
+                    .map(|term| term.name().to_string())
+            })
+            .collect())
+    }
+
+    /// Returns the HPO-IDs of all terms
+    ///
+    /// Returns
+    /// -------
+    /// list[str | None]
+    ///     The HPO-ID of every term, e.g. ``HP:0000118``,
+    ///     ``None`` for missing values
+    ///
+    fn hpo_ids(&self) -> Vec<Option<String>> {
+        self.ids
+            .iter()
+            .map(|id| id.map(|id| hpo::HpoTermId::from_u32(id).to_string()))
+            .collect()
+    }
+
+    /// Returns the depth of all terms
+    ///
+    /// The depth is the length of the shortest path to the root term
+    ///
+    /// Returns
+    /// -------
+    /// list[int | None]
+    ///     The depth of every term, ``None`` for missing values
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    ///
+    fn depths(&self) -> PyResult<Vec<Option<usize>>> {
+        let depths = term_depths()?;
+        Ok(self
+            .ids
+            .iter()
+            .map(|id| id.and_then(|id| depths.get(&id).copied()))
+            .collect())
+    }
+
+    /// Returns the information content of all terms
+    ///
+    /// Parameters
+    /// ----------
+    /// kind: str, default: ``omim``
+    ///     Which kind of information content to return
+    ///
+    ///     Available options:
+    ///
+    ///     * **omim**
+    ///     * **orpha**
+    ///     * **gene**
+    ///     * **custom** - or the name of any other custom information
+    ///       content, see :func:`pyhpo.HPOTerm.set_ic`
+    ///
+    /// Returns
+    /// -------
+    /// list[float]
+    ///     The information content of every term, ``NaN`` for missing values
+    ///
+    /// Raises
+    /// ------
+    /// NameError
+    ///     Ontology not yet constructed
+    /// KeyError
+    ///     Invalid ``kind``
+    ///
+    #[pyo3(signature = (kind = "omim"))]
+    #[pyo3(text_signature = "($self, kind)")]
+    fn information_content(&self, kind: &str) -> PyResult<Vec<f32>> {
+        let ont = get_ontology()?;
+        let custom = is_custom_kind(kind).then(|| custom_ic_table(kind));
+        let ic = |id: u32| -> PyResult<f32> {
+            let term = ont.hpo(id).expect("term IDs of the array must be valid");
+            let ic = term.information_content();
+            match kind {
+                "omim" => Ok(ic.omim_disease()),
+                "orpha" => Ok(ic.orpha_disease()),
+                "gene" => Ok(ic.gene()),
+                _ => match &custom {
+                    Some(custom) => Ok(custom.get(&id).copied().unwrap_or_default()),
+                    None => Err(PyKeyError::new_err(format!(
+                        "Unknown information content kind {kind}"
+                    ))),
+                },
+            }
+        };
+        self.ids
+            .iter()
+            .map(|id| id.map_or(Ok(f32::NAN), ic))
+            .collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<TermArray with {} terms>", self.ids.len())
+    }
+}
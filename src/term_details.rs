@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use once_cell::sync::OnceCell;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::PyResult;
+
+/// Textual details of all terms, only set when the Ontology
+/// is built from the JAX source files
+pub(crate) static TERM_DETAILS: OnceCell<HashMap<u32, TermDetails>> = OnceCell::new();
+
+/// Details of a term from the OBO file that are not part of the `hpo` Ontology
+#[derive(Default)]
+pub(crate) struct TermDetails {
+    pub definition: Option<String>,
+    pub synonyms: Vec<String>,
+    pub comment: Option<String>,
+    pub xrefs: Vec<String>,
+}
+
+/// The IDs of all terms by their xrefs, built on first use
+static XREF_INDEX: OnceCell<HashMap<&'static str, Vec<u32>>> = OnceCell::new();
+
+/// Returns the details of a term, if available
+pub(crate) fn term_details(id: u32) -> Option<&'static TermDetails> {
+    TERM_DETAILS.get()?.get(&id)
+}
+
+/// Returns the details of all terms
+///
+/// # Errors
+///
+/// - PyRuntimeError: The Ontology was not built from the JAX source files
+pub(crate) fn all_term_details() -> PyResult<&'static HashMap<u32, TermDetails>> {
+    TERM_DETAILS.get().ok_or_else(|| {
+        PyRuntimeError::new_err(
+            "Synonyms, definitions, comments and xrefs of terms are only available \
+            if the Ontology is built from the JAX source files, \
+            e.g. `Ontology(\"/path/to/jax/files/\")`",
+        )
+    })
+}
+
+/// Returns the ID of an OBO xref value, without a trailing description
+///
+/// e.g. `UMLS:C0024796 {source="..."}` → `UMLS:C0024796`
+pub(crate) fn xref_id(xref: &str) -> &str {
+    xref.split_whitespace().next().unwrap_or_default()
+}
+
+/// Returns the IDs of all terms with the xref, sorted ascending
+///
+/// # Errors
+///
+/// - PyRuntimeError: The Ontology was not built from the JAX source files
+pub(crate) fn terms_by_xref(xref: &str) -> PyResult<&'static [u32]> {
+    let details = all_term_details()?;
+    let index = XREF_INDEX.get_or_init(|| {
+        let mut index: HashMap<&'static str, Vec<u32>> = HashMap::new();
+        for (id, term) in details {
+            for xref in &term.xrefs {
+                index.entry(xref_id(xref)).or_default().push(*id);
+            }
+        }
+        for ids in index.values_mut() {
+            ids.sort_unstable();
+            ids.dedup();
+        }
+        index
+    });
+    Ok(index.get(xref.trim()).map_or(&[], Vec::as_slice))
+}
+
+/// Returns the content of the leading quoted string of an OBO value
+///
+/// e.g. `"Abnormal \"hand\" shape." [HPO:probinson]` → `Abnormal "hand" shape.`
+pub(crate) fn quoted(value: &str) -> Option<String> {
+    let mut chars = value.trim_start().strip_prefix('"')?.chars();
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.push(chars.next()?),
+            '"' => return Some(text),
+            _ => text.push(c),
+        }
+    }
+    None
+}
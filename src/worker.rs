@@ -0,0 +1,272 @@
+//! Using the Ontology in `multiprocessing` worker processes
+//!
+//! The Ontology is stored in a Rust `static`, outside of the Python heap.
+//! Processes created with `fork` share its memory pages copy-on-write.
+//! Python's reference counting never writes to these pages, so the
+//! child processes do not copy the Ontology.
+//!
+//! The threads of the global rayon thread pool do not survive a `fork`.
+//! Forked child processes use their own thread pool instead, otherwise the
+//! parallel helpers would wait forever for the non-existing threads.
+//!
+//! Single calls can be limited to a number of threads with
+//! [`install_with`], without affecting the parallelism of other calls.
+//!
+//! Processes created with `spawn` or `forkserver` start without an
+//! Ontology and must build it again with [`init_worker`], using the
+//! arguments of the parent process.
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use once_cell::sync::OnceCell;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::get_ontology;
+use crate::loader::Source;
+
+/// The process that uses the global rayon thread pool
+static GLOBAL_POOL_PID: OnceCell<u32> = OnceCell::new();
+
+/// The thread pool of a forked process and the ID of that process
+static FORK_POOL: Mutex<Option<(u32, Arc<ThreadPool>)>> = Mutex::new(None);
+
+/// The most recent thread pool of [`install_with`], the ID of the
+/// process that started it and its number of threads
+static WORKER_POOL: Mutex<Option<(u32, usize, Arc<ThreadPool>)>> = Mutex::new(None);
+
+/// The arguments that were used to build the Ontology
+///
+/// `None` if the Ontology was built from `bytes` or file-like objects
+pub(crate) static BUILD_ARGS: OnceCell<Option<BuildArgs>> = OnceCell::new();
+
+/// Runs `op` in a thread pool that is usable in the current process
+///
+/// All parallel iterators must be run through this function, so that
+/// they also work in processes that were forked after the global thread
+/// pool was started.
+///
+/// `op` runs detached from the Python interpreter, so that other Python
+/// threads can continue in the meantime.
+pub(crate) fn install<OP, R>(py: Python<'_>, op: OP) -> R
+where
+    OP: FnOnce() -> R + Send,
+    R: Send,
+{
+    py.allow_threads(|| {
+        let pid = std::process::id();
+        if *GLOBAL_POOL_PID.get_or_init(|| pid) == pid {
+            return op();
+        }
+        fork_pool(pid).install(op)
+    })
+}
+
+/// Runs `op` like [`install`], limited to `workers` threads
+///
+/// Without `workers`, `op` runs in the shared thread pool. Otherwise it
+/// runs in a thread pool with `workers` threads. Only the most recent pool
+/// is kept and shared by all later calls with the same `workers`, a call
+/// with a different number of threads replaces it.
+///
+/// # Errors
+///
+/// - PyValueError: `workers` is `0`
+/// - PyRuntimeError: The threads cannot be started
+pub(crate) fn install_with<OP, R>(py: Python<'_>, workers: Option<usize>, op: OP) -> PyResult<R>
+where
+    OP: FnOnce() -> R + Send,
+    R: Send,
+{
+    let Some(workers) = workers else {
+        return Ok(install(py, op));
+    };
+    if workers == 0 {
+        return Err(PyValueError::new_err("workers must be at least 1"));
+    }
+    py.allow_threads(|| Ok(worker_pool(workers)?.install(op)))
+}
+
+/// Returns the thread pool with `workers` threads of the current process
+fn worker_pool(workers: usize) -> PyResult<Arc<ThreadPool>> {
+    let pid = std::process::id();
+    let mut worker_pool = WORKER_POOL.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((owner, threads, pool)) = worker_pool.as_ref() {
+        if *owner == pid && *threads == workers {
+            return Ok(Arc::clone(pool));
+        }
+    }
+    let pool = Arc::new(
+        ThreadPoolBuilder::new()
+            .num_threads(workers)
+            .build()
+            .map_err(|err| PyRuntimeError::new_err(format!("Cannot start workers: {err}")))?,
+    );
+    match worker_pool.replace((pid, workers, Arc::clone(&pool))) {
+        // The threads of an inherited pool do not exist in this process,
+        // so it must not be dropped
+        Some((owner, _, inherited)) if owner != pid => std::mem::forget(inherited),
+        // The threads of the previous pool stop once all running calls are finished
+        _ => {}
+    }
+    Ok(pool)
+}
+
+/// Returns the thread pool of the forked process `pid`
+fn fork_pool(pid: u32) -> Arc<ThreadPool> {
+    let mut fork_pool = FORK_POOL.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((owner, pool)) = fork_pool.as_ref() {
+        if *owner == pid {
+            return Arc::clone(pool);
+        }
+    }
+    let pool = Arc::new(
+        ThreadPoolBuilder::new()
+            .build()
+            .expect("thread pool of forked process must be created"),
+    );
+    // The threads of an inherited pool do not exist in this process,
+    // so it must not be dropped
+    if let Some(inherited) = fork_pool.replace((pid, Arc::clone(&pool))) {
+        std::mem::forget(inherited);
+    }
+    pool
+}
+
+/// Arguments of `Ontology()` that can be used to build
+/// the same Ontology in a different process
+pub(crate) struct BuildArgs {
+    pub data_folder: Option<PathBuf>,
+    pub from_obo_file: bool,
+    pub transitive: bool,
+    pub obo: Option<PathBuf>,
+    pub hpoa: Option<PathBuf>,
+    pub gene_file: Option<PathBuf>,
+    pub annotations: bool,
+    pub min_frequency: Option<f32>,
+    pub evidence_codes: Option<Vec<String>>,
+    pub gene_annotation_mode: Option<String>,
+}
+
+impl BuildArgs {
+    /// Returns the arguments with the given sources and default settings
+    ///
+    /// Returns `None` if any of the sources is not a path
+    pub fn from_sources(
+        data_folder: Option<&Source>,
+        obo: Option<&Source>,
+        hpoa: Option<&Source>,
+        gene_file: Option<&Source>,
+    ) -> Option<Self> {
+        Some(Self {
+            data_folder: source_path(data_folder)?,
+            from_obo_file: true,
+            transitive: false,
+            obo: source_path(obo)?,
+            hpoa: source_path(hpoa)?,
+            gene_file: source_path(gene_file)?,
+            annotations: true,
+            min_frequency: None,
+            evidence_codes: None,
+            gene_annotation_mode: None,
+        })
+    }
+
+    /// Returns the keyword arguments for `Ontology()`
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("data_folder", &self.data_folder)?;
+        dict.set_item("from_obo_file", self.from_obo_file)?;
+        dict.set_item("transitive", self.transitive)?;
+        dict.set_item("obo", &self.obo)?;
+        dict.set_item("hpoa", &self.hpoa)?;
+        dict.set_item("gene_file", &self.gene_file)?;
+        dict.set_item("annotations", self.annotations)?;
+        dict.set_item("min_frequency", self.min_frequency)?;
+        dict.set_item("evidence_codes", &self.evidence_codes)?;
+        dict.set_item("gene_annotation_mode", &self.gene_annotation_mode)?;
+        Ok(dict)
+    }
+}
+
+/// Returns the path of an optional source, `None` if it is not a path
+fn source_path(source: Option<&Source>) -> Option<Option<PathBuf>> {
+    match source {
+        None => Some(None),
+        Some(Source::Path(path)) => Some(Some(path.clone())),
+        Some(_) => None,
+    }
+}
+
+/// Returns the keyword arguments that were used to build the Ontology
+///
+/// # Errors
+///
+/// - PyNameError: Ontology not yet constructed
+/// - PyValueError: Ontology was built from `bytes` or file-like objects
+pub(crate) fn build_kwargs(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    get_ontology()?;
+    match BUILD_ARGS.get() {
+        Some(Some(args)) => args.to_dict(py),
+        _ => Err(PyValueError::new_err(
+            "The Ontology was built from bytes or a file-like object \
+            and cannot be built again from its arguments",
+        )),
+    }
+}
+
+/// Builds the Ontology in a ``multiprocessing`` worker process
+///
+/// Use this function as ``initializer`` of a ``multiprocessing.Pool``
+/// or ``concurrent.futures.ProcessPoolExecutor`` together with the
+/// arguments of :func:`pyhpo.Ontology.build_kwargs`.
+///
+/// Processes that are started with ``fork`` share the Ontology of the
+/// parent process without copying it, this function does nothing in them.
+/// Processes started with ``spawn`` or ``forkserver`` (the default on
+/// macOS and Windows) build the Ontology again, so that ``HPOTerm``,
+/// ``HPOSet``, ``Gene`` and diseases can be unpickled in the worker.
+///
+/// Parameters
+/// ----------
+/// kwargs: dict, optional
+///     The keyword arguments for ``Ontology()``, usually from
+///     :func:`pyhpo.Ontology.build_kwargs`. Builds the default Ontology
+///     if not provided.
+///
+/// Examples
+/// --------
+///
+/// .. code-block:: python
+///
+///     import multiprocessing
+///     from pyhpo import Ontology, HPOSet, helper
+///
+///     Ontology()
+///
+///     def similarity(sets):
+///         return sets[0].similarity(sets[1])
+///
+///     sets = [(HPOSet.from_queries([118, 2650]), HPOSet.from_queries([7, 1743]))]
+///     ctx = multiprocessing.get_context("spawn")
+///     with ctx.Pool(
+///         4,
+///         initializer=helper.init_worker,
+///         initargs=(Ontology.build_kwargs(),),
+///     ) as pool:
+///         scores = pool.map(similarity, sets)
+///
+#[pyfunction]
+#[pyo3(signature = (kwargs = None))]
+#[pyo3(text_signature = "(kwargs)")]
+pub(crate) fn init_worker(py: Python<'_>, kwargs: Option<Bound<'_, PyDict>>) -> PyResult<()> {
+    if get_ontology().is_ok() {
+        return Ok(());
+    }
+    PyModule::import(py, "pyhpo")?
+        .getattr("Ontology")?
+        .call((), kwargs.as_ref())?;
+    Ok(())
+}
@@ -1,9 +1,21 @@
+import array
+import json
+import math
+import multiprocessing
+import os
+import pickle
+import sys
+import tempfile
 import unittest
+from concurrent.futures import ThreadPoolExecutor
 
-from pyhpo import Ontology
+import pyhpo
+from pyhpo import Ontology, Patient, Cohort, register_similarity
 from pyhpo.set import HPOSet
-from pyhpo.stats import EnrichmentModel
+from pyhpo.stats import EnrichmentModel, SimilarityKernel, hypergeom, linkage
 from pyhpo import annotations as an
+from pyhpo import helper
+from pyhpo.types import SimilarityMatrix, TermArray
 
 # Number of terms in HPO Ontology
 # grep "^\[Term\]$" pyhpo/data/hp.obo | wc -l
@@ -23,6 +35,204 @@ N_OMIM = 8251
 N_ORPHA = 4244
 
 
+def _worker_similarity(sets):
+    """
+    Runs in multiprocessing workers, must be defined on module level
+    """
+    return helper.batch_set_similarity([sets])[0]
+
+
+def _binary_direct_hpo(path):
+    """
+    Loads custom binary data in a fresh interpreter,
+    must be defined on module level
+    """
+    Ontology(path, from_obo_file=False)
+    try:
+        return Ontology.genes[0].direct_hpo
+    except RuntimeError as err:
+        return str(err)
+
+
+def _binary_len(path):
+    """
+    Loads binary data in a fresh interpreter,
+    must be defined on module level
+    """
+    Ontology(path, from_obo_file=False)
+    return len(Ontology)
+
+
+def _jax_annotation_metadata(folder):
+    """
+    Loads the JAX fixture in a fresh interpreter,
+    must be defined on module level
+    """
+    Ontology(folder)
+    return Ontology.annotation_metadata()
+
+
+def _fixture_build(folder, sources="folder", **options):
+    """
+    Builds the Ontology from the JAX fixture in a fresh interpreter
+    and returns a summary, must be defined on module level
+
+    ``sources`` selects how the fixture is provided: as ``folder``,
+    as in-memory ``contents``, only the ``obo`` file or as binary
+    data (a path to it)
+    """
+    import io
+
+    events = []
+    # Raise an error in the progress callback of this stage
+    fail_at = options.pop("fail_at", None)
+    if sources == "folder":
+        args = {"data_folder": folder}
+    elif sources == "contents":
+        with open(os.path.join(folder, "hp.obo"), "rb") as fh:
+            obo = fh.read()
+        with open(os.path.join(folder, "phenotype.hpoa"), "rb") as fh:
+            hpoa = io.BytesIO(fh.read())
+        with open(os.path.join(folder, "genes_to_phenotype.txt")) as fh:
+            gene_file = io.StringIO(fh.read())
+        args = {"obo": obo, "hpoa": hpoa, "gene_file": gene_file}
+    elif sources == "obo":
+        args = {"obo": os.path.join(folder, "hp.obo")}
+    else:
+        with open(sources, "rb") as fh:
+            args = {"data_folder": fh.read(), "from_obo_file": False}
+
+    def progress(stage, count):
+        if stage == fail_at:
+            raise ZeroDivisionError(stage)
+        events.append((stage, count))
+
+    try:
+        Ontology(progress=progress, **args, **options)
+    except Exception as err:
+        return {"error": (type(err).__name__, str(err))}
+
+    return {
+        "len": len(Ontology),
+        "n_genes": len(Ontology.genes),
+        "genes": {gene.name: sorted(int(t) for t in gene.hpo) for gene in Ontology.genes},
+        "omim": {d.id: sorted(int(t) for t in d.hpo) for d in Ontology.omim_diseases},
+        "orpha": {d.id: sorted(int(t) for t in d.hpo) for d in Ontology.orpha_diseases},
+        "direct_hpo": {gene.name: _direct_hpo_ids(gene) for gene in Ontology.genes},
+        "progress": events,
+    }
+
+
+def _long_name_build(folder, name, newline="\n"):
+    """
+    Builds the Ontology from the JAX fixture with an additional term
+    named ``name`` in a fresh interpreter, must be defined on module level
+    """
+    with open(os.path.join(folder, "hp.obo")) as fh:
+        obo = fh.read()
+    obo = obo.replace(
+        "[Typedef]",
+        f"[Term]\nid: HP:0000006\nname: {name}\nis_a: HP:0000118 ! Phenotypic abnormality\n\n[Typedef]"
+    )
+    Ontology(
+        obo=obo.replace("\n", newline).encode(),
+        hpoa=os.path.join(folder, "phenotype.hpoa"),
+        gene_file=os.path.join(folder, "genes_to_phenotype.txt"),
+    )
+    obsolete = Ontology.hpo(4)
+    return {
+        "name": Ontology.hpo(6).name,
+        "parents": sorted(int(term) for term in Ontology.hpo(6).parents),
+        "len": len(Ontology),
+        "version": Ontology.version(),
+        "obsolete": (obsolete.is_obsolete, obsolete.replaced_by),
+    }
+
+
+def _direct_hpo_ids(gene):
+    try:
+        return sorted(int(term) for term in gene.direct_hpo)
+    except RuntimeError:
+        return None
+
+
+def _zstd_rle_frame(size):
+    """
+    Returns a zstd frame of RLE blocks that decompresses to ``size`` zero bytes
+    """
+    block_size = 128 * 1024
+    # magic number, frame header without content size, 128 KB window
+    frame = bytearray(b"\x28\xb5\x2f\xfd\x00\x38")
+    for idx in range(size // block_size):
+        last = int(idx == size // block_size - 1)
+        header = last | (1 << 1) | (block_size << 3)
+        frame += header.to_bytes(3, "little") + b"\x00"
+    return bytes(frame)
+
+
+def _jax_fixture_summary(folder):
+    """
+    Builds the Ontology from the JAX fixture files in a fresh
+    interpreter, must be defined on module level
+    """
+    try:
+        Ontology(
+            obo=os.path.join(folder, "missing_parent.obo"),
+            hpoa=os.path.join(folder, "phenotype.hpoa"),
+            gene_file=os.path.join(folder, "genes_to_phenotype.txt"),
+        )
+        error = None
+    except RuntimeError as err:
+        error = str(err)
+
+    Ontology(folder)
+    kidney = Ontology.hpo(3)
+    obsolete = Ontology.hpo(4)
+    return {
+        "error": error,
+        "len": len(Ontology),
+        "version": Ontology.version(),
+        "parents": sorted(int(term) for term in kidney.parents),
+        "all_parents": sorted(int(term) for term in kidney.all_parents),
+        "children": sorted(int(term) for term in Ontology.hpo(118).children),
+        "obsolete": (obsolete.is_obsolete, obsolete.replaced_by, kidney.is_obsolete),
+        "obsolete_parents": len(obsolete.parents),
+        "json": kidney.toJSON(verbose=True),
+        "json_str": kidney.to_json_str(verbose=True),
+        "ic": kidney.information_content.gene,
+        "xrefs": (kidney.xrefs(), kidney.xrefs(system="umls"), Ontology.hpo(1).xrefs()),
+        "by_xref": [
+            [int(term) for term in Ontology.term_by_xref(xref)]
+            for xref in ("UMLS:C3714581", " SNOMEDCT_US:204962002 ", "UMLS:C0000000")
+        ],
+        "root": Ontology.hpo(1).toJSON(verbose=True)["comment"],
+        "genes": sorted(gene.name for gene in kidney.genes),
+        "direct_hpo": sorted(an.Gene.get("GENE1").direct_hpo),
+        "omim": sorted(disease.id for disease in Ontology.hpo(2).omim_diseases),
+        "orpha": sorted(disease.id for disease in Ontology.hpo(2).orpha_diseases),
+        "omim_annotations": an.Omim.get(600001).annotations(),
+        "orpha_annotations": an.Orpha.get(1001).annotations(),
+        "modifiers": {
+            int(term): [int(modifier) for modifier in modifiers]
+            for term, modifiers in an.Omim.get(600001).hpo_with_modifiers().items()
+        },
+        "search": [
+            [int(term) for term in Ontology.search(query, fields=fields)]
+            for query, fields in (
+                ("renal", ["name"]),
+                ("renal", ["synonym"]),
+                ("generation", ["definition"]),
+                ("bnormal", ["name", "synonym", "definition"]),
+            )
+        ],
+        "frequencies": [
+            an.Omim.get(600001).frequency(Ontology.hpo(3)),
+            an.Omim.get(600001).frequency(Ontology.hpo(2)),
+            an.Orpha.get(1001).frequency(Ontology.hpo(2)),
+        ],
+    }
+
+
 class IntegrationFullTest(unittest.TestCase):
     @classmethod
     def setUpClass(cls):
@@ -60,6 +270,91 @@ class IntegrationFullTest(unittest.TestCase):
         """
         self.assertEqual(len(self.terms.orpha_diseases), N_ORPHA)
 
+    def test_filter_annotations(self):
+        genes = self.terms.genes
+        self.assertIsInstance(genes, list)
+        self.assertIsInstance(self.terms.omim_diseases, list)
+        self.assertIsInstance(self.terms.orpha_diseases, list)
+        self.assertEqual(len(genes + self.terms.genes), 2 * len(genes))
+        self.assertEqual(self.terms.filter_genes(), genes)
+        self.assertEqual(
+            self.terms.filter_genes(sorted_by="id"),
+            sorted(genes, key=lambda gene: gene.id)
+        )
+
+        scoliosis = self.terms.get_hpo_object(2650)
+        by_name = self.terms.filter_genes(sorted_by="name", having_term=scoliosis)
+        self.assertEqual(set(by_name), scoliosis.genes)
+        self.assertEqual([g.name for g in by_name], sorted(g.name for g in by_name))
+        self.assertEqual(
+            self.terms.filter_genes(sorted_by="id", having_term=2650),
+            scoliosis.sorted_genes()
+        )
+
+        diseases = self.terms.filter_omim_diseases(min_terms=5)
+        self.assertGreater(len(diseases), 0)
+        self.assertLess(len(diseases), len(self.terms.omim_diseases))
+        for disease in diseases:
+            self.assertGreaterEqual(len(disease.hpo), 5)
+
+        by_terms = self.terms.filter_orpha_diseases(sorted_by="terms", having_term=2650)
+        self.assertEqual(set(by_terms), scoliosis.orpha_diseases)
+        counts = [len(d.hpo) for d in by_terms]
+        self.assertEqual(counts, sorted(counts, reverse=True))
+
+        with self.assertRaises(KeyError):
+            self.terms.filter_genes(sorted_by="foobar")
+        with self.assertRaises(KeyError):
+            self.terms.filter_genes(having_term=999999999)
+
+    def test_omim_get_prefixed(self):
+        disease = an.Omim.get(620402)
+        self.assertEqual(an.Omim.get("620402"), disease)
+        self.assertEqual(an.Omim.get("OMIM:620402"), disease)
+
+        for query in ("ORPHA:620402", "OMIM:", "omim:620402", "OMIM:62040a", "foo"):
+            with self.assertRaises(ValueError):
+                an.Omim.get(query)
+        with self.assertRaises(KeyError):
+            an.Omim.get("OMIM:99999999")
+
+    def test_orpha_get_prefixed(self):
+        disease = an.Orpha.get(5)
+        self.assertEqual(an.Orpha.get("5"), disease)
+        self.assertEqual(an.Orpha.get("ORPHA:5"), disease)
+        self.assertEqual(an.Orpha.get("Orphanet:5"), disease)
+
+        for query in ("OMIM:5", "Orphanet:", "orpha:5", "ORPHA:x", "foo"):
+            with self.assertRaises(ValueError):
+                an.Orpha.get(query)
+        with self.assertRaises(KeyError):
+            an.Orpha.get("ORPHA:99999999")
+
+    def test_disease_specificity(self):
+        for disease, n_diseases, diseases_of in (
+            (an.Omim.get(620402), len(self.terms.omim_diseases), lambda t: t.omim_diseases),
+            (an.Orpha.get(5), len(self.terms.orpha_diseases), lambda t: t.orpha_diseases),
+        ):
+            terms = list(disease.hpo_set())
+            self.assertGreater(len(terms), 0)
+            for term in terms:
+                self.assertAlmostEqual(
+                    disease.specificity(term),
+                    math.log(n_diseases / len(diseases_of(term))),
+                    places=4
+                )
+                # Ancestors are shared with more diseases
+                for parent in term.parents:
+                    self.assertLessEqual(
+                        disease.specificity(parent),
+                        disease.specificity(term) + 1e-6
+                    )
+
+            unrelated = next(
+                term for term in self.terms if disease not in diseases_of(term)
+            )
+            self.assertEqual(disease.specificity(unrelated), 0.0)
+
     def test_average_annotation_numbers(self):
         """
         These test will most likely need to be updated
@@ -74,6 +369,105 @@ class IntegrationFullTest(unittest.TestCase):
         assert sum(genes)/len(genes) > 36, sum(genes)/len(genes)
         assert sum(omim)/len(omim) > 29, sum(omim)/len(omim)
 
+    def test_direct_annotations(self):
+        for term in list(self.terms)[:300]:
+            for direct, all_items, attr in (
+                (term.direct_genes, term.genes, "genes"),
+                (term.direct_omim_diseases, term.omim_diseases, "omim_diseases"),
+                (term.direct_orpha_diseases, term.orpha_diseases, "orpha_diseases"),
+            ):
+                self.assertTrue(direct.issubset(all_items))
+                for item in direct:
+                    self.assertIn(int(term), item.hpo)
+                inherited = set()
+                for child in term.children:
+                    inherited |= getattr(child, attr)
+                self.assertEqual(direct | inherited, all_items)
+
+    def test_gene_direct_hpo(self):
+        # The builtin Ontology only contains direct gene annotations
+        for gene in list(self.terms.genes)[:200]:
+            self.assertEqual(gene.direct_hpo, gene.hpo)
+
+        # Custom binary data can contain transitive annotations
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = os.path.join(tmpdir, "ontology.hpo")
+            self.terms.save(path)
+            with multiprocessing.get_context("spawn").Pool(1) as pool:
+                error = pool.apply(_binary_direct_hpo, (path,))
+        self.assertIn("Direct gene annotations are only available", error)
+
+    def test_disease_frequency(self):
+        # The builtin Ontology does not contain annotation frequencies
+        for disease in (self.terms.omim_diseases[0], self.terms.orpha_diseases[0]):
+            with self.assertRaisesRegex(RuntimeError, "JAX source files"):
+                disease.frequency(disease.hpo_set()[0])
+
+    def test_search_fields(self):
+        self.assertEqual(
+            [term.id for term in self.terms.search("kidney dis")],
+            ["HP:0003774", "HP:0012622", "HP:0012623", "HP:0012624", "HP:0012625", "HP:0012626"]
+        )
+        self.assertEqual(
+            self.terms.search("kidney dis", fields=["name"]),
+            self.terms.search("kidney dis")
+        )
+        with self.assertRaises(KeyError):
+            self.terms.search("kidney", fields=["comment"])
+        # The builtin Ontology does not contain synonyms and definitions
+        with self.assertRaisesRegex(RuntimeError, "JAX source files"):
+            self.terms.search("kidney", fields=["name", "synonym"])
+        with self.assertRaisesRegex(RuntimeError, "JAX source files"):
+            self.terms.search("kidney", fields=["definition"])
+
+    def test_sorted_term_relations(self):
+        for term in (self.terms.hpo(1), self.terms.hpo(108), self.terms.hpo(188)):
+            for items, sorted_items in (
+                (term.parents, term.sorted_parents()),
+                (term.children, term.sorted_children()),
+                (term.genes, term.sorted_genes()),
+                (term.omim_diseases, term.sorted_omim_diseases()),
+                (term.orpha_diseases, term.sorted_orpha_diseases()),
+            ):
+                self.assertIsInstance(sorted_items, list)
+                self.assertEqual(set(sorted_items), items)
+                self.assertEqual([int(item) for item in sorted_items], sorted(int(item) for item in items))
+
+        self.assertEqual(
+            [term.id for term in self.terms.hpo(108).sorted_parents()],
+            ["HP:0000107", "HP:0011035", "HP:0100957"]
+        )
+        self.assertEqual(self.terms.hpo(1).sorted_parents(), [])
+
+    def test_disease_annotations_unavailable(self):
+        # The builtin Ontology does not contain annotation details
+        with self.assertRaisesRegex(RuntimeError, "JAX source files"):
+            self.terms.omim_diseases[0].annotations()
+        with self.assertRaisesRegex(RuntimeError, "JAX source files"):
+            self.terms.orpha_diseases[0].annotations()
+
+    def test_disease_modifiers_unavailable(self):
+        # The builtin Ontology does not contain clinical modifiers
+        with self.assertRaisesRegex(RuntimeError, "JAX source files"):
+            self.terms.omim_diseases[0].hpo_with_modifiers()
+        with self.assertRaisesRegex(RuntimeError, "JAX source files"):
+            self.terms.orpha_diseases[0].hpo_with_modifiers()
+
+    def test_annotation_counts(self):
+        for term in list(self.terms)[:500]:
+            self.assertEqual(term.gene_count, len(term.genes))
+            self.assertEqual(term.omim_count, len(term.omim_diseases))
+            self.assertEqual(term.orpha_count, len(term.orpha_diseases))
+
+    def test_memory_usage(self):
+        usage = self.terms.memory_usage()
+        for key in ("terms", "annotations", "information_content"):
+            assert usage[key] > 0, key
+        self.assertEqual(
+            usage["total"],
+            sum(value for key, value in usage.items() if key != "total")
+        )
+
     def test_annotation_inheritance(self):
         for term in self.terms:
             lg = len(term.genes)
@@ -96,6 +490,22 @@ class IntegrationFullTest(unittest.TestCase):
                     assert term.omim_diseases.issubset(parent.omim_diseases)
 
 
+    def test_all_is_a(self):
+        for term_id in (10049, 2650, 118):
+            term = self.terms.hpo(term_id)
+            ancestors = sorted(term.all_parents, key=int)
+            self.assertEqual(
+                term._all_is_a,
+                ["{} ! {}".format(ancestor.id, ancestor.name) for ancestor in ancestors]
+            )
+            # The direct parents are part of the closure
+            self.assertTrue(set(term._is_a).issubset(term._all_is_a))
+
+        term = self.terms.hpo(10049)
+        self.assertGreater(len(term._all_is_a), len(term._is_a))
+        self.assertEqual(term._all_is_a[0], "HP:0000001 ! All")
+        self.assertEqual(self.terms.root._all_is_a, [])
+
     def test_relationships(self):
         for term in self.terms:
             for child in term.children:
@@ -130,6 +540,15 @@ class IntegrationFullTest(unittest.TestCase):
             0
         )
 
+        self.assertLessEqual(
+            len(full_set.remove_modifier(keep=["phenotypic_abnormality"])),
+            len(phenoterms)
+        )
+        self.assertEqual(
+            len(full_set.remove_modifier(remove=[])),
+            len(full_set)
+        )
+
         self.assertIn(
             self.terms[5],
             full_set
@@ -140,6 +559,966 @@ class IntegrationFullTest(unittest.TestCase):
             phenoterms
         )
 
+    def test_set_terms_iterator(self):
+        hposet = HPOSet.from_queries([2650, 118, 7])
+        expected = [self.terms.hpo(7), self.terms.hpo(118), self.terms.hpo(2650)]
+
+        terms = hposet.terms()
+        self.assertNotIsInstance(terms, list)
+        self.assertIs(iter(terms), terms)
+        self.assertEqual(next(terms), expected[0])
+        self.assertEqual(list(terms), expected[1:])
+        with self.assertRaises(StopIteration):
+            next(terms)
+
+        self.assertEqual(hposet.terms(as_list=True), expected)
+        self.assertEqual(list(HPOSet.from_queries([]).terms()), [])
+
+    def test_set_indexing(self):
+        hposet = HPOSet.from_queries([2650, 118, 7])
+        self.assertEqual(hposet[0], self.terms.hpo(7))
+        self.assertEqual(hposet[2], self.terms.hpo(2650))
+        self.assertEqual(hposet[-1], self.terms.hpo(2650))
+        self.assertEqual([term for term in hposet[1:]], [self.terms.hpo(118), self.terms.hpo(2650)])
+        self.assertIsInstance(hposet[1:], HPOSet)
+        self.assertEqual(len(hposet[::2]), 2)
+        self.assertEqual(len(hposet[5:]), 0)
+
+        with self.assertRaises(IndexError):
+            hposet[3]
+        with self.assertRaises(IndexError):
+            hposet[-4]
+        with self.assertRaises(TypeError):
+            hposet["a"]
+
+        self.assertIn(self.terms.hpo(2650), hposet)
+        self.assertNotIn(self.terms.hpo(2651), hposet)
+
+    def test_terms_by_ic(self):
+        hposet = HPOSet.from_queries([2650, 118, 7])
+        for kind in ("omim", "orpha", "gene"):
+            terms = hposet.terms_by_ic(kind=kind)
+            ics = [term.information_content[kind] for term in terms]
+            self.assertEqual(ics, sorted(ics, reverse=True))
+            self.assertEqual(set(terms), set(hposet))
+            self.assertEqual(hposet.terms_by_ic(kind=kind, descending=False), terms[::-1])
+        self.assertEqual(hposet.terms_by_ic()[0], self.terms.hpo(2650))
+        self.assertEqual(HPOSet.from_queries([]).terms_by_ic(), [])
+
+        with self.assertRaises(KeyError):
+            hposet.terms_by_ic(kind="foo")
+
+    def test_subgraph(self):
+        hposet = HPOSet.from_queries([2650, 3])
+        nodes, edges = hposet.subgraph()
+
+        expected = set(hposet)
+        for term in hposet:
+            expected |= term.all_parents
+        self.assertEqual(set(nodes), expected)
+        self.assertEqual([int(term) for term in nodes], sorted(int(term) for term in expected))
+
+        expected_edges = {
+            (int(term), int(parent)) for term in expected for parent in term.parents
+        }
+        self.assertEqual(len(edges), len(expected_edges))
+        self.assertEqual(set(edges), expected_edges)
+
+        self.assertEqual(HPOSet.from_queries([]).subgraph(), ([], []))
+
+    def test_set_categories(self):
+        hposet = HPOSet.from_queries([2650, 7, 1250, 3, 1166])
+        expected = {}
+        for term in hposet:
+            for category in term.categories:
+                expected.setdefault(category, []).append(term)
+
+        categories = hposet.categories()
+        self.assertEqual(set(categories), set(expected))
+        self.assertEqual(
+            [int(category) for category in categories],
+            sorted(int(category) for category in categories)
+        )
+        for category, terms in categories.items():
+            self.assertEqual(terms, sorted(expected[category], key=int))
+
+        self.assertEqual(
+            hposet.categories(counts=True),
+            {category: len(terms) for category, terms in categories.items()}
+        )
+        self.assertEqual(HPOSet.from_queries([]).categories(), {})
+
+    def test_filter_category(self):
+        hposet = HPOSet.from_queries([1631, 1250, 30148, 2650, 1626])
+        for category_id in (1626, 707, 118, 2650):
+            category = self.terms.hpo(category_id)
+            expected = {
+                term for term in hposet
+                if term == category or term.child_of(category)
+            }
+            self.assertEqual(set(hposet.filter_category(category)), expected)
+            self.assertEqual(set(hposet.filter_category(category_id)), expected)
+
+        self.assertEqual(len(hposet.filter_category(self.terms.hpo(5))), 0)
+        # The original set is not modified
+        self.assertEqual(len(hposet), 5)
+        with self.assertRaises(KeyError):
+            hposet.filter_category(99999999)
+
+    def test_map_to_level(self):
+        hposet = HPOSet.from_queries([1631, 1250, 30148, 2650, 118])
+        for level in (0, 1, 2, 3, 5):
+            expected = set()
+            for term in hposet:
+                if term.shortest_path_to_root() <= level:
+                    expected.add(term)
+                else:
+                    expected |= {
+                        parent for parent in term.all_parents
+                        if parent.shortest_path_to_root() == level
+                    }
+            mapped = hposet.map_to_level(level)
+            self.assertEqual(set(mapped), expected)
+            for term in mapped:
+                self.assertLessEqual(term.shortest_path_to_root(), level)
+
+        self.assertEqual(set(hposet.map_to_level(0)), {self.terms.root})
+        self.assertEqual(
+            set(hposet.map_to_level(2)),
+            {self.terms.hpo(118), self.terms.hpo(707), self.terms.hpo(1626), self.terms.hpo(33127)}
+        )
+
+    def test_map_to_slim(self):
+        hposet = HPOSet.from_queries([1631, 1250, 30148, 2650, 5])
+        slim = [self.terms.hpo(term_id) for term_id in (1626, 707, 1627, 1250)]
+
+        expected = set()
+        unmapped = set()
+        for term in hposet:
+            if term in slim:
+                expected.add(term)
+                continue
+            distances = {
+                ancestor: term.shortest_path_to_parent(ancestor)[0]
+                for ancestor in slim if term.child_of(ancestor)
+            }
+            if not distances:
+                unmapped.add(term)
+                continue
+            closest = min(distances.values())
+            expected |= {ancestor for ancestor, dist in distances.items() if dist == closest}
+
+        self.assertGreater(len(unmapped), 0)
+        self.assertEqual(set(hposet.map_to_slim(slim)), expected)
+        self.assertEqual(set(hposet.map_to_slim([int(t) for t in slim])), expected)
+        self.assertEqual(set(hposet.map_to_slim(HPOSet(slim))), expected)
+        self.assertEqual(set(hposet.map_to_slim(slim, keep_unmapped=True)), expected | unmapped)
+        # 1631 is mapped to the closer 1627, not to 1626
+        self.assertEqual(
+            set(HPOSet.from_queries([1631]).map_to_slim(slim)), {self.terms.hpo(1627)}
+        )
+
+        self.assertEqual(len(hposet.map_to_slim([])), 0)
+        with self.assertRaises(KeyError):
+            hposet.map_to_slim([99999999])
+
+    def test_set_from_buffer(self):
+        expected = HPOSet([118, 2650, 11968]).serialize()
+        for typecode in ("q", "Q", "l", "i", "I"):
+            ids = array.array(typecode, [2650, 118, 11968, 118])
+            self.assertEqual(HPOSet(ids).serialize(), expected)
+            self.assertEqual(HPOSet(memoryview(ids)).serialize(), expected)
+        self.assertEqual(len(HPOSet(array.array("q"))), 0)
+
+        with self.assertRaises(KeyError):
+            HPOSet(array.array("q", [118, 9999999]))
+        with self.assertRaises(ValueError):
+            HPOSet(array.array("q", [118, -1]))
+        with self.assertRaises(ValueError):
+            HPOSet(memoryview(array.array("q", [118, 2650])).cast("B").cast("q", [1, 2]))
+        with self.assertRaises(TypeError):
+            HPOSet(array.array("d", [118.0]))
+
+    def test_set_from_series(self):
+        class NAType:
+            """Mimics pandas.NA"""
+
+        series = [
+            " HP:0002650", None, float("nan"), "", NAType(),
+            "Feeding difficulties", 118, self.terms.hpo(7)
+        ]
+        self.assertEqual(
+            HPOSet.from_series(series).serialize(),
+            HPOSet([7, 118, 2650, 11968]).serialize()
+        )
+        self.assertEqual(
+            HPOSet.from_series(iter(series)).serialize(),
+            HPOSet([7, 118, 2650, 11968]).serialize()
+        )
+
+        with self.assertRaises(RuntimeError):
+            HPOSet.from_series(["HP:0002650", "Unknown term"])
+        with self.assertRaises(TypeError):
+            HPOSet.from_series(["HP:0002650", 1.5])
+        self.assertEqual(
+            len(HPOSet.from_series(["HP:0002650", "Unknown term", 1.5], skip_invalid=True)),
+            1
+        )
+
+    def test_verbose_json(self):
+        term = self.terms.hpo(265)
+        data = term.toJSON(verbose=True)
+        self.assertEqual(data["is_a"], term._is_a)
+        # The builtin Ontology does not contain synonyms and definitions
+        self.assertEqual(
+            (data["synonym"], data["definition"], data["comment"], data["xref"]),
+            ([], "", "", [])
+        )
+        self.assertEqual(
+            data["ic"]["orpha"],
+            term.information_content.orpha
+        )
+
+        hposet = HPOSet.from_queries([265, 2650])
+        self.assertEqual(
+            hposet.toJSON(verbose=True),
+            [term.toJSON(verbose=True) for term in hposet]
+        )
+        self.assertEqual(
+            json.loads(hposet.to_json_str(verbose=True)),
+            hposet.toJSON(verbose=True)
+        )
+
+    def test_load_phenopackets(self):
+        def phenopacket(subject, *features):
+            return {
+                "id": f"packet-{subject}",
+                "subject": {"id": subject},
+                "phenotypicFeatures": [
+                    {"type": {"id": term, "label": "..."}, "excluded": excluded}
+                    for term, excluded in features
+                ]
+            }
+
+        cohort = {
+            "id": "cohort",
+            "members": [
+                phenopacket("P1", ("HP:0002650", False), ("HP:0000118", True)),
+                phenopacket("P2", ("HP:0001166", False), ("HP:0001250", False)),
+            ]
+        }
+        family = {
+            "id": "family",
+            "proband": phenopacket("P3", ("HP:0001166", False)),
+            "relatives": [phenopacket("P4", ("HP:9999999", False))],
+        }
+
+        subjects = helper.load_phenopackets(json.dumps(cohort).encode())
+        self.assertEqual([subject for subject, _ in subjects], ["P1", "P2"])
+        self.assertEqual(subjects[0][1].serialize(), "2650")
+        self.assertEqual(subjects[1][1].serialize(), "1166+1250")
+
+        with self.assertRaises(KeyError):
+            helper.load_phenopackets(json.dumps(family).encode())
+        with self.assertRaises(ValueError):
+            helper.load_phenopackets(b"[1, 2, 3]")
+
+        with tempfile.TemporaryDirectory() as tmpdir:
+            for name, message in [("a.json", cohort), ("b.json", family)]:
+                with open(os.path.join(tmpdir, name), "w") as fh:
+                    json.dump(message, fh)
+            with open(os.path.join(tmpdir, "README.txt"), "w") as fh:
+                fh.write("not a phenopacket")
+
+            with open(os.path.join(tmpdir, "b.json")) as fh:
+                self.assertEqual(
+                    len(helper.load_phenopackets(fh, skip_invalid=True)),
+                    2
+                )
+            subjects = helper.load_phenopackets(tmpdir, skip_invalid=True)
+
+        self.assertEqual(
+            [(subject, len(hpo_set)) for subject, hpo_set in subjects],
+            [("P1", 1), ("P2", 2), ("P3", 1), ("P4", 0)]
+        )
+
+    def test_fhir(self):
+        system = "http://purl.obolibrary.org/obo/hp.owl"
+        term = self.terms.hpo(2650)
+        coding = term.to_fhir_coding()
+        self.assertEqual(
+            coding,
+            {"system": system, "code": "HP:0002650", "display": "Scoliosis"}
+        )
+        self.assertEqual(
+            term.to_fhir_codeable_concept(),
+            {"coding": [coding], "text": "Scoliosis"}
+        )
+        self.assertEqual(self.terms.hpo_from_fhir(coding), term)
+        self.assertEqual(
+            self.terms.hpo_from_fhir({"coding": [
+                {"system": "http://snomed.info/sct", "code": "298382003"},
+                coding,
+            ]}),
+            term
+        )
+
+        hposet = HPOSet.from_queries([2650, 1166, 118])
+        concepts = hposet.to_fhir()
+        self.assertEqual(len(concepts), 3)
+        self.assertEqual(
+            HPOSet.from_fhir(json.loads(json.dumps(concepts))).serialize(),
+            hposet.serialize()
+        )
+
+        with self.assertRaises(ValueError):
+            self.terms.hpo_from_fhir({"system": "http://snomed.info/sct", "code": "1"})
+        with self.assertRaises(ValueError):
+            self.terms.hpo_from_fhir({"system": system, "code": "Scoliosis"})
+        with self.assertRaises(KeyError):
+            self.terms.hpo_from_fhir({"system": system, "code": "HP:9999999"})
+        with self.assertRaises(TypeError):
+            self.terms.hpo_from_fhir("HP:0002650")
+        self.assertEqual(
+            HPOSet.from_fhir(
+                concepts + [{"system": system, "code": "HP:9999999"}],
+                skip_invalid=True
+            ).serialize(),
+            hposet.serialize()
+        )
+
+    def test_patient(self):
+        hposet = HPOSet.from_queries([2650, 1166, 1083])
+        patient = Patient("P1", hposet, {"sex": "female"})
+        self.assertEqual(patient.id, "P1")
+        self.assertEqual(len(patient), 3)
+        self.assertEqual(patient.metadata, {"sex": "female"})
+        patient.metadata["age"] = 12
+        self.assertEqual(patient.metadata["age"], 12)
+        self.assertEqual(Patient("P2", hposet).metadata, {})
+
+        other = Patient("P2", HPOSet.from_queries([7, 1743]))
+        self.assertEqual(patient.similarity(other), hposet.similarity(other.hpo_set))
+
+        diseases = patient.rank_diseases(limit=5)
+        self.assertEqual(len(diseases), 5)
+        scores = [score for _, score in diseases]
+        self.assertEqual(scores, sorted(scores, reverse=True))
+        omim = list(self.terms.omim_diseases)
+        best = max(
+            hposet.similarity_scores([disease.hpo_set() for disease in omim])
+        )
+        self.assertEqual(scores[0], best)
+        self.assertIsInstance(patient.rank_diseases("orpha", limit=1)[0][0], an.Orpha)
+        with self.assertRaises(ValueError):
+            patient.rank_diseases("decipher")
+
+        genes = patient.rank_genes(limit=None)
+        self.assertEqual(len(genes), len(self.terms.genes))
+        self.assertIsInstance(genes[0][0], an.Gene)
+
+        restored = pickle.loads(pickle.dumps(patient))
+        self.assertEqual(restored.id, "P1")
+        self.assertEqual(restored.hpo_set.serialize(), hposet.serialize())
+        self.assertEqual(restored.metadata, {"sex": "female", "age": 12})
+
+    def test_cohort(self):
+        sets = [
+            HPOSet.from_queries([2650, 1166, 1083]),
+            HPOSet.from_queries([2650, 7]),
+            HPOSet.from_queries([7, 1743]),
+        ]
+        cohort = Cohort([
+            Patient("P1", sets[0], {"sex": "female"}),
+            ("P2", sets[1]),
+            ("P3", sets[2]),
+        ])
+        self.assertEqual(len(cohort), 3)
+        self.assertEqual(cohort.ids, ["P1", "P2", "P3"])
+        self.assertEqual(cohort["P1"].metadata, {"sex": "female"})
+        self.assertEqual(cohort[-1].id, "P3")
+        self.assertEqual([patient.id for patient in cohort], cohort.ids)
+        with self.assertRaises(KeyError):
+            cohort["P4"]
+        with self.assertRaises(IndexError):
+            cohort[3]
+        with self.assertRaises(TypeError):
+            Cohort([sets[0]])
+
+        matrix = cohort.similarity_matrix()
+        for a, set_a in enumerate(sets):
+            for b, set_b in enumerate(sets):
+                self.assertEqual(matrix[a][b], set_a.similarity(set_b))
+
+        prevalence = cohort.term_prevalence()
+        self.assertEqual(prevalence[Ontology.hpo(2650)], 2)
+        self.assertEqual(prevalence[Ontology.hpo(1743)], 1)
+        self.assertNotIn(Ontology.hpo(1), prevalence)
+        self.assertEqual(cohort.term_prevalence(inherited=True)[Ontology.hpo(1)], 3)
+
+        controls = Cohort([
+            ("C1", HPOSet.from_queries([7, 1743])),
+            ("C2", HPOSet.from_queries([1743])),
+        ])
+        enrichment = cohort.enrichment(controls)
+        self.assertEqual(enrichment[0]["rank"], 1)
+        results = {result["item"]: result for result in enrichment}
+        self.assertEqual(results[Ontology.hpo(2650)]["count"], 2)
+        self.assertEqual(results[Ontology.hpo(2650)]["other_count"], 0)
+        self.assertAlmostEqual(results[Ontology.hpo(2650)]["enrichment"], 0.3)
+        self.assertAlmostEqual(results[Ontology.hpo(2650)]["fold"], 5 / 3)
+        self.assertEqual(results[Ontology.hpo(1)]["enrichment"], 1.0)
+        with self.assertRaises(ValueError):
+            cohort.enrichment(controls, sort_by="name")
+
+        restored = pickle.loads(pickle.dumps(cohort))
+        self.assertEqual(restored.ids, cohort.ids)
+        self.assertEqual(restored["P1"].metadata, {"sex": "female"})
+
+        calls = []
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "similarity.csv")
+            written = cohort.similarity_to_file(
+                path,
+                progress=lambda done, total: calls.append((done, total)),
+                chunk_size=1,
+            )
+            self.assertEqual(written, 3)
+            self.assertEqual(calls, [(2, 3), (3, 3)])
+            with open(path) as fh:
+                lines = fh.read().splitlines()
+            self.assertEqual(lines[0], "id_a,id_b,similarity")
+            self.assertEqual(
+                [line.split(",")[:2] for line in lines[1:]],
+                [["P1", "P2"], ["P1", "P3"], ["P2", "P3"]]
+            )
+            self.assertAlmostEqual(float(lines[1].split(",")[2]), matrix[0][1])
+
+            path = os.path.join(tmp, "similarity.npy")
+            cohort.similarity_to_file(path)
+            with open(path, "rb") as fh:
+                data = fh.read()
+            self.assertTrue(data.startswith(b"\x93NUMPY"))
+            self.assertIn(b"'shape': (3,)", data[:128])
+            self.assertEqual(
+                list(array.array("f", data[128:])),
+                [matrix[0][1], matrix[0][2], matrix[1][2]]
+            )
+            with self.assertRaises(ValueError):
+                cohort.similarity_to_file(path, format="xlsx")
+
+        try:
+            import pandas  # noqa: F401
+        except ImportError:
+            return
+        df = cohort.to_dataframe()
+        self.assertEqual(list(df["id"]), cohort.ids)
+        self.assertEqual(list(df["n_terms"]), [3, 2, 2])
+
+    def test_versioned_serialization(self):
+        hposet = HPOSet.from_queries([118, 2650])
+        serialized = hposet.serialize(include_version=True)
+        self.assertEqual(
+            serialized,
+            f"{Ontology.version()}:{hposet.serialize()}"
+        )
+        self.assertEqual(
+            HPOSet.from_serialized(serialized).serialize(),
+            hposet.serialize()
+        )
+
+        outdated = "1999-01-01:" + hposet.serialize()
+        with self.assertWarns(UserWarning):
+            HPOSet.from_serialized(outdated)
+        with self.assertRaises(ValueError):
+            HPOSet.from_serialized(outdated, on_version_mismatch="raise")
+        self.assertEqual(
+            HPOSet.from_serialized(
+                outdated, on_version_mismatch="ignore"
+            ).serialize(),
+            hposet.serialize()
+        )
+
+    def test_compact_serialization(self):
+        for gene in list(self.terms.genes)[:100]:
+            hposet = gene.hpo_set()
+            compact = hposet.serialize_compact()
+            assert len(compact) < len(hposet.serialize())
+            self.assertEqual(
+                HPOSet.from_compact(compact).serialize(),
+                hposet.serialize()
+            )
+
+        self.assertEqual(len(HPOSet.from_compact("")), 0)
+        with self.assertRaises(ValueError):
+            HPOSet.from_compact("!!")
+
+    def test_to_obo(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = os.path.join(tmpdir, "hp.obo")
+            Ontology.to_obo(path)
+            with open(path) as fh:
+                stanzas = fh.read().split("\n\n")
+
+        self.assertEqual(
+            stanzas[0],
+            f"format-version: 1.2\ndata-version: hp/releases/{Ontology.version()}\nontology: hp"
+        )
+        # header and trailing empty line
+        self.assertEqual(len(stanzas) - 2, len(Ontology))
+        self.assertIn(
+            "[Term]\nid: HP:0000057\nname: obsolete Clitoromegaly\n"
+            "is_obsolete: true\nreplaced_by: HP:0008665",
+            stanzas
+        )
+        self.assertIn(
+            "[Term]\nid: HP:0000002\nname: Abnormality of body height\n"
+            "is_a: HP:0001507 ! Growth abnormality",
+            stanzas
+        )
+
+    def test_to_owl(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            turtle = os.path.join(tmpdir, "hp.ttl")
+            functional = os.path.join(tmpdir, "hp.ofn")
+            Ontology.to_owl(turtle)
+            Ontology.to_owl(functional)
+            with open(turtle) as fh:
+                turtle = fh.read()
+            with open(functional) as fh:
+                functional = fh.read()
+            with self.assertRaises(ValueError):
+                Ontology.to_owl(os.path.join(tmpdir, "hp.owl"), syntax="rdfxml")
+
+        self.assertIn(
+            "obo:HP_0000057 a owl:Class ;\n"
+            "    rdfs:label \"obsolete Clitoromegaly\" ;\n"
+            "    owl:deprecated \"true\"^^xsd:boolean ;\n"
+            "    obo:IAO_0100001 obo:HP_0008665 .\n",
+            turtle
+        )
+        self.assertEqual(turtle.count(" a owl:Class"), len(Ontology))
+
+        self.assertIn("SubClassOf(obo:HP_0000002 obo:HP_0001507)\n", functional)
+        self.assertEqual(functional.count("Declaration(Class("), len(Ontology))
+        self.assertTrue(functional.endswith(")\n"))
+
+    def test_pickle(self):
+        term = self.terms.hpo(11968)
+        self.assertEqual(pickle.loads(pickle.dumps(term)), term)
+        gene = self.terms.genes[0]
+        self.assertEqual(pickle.loads(pickle.dumps(gene)), gene)
+        disease = self.terms.omim_diseases[0]
+        self.assertEqual(pickle.loads(pickle.dumps(disease)), disease)
+        hposet = gene.hpo_set()
+        self.assertEqual(
+            pickle.loads(pickle.dumps(hposet)).serialize(),
+            hposet.serialize()
+        )
+        self.assertIs(pickle.loads(pickle.dumps(self.terms)), self.terms)
+
+    def test_multiprocessing(self):
+        sets = [
+            (HPOSet.from_queries([11968, 1743]), HPOSet.from_queries([118, 2650])),
+            (HPOSet.from_queries([7, 118]), HPOSet([])),
+        ]
+        # The global thread pool must be started before forking
+        expected = helper.batch_set_similarity(sets)
+
+        with multiprocessing.get_context("fork").Pool(2) as pool:
+            self.assertEqual(pool.map(_worker_similarity, sets), expected)
+
+        self.assertEqual(self.terms.build_kwargs()["data_folder"], None)
+        ctx = multiprocessing.get_context("spawn")
+        with ctx.Pool(
+            1,
+            initializer=helper.init_worker,
+            initargs=(self.terms.build_kwargs(),),
+        ) as pool:
+            self.assertEqual(pool.map(_worker_similarity, sets), expected)
+
+    def test_threads(self):
+        gene_sets = [gene.hpo_set() for gene in self.terms.genes[:20]]
+        comparisons = [(a, b) for a in gene_sets for b in gene_sets]
+        expected = helper.batch_set_similarity(comparisons)
+
+        with ThreadPoolExecutor(4) as executor:
+            results = list(executor.map(
+                lambda _: helper.batch_set_similarity(comparisons),
+                range(4)
+            ))
+            scores = list(executor.map(
+                lambda a: a.similarity_scores(gene_sets),
+                gene_sets
+            ))
+        for result in results:
+            self.assertEqual(result, expected)
+        self.assertEqual(
+            [score for row in scores for score in row],
+            expected
+        )
+
+    def test_term_array(self):
+        terms = TermArray([118, "HP:0002650", None, self.terms.hpo(11968)])
+        self.assertEqual(len(terms), 4)
+        self.assertEqual(terms.ids(), [118, 2650, None, 11968])
+        self.assertEqual(terms.isna(), [False, False, True, False])
+        self.assertEqual(terms[1], self.terms.hpo(2650))
+        self.assertIsNone(terms[2])
+        self.assertEqual(
+            terms.names(),
+            ["Phenotypic abnormality", "Scoliosis", None, "Feeding difficulties"]
+        )
+        self.assertEqual(
+            terms.depths()[:3],
+            [1, self.terms.hpo(2650).shortest_path_to_root(), None]
+        )
+
+        ic = terms.information_content("gene")
+        self.assertEqual(ic[1], self.terms.hpo(2650).information_content.gene)
+        assert math.isnan(ic[2])
+
+        self.assertEqual(
+            terms.take([3, -1], allow_fill=True, fill_value=118).ids(),
+            [11968, 118]
+        )
+        self.assertEqual(TermArray.concat([terms, terms]).ids()[4:], terms.ids())
+        with self.assertRaises(IndexError):
+            terms[4]
+
+    def test_resolve(self):
+        terms, errors = helper.resolve(
+            [118, "HP:0002650", "Scoliosis", "Foo", "HP:abc", 1.5]
+        )
+        self.assertEqual(
+            terms[:3],
+            [self.terms.hpo(118), self.terms.hpo(2650), self.terms.hpo(2650)]
+        )
+        self.assertEqual(terms[3:], [None, None, None])
+        self.assertEqual([idx for idx, _ in errors], [3, 4, 5])
+
+        names = [term.name for term in self.terms]
+        terms, errors = helper.resolve(names)
+        self.assertEqual(errors, [])
+        self.assertEqual([term.name for term in terms], names)
+
+    def test_dendrogram(self):
+        sets = [gene.hpo_set() for gene in list(self.terms.genes)[:10]]
+        dendrogram = linkage(sets)
+        self.assertEqual(dendrogram.n_observations, 10)
+        self.assertEqual(len(dendrogram), 9)
+        self.assertEqual(list(dendrogram), dendrogram.clusters)
+        self.assertEqual(dendrogram[0], dendrogram.clusters[0])
+        self.assertEqual(
+            dendrogram.distances,
+            [cluster[2] for cluster in dendrogram.clusters]
+        )
+        self.assertEqual(dendrogram.members(8), list(range(10)))
+        for idx, (_, _, _, size) in enumerate(dendrogram):
+            self.assertEqual(len(dendrogram.members(idx)), size)
+        with self.assertRaises(IndexError):
+            dendrogram.members(9)
+
+    def test_custom_ic_linkage(self):
+        sets = [
+            HPOSet.from_queries(queries)
+            for queries in ([2650, 1166], [2651, 1250], [1631], [2650, 1631])
+        ]
+        for hposet in sets:
+            for term in hposet:
+                for ancestor in term.all_parents | {term}:
+                    ancestor.set_custom_ic(float(ancestor.shortest_path_to_root()))
+
+        dendrogram = linkage(sets, kind="custom", similarity_method="lin", deterministic=True)
+        similarity = {
+            (i, j): sets[i].similarity(sets[j], kind="custom", method="lin")
+            for i in range(len(sets)) for j in range(len(sets)) if i != j
+        }
+
+        # Single linkage merges the closest clusters first,
+        # the distance is `1 - similarity` of the closest members
+        clusters = {idx: {idx} for idx in range(len(sets))}
+        for left, right, distance, size in dendrogram:
+            expected = 1 - max(
+                similarity[(i, j)] for i in clusters[left] for j in clusters[right]
+            )
+            self.assertAlmostEqual(distance, expected, places=6)
+            clusters[len(clusters)] = clusters[left] | clusters[right]
+            self.assertEqual(size, len(clusters[len(clusters) - 1]))
+
+        # The custom information content changes the clustering
+        self.assertNotEqual(
+            dendrogram.distances,
+            linkage(sets, kind="omim", similarity_method="lin", deterministic=True).distances
+        )
+        with self.assertRaises(KeyError):
+            linkage(sets, kind="foo", similarity_method="lin")
+
+    def test_deterministic_linkage(self):
+        genes = [gene.hpo_set() for gene in list(self.terms.genes)[:12]]
+        for method in ("single", "union", "complete", "average"):
+            default = linkage(genes, method=method)
+            ordered = linkage(genes, method=method, deterministic=True)
+            self.assertEqual(ordered.n_observations, 12)
+            self.assertEqual(len(ordered), 11)
+            self.assertEqual(ordered.members(10), list(range(12)))
+            if len(set(default.distances)) == len(default):
+                self.assertEqual(ordered.clusters, default.clusters)
+
+        # Identical sets have the same distance to all other sets
+        sets = [genes[0], genes[1], genes[0], genes[1], genes[0], genes[2]]
+        for method in ("single", "union", "complete", "average"):
+            expected = linkage(sets, method=method, deterministic=True)
+            self.assertEqual(expected[0][:2], (0, 2))
+            for _ in range(10):
+                self.assertEqual(
+                    linkage(sets, method=method, deterministic=True).clusters,
+                    expected.clusters
+                )
+            if method == "single":
+                # The distances of single linkage do not depend on the order
+                self.assertEqual(
+                    sorted(linkage(sets, method=method).distances),
+                    sorted(expected.distances)
+                )
+
+        with self.assertRaises(RuntimeError):
+            linkage(sets, method="ward", deterministic=True)
+
+    def test_double_precision(self):
+        sets = [gene.hpo_set() for gene in list(self.terms.genes)[:8]]
+        for combine in ("funSimAvg", "funSimMax", "BMA"):
+            self.assertAlmostEqual(
+                sets[0].similarity(sets[1], combine=combine),
+                sets[0].similarity(sets[1], combine=combine, dtype="float64"),
+                places=5
+            )
+        self.assertEqual(sets[0].similarity(HPOSet([]), dtype="float64"), 0.0)
+
+        scores = sets[0].similarity_scores(sets, dtype="float64")
+        self.assertEqual(scores[0], 1.0)
+        self.assertEqual(scores[1], sets[0].similarity(sets[1], dtype="float64"))
+
+        comparisons = [(sets[0], other) for other in sets]
+        self.assertEqual(
+            helper.batch_set_similarity(comparisons, dtype="float64"),
+            scores
+        )
+        buffer = helper.batch_set_similarity(
+            comparisons, output="buffer", dtype="float64"
+        )
+        self.assertEqual(buffer.dtype, "float64")
+        self.assertEqual(buffer.tolist(), scores)
+        multi = helper.batch_set_similarity(
+            comparisons, methods=["graphic", "lin"], dtype="float64"
+        )
+        self.assertEqual(multi["graphic"], scores)
+
+        for method in ("single", "union", "complete", "average"):
+            double = linkage(sets, method=method, dtype="float64")
+            # float64 always merges clusters in a fixed order
+            self.assertEqual(
+                list(double),
+                list(linkage(sets, method=method, dtype="float64", deterministic=True))
+            )
+            single = linkage(sets, method=method, deterministic=True)
+            self.assertEqual(double.members(6), list(range(8)))
+            for a, b in zip(double.distances, single.distances):
+                self.assertAlmostEqual(a, b, places=5)
+
+        with self.assertRaises(ValueError):
+            sets[0].similarity(sets[1], dtype="float16")
+        with self.assertRaises(ValueError):
+            helper.batch_set_similarity(comparisons, dtype="float16")
+        with self.assertRaises(ValueError):
+            linkage(sets, dtype="float16")
+
+    def test_fcluster(self):
+        sets = [gene.hpo_set() for gene in list(self.terms.genes)[:10]]
+        for method in ("single", "union", "complete", "average"):
+            dendrogram = linkage(sets, method=method)
+            self.assertEqual(dendrogram.fcluster(k=1), [1] * 10)
+            self.assertEqual(dendrogram.fcluster(k=20), list(range(1, 11)))
+            for k in range(1, 11):
+                self.assertEqual(len(set(dendrogram.fcluster(k=k))), k)
+
+            threshold = dendrogram.distances[3]
+            labels = dendrogram.fcluster(threshold=threshold)
+            for idx, (_, _, distance, _) in enumerate(dendrogram):
+                members = {labels[i] for i in dendrogram.members(idx)}
+                if distance <= threshold and method != "union":
+                    self.assertEqual(len(members), 1)
+
+        with self.assertRaises(ValueError):
+            dendrogram.fcluster()
+        with self.assertRaises(ValueError):
+            dendrogram.fcluster(threshold=0.5, k=2)
+
+    def test_batch_set_similarity_methods(self):
+        sets = [gene.hpo_set() for gene in list(self.terms.genes)[:6]]
+        comparisons = [(a, b) for a in sets for b in sets]
+        methods = ["resnik", "lin", "graphic", "jc"]
+
+        scores = helper.batch_set_similarity(comparisons, methods=methods)
+        self.assertEqual(list(scores), methods)
+        for method in methods:
+            self.assertEqual(
+                scores[method],
+                helper.batch_set_similarity(comparisons, method=method)
+            )
+
+        buffers = helper.batch_set_similarity(
+            comparisons, methods=["resnik", "lin"], output="buffer"
+        )
+        self.assertEqual(list(buffers), ["resnik", "lin"])
+        self.assertEqual(buffers["lin"].tolist(), scores["lin"])
+
+        with self.assertRaises(RuntimeError):
+            helper.batch_set_similarity(comparisons, methods=["lin", "foo"])
+
+    def test_batch_set_similarity_chunks(self):
+        sets = [gene.hpo_set() for gene in list(self.terms.genes)[:10]]
+        comparisons = [(a, b) for a in sets for b in sets]
+        expected = helper.batch_set_similarity(comparisons)
+
+        chunks = list(helper.batch_set_similarity_chunks(
+            iter(comparisons), chunk_size=30, output="list"
+        ))
+        self.assertEqual([len(chunk) for chunk in chunks], [30, 30, 30, 10])
+        self.assertEqual(sum(chunks, []), expected)
+
+        chunks = list(helper.batch_set_similarity_chunks(
+            [(sets[0], sets[1]), (sets[0], None), (sets[1], sets[2])],
+            chunk_size=2,
+            output="list",
+            errors="collect"
+        ))
+        self.assertEqual([idx for idx, _ in chunks[0][1]], [1])
+        self.assertEqual(chunks[1][1], [])
+
+        with self.assertRaises(ValueError):
+            helper.batch_set_similarity_chunks(comparisons, chunk_size=0)
+
+    def test_similarity_kernel(self):
+        sets = [gene.hpo_set() for gene in list(self.terms.genes)[:4]]
+        kernel = SimilarityKernel(sets, output="list")
+        self.assertEqual(kernel.n_sets, 4)
+
+        gram = kernel([[0], [1], [2], [3]])
+        for a, set_a in enumerate(sets):
+            for b, set_b in enumerate(sets):
+                self.assertAlmostEqual(gram[a][b], set_a.similarity(set_b), places=6)
+
+        self.assertEqual(kernel([0.0, 1.0], [3]), [[gram[0][3]], [gram[1][3]]])
+        self.assertEqual(
+            kernel([sets[2].serialize()], [sets[1]]),
+            [[gram[2][1]]]
+        )
+        self.assertEqual(kernel([]), [])
+
+        with self.assertRaises(IndexError):
+            kernel([4])
+        with self.assertRaises(ValueError):
+            kernel([0.5])
+        with self.assertRaises(TypeError):
+            kernel([None])
+        with self.assertRaises(ValueError):
+            SimilarityKernel(output="matrix")
+
+        cohort = Cohort([(str(idx), s) for idx, s in enumerate(sets)])
+        self.assertEqual(
+            SimilarityKernel(cohort, output="list")([[0], [1], [2], [3]]),
+            gram
+        )
+        restored = pickle.loads(pickle.dumps(kernel))
+        self.assertEqual(restored([[1], [2]]), kernel([[1], [2]]))
+
+    def test_batch_output_path(self):
+        sets = [gene.hpo_set() for gene in list(self.terms.genes)[:5]]
+        comparisons = [(a, b) for a in sets for b in sets]
+        expected = helper.batch_set_similarity(comparisons)
+
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "similarity.csv")
+            written = helper.batch_set_similarity(comparisons, output_path=path)
+            self.assertEqual(written, len(comparisons))
+            with open(path) as fh:
+                lines = fh.read().splitlines()
+            self.assertEqual(lines[0], "index,similarity")
+            self.assertEqual(
+                [float(line.split(",")[1]) for line in lines[1:]],
+                expected
+            )
+
+            path = os.path.join(tmp, "similarity.npy")
+            written, errors = helper.batch_set_similarity(
+                comparisons + [(sets[0], None)],
+                methods=["resnik", "graphic"],
+                errors="collect",
+                output_path=path
+            )
+            self.assertEqual(written, len(comparisons) + 1)
+            self.assertEqual([idx for idx, _ in errors], [len(comparisons)])
+            with open(path, "rb") as fh:
+                data = fh.read()
+            self.assertIn(f"'shape': ({written}, 2)".encode(), data[:128])
+            scores = array.array("f", data[128:])
+            self.assertEqual(list(scores[1::2][:-1]), expected)
+            self.assertTrue(math.isnan(scores[-1]))
+
+            path = os.path.join(tmp, "enrichment.csv")
+            enrichment = helper.batch_gene_enrichment(sets[:2])
+            written = helper.batch_gene_enrichment(sets[:2], output_path=path)
+            self.assertEqual(written, sum(len(result) for result in enrichment))
+            with open(path) as fh:
+                lines = fh.read().splitlines()
+            self.assertEqual(
+                lines[0], "set,rank,id,name,enrichment,log10_pvalue,qvalue,log10_qvalue,fold,count"
+            )
+            first = lines[1].split(",")
+            self.assertEqual(first[:2], ["0", "1"])
+            self.assertEqual(first[3], enrichment[0][0]["item"].name)
+            self.assertEqual(float(first[4]), enrichment[0][0]["enrichment"])
+            self.assertEqual(lines[-1].split(",")[0], "1")
+
+            self.assertIsInstance(
+                helper.batch_omim_disease_enrichment(
+                    sets[:2], output_path=os.path.join(tmp, "omim.txt")
+                ),
+                int
+            )
+            with self.assertRaises(ValueError):
+                helper.batch_orpha_disease_enrichment(
+                    sets[:2], output_path=path, format="xlsx"
+                )
+
+    def test_similarity_cache(self):
+        sets = [gene.hpo_set() for gene in list(self.terms.genes)[:5]]
+        comparisons = [(a, b) for a in sets for b in sets]
+        expected = helper.batch_set_similarity(comparisons)
+
+        cache = helper.similarity_cache
+        cache.clear()
+        cache.enable()
+        try:
+            self.assertEqual(helper.batch_set_similarity(comparisons), expected)
+            misses = cache.stats()["misses"]
+            self.assertEqual(cache.stats()["size"], misses)
+
+            self.assertEqual(helper.batch_set_similarity(comparisons), expected)
+            self.assertEqual(cache.stats()["misses"], misses)
+            self.assertGreater(cache.stats()["hits"], 0)
+
+            cache.clear()
+            self.assertEqual(cache.stats()["size"], 0)
+            cache.enable(max_size=10)
+            sets[0].similarity(sets[1])
+            self.assertEqual(cache.stats()["size"], 10)
+        finally:
+            cache.disable()
+            cache.clear()
+        self.assertFalse(cache.enabled)
+
     def test_gene_enrichment(self):
         hposet = HPOSet.from_queries('HP:0007401,HP:0010885'.split(','))
         gene_model = EnrichmentModel('gene')
@@ -151,6 +1530,11 @@ class IntegrationFullTest(unittest.TestCase):
         self.assertIsInstance(res[0]['item'], an.Gene)
         self.assertIsInstance(res[0]['count'], int)
         self.assertIsInstance(res[0]['enrichment'], float)
+        self.assertEqual([r['rank'] for r in res], list(range(1, len(res) + 1)))
+        for result in res:
+            self.assertGreaterEqual(result['log10_qvalue'], result['log10_pvalue'])
+            self.assertLessEqual(result['log10_qvalue'], 0.0)
+            self.assertLessEqual(result['qvalue'], 1.0)
 
     def test_omim_enrichment(self):
         hposet = HPOSet.from_queries('HP:0007401,HP:0010885'.split(','))
@@ -175,3 +1559,1521 @@ class IntegrationFullTest(unittest.TestCase):
         self.assertIsInstance(res[0]["item"], an.Orpha)
         self.assertIsInstance(res[0]["count"], int)
         self.assertIsInstance(res[0]["enrichment"], float)
+
+    def test_enrichment_tie_breaking(self):
+        hposet = HPOSet.from_queries([2650, 1166, 1631, 2651, 1250])
+        batches = {
+            'gene': helper.batch_gene_enrichment,
+            'omim': helper.batch_omim_disease_enrichment,
+            'orpha': helper.batch_orpha_disease_enrichment,
+        }
+        for kind, batch in batches.items():
+            res = EnrichmentModel(kind).enrichment('hypergeom', hposet)
+            keys = [
+                (r['log10_pvalue'], -r['fold'], r['item'].id)
+                for r in res
+            ]
+            # Many results share the same p-value and fold enrichment
+            self.assertGreater(
+                sum(a[:2] == b[:2] for a, b in zip(keys, keys[1:])),
+                0
+            )
+            self.assertEqual(keys, sorted(keys))
+            self.assertEqual(
+                EnrichmentModel(kind).enrichment('hypergeom', hposet),
+                res
+            )
+            self.assertEqual(batch([hposet, hposet]), [res, res])
+
+    def test_enrichment_sort_by(self):
+        hposet = HPOSet.from_queries([2650, 1166, 1631, 2651, 1250])
+        model = EnrichmentModel('omim')
+        by_pvalue = model.enrichment('hypergeom', hposet)
+        self.assertEqual(
+            model.enrichment('hypergeom', hposet, sort_by='pvalue'),
+            by_pvalue
+        )
+        ranks = {r['item'].id: r['rank'] for r in by_pvalue}
+
+        for sort_by, key in (('fold', 'fold'), ('count', 'count')):
+            res = model.enrichment('hypergeom', hposet, sort_by=sort_by)
+            self.assertEqual(len(res), len(by_pvalue))
+            # Descending by the sort key, ties keep their p-value order
+            self.assertEqual(
+                [(-r[key], r['rank']) for r in res],
+                sorted((-r[key], r['rank']) for r in res)
+            )
+            # The rank is the p-value rank, independent of the order
+            for r in res:
+                self.assertEqual(r['rank'], ranks[r['item'].id])
+            self.assertEqual(
+                helper.batch_omim_disease_enrichment([hposet], sort_by=sort_by),
+                [res]
+            )
+        self.assertNotEqual(
+            model.enrichment('hypergeom', hposet, sort_by='fold'),
+            model.enrichment('hypergeom', hposet, sort_by='count')
+        )
+
+        with self.assertRaises(ValueError):
+            model.enrichment('hypergeom', hposet, sort_by='foo')
+        with self.assertRaises(ValueError):
+            helper.batch_gene_enrichment([hposet], sort_by='foo')
+
+    def test_enrichment_background(self):
+        hposet = HPOSet.from_queries('HP:0007401,HP:0010885'.split(','))
+        full = EnrichmentModel('omim')
+        annotated = EnrichmentModel('omim', background='annotated')
+        self.assertEqual(full.background, 'ontology')
+        self.assertEqual(annotated.background, 'annotated')
+        self.assertEqual(full.universe_size, len(self.terms))
+        self.assertEqual(
+            annotated.universe_size,
+            sum(1 for term in self.terms if term.omim_diseases)
+        )
+        self.assertLess(annotated.universe_size, full.universe_size)
+
+        full_res = {r['item']: r for r in full.enrichment('hypergeom', hposet)}
+        annotated_res = annotated.enrichment('hypergeom', hposet)
+        self.assertEqual(set(full_res), {r['item'] for r in annotated_res})
+        for result in annotated_res:
+            self.assertGreaterEqual(
+                result['enrichment'],
+                full_res[result['item']]['enrichment']
+            )
+
+        with self.assertRaises(ValueError):
+            EnrichmentModel('gene', background='all')
+
+    def test_hypergeom(self):
+        expected = sum(
+            math.comb(20, k) * math.comb(980, 10 - k) for k in range(4, 11)
+        ) / math.comb(1000, 10)
+        self.assertAlmostEqual(hypergeom(4, 10, 20, 1000), expected, places=12)
+        self.assertEqual(hypergeom(0, 10, 20, 1000), 1.0)
+        self.assertEqual(hypergeom(11, 10, 20, 1000), 0.0)
+        with self.assertRaises(ValueError):
+            hypergeom(1, 10, 2000, 1000)
+
+        # No lookup table of the size of the population. The log-gamma
+        # approximation loses precision for such large populations
+        self.assertTrue(math.isclose(hypergeom(1, 10, 20, 10**12), 2e-10, rel_tol=1e-2))
+        self.assertEqual(hypergeom(0, 10, 20, 10**12), 1.0)
+
+        # Identical to the p-values of the enrichment
+        hposet = HPOSet.from_queries('HP:0007401,HP:0010885'.split(','))
+        model = EnrichmentModel('omim')
+        results = model.enrichment('hypergeom', hposet)
+        disease_terms = {result['item']: 0 for result in results[:5]}
+        for term in self.terms:
+            for disease in term.omim_diseases:
+                if disease in disease_terms:
+                    disease_terms[disease] += 1
+        for result in results[:5]:
+            self.assertEqual(
+                hypergeom(result['count'], len(hposet), disease_terms[result['item']], len(self.terms)),
+                result['enrichment']
+            )
+
+    def test_enrichment_methods(self):
+        hposet = HPOSet.from_queries('HP:0007401,HP:0010885'.split(','))
+        model = EnrichmentModel('gene')
+        hypergeom_res = model.enrichment('hypergeom', hposet)
+        expected = {r['item']: r for r in hypergeom_res}
+        n_terms = len(self.terms)
+        for method in ('binomial', 'chisquare'):
+            res = model.enrichment(method, hposet)
+            self.assertEqual(set(expected), {r['item'] for r in res})
+            self.assertEqual(set(res[0]), set(hypergeom_res[0]))
+            self.assertEqual([r['rank'] for r in res], list(range(1, len(res) + 1)))
+            for result in res:
+                other = expected[result['item']]
+                self.assertEqual(result['count'], other['count'])
+                self.assertAlmostEqual(result['fold'], other['fold'])
+                self.assertGreaterEqual(result['enrichment'], 0.0)
+                self.assertLessEqual(result['enrichment'], 1.0)
+
+        result = model.enrichment('binomial', hposet)[0]
+        gene_terms = sum(1 for term in self.terms if result['item'] in term.genes)
+        p = gene_terms / n_terms
+        n = len(hposet)
+        binomial = sum(
+            math.comb(n, k) * p ** k * (1 - p) ** (n - k)
+            for k in range(result['count'], n + 1)
+        )
+        self.assertTrue(math.isclose(result['enrichment'], binomial, rel_tol=1e-6))
+
+        result = model.enrichment('chisquare', hposet)[-1]
+        gene_terms = sum(1 for term in self.terms if result['item'] in term.genes)
+        z = (
+            (result['count'] * n_terms - gene_terms * n) * math.sqrt(n_terms)
+            / math.sqrt(gene_terms * (n_terms - gene_terms) * n * (n_terms - n))
+        )
+        self.assertTrue(math.isclose(
+            result['enrichment'],
+            0.5 * math.erfc(z / math.sqrt(2)),
+            rel_tol=1e-5
+        ))
+
+        with self.assertRaises(NotImplementedError):
+            model.enrichment('fisher', hposet)
+
+    def test_log10_pvalues(self):
+        gene = max(self.terms.genes, key=lambda gene: len(gene.hpo))
+        hposet = gene.hpo_set()
+        model = EnrichmentModel('gene')
+        for method in ('hypergeom', 'binomial', 'chisquare'):
+            res = model.enrichment(method, hposet)
+            log10_pvalues = [result['log10_pvalue'] for result in res]
+            self.assertEqual(log10_pvalues, sorted(log10_pvalues))
+            for result in res:
+                self.assertLessEqual(result['log10_pvalue'], 0.0)
+                if result['enrichment'] > 1e-300:
+                    self.assertTrue(math.isclose(
+                        result['log10_pvalue'],
+                        math.log10(result['enrichment']),
+                        rel_tol=1e-6,
+                        abs_tol=1e-9
+                    ))
+            # The p-value of the strongest enrichment underflows
+            self.assertEqual(res[0]['enrichment'], 0.0)
+            self.assertTrue(math.isfinite(res[0]['log10_pvalue']))
+            self.assertLess(res[0]['log10_pvalue'], -300)
+
+        # Benjamini-Hochberg q-values from the log10 p-values
+        res = model.enrichment('hypergeom', hposet)
+        tests = len(res)
+        running_min = 0.0
+        for result in reversed(res):
+            running_min = min(
+                running_min,
+                result['log10_pvalue'] + math.log10(tests) - math.log10(result['rank'])
+            )
+            self.assertAlmostEqual(result['log10_qvalue'], running_min, places=9)
+            self.assertEqual(result['qvalue'], 10 ** result['log10_qvalue'])
+        # The q-value of the strongest enrichment underflows as well
+        self.assertEqual(res[0]['qvalue'], 0.0)
+        self.assertLess(res[0]['log10_qvalue'], -300)
+        self.assertEqual(
+            [result['log10_qvalue'] for result in res],
+            sorted(result['log10_qvalue'] for result in res)
+        )
+
+        batch = helper.batch_gene_enrichment([hposet])[0]
+        self.assertEqual(
+            [result['log10_qvalue'] for result in batch],
+            [result['log10_qvalue'] for result in res]
+        )
+        self.assertEqual(
+            [result['log10_pvalue'] for result in batch],
+            [result['log10_pvalue'] for result in model.enrichment('hypergeom', hposet)]
+        )
+
+        cohort = Cohort([("P1", hposet), ("P2", hposet)])
+        controls = Cohort([("C1", HPOSet.from_queries([7]))])
+        for result in cohort.enrichment(controls):
+            self.assertAlmostEqual(
+                result['log10_pvalue'], math.log10(result['enrichment'])
+            )
+
+    def test_term_lookup(self):
+        term = self.terms.hpo(2650)
+        self.assertEqual(self.terms.hpo("HP:0002650"), term)
+        self.assertEqual(self.terms["HP:0002650"], term)
+        self.assertEqual(self.terms["Scoliosis"], term)
+        self.assertEqual(self.terms.hpos([2650, "HP:0002650"]), [term, term])
+
+        self.assertIn(2650, self.terms)
+        self.assertIn("HP:0002650", self.terms)
+        self.assertIn(term, self.terms)
+        self.assertNotIn(99999999, self.terms)
+        self.assertNotIn("HP:9999999", self.terms)
+        self.assertNotIn("Invalid term", self.terms)
+
+        with self.assertRaises(KeyError):
+            self.terms["HP:9999999"]
+
+    def test_hpo_id_strings(self):
+        term = self.terms.hpo(118)
+        self.assertEqual(self.terms.hpo("HP:0000118"), term)
+        self.assertEqual(self.terms.hpo("HP:0000118").id, "HP:0000118")
+        self.assertEqual(self.terms.hpo("HP:0002650"), self.terms.hpo(2650))
+
+        with self.assertRaises(KeyError):
+            self.terms.hpo("HP:9999999")
+        with self.assertRaises(KeyError):
+            self.terms.hpo(99999999)
+        with self.assertRaises(ValueError):
+            self.terms.hpo("HP:abc")
+        # Term names are not resolved, use `get_hpo_object` instead
+        with self.assertRaises(ValueError):
+            self.terms.hpo("Scoliosis")
+
+    def test_getitem_queries(self):
+        term = self.terms.get_hpo_object("Scoliosis")
+        self.assertEqual(self.terms[2650], term)
+        self.assertEqual(self.terms["HP:0002650"], term)
+        self.assertEqual(self.terms["Scoliosis"], term)
+        for query in (118, "HP:0000925", "Abnormality of the eye"):
+            self.assertEqual(self.terms[query], self.terms.get_hpo_object(query))
+
+        # Unknown terms raise a KeyError, like a `dict`
+        for query in (99999999, "HP:9999999", "Foobar"):
+            with self.assertRaises(KeyError):
+                self.terms[query]
+        with self.assertRaises(ValueError):
+            self.terms["HP:abc"]
+
+    def test_bulk_term_lookup(self):
+        queries = [118, 2650, "HP:0000925", "HP:0000118"]
+        terms = self.terms.hpos(queries)
+        self.assertEqual(terms, [self.terms.hpo(query) for query in queries])
+        # The order and duplicates of the queries are kept
+        self.assertEqual(terms[0], terms[3])
+        self.assertEqual(self.terms.hpos([]), [])
+
+        # A single invalid query fails the whole lookup
+        with self.assertRaises(KeyError):
+            self.terms.hpos([118, 99999999])
+        with self.assertRaises(ValueError):
+            self.terms.hpos([118, "HP:abc"])
+
+    def test_similarity_matrix(self):
+        terms = [self.terms.hpo(x) for x in (11968, 1743, 118, 25031)]
+        matrix = helper.similarity_matrix(terms, kind="omim", method="graphic")
+        self.assertEqual(len(matrix), 4)
+
+        for a in terms:
+            for b in terms:
+                self.assertEqual(matrix.get(a, b), a.similarity_score(b))
+                self.assertEqual(a.similarity_score(b, cache=matrix), a.similarity_score(b))
+
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = os.path.join(tmpdir, "matrix.sim")
+            matrix.save(path)
+            loaded = SimilarityMatrix.load(path)
+        self.assertEqual(loaded.ids, matrix.ids)
+        self.assertEqual(loaded.get(terms[0], terms[1]), matrix.get(terms[0], terms[1]))
+
+        with self.assertRaises(ValueError):
+            terms[0].similarity_score(terms[1], kind="gene", cache=matrix)
+
+    def test_similarity_to_term(self):
+        hposet = HPOSet.from_queries([2650, 7])
+        other = self.terms.hpo(2651)
+        scores = [term.similarity_score(other) for term in hposet]
+
+        self.assertEqual(hposet.similarity_to_term(other), max(scores))
+        self.assertAlmostEqual(
+            hposet.similarity_to_term(other, combine="mean"),
+            sum(scores) / len(scores)
+        )
+        self.assertEqual(
+            hposet.similarity_to_term(other, kind="gene", method="resnik"),
+            max(term.similarity_score(other, kind="gene", method="resnik") for term in hposet)
+        )
+        self.assertEqual(hposet.similarity_to_term(self.terms.hpo(2650)), 1.0)
+
+        empty = HPOSet.from_queries([])
+        self.assertEqual(empty.similarity_to_term(other), 0.0)
+        self.assertTrue(math.isnan(empty.similarity_to_term(other, on_empty="nan")))
+        with self.assertRaises(ValueError):
+            empty.similarity_to_term(other, on_empty="raise")
+
+        with self.assertRaises(RuntimeError):
+            hposet.similarity_to_term(other, combine="foo")
+        with self.assertRaises(RuntimeError):
+            hposet.similarity_to_term(other, method="foo")
+        with self.assertRaises(KeyError):
+            hposet.similarity_to_term(other, kind="foo")
+
+    def test_register_similarity(self):
+        a = self.terms.hpo(11968)
+        b = self.terms.hpo(1743)
+        set_a = HPOSet.from_queries([11968, 1743])
+        set_b = HPOSet.from_queries([118, 2650])
+
+        register_similarity("test_alias", "lin")
+        self.assertEqual(
+            a.similarity_score(b, method="TEST_ALIAS"),
+            a.similarity_score(b, method="lin")
+        )
+        self.assertEqual(
+            set_a.similarity(set_b, method="test_alias"),
+            set_a.similarity(set_b, method="lin")
+        )
+
+        def shared_parents(x, y):
+            return float(len(set(x.parent_ids()) & set(y.parent_ids())))
+
+        register_similarity("test_parents", shared_parents)
+        expected = shared_parents(a, b)
+        self.assertEqual(a.similarity_score(b, method="test_parents"), expected)
+        self.assertEqual(
+            helper.batch_similarity([(a, b), (b, a)], method="test_parents"),
+            [expected, expected]
+        )
+        self.assertEqual(
+            helper.batch_set_similarity(
+                [(set_a, set_b)], method="test_parents", combine="BMA"
+            ),
+            [set_a.similarity(set_b, method="test_parents", combine="BMA")]
+        )
+        self.assertEqual(
+            len(linkage([set_a, set_b, set_a], similarity_method="test_parents")),
+            2
+        )
+
+        register_similarity("test_parents", lambda x, y: 1.0)
+        self.assertEqual(a.similarity_score(b, method="test_parents"), 1.0)
+
+        register_similarity("test_parents", lambda x, y: 1 / 0)
+        reported = []
+        unraisablehook = sys.unraisablehook
+        sys.unraisablehook = reported.append
+        try:
+            self.assertTrue(math.isnan(a.similarity_score(b, method="test_parents")))
+        finally:
+            sys.unraisablehook = unraisablehook
+        self.assertIsInstance(reported[0].exc_value, ZeroDivisionError)
+
+        with self.assertRaises(ValueError):
+            register_similarity("graphic", shared_parents)
+        with self.assertRaises(ValueError):
+            register_similarity("test_invalid", "no_such_method")
+        with self.assertRaises(TypeError):
+            register_similarity("test_invalid", 1)
+        with self.assertRaises(RuntimeError):
+            a.similarity_score(b, method="test_invalid")
+
+    def test_similarity_all(self):
+        term = self.terms.hpo(11968)
+        top = term.similarity_all(top_n=10)
+        self.assertEqual(len(top), 10)
+        scores = [score for _, score in top]
+        self.assertEqual(scores, sorted(scores, reverse=True))
+        for term_id, score in top:
+            self.assertNotEqual(term_id, 11968)
+            self.assertEqual(
+                score,
+                term.similarity_score(self.terms.hpo(term_id))
+            )
+
+        non_obsolete = [t for t in self.terms if not t.is_obsolete and t != term]
+        scores = term.similarity_all(kind="gene", method="lin")
+        self.assertEqual(len(scores), len(non_obsolete))
+        self.assertEqual(
+            scores[:10],
+            term.similarity_all(kind="gene", method="lin", top_n=10)
+        )
+
+    def test_ancestor_and_adjacency_matrix(self):
+        terms = list(self.terms)
+        ids = sorted(int(term) for term in terms)
+
+        def row_columns(matrix, term):
+            row = matrix.index.index(int(term))
+            return {matrix.columns[c] for r, c in zip(matrix.row, matrix.col) if r == row}
+
+        ancestors = self.terms.ancestor_matrix()
+        self.assertEqual(ancestors.shape, (len(ids), len(ids)))
+        self.assertEqual(ancestors.index, ids)
+        self.assertEqual(ancestors.columns, ids)
+        self.assertEqual(ancestors.nnz, sum(len(term.all_parents) for term in terms))
+        self.assertEqual(set(ancestors.data), {1.0})
+        for term_id in (7, 2650, 1):
+            term = self.terms.hpo(term_id)
+            self.assertEqual(row_columns(ancestors, term), {int(t) for t in term.all_parents})
+
+        with_self = self.terms.ancestor_matrix(include_self=True)
+        self.assertEqual(with_self.nnz, ancestors.nnz + len(ids))
+        term = self.terms.hpo(2650)
+        self.assertEqual(
+            row_columns(with_self, term),
+            {int(t) for t in term.all_parents} | {2650}
+        )
+
+        adjacency = self.terms.adjacency_matrix()
+        self.assertEqual(adjacency.shape, (len(ids), len(ids)))
+        self.assertEqual(adjacency.nnz, sum(len(term.parents) for term in terms))
+        for term_id in (108, 2650, 1):
+            term = self.terms.hpo(term_id)
+            self.assertEqual(row_columns(adjacency, term), {int(t) for t in term.parents})
+
+    def test_category_counts(self):
+        rows = self.terms.category_counts()
+        categories = set()
+        for term in self.terms:
+            categories |= set(term.categories)
+        self.assertEqual({row["category"] for row in rows}, categories)
+        self.assertEqual(
+            [int(row["category"]) for row in rows],
+            sorted(int(row["category"]) for row in rows)
+        )
+
+        descendants = {category: 0 for category in categories}
+        for term in self.terms:
+            for parent in term.all_parents & categories:
+                descendants[parent] += 1
+
+        for row in rows:
+            category = row["category"]
+            self.assertEqual(row["terms"], descendants[category])
+            self.assertEqual(row["genes"], category.gene_count)
+            self.assertEqual(row["omim"], category.omim_count)
+            self.assertEqual(row["orpha"], category.orpha_count)
+
+    def test_ancestor_embedding(self):
+        term = self.terms.hpo(11968)
+        other = self.terms.hpo(1743)
+        items = [term, HPOSet([term, other]), HPOSet([])]
+        embedding = helper.ancestor_embedding(items)
+        self.assertEqual(embedding.shape, (3, len(self.terms)))
+        self.assertEqual(embedding.index, [0, 1, 2])
+
+        data, indices, indptr = embedding.to_csr()
+        self.assertEqual(len(indptr), 4)
+        self.assertEqual(len(data), embedding.nnz)
+        self.assertEqual(len(indices), embedding.nnz)
+        self.assertEqual(indptr[2], indptr[3])
+
+        def row(i):
+            return {embedding.columns[c] for c in indices[indptr[i]:indptr[i + 1]]}
+
+        expected = {int(t) for t in term.all_parents} | {int(term)}
+        self.assertEqual(row(0), expected)
+        expected |= {int(t) for t in other.all_parents} | {int(other)}
+        self.assertEqual(row(1), expected)
+
+        without_self = helper.ancestor_embedding([term], include_self=False)
+        self.assertEqual(without_self.nnz, len(term.all_parents))
+
+    def test_mondo(self):
+        omim = self.terms.omim_diseases[0]
+        orpha = self.terms.orpha_diseases[0]
+        sssom = "\n".join([
+            "# mapping_set_id: https://w3id.org/sssom/mondo.sssom.tsv",
+            "subject_id\tsubject_label\tpredicate_id\tobject_id",
+            f"MONDO:0000001\tFirst\tskos:exactMatch\tOMIM:{omim.id}",
+            f"MONDO:0000001\tFirst\tskos:exactMatch\tOrphanet:{orpha.id}",
+            f"MONDO:0000002\tSecond\tskos:closeMatch\tOMIM:{omim.id}",
+            "MONDO:0000003\tThird\tskos:exactMatch\tDOID:1234",
+        ])
+        self.assertEqual(self.terms.load_mondo(sssom.encode()), 1)
+
+        mondo = an.Mondo.get("MONDO:0000001")
+        self.assertEqual(mondo, an.Mondo.get(1))
+        self.assertEqual(mondo.name, "First")
+        self.assertEqual(mondo.omim_diseases, [omim])
+        self.assertEqual(mondo.orpha_diseases, [orpha])
+        self.assertEqual(mondo.hpo, omim.hpo | orpha.hpo)
+        self.assertEqual(len(mondo.hpo_set()), len(omim.hpo | orpha.hpo))
+        self.assertEqual(omim.mondo, [mondo])
+        self.assertEqual(orpha.mondo, [mondo])
+        self.assertEqual(self.terms.omim_diseases[1].mondo, [])
+
+        for query in ("MONDO:0000002", "MONDO:0000003"):
+            with self.assertRaises(KeyError):
+                an.Mondo.get(query)
+        with self.assertRaises(ValueError):
+            an.Mondo.get("MONDO:foo")
+        with self.assertRaises(ValueError):
+            self.terms.load_mondo(b"subject_id\tobject_id\n")
+
+    def test_xrefs(self):
+        # The builtin Ontology does not contain cross references
+        term = self.terms.hpo(1166)
+        with self.assertRaisesRegex(RuntimeError, "JAX source files"):
+            term.xrefs()
+        with self.assertRaisesRegex(RuntimeError, "JAX source files"):
+            term.xrefs(system="UMLS")
+        with self.assertRaisesRegex(RuntimeError, "JAX source files"):
+            self.terms.term_by_xref("UMLS:C0024796")
+
+    def test_common_ancestors_of_many(self):
+        terms = [self.terms.hpo(i) for i in (2650, 9121, 2808)]
+        expected = terms[0].common_ancestors(terms[1]) & terms[0].common_ancestors(terms[2])
+        self.assertEqual(self.terms.common_ancestors(terms), expected)
+        self.assertEqual(self.terms.common_ancestors([2650, 9121, 2808]), expected)
+        self.assertEqual(HPOSet(terms).common_ancestors(), expected)
+        self.assertEqual(
+            self.terms.common_ancestors(terms[:2]),
+            terms[0].common_ancestors(terms[1])
+        )
+        self.assertEqual(self.terms.common_ancestors([]), set())
+
+        mica = self.terms.most_informative_common_ancestor(terms)
+        self.assertIn(mica, expected)
+        for term in expected:
+            self.assertLessEqual(
+                term.information_content.omim,
+                mica.information_content.omim
+            )
+        self.assertEqual(HPOSet(terms).most_informative_common_ancestor(), mica)
+        self.assertIsNone(HPOSet([]).most_informative_common_ancestor())
+        with self.assertRaises(KeyError):
+            self.terms.most_informative_common_ancestor(terms, kind="foo")
+
+        parent = self.terms.hpo(2650).parents.pop()
+        with_parent = [self.terms.hpo(2650), parent]
+        self.assertNotIn(parent, self.terms.common_ancestors(with_parent))
+        self.assertIn(parent, self.terms.common_ancestors(with_parent, include_self=True))
+        self.assertEqual(
+            self.terms.most_informative_common_ancestor(with_parent, include_self=True),
+            parent
+        )
+
+    def test_term_distance_matrix(self):
+        term = self.terms.hpo(2650)
+        parent = term.parents.pop()
+        sibling = next(child for child in parent.children if child != term)
+        terms = [term, parent, sibling, int(term)]
+        matrix = helper.term_distance_matrix(terms, output="list")
+        self.assertEqual(matrix, [
+            [0, 1, 2, 0],
+            [1, 0, 1, 1],
+            [2, 1, 0, 2],
+            [0, 1, 2, 0],
+        ])
+        # The undirected path can be shorter than the path via ancestors
+        root = helper.term_distance_matrix([1, term], output="list")
+        self.assertLessEqual(root[0][1], term.shortest_path_to_root())
+        self.assertEqual(root[0][1], root[1][0])
+        self.assertEqual(helper.term_distance_matrix([], output="list"), [])
+        with self.assertRaises(ValueError):
+            helper.term_distance_matrix(terms, output="foo")
+
+    def test_random_sets(self):
+        sets = helper.random_sets(5, size=4, seed=42)
+        self.assertEqual(len(sets), 5)
+        for s in sets:
+            self.assertEqual(len(s), 4)
+            for term in s:
+                self.assertFalse(term.is_obsolete)
+                self.assertTrue(term.child_of(self.terms.hpo(118)))
+        self.assertEqual(
+            [s.toJSON() for s in sets],
+            [s.toJSON() for s in helper.random_sets(5, size=4, seed=42)]
+        )
+        self.assertNotEqual(
+            [s.toJSON() for s in sets],
+            [s.toJSON() for s in helper.random_sets(5, size=4, seed=43)]
+        )
+
+        reference = HPOSet.from_queries([2650, 9121, 2808, 100490])
+        matched = helper.random_sets(20, match_ic_distribution_of=reference, seed=1)
+        for s in matched:
+            self.assertEqual(len(s), len(reference))
+        reference_ic = sorted(term.information_content.omim for term in reference)
+        mean_ic = sum(
+            term.information_content.omim for s in matched for term in s
+        ) / (20 * len(reference))
+        self.assertAlmostEqual(
+            mean_ic, sum(reference_ic) / len(reference_ic), delta=1.0
+        )
+
+        self.assertEqual(helper.random_sets(0, size=3), [])
+        with self.assertRaises(ValueError):
+            helper.random_sets(1)
+        with self.assertRaises(ValueError):
+            helper.random_sets(1, size=2, match_ic_distribution_of=reference)
+        with self.assertRaises(ValueError):
+            helper.random_sets(1, size=100000)
+        with self.assertRaises(KeyError):
+            helper.random_sets(1, size=2, kind="foo")
+
+    def test_similarity_significance(self):
+        patient = HPOSet.from_queries([2650, 9121, 2808])
+        disease = an.Omim.get(620402).hpo_set()
+        result = helper.similarity_significance(patient, disease, n_permutations=200, seed=42)
+        self.assertAlmostEqual(result["similarity"], patient.similarity(disease), places=5)
+        self.assertEqual(result["n_permutations"], 200)
+        self.assertGreater(result["pvalue"], 0)
+        self.assertLessEqual(result["pvalue"], 1)
+        self.assertAlmostEqual(
+            result["zscore"],
+            (result["similarity"] - result["null_mean"]) / result["null_std"]
+        )
+        self.assertEqual(
+            result,
+            helper.similarity_significance(patient, disease, n_permutations=200, seed=42)
+        )
+
+        # A set is more similar to itself than random sets
+        identical = helper.similarity_significance(disease, disease, n_permutations=200, seed=1)
+        self.assertAlmostEqual(identical["pvalue"], 1 / 201)
+        self.assertGreater(identical["zscore"], 0)
+
+        with self.assertRaises(ValueError):
+            helper.similarity_significance(HPOSet([]), disease)
+        with self.assertRaises(ValueError):
+            helper.similarity_significance(patient, disease, n_permutations=0)
+        with self.assertRaises(RuntimeError):
+            helper.similarity_significance(patient, disease, method="foo")
+
+    def test_similarity_explain(self):
+        set1 = HPOSet.from_queries([2650, 9121, 2808])
+        set2 = an.Omim.get(620402).hpo_set()
+        explanation = set1.similarity(set2, explain=True)
+        self.assertEqual(explanation["similarity"], set1.similarity(set2))
+        self.assertEqual(len(explanation["matches"]), len(set1))
+        self.assertEqual(len(explanation["reverse_matches"]), len(set2))
+
+        for match in explanation["matches"]:
+            self.assertIn(match["term"], set1)
+            self.assertIn(match["match"], set2)
+            self.assertEqual(
+                match["similarity"],
+                max(match["term"].similarity_score(other) for other in set2)
+            )
+            self.assertEqual(match["similarity"], match["term"].similarity_score(match["match"]))
+            self.assertIsNotNone(match["mica"])
+            self.assertIn(match["mica"], match["term"].common_ancestors(match["match"]) | {match["term"]})
+
+        # funSimAvg is the mean of the best matches in both directions
+        forward = explanation["matches"]
+        reverse = explanation["reverse_matches"]
+        self.assertAlmostEqual(
+            explanation["similarity"],
+            (
+                sum(m["similarity"] for m in forward) / len(forward)
+                + sum(m["similarity"] for m in reverse) / len(reverse)
+            ) / 2,
+            places=5
+        )
+
+        identical = set1.similarity(set1, explain=True)
+        for match in identical["matches"]:
+            self.assertEqual(match["term"], match["match"])
+            self.assertEqual(match["mica"], match["term"])
+
+        empty = set1.similarity(HPOSet([]), explain=True)
+        self.assertEqual(empty["similarity"], 0.0)
+        self.assertEqual(empty["matches"], [])
+        self.assertEqual(empty["reverse_matches"], [])
+
+    def test_set_index(self):
+        query = HPOSet.from_queries([2650, 9121, 2808])
+        index = helper.SetIndex.from_diseases("omim")
+        self.assertEqual(len(index), len(list(self.terms.omim_diseases)))
+        nearest = index.nearest(query, k=5)
+        self.assertEqual(len(nearest), 5)
+        self.assertIsInstance(nearest[0][0], an.Omim)
+
+        # Same results as comparing the query to every disease
+        patient = Patient("P1", query)
+        expected = patient.rank_diseases(limit=5)
+        self.assertEqual([int(d) for d, _ in nearest], [int(d) for d, _ in expected])
+        for (_, score), (_, expected_score) in zip(nearest, expected):
+            self.assertAlmostEqual(score, expected_score, places=5)
+
+        sets = [d.hpo_set() for d in list(self.terms.omim_diseases)[:300]]
+        for method in ("graphic", "resnik", "jc"):
+            index = helper.SetIndex(sets, method=method)
+            brute = sorted(
+                ((idx, query.similarity(s, method=method)) for idx, s in enumerate(sets)),
+                key=lambda x: (-x[1], x[0])
+            )
+            self.assertEqual(index.nearest(query, k=None), [
+                (idx, score) for idx, score in brute
+            ])
+
+        index = helper.SetIndex(sets[:3], keys=["a", "b", "c"])
+        self.assertEqual(sorted(key for key, _ in index.nearest(query)), ["a", "b", "c"])
+        self.assertEqual(index.nearest(HPOSet([]), k=2), [("a", 0.0), ("b", 0.0)])
+
+        cohort = Cohort([("x", sets[0]), ("y", query)])
+        self.assertEqual(helper.SetIndex(cohort).nearest(query, k=1), [("y", 1.0)])
+
+        with self.assertRaises(ValueError):
+            helper.SetIndex(sets[:3], keys=["a"])
+        with self.assertRaises(ValueError):
+            helper.SetIndex.from_diseases("decipher")
+        with self.assertRaises(RuntimeError):
+            helper.SetIndex(sets, method="foo")
+
+    def test_minhash_index(self):
+        sets = [d.hpo_set() for d in list(self.terms.omim_diseases)[:300]]
+        sets.append(sets[0])
+        sets.append(HPOSet([]))
+        index = helper.MinHashIndex(sets, num_perm=128, bands=32, seed=1)
+        self.assertEqual(len(index), len(sets))
+        self.assertAlmostEqual(index.threshold, (1 / 32) ** (1 / 4))
+
+        pairs = index.candidate_pairs(output="list")
+        self.assertEqual(pairs, sorted(set(pairs)))
+        self.assertIn((0, 300), pairs)
+        for a, b in pairs:
+            self.assertLess(a, b)
+            self.assertNotEqual(b, 301)
+
+        def features(hposet):
+            ancestors = set()
+            for term in hposet:
+                ancestors.add(int(term))
+                ancestors.update(int(parent) for parent in term.all_parents)
+            ancestors.discard(1)
+            return ancestors
+
+        ancestor_sets = [features(s) for s in sets[:300]]
+        for a in range(300):
+            for b in range(a + 1, 300):
+                union = ancestor_sets[a] | ancestor_sets[b]
+                if union and len(ancestor_sets[a] & ancestor_sets[b]) / len(union) >= 0.9:
+                    self.assertIn((a, b), pairs)
+
+        self.assertIn(0, index.candidates(sets[0]))
+        self.assertIn(300, index.candidates(sets[0]))
+        self.assertEqual(index.candidates(HPOSet([])), [])
+        self.assertEqual(
+            helper.MinHashIndex(sets, seed=1).candidate_pairs(output="list"),
+            pairs
+        )
+
+        with self.assertRaises(ValueError):
+            helper.MinHashIndex(sets, num_perm=100, bands=32)
+        with self.assertRaises(ValueError):
+            helper.MinHashIndex(sets, bands=0)
+        with self.assertRaises(ValueError):
+            index.candidate_pairs(output="foo")
+
+    def test_table_outputs(self):
+        import types
+        from unittest import mock
+
+        polars = types.ModuleType("polars")
+        polars.DataFrame = lambda columns: columns
+        pyarrow = types.ModuleType("pyarrow")
+        pyarrow.RecordBatch = types.SimpleNamespace(from_pydict=lambda columns: ("arrow", columns))
+
+        hposet = HPOSet.from_queries([2650, 1166, 1083])
+        model = EnrichmentModel("omim")
+        patient = Patient("P1", hposet)
+        with mock.patch.dict(sys.modules, {"polars": polars, "pyarrow": pyarrow}):
+            records = model.enrichment("hypergeom", hposet)
+            table = model.enrichment("hypergeom", hposet, output="polars")
+            self.assertEqual(
+                list(table),
+                [
+                    "id", "name", "rank", "enrichment", "log10_pvalue",
+                    "qvalue", "log10_qvalue", "fold", "count"
+                ]
+            )
+            self.assertEqual(table["id"], [int(r["item"]) for r in records])
+            self.assertEqual(table["name"], [r["item"].name for r in records])
+            self.assertEqual(table["enrichment"], [r["enrichment"] for r in records])
+
+            tag, table = model.enrichment("hypergeom", hposet, output="arrow")
+            self.assertEqual(tag, "arrow")
+            self.assertEqual(table["count"], [r["count"] for r in records])
+
+            cohort = Cohort([("P1", hposet), ("P2", HPOSet.from_queries([7, 1743]))])
+            other = Cohort([("P3", HPOSet.from_queries([2650]))])
+            table = cohort.enrichment(other, output="polars")
+            self.assertEqual(list(table)[-1], "other_count")
+            self.assertEqual(len(table["id"]), len(cohort.enrichment(other)))
+
+            diseases = patient.rank_diseases(limit=5)
+            table = patient.rank_diseases(limit=5, output="polars")
+            self.assertEqual(list(table), ["id", "name", "similarity"])
+            self.assertEqual(table["id"], [int(d) for d, _ in diseases])
+            self.assertEqual(table["similarity"], [score for _, score in diseases])
+            table = patient.rank_genes(limit=3, output="polars")
+            self.assertEqual(len(table["name"]), 3)
+
+            table = Ontology.to_table()
+            self.assertEqual(
+                list(table),
+                [
+                    "id", "name", "parents", "children", "obsolete", "ic_omim",
+                    "ic_orpha", "ic_gene", "n_omim", "n_orpha", "n_genes"
+                ]
+            )
+            self.assertEqual(len(table["id"]), N_TERMS)
+            self.assertEqual(table["id"], sorted(table["id"]))
+
+        rows = Ontology.to_table(output="list")
+        self.assertEqual(len(rows), N_TERMS)
+        row = next(r for r in rows if r["id"] == 2650)
+        self.assertEqual(row["name"], Ontology.get_hpo_object(2650).name)
+        self.assertEqual(row["n_genes"], len(Ontology.get_hpo_object(2650).genes))
+        self.assertEqual(row["children"], sorted(row["children"]))
+
+        with self.assertRaises(ValueError):
+            model.enrichment("hypergeom", hposet, output="foo")
+        with self.assertRaises(ValueError):
+            patient.rank_genes(output="foo")
+        with self.assertRaises(ValueError):
+            Ontology.to_table(output="numpy")
+
+    def test_score_buffer(self):
+        import ctypes
+        from pyhpo.types import ScoreBuffer
+
+        terms = [self.terms.get_hpo_object(t) for t in (2650, 1166, 1083, 7)]
+        term_pairs = [(a, b) for a in terms for b in terms]
+        expected = helper.batch_similarity(term_pairs)
+        buffer = helper.batch_similarity(term_pairs, output="buffer")
+        self.assertIsInstance(buffer, ScoreBuffer)
+        self.assertEqual(len(buffer), 16)
+        self.assertEqual(buffer.shape, [16])
+        self.assertEqual(buffer.dtype, "float32")
+        self.assertEqual(buffer.nbytes, 64)
+        self.assertEqual(buffer.tolist(), expected)
+
+        view = memoryview(buffer)
+        self.assertIs(view.obj, buffer)
+        self.assertEqual(view.format, "f")
+        self.assertEqual(view.itemsize, 4)
+        self.assertEqual(view.shape, (16,))
+        self.assertTrue(view.readonly)
+        self.assertTrue(view.c_contiguous)
+        self.assertEqual(view.tolist(), expected)
+        self.assertEqual(memoryview(buffer).tolist(), expected)
+        with self.assertRaises(TypeError):
+            (ctypes.c_float * 16).from_buffer(buffer)
+        del view
+
+        sets = [gene.hpo_set() for gene in list(self.terms.genes)[:4]]
+        comparisons = [(a, b) for a in sets for b in sets]
+        expected = helper.batch_set_similarity(comparisons)
+        buffer = helper.batch_set_similarity(comparisons, output="buffer")
+        self.assertEqual(memoryview(buffer).tolist(), expected)
+        scores = helper.batch_set_similarity(
+            comparisons, methods=["lin", "graphic"], output="buffer"
+        )
+        self.assertEqual(memoryview(scores["graphic"]).tolist(), expected)
+        buffer, errors = helper.batch_set_similarity(
+            comparisons + [(sets[0], None)], errors="collect", output="buffer"
+        )
+        self.assertEqual(len(buffer), 17)
+        self.assertTrue(math.isnan(buffer.tolist()[16]))
+        self.assertEqual([idx for idx, _ in errors], [16])
+
+        chunks = list(helper.batch_set_similarity_chunks(
+            iter(comparisons), chunk_size=10, output="buffer"
+        ))
+        self.assertEqual([len(chunk) for chunk in chunks], [10, 6])
+        self.assertEqual(chunks[0].tolist() + chunks[1].tolist(), expected)
+
+        kernel = SimilarityKernel(sets, output="buffer")
+        gram = kernel([[0], [1], [2]], [[1], [3]])
+        self.assertEqual(gram.shape, [3, 2])
+        self.assertEqual(gram.dtype, "float64")
+        view = memoryview(gram)
+        self.assertEqual(view.format, "d")
+        self.assertEqual(view.shape, (3, 2))
+        self.assertEqual(view.strides, (16, 8))
+        self.assertEqual(
+            view.tolist(),
+            SimilarityKernel(sets, output="list")([[0], [1], [2]], [[1], [3]])
+        )
+        self.assertEqual(gram.tolist(), view.tolist())
+        self.assertEqual(kernel([]).shape, [0, 0])
+        self.assertEqual(kernel([]).tolist(), [])
+
+        with self.assertRaises(ValueError):
+            helper.batch_similarity(term_pairs, output="foo")
+        with self.assertRaises(ValueError):
+            helper.batch_set_similarity(comparisons, output="foo")
+
+    def test_batch_workers(self):
+        import threading
+
+        terms = [self.terms.hpo(t) for t in (2650, 1166, 1083, 7, 1743, 11968)]
+        term_pairs = [(a, b) for a in terms for b in terms]
+        threads = set()
+
+        def record_thread(a, b):
+            threads.add(threading.get_ident())
+            return 0.5
+
+        register_similarity("test_workers", record_thread)
+        scores = helper.batch_similarity(term_pairs, method="test_workers", workers=1)
+        self.assertEqual(scores, [0.5] * len(term_pairs))
+        self.assertEqual(len(threads), 1)
+        self.assertNotIn(threading.get_ident(), threads)
+
+        # Calls with the same number of workers reuse the same threads
+        native_threads = set()
+
+        def record_native_thread(a, b):
+            native_threads.add(threading.get_native_id())
+            return 0.5
+
+        register_similarity("test_worker_pool", record_native_thread)
+        for _ in range(3):
+            helper.batch_similarity(term_pairs, method="test_worker_pool", workers=1)
+        self.assertEqual(len(native_threads), 1)
+
+        # Only the most recent pool is kept
+        helper.batch_similarity(term_pairs, method="test_worker_pool", workers=2)
+        self.assertLessEqual(len(native_threads), 3)
+        first_pool = set(native_threads)
+        native_threads.clear()
+        helper.batch_similarity(term_pairs, method="test_worker_pool", workers=1)
+        self.assertEqual(len(native_threads), 1)
+        self.assertTrue(native_threads.isdisjoint(first_pool))
+
+        expected = helper.batch_similarity(term_pairs)
+        self.assertEqual(helper.batch_similarity(term_pairs, workers=2), expected)
+
+        sets = [gene.hpo_set() for gene in list(self.terms.genes)[:4]]
+        comparisons = [(a, b) for a in sets for b in sets]
+        expected = helper.batch_set_similarity(comparisons)
+        self.assertEqual(helper.batch_set_similarity(comparisons, workers=1), expected)
+        self.assertEqual(
+            helper.batch_set_similarity(comparisons, methods=["graphic"], workers=3),
+            {"graphic": expected}
+        )
+        chunks = helper.batch_set_similarity_chunks(
+            iter(comparisons), chunk_size=10, output="list", workers=2
+        )
+        self.assertEqual([score for chunk in chunks for score in chunk], expected)
+        self.assertEqual(
+            helper.batch_gene_enrichment(sets[:2], workers=2),
+            helper.batch_gene_enrichment(sets[:2])
+        )
+        self.assertEqual(
+            helper.batch_omim_disease_enrichment(sets[:2], workers=1),
+            helper.batch_omim_disease_enrichment(sets[:2])
+        )
+        self.assertEqual(
+            helper.batch_orpha_disease_enrichment(sets[:2], workers=1),
+            helper.batch_orpha_disease_enrichment(sets[:2])
+        )
+
+        with self.assertRaises(ValueError):
+            helper.batch_similarity(term_pairs, workers=0)
+        with self.assertRaises(ValueError):
+            helper.batch_set_similarity(comparisons, workers=0)
+        with self.assertRaises(ValueError):
+            helper.batch_set_similarity_chunks(comparisons, workers=0)
+        with self.assertRaises(ValueError):
+            helper.batch_gene_enrichment(sets, workers=0)
+
+    def test_leaves(self):
+        expected = [
+            term for term in self.terms
+            if not term.children and not term.is_obsolete
+        ]
+        self.assertEqual(list(self.terms.leaves()), expected)
+
+        scoliosis = self.terms.hpo(2650)
+        leaves = list(self.terms.leaves(scoliosis))
+        self.assertGreater(len(leaves), 0)
+        self.assertEqual(leaves, list(self.terms.leaves(2650)))
+        self.assertEqual([int(term) for term in leaves], sorted(int(term) for term in leaves))
+        for term in leaves:
+            self.assertEqual(term.children, set())
+            self.assertTrue(term.child_of(scoliosis))
+
+        # A leaf term is its own only leaf
+        self.assertEqual(list(self.terms.leaves(leaves[0])), [leaves[0]])
+
+        with self.assertRaises(KeyError):
+            self.terms.leaves(99999999)
+
+    def test_root_and_branches(self):
+        root = self.terms.root
+        self.assertEqual(root.id, "HP:0000001")
+        self.assertEqual(root.parents, set())
+
+        named = {
+            "phenotypic_abnormality": ("HP:0000118", self.terms.phenotypic_abnormality),
+            "mode_of_inheritance": ("HP:0000005", self.terms.mode_of_inheritance),
+            "clinical_modifier": ("HP:0012823", self.terms.clinical_modifier),
+            "frequency": ("HP:0040279", self.terms.frequency),
+            "past_medical_history": ("HP:0032443", self.terms.past_medical_history),
+        }
+        branches = self.terms.branches
+        for name, (term_id, term) in named.items():
+            self.assertEqual(term.id, term_id)
+            self.assertEqual(branches[name], term)
+
+        # The branches are all direct children of the root term
+        self.assertEqual(set(branches.values()), root.children)
+        for term in branches.values():
+            self.assertEqual(term.parents, {root})
+
+    def test_path_to_root(self):
+        term = self.terms.hpo(100490)
+        path = term.path_to_root()
+        self.assertEqual(path[0], term)
+        self.assertEqual(path[-1], self.terms.hpo(1))
+        self.assertEqual(len(path) - 1, term.shortest_path_to_root())
+        for child, parent in zip(path, path[1:]):
+            self.assertIn(parent, child.parents)
+        self.assertEqual(self.terms.hpo(1).path_to_root(), [self.terms.hpo(1)])
+
+    def test_paths_to_ancestor(self):
+        def all_paths(term, ancestor):
+            if term == ancestor:
+                return [[]]
+            return [
+                [parent] + path
+                for parent in term.parents
+                for path in all_paths(parent, ancestor)
+            ]
+
+        for term_id, ancestor_id in ((108, 118), (2650, 1), (108, 107)):
+            term = self.terms.hpo(term_id)
+            ancestor = self.terms.hpo(ancestor_id)
+            paths = term.paths_to_ancestor(ancestor)
+            expected = all_paths(term, ancestor)
+            self.assertEqual(len(paths), len(expected))
+            self.assertEqual(
+                sorted(tuple(int(t) for t in path) for path in paths),
+                sorted(tuple(int(t) for t in path) for path in expected)
+            )
+            for path in paths:
+                self.assertEqual(path[-1], ancestor)
+
+        self.assertGreater(len(self.terms.hpo(108).paths_to_ancestor(self.terms.hpo(118))), 1)
+        # Terms that are not ancestors have no paths
+        self.assertEqual(self.terms.hpo(118).paths_to_ancestor(self.terms.hpo(108)), [])
+        self.assertEqual(self.terms.hpo(108).paths_to_ancestor(self.terms.hpo(108)), [])
+
+    def test_path_replace_obsolete(self):
+        obsolete = self.terms.hpo(100637)
+        term = self.terms.hpo(40064)
+        with self.assertRaises(RuntimeError):
+            obsolete.path_to_other(term)
+
+        expected = obsolete.replace().path_to_other(term)
+        self.assertEqual(obsolete.path_to_other(term, replace_obsolete=True), expected)
+        self.assertEqual(
+            self.terms.path(100637, 40064, replace_obsolete=True),
+            expected
+        )
+        self.assertEqual(
+            term.path_to_other(obsolete, replace_obsolete=True),
+            term.path_to_other(obsolete.replace())
+        )
+
+        no_replacement = next(
+            t for t in self.terms if t.is_obsolete and t.replace() is None
+        )
+        with self.assertRaises(RuntimeError):
+            no_replacement.path_to_other(term, replace_obsolete=True)
+
+    def test_representative_terms(self):
+        hpo_set = an.Omim.get(620402).hpo_set()
+        selected = hpo_set.representative_terms(5)
+        self.assertLessEqual(len(selected), 5)
+        self.assertGreater(len(selected), 0)
+        self.assertEqual(len(set(selected)), len(selected))
+        for term in selected:
+            self.assertIn(term, hpo_set)
+        self.assertEqual(selected[0], hpo_set.terms_by_ic()[0])
+        self.assertEqual(hpo_set.representative_terms(5), selected)
+
+        # Ancestors of selected terms do not add information
+        term = self.terms.hpo(2650)
+        parent = term.parents.pop()
+        self.assertEqual(HPOSet([term, parent]).representative_terms(2), [term])
+        self.assertEqual(HPOSet([term]).representative_terms(0), [])
+        self.assertEqual(HPOSet([]).representative_terms(3), [])
+        with self.assertRaises(KeyError):
+            hpo_set.representative_terms(3, kind="foo")
+
+    def test_redundancy_and_diversity(self):
+        terms = [self.terms.hpo(i) for i in (2650, 9121, 2808)]
+        pairs = [
+            a.similarity_score(b)
+            for i, a in enumerate(terms) for b in terms[i + 1:]
+        ]
+        hpo_set = HPOSet(terms)
+        self.assertAlmostEqual(hpo_set.redundancy(), sum(pairs) / 3, places=5)
+        self.assertAlmostEqual(
+            hpo_set.diversity(),
+            9 / (3 + 2 * sum(pairs)),
+            places=5
+        )
+        self.assertGreaterEqual(hpo_set.diversity(), 1.0)
+        self.assertLessEqual(hpo_set.diversity(), 3.0)
+
+        # a term with its parent is more redundant than unrelated terms
+        related = HPOSet([terms[0], terms[0].parents.pop()])
+        unrelated = HPOSet([terms[0], self.terms.hpo(7)])
+        self.assertGreater(related.redundancy(), unrelated.redundancy())
+        self.assertLess(related.diversity(), unrelated.diversity())
+
+        self.assertEqual(HPOSet([terms[0]]).redundancy(), 0.0)
+        self.assertEqual(HPOSet([terms[0]]).diversity(), 1.0)
+        self.assertEqual(HPOSet([]).diversity(), 0.0)
+        with self.assertRaises(RuntimeError):
+            hpo_set.redundancy(method="foo")
+
+    def test_named_custom_ic(self):
+        a = self.terms.hpo(11968)
+        b = self.terms.hpo(1743)
+        root = self.terms.hpo(118)
+        for term, peds, adult in ((a, 3.0, 1.0), (b, 2.5, 1.5), (root, 2.0, 0.5)):
+            term.set_ic("test_peds", peds)
+            term.set_ic("test_adult", adult)
+
+        self.assertEqual(a.information_content["test_peds"], 3.0)
+        self.assertEqual(a.information_content["test_adult"], 1.0)
+        self.assertEqual(self.terms.hpo(1).information_content["test_peds"], 0.0)
+        with self.assertRaises(KeyError):
+            a.information_content["test_unknown"]
+
+        self.assertEqual(a.similarity_score(b, kind="test_peds", method="resnik"), 2.0)
+        self.assertEqual(a.similarity_score(b, kind="test_adult", method="resnik"), 0.5)
+        self.assertEqual(
+            helper.batch_similarity([(a, b)], kind="test_peds", method="resnik"),
+            [2.0]
+        )
+        set_a = HPOSet([a])
+        set_b = HPOSet([b])
+        self.assertEqual(
+            set_a.similarity(set_b, kind="test_peds", method="resnik"),
+            2.0
+        )
+        scores = helper.batch_set_similarity(
+            [(set_a, set_b)], kind="test_adult", methods=["resnik", "lin"]
+        )
+        self.assertEqual(scores["resnik"], [0.5])
+        self.assertAlmostEqual(scores["lin"][0], 2 * 0.5 / (1.0 + 1.5))
+        self.assertEqual(
+            len(linkage([set_a, set_b, set_a], kind="test_peds", similarity_method="lin")),
+            2
+        )
+
+        with self.assertRaises(ValueError):
+            a.set_ic("omim", 1.0)
+        with self.assertRaises(KeyError):
+            a.similarity_score(b, kind="test_unknown")
+
+    def test_batch_similarity_errors(self):
+        terms = [self.terms.hpo(x) for x in (11968, 1743)]
+        comparisons = [(terms[0], terms[1]), (terms[0], None)]
+
+        with self.assertRaises(TypeError):
+            helper.batch_similarity(comparisons)
+
+        scores = helper.batch_similarity(comparisons, errors="nan")
+        self.assertEqual(scores[0], terms[0].similarity_score(terms[1]))
+        self.assertTrue(math.isnan(scores[1]))
+
+        scores, errors = helper.batch_similarity(comparisons, errors="collect")
+        self.assertEqual(len(scores), 2)
+        self.assertEqual([idx for idx, _ in errors], [1])
+
+        with self.assertRaises(ValueError):
+            helper.batch_similarity(comparisons, errors="ignore")
+
+    def test_empty_set_similarity(self):
+        full = HPOSet.from_queries([11968, 1743])
+        empty = HPOSet([])
+
+        self.assertEqual(full.similarity(empty), 0.0)
+        self.assertTrue(math.isnan(full.similarity(empty, on_empty="nan")))
+        with self.assertRaises(ValueError):
+            empty.similarity(full, on_empty="raise")
+
+        scores = full.similarity_scores([full, empty], on_empty="nan")
+        self.assertEqual(scores[0], full.similarity(full))
+        self.assertTrue(math.isnan(scores[1]))
+
+        scores = full.similarity_scores([full, empty], on_empty="nan", nan_policy="substitute", nan_value=-1.0)
+        self.assertEqual(scores[1], -1.0)
+        self.assertEqual(len(full.similarity_scores([full, empty], on_empty="nan", nan_policy="skip")), 1)
+        with self.assertRaises(ValueError):
+            full.similarity_scores([full, empty], on_empty="nan", nan_policy="raise")
+
+    def test_jax_fixture_roundtrip(self):
+        folder = os.path.join(os.path.dirname(__file__), "data", "jax")
+        # The Ontology is a singleton, so the fixture is loaded in a new interpreter
+        with multiprocessing.get_context("spawn").Pool(1) as pool:
+            summary = pool.apply(_jax_fixture_summary, (folder,))
+
+        self.assertIn("HP:0009999", summary["error"])
+        self.assertEqual(summary["len"], 8)
+        self.assertEqual(summary["version"], "2024-01-02")
+        self.assertEqual(summary["parents"], [2, 118])
+        self.assertEqual(summary["all_parents"], [1, 2, 118])
+        self.assertEqual(summary["children"], [2, 3])
+        self.assertEqual(summary["obsolete"], (True, "HP:0000003", False))
+        self.assertEqual(summary["obsolete_parents"], 0)
+
+        details = summary["json"]
+        self.assertEqual(details["name"], "Multicystic kidney dysplasia")
+        self.assertEqual(details["definition"], "Multicystic dysplasia of the kidney.")
+        self.assertEqual(details["synonym"], ["Multicystic kidneys", "Multicystic renal dysplasia"])
+        self.assertEqual(details["xref"], ["UMLS:C3714581", "SNOMEDCT_US:204962002"])
+        self.assertEqual(
+            sorted(details["is_a"]),
+            ["HP:0000002 ! Abnormality of body height", "HP:0000118 ! Phenotypic abnormality"]
+        )
+        self.assertEqual(details["ic"]["gene"], summary["ic"])
+        self.assertEqual(
+            summary["xrefs"],
+            (["UMLS:C3714581", "SNOMEDCT_US:204962002"], ["UMLS:C3714581"], [])
+        )
+        self.assertEqual(summary["by_xref"], [[3], [3], []])
+        self.assertEqual(json.loads(summary["json_str"]), details)
+        self.assertEqual(summary["root"], "Root of all terms in the Human Phenotype Ontology.")
+        self.assertEqual(summary["search"], [[], [3], [5], [2, 118]])
+
+        self.assertEqual(summary["genes"], ["GENE1"])
+        self.assertEqual(summary["direct_hpo"], [3])
+        # The `NOT` annotation of OMIM:600001 is skipped
+        self.assertEqual(summary["omim"], [600001])
+        self.assertEqual(summary["orpha"], [1001])
+        self.assertEqual(
+            summary["omim_annotations"],
+            [{"hpo": 3, "evidence": "PCS", "frequency": "1/2", "modifiers": [12828]}]
+        )
+        self.assertEqual(
+            summary["orpha_annotations"],
+            [{"hpo": 2, "evidence": "TAS", "frequency": "HP:0040281", "modifiers": []}]
+        )
+        # Unknown modifier terms are ignored
+        self.assertEqual(summary["modifiers"], {3: [12828]})
+        # `NOT` annotations have no frequency, `HP:0040281` is at least 80%
+        omim, excluded, orpha = summary["frequencies"]
+        self.assertEqual(omim, 0.5)
+        self.assertIsNone(excluded)
+        self.assertAlmostEqual(orpha, 0.8)
+
+    def test_annotation_metadata(self):
+        # The builtin Ontology is not built from annotation files
+        self.assertEqual(self.terms.annotation_metadata(), {})
+
+        folder = os.path.join(os.path.dirname(__file__), "data", "jax")
+        with multiprocessing.get_context("spawn").Pool(1) as pool:
+            metadata = pool.apply(_jax_annotation_metadata, (folder,))
+        self.assertEqual(
+            metadata,
+            {
+                # The `NOT` annotation is skipped
+                "phenotype.hpoa": {
+                    "version": None, "date": "2024-01-02", "records": 2, "skipped": 1
+                },
+                "genes_to_phenotype.txt": {
+                    "version": None, "date": None, "records": 2, "skipped": 0
+                },
+            }
+        )
+
+    def test_build_from_contents(self):
+        folder = os.path.join(os.path.dirname(__file__), "data", "jax")
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = os.path.join(tmpdir, "ontology.hpo")
+            self.terms.save(path)
+            # Each build needs a new interpreter
+            with multiprocessing.get_context("spawn").Pool(1, maxtasksperchild=1) as pool:
+                from_folder = pool.apply(_fixture_build, (folder,))
+                # `bytes`, binary and text file-like objects
+                from_contents = pool.apply(_fixture_build, (folder, "contents"))
+                from_binary = pool.apply(_fixture_build, (folder, path))
+
+        for summary in (from_folder, from_contents):
+            self.assertEqual(summary["len"], 8)
+            self.assertEqual(summary["genes"], {"GENE1": [3], "GENE2": [2]})
+            self.assertEqual(summary["omim"], {600001: [3]})
+            self.assertEqual(summary["orpha"], {1001: [2]})
+        self.assertEqual(from_contents["genes"], from_folder["genes"])
+
+        self.assertEqual(from_binary["len"], len(self.terms))
+        self.assertEqual(from_binary["n_genes"], len(self.terms.genes))
+
+    def test_build_long_term_names(self):
+        folder = os.path.join(os.path.dirname(__file__), "data", "jax")
+        # More than 255 bytes, with multi-byte characters
+        name = "Abnormality of the " + "ä" * 200
+        with multiprocessing.get_context("spawn").Pool(1, maxtasksperchild=1) as pool:
+            summary = pool.apply(_long_name_build, (folder, name))
+            crlf = pool.apply(_long_name_build, (folder, name, "\r\n"))
+
+        self.assertGreater(len(name.encode()), 255)
+        for result in (summary, crlf):
+            self.assertEqual(result["name"], name)
+            self.assertEqual(result["parents"], [118])
+            self.assertEqual(result["len"], 9)
+            self.assertEqual(result["version"], "2024-01-02")
+            self.assertEqual(result["obsolete"], (True, "HP:0000003"))
+
+    def test_build_progress(self):
+        folder = os.path.join(os.path.dirname(__file__), "data", "jax")
+        with multiprocessing.get_context("spawn").Pool(1, maxtasksperchild=1) as pool:
+            progress = pool.apply(_fixture_build, (folder,))["progress"]
+            terms_only = pool.apply(_fixture_build, (folder,), {"annotations": False})["progress"]
+            failed = pool.apply(_fixture_build, (folder,), {"fail_at": "genes"})
+
+        size = sum(
+            os.path.getsize(os.path.join(folder, filename))
+            for filename in ("hp.obo", "phenotype.hpoa", "genes_to_phenotype.txt")
+        )
+        self.assertEqual(
+            progress,
+            [
+                ("read", size),
+                ("terms", 8),
+                ("genes", 2),
+                ("diseases", 2),
+                ("information_content", 8),
+            ]
+        )
+        # The annotation stages are skipped without annotations
+        self.assertEqual(
+            [stage for stage, _ in terms_only], ["read", "terms", "information_content"]
+        )
+        # Errors of the callback abort the build
+        self.assertEqual(failed, {"error": ("ZeroDivisionError", "genes")})
+
+    def test_migrate_binary(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = os.path.join(tmpdir, "ontology.hpo")
+            self.terms.save(path)
+            with open(path, "rb") as fh:
+                data = fh.read()
+            self.assertEqual(data[:4], b"HPO\x03")
+
+            migrated = os.path.join(tmpdir, "migrated.hpo")
+            self.assertEqual(pyhpo.migrate_binary(path, migrated), 3)
+            self.assertEqual(os.path.getsize(migrated), len(data))
+            with multiprocessing.get_context("spawn").Pool(1) as pool:
+                self.assertEqual(pool.apply(_binary_len, (migrated,)), len(self.terms))
+
+            # The output is compressed based on the extension or `compress`
+            compressed = os.path.join(tmpdir, "migrated.hpo.zst")
+            pyhpo.migrate_binary(data, compressed)
+            with open(compressed, "rb") as fh:
+                self.assertEqual(fh.read(4), b"\x28\xb5\x2f\xfd")
+            pyhpo.migrate_binary(data, compressed, compress=False)
+            with open(compressed, "rb") as fh:
+                self.assertEqual(fh.read(4), b"HPO\x03")
+
+            # Newer format versions require an update
+            with self.assertRaisesRegex(ValueError, "format version 9.*update hpo3"):
+                pyhpo.migrate_binary(b"HPO\x09" + data[4:], migrated)
+            with self.assertRaisesRegex(ValueError, "Unsupported binary ontology format version 0"):
+                pyhpo.migrate_binary(b"HPO\x00" + data[4:], migrated)
+            # Malformed data raises a ValueError instead of crashing
+            with self.assertRaisesRegex(ValueError, "Invalid binary ontology data"):
+                pyhpo.migrate_binary(data[:6], migrated)
+            with self.assertRaisesRegex(ValueError, "Invalid binary ontology data"):
+                pyhpo.migrate_binary(data[:len(data) // 2], migrated)
+            with self.assertRaisesRegex(ValueError, "unexpected trailing data"):
+                pyhpo.migrate_binary(data + b"\x00", migrated)
+            with self.assertRaises(FileNotFoundError):
+                pyhpo.migrate_binary(os.path.join(tmpdir, "missing.hpo"), migrated)
+
+    def test_build_terms_only(self):
+        folder = os.path.join(os.path.dirname(__file__), "data", "jax")
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = os.path.join(tmpdir, "ontology.hpo")
+            self.terms.save(path)
+            with multiprocessing.get_context("spawn").Pool(1, maxtasksperchild=1) as pool:
+                from_folder = pool.apply(_fixture_build, (folder,), {"annotations": False})
+                # The annotation files are not required
+                from_obo = pool.apply(_fixture_build, (folder, "obo"), {"annotations": False})
+                missing = pool.apply(_fixture_build, (folder, "obo"))
+                from_binary = pool.apply(_fixture_build, (folder, path), {"annotations": False})
+
+        for summary in (from_folder, from_obo):
+            self.assertEqual(summary["len"], 8)
+            self.assertEqual(summary["n_genes"], 0)
+            self.assertEqual(summary["omim"], {})
+            self.assertEqual(summary["orpha"], {})
+        self.assertEqual(missing["error"][0], "ValueError")
+
+        self.assertEqual(from_binary["len"], len(self.terms))
+        self.assertEqual(from_binary["n_genes"], 0)
+        self.assertEqual(from_binary["omim"], {})
+        self.assertEqual(from_binary["orpha"], {})
+
+    def test_build_min_frequency(self):
+        folder = os.path.join(os.path.dirname(__file__), "data", "jax")
+        binary = os.path.join(os.path.dirname(__file__), "..", "data", "ontology.hpo")
+        with multiprocessing.get_context("spawn").Pool(1, maxtasksperchild=1) as pool:
+            results = {
+                frequency: pool.apply(_fixture_build, (folder,), {"min_frequency": frequency})
+                for frequency in (0.0, 0.5, 0.6, 0.9, 1.5)
+            }
+            from_binary = pool.apply(_fixture_build, (folder, binary), {"min_frequency": 0.5})
+
+        # OMIM:600001 is annotated with a frequency of 1/2,
+        # ORPHA:1001 with `Very frequent` (80-99%)
+        for frequency in (0.0, 0.5):
+            self.assertEqual(results[frequency]["omim"], {600001: [3]})
+            self.assertEqual(results[frequency]["orpha"], {1001: [2]})
+        self.assertEqual(results[0.6]["omim"], {})
+        self.assertEqual(results[0.6]["orpha"], {1001: [2]})
+        self.assertEqual(results[0.9]["omim"], {})
+        self.assertEqual(results[0.9]["orpha"], {})
+        # Gene annotations are not filtered
+        self.assertEqual(results[0.9]["genes"], {"GENE1": [3], "GENE2": [2]})
+
+        self.assertEqual(results[1.5]["error"][0], "ValueError")
+        self.assertIn("only supported for the JAX source files", from_binary["error"][1])
+
+    def test_gene_annotation_mode(self):
+        folder = os.path.join(os.path.dirname(__file__), "data", "jax")
+        with multiprocessing.get_context("spawn").Pool(1, maxtasksperchild=1) as pool:
+            modes = {
+                mode: pool.apply(_fixture_build, (folder,), {"gene_annotation_mode": mode})
+                for mode in ("direct", "transitive", "union", "foo")
+            }
+            transitive = pool.apply(_fixture_build, (folder,), {"transitive": True})
+            conflict = pool.apply(
+                _fixture_build, (folder,), {"transitive": True, "gene_annotation_mode": "union"}
+            )
+
+        # genes_to_phenotype.txt
+        self.assertEqual(modes["direct"]["genes"], {"GENE1": [3], "GENE2": [2]})
+        self.assertEqual(modes["direct"]["direct_hpo"], modes["direct"]["genes"])
+        # phenotype_to_genes.txt
+        self.assertEqual(
+            modes["transitive"]["genes"],
+            {"GENE1": [3, 118], "GENE2": [118], "GENE3": [12828]}
+        )
+        self.assertEqual(
+            modes["transitive"]["direct_hpo"],
+            {"GENE1": None, "GENE2": None, "GENE3": None}
+        )
+        self.assertEqual(transitive["genes"], modes["transitive"]["genes"])
+        # both files, the direct terms are still only from genes_to_phenotype.txt
+        self.assertEqual(
+            modes["union"]["genes"],
+            {"GENE1": [3, 118], "GENE2": [2, 118], "GENE3": [12828]}
+        )
+        self.assertEqual(
+            modes["union"]["direct_hpo"],
+            {"GENE1": [3], "GENE2": [2], "GENE3": []}
+        )
+
+        self.assertEqual(modes["foo"]["error"][0], "ValueError")
+        self.assertEqual(conflict["error"][0], "ValueError")
+
+    def test_compressed_binary(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            plain = os.path.join(tmpdir, "ontology.hpo")
+            compressed = os.path.join(tmpdir, "ontology.hpo.zst")
+            self.terms.save(plain)
+            self.terms.save(compressed)
+            with open(plain, "rb") as fh:
+                plain_bytes = fh.read()
+            with open(compressed, "rb") as fh:
+                compressed_bytes = fh.read()
+            self.assertTrue(compressed_bytes.startswith(b"\x28\xb5\x2f\xfd"))
+            self.assertLess(len(compressed_bytes), len(plain_bytes) / 2)
+
+            with multiprocessing.get_context("spawn").Pool(1) as pool:
+                self.assertEqual(pool.apply(_binary_len, (compressed,)), len(self.terms))
+
+            migrated = os.path.join(tmpdir, "migrated.hpo")
+            self.assertEqual(pyhpo.migrate_binary(compressed_bytes, migrated), 3)
+            # The order of the annotations is not deterministic
+            self.assertEqual(os.path.getsize(migrated), len(plain_bytes))
+
+            # Small streams that expand to huge outputs are rejected
+            bomb = _zstd_rle_frame(257 * 1024 * 1024)
+            self.assertLess(len(bomb), 16 * 1024)
+            with self.assertRaisesRegex(ValueError, "decompressed data exceeds"):
+                pyhpo.migrate_binary(bomb, migrated)
+            with self.assertRaisesRegex(ValueError, "unable to decompress"):
+                pyhpo.migrate_binary(compressed_bytes[:100], migrated)